@@ -18,7 +18,7 @@ use std::{
 };
 use thermometer::{
     Ds18b20Driver, Error, Result,
-    scratchpad::{ConfigurationRegister, Resolution, Scratchpad},
+    scratchpad::{AlarmThreshold, ConfigurationRegister, Resolution, Scratchpad},
 };
 
 static ADDRESSES: OnceLock<Vec<OWAddress>> = OnceLock::new();
@@ -51,8 +51,8 @@ fn main() -> Result<()> {
             .initialization()?
             .match_rom(&address)?
             .write_scratchpad(&Scratchpad {
-                alarm_high_trigger_register: 30,
-                alarm_low_trigger_register: 10,
+                alarm_high_trigger_register: AlarmThreshold::clamped(30),
+                alarm_low_trigger_register: AlarmThreshold::clamped(10),
                 configuration_register: ConfigurationRegister {
                     resolution: Resolution::Twelve,
                 },
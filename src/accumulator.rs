@@ -0,0 +1,222 @@
+//! Degree-minute accumulation
+//!
+//! Integrates the temperature of every sensor above and below a baseline over
+//! time, e.g. for brewing, curing or HVAC billing estimates. As a pipeline
+//! [`Stage`] it is fed by the sampler; every reading stands for one sampling
+//! period:
+//!
+//! ```ignore
+//! let mut sampler = Sampler::new(driver, addresses, Duration::from_secs(60))
+//!     .pipeline(Pipeline::new().stage(DegreeMinutes::new(18.0, Duration::from_secs(60))));
+//! ```
+//!
+//! The totals survive reboots: they are saved with the pipeline state on
+//! [`Sampler::shutdown`](crate::sampler::Sampler::shutdown) and restored by
+//! [`Sampler::restore`](crate::sampler::Sampler::restore).
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::{Error, Result},
+    persistence::Store,
+    pipeline::{Reading, Stage},
+};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Accumulated degree-minutes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Totals {
+    /// Degree-minutes above the baseline
+    pub above: f32,
+    /// Degree-minutes below the baseline
+    pub below: f32,
+}
+
+impl Totals {
+    /// Little-endian `above` and `below`.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        bytes[..4].copy_from_slice(&self.above.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.below.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        let [a, b, c, d, e, f, g, h] = bytes;
+        Self {
+            above: f32::from_le_bytes([a, b, c, d]),
+            below: f32::from_le_bytes([e, f, g, h]),
+        }
+    }
+}
+
+/// Degree-minute accumulator
+///
+/// Readings of sensors beyond the capacity and stale readings aren't
+/// accumulated.
+#[derive(Clone, Debug)]
+pub struct DegreeMinutes<const N: usize = CAPACITY> {
+    /// Baseline (°C)
+    pub baseline: f32,
+    /// Sampling period
+    pub period: Duration,
+    totals: Map<Address, Totals, N>,
+}
+
+impl DegreeMinutes {
+    pub fn new(baseline: f32, period: Duration) -> Self {
+        Self {
+            baseline,
+            period,
+            totals: Map::new(),
+        }
+    }
+}
+
+impl<const N: usize> DegreeMinutes<N> {
+    /// Sets the maximum number of tracked sensors.
+    pub fn capacity<const M: usize>(self) -> DegreeMinutes<M> {
+        DegreeMinutes {
+            baseline: self.baseline,
+            period: self.period,
+            totals: self.totals.into_capacity(),
+        }
+    }
+
+    pub fn get(&self, address: &Address) -> Option<Totals> {
+        self.totals.get(address).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, &Totals)> {
+        self.totals.iter()
+    }
+
+    /// Sets the totals of the sensor, e.g. carried over from another
+    /// device.
+    pub fn set(&mut self, address: Address, totals: Totals) -> Result<()> {
+        self.totals.insert(address, totals)?;
+        Ok(())
+    }
+
+    /// Resets the totals of the sensor.
+    pub fn reset(&mut self, address: &Address) -> Option<Totals> {
+        self.totals.remove(address)
+    }
+
+    /// Resets the totals of all sensors.
+    pub fn clear(&mut self) {
+        self.totals.clear();
+    }
+}
+
+impl<const N: usize> Stage for DegreeMinutes<N> {
+    fn process(&mut self, reading: Reading) -> Option<Reading> {
+        if reading.is_stale() {
+            return Some(reading);
+        }
+        if let Ok(totals) = self
+            .totals
+            .get_or_insert(reading.address, Totals::default())
+        {
+            let degree_minutes =
+                (reading.temperature - self.baseline) * self.period.as_secs_f32() / 60.0;
+            if degree_minutes > 0.0 {
+                totals.above += degree_minutes;
+            } else {
+                totals.below -= degree_minutes;
+            }
+        }
+        Some(reading)
+    }
+
+    /// Saves the totals under `degree_minutes` as little-endian addresses
+    /// followed by [`Totals::to_bytes`].
+    fn persist(&mut self, store: &mut dyn Store) -> Result<()> {
+        let mut value = Vec::with_capacity(self.totals.len() * 16);
+        for (address, totals) in self.totals.iter() {
+            value.extend_from_slice(&address.0.to_le_bytes());
+            value.extend_from_slice(&totals.to_bytes());
+        }
+        store.save("degree_minutes", &value)
+    }
+
+    /// Replaces the totals with the saved ones, if any. Nothing is changed
+    /// on failure.
+    fn restore(&mut self, store: &mut dyn Store) -> Result<()> {
+        let Some(value) = store.load("degree_minutes")? else {
+            return Ok(());
+        };
+        let (chunks, remainder) = value.as_chunks::<16>();
+        if !remainder.is_empty() {
+            return Err(Error::Decode {
+                offset: value.len() - remainder.len(),
+            });
+        }
+        let mut totals = Map::new();
+        for chunk in chunks {
+            let (address, bytes) = chunk.split_at(8);
+            totals.insert(
+                Address(u64::from_le_bytes(address.try_into().unwrap())),
+                Totals::from_bytes(bytes.try_into().unwrap()),
+            )?;
+        }
+        self.totals = totals;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accumulate() {
+        let address = Address(1);
+        let mut accumulator = DegreeMinutes::new(18.0, Duration::from_secs(30));
+        for temperature in [20.0, 22.0, 16.0, 18.0] {
+            accumulator.process(Reading::new(address, temperature));
+        }
+        accumulator.process(Reading {
+            stale: Some(Duration::from_secs(60)),
+            ..Reading::new(address, 100.0)
+        });
+        assert_eq!(
+            accumulator.get(&address),
+            Some(Totals {
+                above: 3.0,
+                below: 1.0,
+            })
+        );
+        let totals = accumulator.reset(&address).unwrap();
+        assert_eq!(Totals::from_bytes(totals.to_bytes()), totals);
+        assert_eq!(accumulator.get(&address), None);
+    }
+
+    #[test]
+    fn persist() {
+        let mut store = alloc::collections::BTreeMap::new();
+        let mut accumulator = DegreeMinutes::new(18.0, Duration::from_secs(60));
+        accumulator.process(Reading::new(Address(1), 20.0));
+        accumulator.process(Reading::new(Address(2), 15.0));
+        accumulator.persist(&mut store).unwrap();
+        let mut restored = DegreeMinutes::new(18.0, Duration::from_secs(60));
+        restored.restore(&mut store).unwrap();
+        assert_eq!(
+            restored.iter().collect::<Vec<_>>(),
+            accumulator.iter().collect::<Vec<_>>()
+        );
+        store.insert("degree_minutes".into(), [0; 20].into());
+        assert_eq!(
+            restored.restore(&mut store),
+            Err(Error::Decode { offset: 16 })
+        );
+        assert_eq!(
+            restored.get(&Address(2)),
+            Some(Totals {
+                above: 0.0,
+                below: 3.0
+            })
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{
+    crc8,
+    error::{Error, Result},
+};
+use core::fmt::{self, Display, Formatter};
+
+/// 1-Wire ROM code
+///
+/// The 64-bit lasered ROM code of a device: 8-bit family code, 48-bit serial
+/// number and 8-bit CRC, least significant byte first. Unlike `OWAddress` it
+/// doesn't depend on esp-idf, so it can be used by the data model on any
+/// target.
+///
+/// On the bus and in the ROM commands the code is sent least significant
+/// byte first ([`to_bytes`](Self::to_bytes)); as text it is written most
+/// significant digit first, CRC first and family code last
+/// ([`Display`](core::fmt::Display)), e.g. `230000046eafbc28`.
+///
+/// The order and hash are those of the 64-bit code, stable across builds and
+/// targets, so addresses can key ordered and hashed collections.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address(pub u64);
+
+/// Decoding validation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// Any 64 bits are accepted.
+    Lenient,
+    /// The CRC of the ROM code must match.
+    #[default]
+    Strict,
+}
+
+impl Address {
+    pub const fn new(address: u64) -> Self {
+        Self(address)
+    }
+
+    pub const fn address(&self) -> u64 {
+        self.0
+    }
+
+    pub const fn family_code(&self) -> u8 {
+        self.0 as u8
+    }
+
+    /// The ROM code in bus order: family code first, CRC last.
+    pub const fn to_bytes(&self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+
+    /// Decodes the ROM code in bus order.
+    pub fn from_bytes(bytes: [u8; 8], validation: Validation) -> Result<Self> {
+        let address = Self(u64::from_le_bytes(bytes));
+        address.validate(validation)?;
+        Ok(address)
+    }
+
+    /// Decodes the ROM code written as 16 hex digits.
+    pub fn from_hex(hex: &str, validation: Validation) -> Result<Self> {
+        let hex = hex.trim();
+        if hex.len() != 16 {
+            return Err(Error::InvalidAddress);
+        }
+        let address = Self(u64::from_str_radix(hex, 16).map_err(|_| Error::InvalidAddress)?);
+        address.validate(validation)?;
+        Ok(address)
+    }
+
+    /// Checks the CRC of the ROM code in strict mode.
+    pub fn validate(&self, validation: Validation) -> Result<()> {
+        if validation == Validation::Strict {
+            crc8::check(&self.to_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl From<u64> for Address {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Address> for u64 {
+    fn from(value: Address) -> Self {
+        value.0
+    }
+}
+
+#[cfg(feature = "esp-idf")]
+impl From<esp_idf_svc::hal::onewire::OWAddress> for Address {
+    fn from(value: esp_idf_svc::hal::onewire::OWAddress) -> Self {
+        Self(value.address())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn family_code() {
+        assert_eq!(Address(0x1E00_0000_0000_0028).family_code(), 0x28);
+        assert_eq!(Address(0x2300_0004_6EAF_BC28).family_code(), 0x28);
+        assert_eq!(Address(0x0000_0000_0000_0010).family_code(), 0x10);
+    }
+
+    #[test]
+    fn bytes() {
+        let address = Address(0x1E00_0000_0000_0028);
+        let bytes = [0x28, 0, 0, 0, 0, 0, 0, 0x1E];
+        assert_eq!(address.to_bytes(), bytes);
+        assert_eq!(Address::from_bytes(bytes, Validation::Strict), Ok(address));
+        let bytes = [0x28, 0, 0, 0, 0, 0, 0, 0x1F];
+        assert!(matches!(
+            Address::from_bytes(bytes, Validation::Strict),
+            Err(Error::Crc(_))
+        ));
+        assert_eq!(
+            Address::from_bytes(bytes, Validation::Lenient),
+            Ok(Address(0x1F00_0000_0000_0028))
+        );
+    }
+
+    #[test]
+    fn order() {
+        use std::{collections::BTreeSet, hash::BuildHasher};
+
+        let addresses = BTreeSet::from([Address(3), Address(1), Address(2)]);
+        assert_eq!(
+            addresses.into_iter().map(u64::from).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        let hasher = std::hash::BuildHasherDefault::<std::hash::DefaultHasher>::default();
+        assert_eq!(hasher.hash_one(Address(1)), hasher.hash_one(1u64));
+    }
+
+    #[test]
+    fn hex() {
+        let address = Address(0x1E00_0000_0000_0028);
+        assert_eq!(address.to_string(), "1e00000000000028");
+        assert_eq!(
+            Address::from_hex("1e00000000000028", Validation::Strict),
+            Ok(address)
+        );
+        // Byte-swapped
+        assert!(matches!(
+            Address::from_hex("280000000000001e", Validation::Strict),
+            Err(Error::Crc(_))
+        ));
+        assert_eq!(
+            Address::from_hex("28", Validation::Lenient),
+            Err(Error::InvalidAddress)
+        );
+        assert_eq!(
+            Address::from_hex("0000000000000028", Validation::Lenient),
+            Ok(Address(0x28))
+        );
+    }
+}
@@ -0,0 +1,396 @@
+//! Alarm engine
+//!
+//! As a pipeline [`Stage`] the engine checks every passing reading against
+//! the limits of its sensor and queues an [`Event`] whenever an alarm is
+//! raised or cleared:
+//!
+//! ```ignore
+//! let mut alarms = Alarms::new(Overflow::DropOldest).limits(address, Limits {
+//!     low: Some(5.0),
+//!     high: Some(60.0),
+//! });
+//! alarms.process(reading);
+//! for event in alarms.events().drain() {
+//!     // ...
+//! }
+//! ```
+//!
+//! The hardware alarm flag only reflects the last conversion. The engine
+//! selects the [`Semantics`] of the delivered events instead: alerting wants
+//! an alarm to stay up until someone has seen it, a controller wants the
+//! current state or just the crossings.
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::Result,
+    event::{EventQueue, Overflow},
+    logging::{Subsystem, log},
+    pipeline::{Reading, Stage},
+    unit::Celsius,
+};
+use log::Level;
+
+pub use crate::types::{AlarmEvent as Event, AlarmKind};
+
+/// Alarm limits (°C)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    pub low: Option<f32>,
+    pub high: Option<f32>,
+}
+
+impl Limits {
+    /// The alarm of the temperature, if any.
+    pub fn check(&self, temperature: f32) -> Option<AlarmKind> {
+        if self.high.is_some_and(|high| temperature > high) {
+            Some(AlarmKind::High)
+        } else if self.low.is_some_and(|low| temperature < low) {
+            Some(AlarmKind::Low)
+        } else {
+            None
+        }
+    }
+
+    /// The limit of the alarm.
+    pub fn threshold(&self, kind: AlarmKind) -> Option<f32> {
+        match kind {
+            AlarmKind::High => self.high,
+            AlarmKind::Low => self.low,
+        }
+    }
+}
+
+/// Alarm semantics
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Semantics {
+    /// The alarm is raised when the temperature leaves the limits and stays
+    /// active until it is [acknowledged](Alarms::acknowledge), even if the
+    /// temperature returns in the meantime.
+    Latched,
+    /// The alarm is active while the temperature is out of the limits: it is
+    /// raised when the temperature leaves them and cleared when it returns.
+    #[default]
+    WhileOutOfRange,
+    /// An alarm is raised every time the temperature leaves the limits, it
+    /// is never cleared.
+    OnCrossing,
+}
+
+/// Alarm engine
+#[derive(Clone, Debug)]
+pub struct Alarms<const N: usize = CAPACITY> {
+    limits: Map<Address, Limits, N>,
+    /// The active alarms and their limits.
+    active: Map<Address, (AlarmKind, f32), N>,
+    events: EventQueue<Event, N>,
+    semantics: Semantics,
+}
+
+impl Alarms {
+    pub fn new(overflow: Overflow) -> Self {
+        Self {
+            limits: Map::new(),
+            active: Map::new(),
+            events: EventQueue::new(overflow),
+            semantics: Semantics::default(),
+        }
+    }
+}
+
+impl Default for Alarms {
+    fn default() -> Self {
+        Self::new(Overflow::default())
+    }
+}
+
+impl<const N: usize> Alarms<N> {
+    /// Sets the maximum number of sensors and queued events.
+    pub fn capacity<const M: usize>(self) -> Alarms<M> {
+        Alarms {
+            limits: self.limits.into_capacity(),
+            active: self.active.into_capacity(),
+            events: self.events.capacity(),
+            semantics: self.semantics,
+        }
+    }
+
+    /// Sets the semantics of the alarms.
+    pub fn semantics(self, semantics: Semantics) -> Self {
+        Self { semantics, ..self }
+    }
+
+    /// Sets the limits of the sensor.
+    pub fn limits(mut self, address: Address, limits: Limits) -> Self {
+        if let Err(error) = self.set_limits(address, limits) {
+            log!(
+                Subsystem::Pipeline,
+                Level::Warn,
+                "Alarm limits of {address:x?} ignored: {error}"
+            );
+        }
+        self
+    }
+
+    /// Sets the limits of the sensor, returning the previous ones.
+    pub fn set_limits(&mut self, address: Address, limits: Limits) -> Result<Option<Limits>> {
+        self.limits.insert(address, limits)
+    }
+
+    /// Removes the limits of the sensor, returning them.
+    pub fn remove_limits(&mut self, address: &Address) -> Option<Limits> {
+        self.limits.remove(address)
+    }
+
+    /// The limits of the sensor.
+    pub fn get_limits(&self, address: &Address) -> Option<Limits> {
+        self.limits.get(address).copied()
+    }
+
+    /// Removes the limits of all sensors.
+    pub fn clear_limits(&mut self) {
+        self.limits.clear();
+    }
+
+    /// The active alarm of the sensor. With [`Semantics::OnCrossing`] the
+    /// alarm of the last reading.
+    pub fn active(&self, address: &Address) -> Option<AlarmKind> {
+        self.active.get(address).map(|(kind, _)| *kind)
+    }
+
+    /// Releases the latched alarm of the sensor, without an event. A sensor
+    /// still out of the limits raises it again on its next reading.
+    pub fn acknowledge(&mut self, address: &Address) -> Option<AlarmKind> {
+        self.active.remove(address).map(|(kind, _)| kind)
+    }
+
+    /// The queued events.
+    pub fn events(&mut self) -> &mut EventQueue<Event, N> {
+        &mut self.events
+    }
+
+    /// Queues the event, returns whether it was accepted.
+    fn emit(&mut self, event: Event) -> bool {
+        let accepted = self.events.push_or_drop(event);
+        if !accepted {
+            log!(
+                Subsystem::Pipeline,
+                Level::Warn,
+                "Alarm event {event:?} dropped, the event queue is full"
+            );
+        }
+        accepted
+    }
+}
+
+impl<const N: usize> Stage for Alarms<N> {
+    fn process(&mut self, reading: Reading) -> Option<Reading> {
+        let Some(limits) = self.limits.get(&reading.address) else {
+            return Some(reading);
+        };
+        let (address, bus) = (reading.address, reading.bus);
+        let alarm = limits
+            .check(reading.temperature)
+            .and_then(|kind| Some((kind, limits.threshold(kind)?)));
+        let active = self.active.get(&address).copied();
+        if active.map(|(kind, _)| kind) == alarm.map(|(kind, _)| kind)
+            || self.semantics == Semantics::Latched && active.is_some()
+        {
+            return Some(reading);
+        }
+        let temperature = Celsius(reading.temperature);
+        // The active alarm changes only with an accepted event, a dropped
+        // one is emitted again on the next reading.
+        if let Some((kind, threshold)) = active {
+            if self.semantics == Semantics::WhileOutOfRange
+                && !self.emit(Event::Cleared {
+                    address,
+                    bus,
+                    kind,
+                    temperature,
+                    threshold: Celsius(threshold),
+                })
+            {
+                return Some(reading);
+            }
+            self.active.remove(&address);
+        }
+        if let Some((kind, threshold)) = alarm
+            && self.emit(Event::Raised {
+                address,
+                bus,
+                kind,
+                temperature,
+                threshold: Celsius(threshold),
+            })
+        {
+            // The active map has room for every sensor with limits.
+            let _ = self.active.insert(address, (kind, threshold));
+        }
+        Some(reading)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::BusId;
+
+    const LIMITS: Limits = Limits {
+        low: Some(5.0),
+        high: Some(60.0),
+    };
+
+    #[test]
+    fn check() {
+        assert_eq!(LIMITS.check(20.0), None);
+        assert_eq!(LIMITS.check(60.0), None);
+        assert_eq!(LIMITS.check(60.5), Some(AlarmKind::High));
+        assert_eq!(LIMITS.check(4.5), Some(AlarmKind::Low));
+        assert_eq!(Limits::default().check(-55.0), None);
+        assert_eq!(LIMITS.threshold(AlarmKind::Low), Some(5.0));
+    }
+
+    #[test]
+    fn events() {
+        let address = Address(1);
+        let mut alarms = Alarms::default().limits(address, LIMITS);
+        for temperature in [20.0, 61.0, 62.0, 0.0, 20.0] {
+            alarms.process(Reading::new(address, temperature));
+        }
+        alarms.process(Reading::new(Address(2), 100.0));
+        assert_eq!(alarms.active(&address), None);
+        assert_eq!(
+            alarms.events().drain().collect::<Vec<_>>(),
+            [
+                Event::Raised {
+                    address,
+                    bus: None,
+                    kind: AlarmKind::High,
+                    temperature: Celsius(61.0),
+                    threshold: Celsius(60.0),
+                },
+                Event::Cleared {
+                    address,
+                    bus: None,
+                    kind: AlarmKind::High,
+                    temperature: Celsius(0.0),
+                    threshold: Celsius(60.0),
+                },
+                Event::Raised {
+                    address,
+                    bus: None,
+                    kind: AlarmKind::Low,
+                    temperature: Celsius(0.0),
+                    threshold: Celsius(5.0),
+                },
+                Event::Cleared {
+                    address,
+                    bus: None,
+                    kind: AlarmKind::Low,
+                    temperature: Celsius(20.0),
+                    threshold: Celsius(5.0),
+                },
+            ]
+        );
+    }
+
+    fn kinds<const N: usize>(alarms: &mut Alarms<N>) -> Vec<(bool, AlarmKind)> {
+        alarms
+            .events()
+            .drain()
+            .map(|event| match event {
+                Event::Raised { kind, .. } => (true, kind),
+                Event::Cleared { kind, .. } => (false, kind),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn semantics() {
+        let address = Address(1);
+        let temperatures = [61.0, 20.0, 0.0, 62.0, 20.0];
+        let mut alarms = Alarms::default()
+            .semantics(Semantics::Latched)
+            .limits(address, LIMITS);
+        for temperature in temperatures {
+            alarms.process(Reading::new(address, temperature));
+        }
+        assert_eq!(kinds(&mut alarms), [(true, AlarmKind::High)]);
+        assert_eq!(alarms.acknowledge(&address), Some(AlarmKind::High));
+        alarms.process(Reading::new(address, 0.0));
+        assert_eq!(kinds(&mut alarms), [(true, AlarmKind::Low)]);
+
+        let mut alarms = Alarms::default()
+            .semantics(Semantics::OnCrossing)
+            .limits(address, LIMITS);
+        for temperature in temperatures {
+            alarms.process(Reading::new(address, temperature));
+        }
+        assert_eq!(
+            kinds(&mut alarms),
+            [
+                (true, AlarmKind::High),
+                (true, AlarmKind::Low),
+                (true, AlarmKind::High)
+            ]
+        );
+        assert_eq!(alarms.active(&address), None);
+    }
+
+    #[test]
+    fn overflow() {
+        let address = Address(1);
+        let mut alarms = Alarms::new(Overflow::DropNewest)
+            .capacity::<1>()
+            .limits(address, LIMITS);
+        for temperature in [61.0, 20.0, 61.0] {
+            alarms.process(Reading::new(address, temperature));
+        }
+        assert_eq!(alarms.events().len(), 1);
+        assert_eq!(alarms.events().counters().dropped_newest, 2);
+    }
+
+    #[test]
+    fn block() {
+        let address = Address(1);
+        let mut alarms = Alarms::new(Overflow::Block)
+            .capacity::<3>()
+            .limits(address, LIMITS);
+        for other in [Address(2), Address(3)] {
+            alarms.set_limits(other, LIMITS).unwrap();
+        }
+        // Raised, cleared and raised fill the queue.
+        for (other, temperature) in [(2, 61.0), (2, 20.0), (3, 61.0)] {
+            alarms.process(Reading::new(Address(other), temperature));
+        }
+        // Refused, the alarm isn't active and is raised again.
+        alarms.process(Reading::new(address, 61.0));
+        assert_eq!(alarms.active(&address), None);
+        assert_eq!(alarms.events().counters().dropped(), 1);
+        alarms.events().pop();
+        alarms.process(Reading::new(address, 62.0));
+        assert_eq!(alarms.active(&address), Some(AlarmKind::High));
+        assert!(matches!(
+            alarms.events().drain().last(),
+            Some(Event::Raised { address: raised, .. }) if raised == address
+        ));
+    }
+
+    #[test]
+    fn provenance() {
+        let address = Address(1);
+        let mut alarms = Alarms::default().limits(address, LIMITS);
+        alarms.process(Reading {
+            bus: Some(BusId(1)),
+            ..Reading::new(address, 61.0)
+        });
+        assert!(matches!(
+            alarms.events().pop(),
+            Some(Event::Raised {
+                bus: Some(BusId(1)),
+                ..
+            })
+        ));
+    }
+}
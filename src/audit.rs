@@ -0,0 +1,278 @@
+//! EEPROM audit
+//!
+//! A sensor that browns out reloads its scratchpad from EEPROM and silently
+//! reverts to the stored configuration. The audit periodically checks the
+//! configuration of every sensor against the expected one, recalls the EEPROM
+//! and compares it as well:
+//!
+//! ```ignore
+//! let mut audit = Audit::new(Duration::from_secs(3600));
+//! audit.expect(address, Configuration::default())?;
+//! loop {
+//!     if let Some(findings) = audit.poll(&mut thermometer) {
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! The recall leaves the EEPROM values in the scratchpad. Under
+//! [`Policy::Repair`] the expected configuration is written back to the
+//! scratchpad, under [`Policy::Alert`] the configuration found before the
+//! recall. The EEPROM itself is never written, mismatches are reported.
+
+use crate::{
+    Ds18b20Driver, Error, Result,
+    address::Address,
+    collections::{CAPACITY, Map},
+    driver::preflight,
+    logging::{Subsystem, log},
+    scratchpad::{AlarmThreshold, ConfigurationRegister, Resolution, Scratchpad},
+};
+use log::Level;
+use std::time::{Duration, Instant};
+
+/// Sensor configuration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Configuration {
+    /// Alarm high trigger register (TH)
+    pub alarm_high: AlarmThreshold,
+    /// Alarm low trigger register (TL)
+    pub alarm_low: AlarmThreshold,
+    pub resolution: Resolution,
+}
+
+impl From<&Scratchpad> for Configuration {
+    fn from(value: &Scratchpad) -> Self {
+        Self {
+            alarm_high: value.alarm_high_trigger_register,
+            alarm_low: value.alarm_low_trigger_register,
+            resolution: value.configuration_register.resolution,
+        }
+    }
+}
+
+impl From<Configuration> for Scratchpad {
+    fn from(value: Configuration) -> Self {
+        Self {
+            alarm_high_trigger_register: value.alarm_high,
+            alarm_low_trigger_register: value.alarm_low,
+            configuration_register: ConfigurationRegister {
+                resolution: value.resolution,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// Mismatch policy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Policy {
+    /// Only report mismatches.
+    Alert,
+    /// Report mismatches and restore the expected configuration in the
+    /// scratchpad.
+    #[default]
+    Repair,
+}
+
+/// Audit finding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// The scratchpad doesn't hold the expected configuration, e.g. after a
+    /// brown-out.
+    Reverted {
+        address: Address,
+        found: Configuration,
+        repaired: bool,
+    },
+    /// The EEPROM doesn't hold the expected configuration.
+    Eeprom {
+        address: Address,
+        found: Configuration,
+    },
+    /// The sensor couldn't be audited.
+    Failed { address: Address, error: Error },
+}
+
+/// EEPROM audit
+#[derive(Clone, Debug)]
+pub struct Audit<const N: usize = CAPACITY> {
+    expected: Map<Address, Configuration, N>,
+    policy: Policy,
+    interval: Duration,
+    next: Instant,
+}
+
+impl Audit {
+    /// The first audit runs after the interval.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            expected: Map::new(),
+            policy: Policy::default(),
+            interval,
+            next: Instant::now() + interval,
+        }
+    }
+}
+
+impl<const N: usize> Audit<N> {
+    /// Sets the maximum number of audited sensors.
+    pub fn capacity<const M: usize>(self) -> Audit<M> {
+        Audit {
+            expected: self.expected.into_capacity(),
+            policy: self.policy,
+            interval: self.interval,
+            next: self.next,
+        }
+    }
+
+    pub fn policy(self, policy: Policy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Sets the expected configuration of the sensor, returning the previous
+    /// one.
+    pub fn expect(
+        &mut self,
+        address: Address,
+        configuration: Configuration,
+    ) -> Result<Option<Configuration>> {
+        self.expected.insert(address, configuration)
+    }
+
+    pub fn forget(&mut self, address: &Address) -> Option<Configuration> {
+        self.expected.remove(address)
+    }
+
+    /// Audits all sensors when due. Returns `None` while not due and the
+    /// findings (empty if all sensors match) otherwise.
+    pub fn poll(&mut self, driver: &mut Ds18b20Driver) -> Option<Vec<Finding>> {
+        let now = Instant::now();
+        if now < self.next {
+            return None;
+        }
+        self.next = now + self.interval;
+        Some(self.run(driver))
+    }
+
+    /// Audits all sensors now.
+    pub fn run(&mut self, driver: &mut Ds18b20Driver) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (address, expected) in self.expected.iter() {
+            if let Err(error) = audit(driver, address, expected, self.policy, &mut findings) {
+                findings.push(Finding::Failed {
+                    address: *address,
+                    error,
+                });
+            }
+        }
+        for finding in &findings {
+            log!(Subsystem::Bus, Level::Warn, "EEPROM audit: {finding:?}");
+        }
+        findings
+    }
+}
+
+fn audit(
+    driver: &mut Ds18b20Driver,
+    address: &Address,
+    expected: &Configuration,
+    policy: Policy,
+    findings: &mut Vec<Finding>,
+) -> Result<()> {
+    preflight(address)?;
+    let scratchpad =
+        driver.retry(|this| this.initialization()?.match_rom(address)?.read_scratchpad())?;
+    driver.retry(|this| this.initialization()?.match_rom(address)?.save_scratchpad())?;
+    let eeprom =
+        driver.retry(|this| this.initialization()?.match_rom(address)?.read_scratchpad())?;
+    let (scratchpad, eeprom) = (
+        Configuration::from(&scratchpad),
+        Configuration::from(&eeprom),
+    );
+    // The recall has overwritten the scratchpad with the EEPROM values.
+    let restore = match policy {
+        Policy::Alert => scratchpad,
+        Policy::Repair => *expected,
+    };
+    if eeprom != restore {
+        driver.write_scratchpad(address, &restore.into())?;
+    }
+    findings.extend(check(address, expected, scratchpad, eeprom, policy));
+    Ok(())
+}
+
+/// The findings of the configurations read back before and after the
+/// recall.
+fn check(
+    address: &Address,
+    expected: &Configuration,
+    scratchpad: Configuration,
+    eeprom: Configuration,
+    policy: Policy,
+) -> impl Iterator<Item = Finding> {
+    let reverted = (scratchpad != *expected).then_some(Finding::Reverted {
+        address: *address,
+        found: scratchpad,
+        repaired: policy == Policy::Repair,
+    });
+    let eeprom = (eeprom != *expected).then_some(Finding::Eeprom {
+        address: *address,
+        found: eeprom,
+    });
+    reverted.into_iter().chain(eeprom)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check() {
+        let address = Address(0x1E00_0000_0000_0028);
+        let expected = Configuration {
+            alarm_high: AlarmThreshold::clamped(30),
+            alarm_low: AlarmThreshold::clamped(19),
+            resolution: Resolution::Twelve,
+        };
+        let factory = Configuration {
+            alarm_high: AlarmThreshold::clamped(75),
+            alarm_low: AlarmThreshold::clamped(70),
+            resolution: Resolution::Twelve,
+        };
+        let check = |scratchpad, eeprom, policy| {
+            super::check(&address, &expected, scratchpad, eeprom, policy).collect::<Vec<_>>()
+        };
+        assert_eq!(check(expected, expected, Policy::Alert), []);
+        // Brown-out to factory EEPROM values.
+        assert_eq!(
+            check(factory, factory, Policy::Repair),
+            [
+                Finding::Reverted {
+                    address,
+                    found: factory,
+                    repaired: true,
+                },
+                Finding::Eeprom {
+                    address,
+                    found: factory,
+                },
+            ],
+        );
+        assert_eq!(
+            check(factory, expected, Policy::Alert),
+            [Finding::Reverted {
+                address,
+                found: factory,
+                repaired: false,
+            }],
+        );
+        assert_eq!(
+            check(expected, factory, Policy::Alert),
+            [Finding::Eeprom {
+                address,
+                found: factory,
+            }],
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Retry backoff
+//!
+//! Failed operations are retried after a jittered exponential backoff: the
+//! delay before retry `n` is drawn uniformly from `0..=min(max, base * 2^n)`
+//! ("full jitter"), so nodes hit by the same interference don't retry, and
+//! transmit, in lockstep. The randomness comes from a pluggable [`Entropy`]
+//! source, the hardware RNG (`esp_random`) on the ESP32:
+//!
+//! ```ignore
+//! thermometer.backoff = Backoff::new(Duration::from_millis(2), Duration::from_millis(100))
+//!     .entropy(Xorshift::new(device_id));
+//! ```
+
+use alloc::boxed::Box;
+use core::time::Duration;
+
+/// Entropy source
+pub trait Entropy {
+    fn next_u32(&mut self) -> u32;
+}
+
+impl<F: FnMut() -> u32> Entropy for F {
+    fn next_u32(&mut self) -> u32 {
+        self()
+    }
+}
+
+/// Xorshift32 pseudo-random generator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Xorshift(u32);
+
+impl Xorshift {
+    /// A zero seed is replaced, xorshift would stay at zero.
+    pub const fn new(seed: u32) -> Self {
+        Self(if seed == 0 { 0x9E37_79B9 } else { seed })
+    }
+}
+
+impl Default for Xorshift {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Entropy for Xorshift {
+    fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// Hardware random number generator
+#[cfg(feature = "esp-idf")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EspRandom;
+
+#[cfg(feature = "esp-idf")]
+impl Entropy for EspRandom {
+    fn next_u32(&mut self) -> u32 {
+        // SAFETY: Reads the RNG register, no preconditions.
+        unsafe { esp_idf_svc::sys::esp_random() }
+    }
+}
+
+/// Jittered exponential backoff
+pub struct Backoff {
+    /// The delay ceiling of the first retry
+    pub base: Duration,
+    /// The largest delay ceiling
+    pub max: Duration,
+    entropy: Box<dyn Entropy + Send>,
+}
+
+impl Backoff {
+    /// Draws from `esp_random` with the `esp-idf` feature, from a
+    /// [`Xorshift`] otherwise.
+    pub fn new(base: Duration, max: Duration) -> Self {
+        #[cfg(feature = "esp-idf")]
+        let entropy = Box::new(EspRandom);
+        #[cfg(not(feature = "esp-idf"))]
+        let entropy = Box::new(Xorshift::default());
+        Self { base, max, entropy }
+    }
+
+    /// Retries immediately.
+    pub fn none() -> Self {
+        Self::new(Duration::ZERO, Duration::ZERO)
+    }
+
+    /// Sets the entropy source.
+    pub fn entropy(self, entropy: impl Entropy + Send + 'static) -> Self {
+        Self {
+            entropy: Box::new(entropy),
+            ..self
+        }
+    }
+
+    /// The delay before the retry, counted from 0.
+    pub fn delay(&mut self, retry: u32) -> Duration {
+        let ceiling = self
+            .base
+            .checked_mul(1 << retry.min(16))
+            .map_or(self.max, |ceiling| ceiling.min(self.max));
+        if ceiling.is_zero() {
+            return Duration::ZERO;
+        }
+        let nanos = ceiling.as_nanos() as u64;
+        let fraction = self.entropy.next_u32() as u128;
+        Duration::from_nanos(((nanos as u128 * (fraction + 1)) >> 32) as _)
+    }
+}
+
+/// 1 ms doubling up to 64 ms.
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(1), Duration::from_millis(64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delay() {
+        let millisecond = Duration::from_millis(1);
+        let mut backoff = Backoff::new(millisecond, 8 * millisecond).entropy(|| u32::MAX);
+        let delays: [_; 5] = core::array::from_fn(|retry| backoff.delay(retry as _));
+        assert_eq!(
+            delays,
+            [1, 2, 4, 8, 8].map(|milliseconds| milliseconds * millisecond)
+        );
+        let mut backoff = backoff.entropy(|| 0);
+        assert!(backoff.delay(3) < Duration::from_nanos(1));
+        let mut backoff = backoff.entropy(Xorshift::new(1));
+        assert!((0..100).all(|_| backoff.delay(40) <= 8 * millisecond));
+        assert_eq!(Backoff::none().delay(3), Duration::ZERO);
+    }
+}
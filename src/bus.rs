@@ -0,0 +1,467 @@
+//! Multi-bus deployments
+//!
+//! A [`BusManager`] drives several 1-Wire buses, e.g. one per cable run. Every
+//! reading (and the alarm events raised from it) carries the [`BusId`] of the
+//! bus it was read from, and the manager keeps [`Health`] metrics per bus:
+//!
+//! ```ignore
+//! let mut buses = BusManager::new();
+//! let boiler = buses.add("gpio4", Ds18b20Driver::new(pins.gpio4, rmt.channel0)?);
+//! let attic = buses.add("gpio5", Ds18b20Driver::new(pins.gpio5, rmt.channel1)?);
+//! buses.scan()?;
+//! for (bus, reading) in buses.read_all() {
+//!     // ...
+//! }
+//! ```
+//!
+//! The buses are searched concurrently, a thread each, so the scan takes
+//! about as long as the slowest bus. The search tree of a bus depends on its
+//! own sensors only, so every bus runs a search of its own. The threads get
+//! the default stack size, `CONFIG_PTHREAD_TASK_STACK_SIZE_DEFAULT` on
+//! ESP-IDF. The combined
+//! progress is reported on the calling thread after every search pass of any
+//! bus:
+//!
+//! ```ignore
+//! buses.scan_with(|progress| info!("{}/{} buses, {} sensors", progress.finished, progress.buses, progress.found))?;
+//! ```
+
+use crate::error::Error;
+#[cfg(feature = "std")]
+use crate::{Result, address::Address};
+use core::fmt::{self, Display, Formatter};
+#[cfg(feature = "std")]
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Bus identifier
+///
+/// The index of the bus in its manager.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BusId(pub u8);
+
+impl Display for BusId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "bus{}", self.0)
+    }
+}
+
+/// Bus health
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Health {
+    /// Successful reads
+    pub reads: usize,
+    /// Failed reads
+    pub failures: usize,
+    /// Failed reads since the last successful read
+    pub consecutive_failures: usize,
+    /// Scans, the failed ones included
+    pub scans: usize,
+    /// Failed scans
+    pub scan_failures: usize,
+    pub last_error: Option<Error>,
+}
+
+impl Health {
+    /// Records a read.
+    pub fn record<T>(&mut self, result: &Result<T, Error>) {
+        match result {
+            Ok(_) => {
+                self.reads += 1;
+                self.consecutive_failures = 0;
+            }
+            Err(error) => {
+                self.failures += 1;
+                self.consecutive_failures += 1;
+                self.last_error = Some(*error);
+            }
+        }
+    }
+
+    /// Records a scan, which doesn't count in the reads.
+    pub fn record_scan<T>(&mut self, result: &Result<T, Error>) {
+        self.scans += 1;
+        if let Err(error) = result {
+            self.scan_failures += 1;
+            self.last_error = Some(*error);
+        }
+    }
+
+    /// The share of failed reads.
+    pub fn failure_rate(&self) -> f32 {
+        match self.reads + self.failures {
+            0 => 0.0,
+            total => self.failures as f32 / total as f32,
+        }
+    }
+}
+
+/// Combined scan progress of all buses
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScanProgress {
+    pub buses: usize,
+    /// Buses whose search is complete or failed
+    pub finished: usize,
+    /// Sensors found so far, those beyond the maximum of a bus included
+    pub passes: usize,
+    /// Sensors found so far
+    pub found: usize,
+}
+
+/// Outcome of the search of a bus
+#[cfg(feature = "std")]
+#[cfg_attr(not(feature = "esp-idf"), allow(dead_code))]
+#[derive(Debug)]
+struct Searched {
+    /// The first sensors found, up to the maximum.
+    found: Vec<Address>,
+    /// The number of sensors found.
+    count: usize,
+    result: Result<()>,
+    duration: Duration,
+}
+
+/// Progress of the search of a bus
+#[cfg(feature = "std")]
+#[cfg_attr(not(feature = "esp-idf"), allow(dead_code))]
+enum Pass {
+    Found { kept: bool },
+    Finished,
+}
+
+/// Runs the searches concurrently, a thread each, keeping up to the maximum
+/// sensors of each. The combined progress is reported on the calling thread
+/// after every search pass.
+#[cfg(feature = "std")]
+#[cfg_attr(not(feature = "esp-idf"), allow(dead_code))]
+fn search_all<S, I>(
+    searches: Vec<(S, usize)>,
+    mut progress: impl FnMut(&ScanProgress),
+) -> Vec<Searched>
+where
+    S: FnOnce() -> Result<I> + Send,
+    I: Iterator<Item = Result<Address>>,
+{
+    let mut state = ScanProgress {
+        buses: searches.len(),
+        ..Default::default()
+    };
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        let handles: Vec<_> = searches
+            .into_iter()
+            .map(|(search, max)| {
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    let start = Instant::now();
+                    let mut found = Vec::new();
+                    let mut count = 0;
+                    let result = search().and_then(|search| {
+                        for address in search {
+                            let address = address?;
+                            count += 1;
+                            let kept = count <= max;
+                            if kept {
+                                found.push(address);
+                            }
+                            let _ = sender.send(Pass::Found { kept });
+                        }
+                        Ok(())
+                    });
+                    let _ = sender.send(Pass::Finished);
+                    Searched {
+                        found,
+                        count,
+                        result,
+                        duration: start.elapsed(),
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+        // Closed once all searches are done.
+        for pass in receiver {
+            match pass {
+                Pass::Found { kept } => {
+                    state.passes += 1;
+                    state.found += kept as usize;
+                }
+                Pass::Finished => state.finished += 1,
+            }
+            progress(&state);
+        }
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+#[cfg(feature = "esp-idf")]
+pub use self::manager::BusManager;
+
+#[cfg(feature = "esp-idf")]
+mod manager {
+    use super::*;
+    use crate::{
+        Ds18b20Driver,
+        logging::{Subsystem, log},
+        pipeline::Reading,
+    };
+    use log::Level;
+
+    struct Bus<'a> {
+        label: String,
+        driver: Ds18b20Driver<'a>,
+        addresses: Vec<Address>,
+        health: Health,
+    }
+
+    /// Bus manager
+    #[derive(Default)]
+    pub struct BusManager<'a> {
+        buses: Vec<Bus<'a>>,
+    }
+
+    impl<'a> BusManager<'a> {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Adds the bus, labeled by its pin or channel.
+        pub fn add(&mut self, label: impl Into<String>, driver: Ds18b20Driver<'a>) -> BusId {
+            let bus = BusId(self.buses.len() as _);
+            self.buses.push(Bus {
+                label: label.into(),
+                driver,
+                addresses: Vec::new(),
+                health: Health::default(),
+            });
+            bus
+        }
+
+        pub fn ids(&self) -> impl Iterator<Item = BusId> {
+            (0..self.buses.len()).map(|index| BusId(index as _))
+        }
+
+        pub fn label(&self, bus: BusId) -> Option<&str> {
+            Some(&self.buses.get(bus.0 as usize)?.label)
+        }
+
+        pub fn driver(&mut self, bus: BusId) -> Option<&mut Ds18b20Driver<'a>> {
+            Some(&mut self.buses.get_mut(bus.0 as usize)?.driver)
+        }
+
+        pub fn health(&self, bus: BusId) -> Option<&Health> {
+            Some(&self.buses.get(bus.0 as usize)?.health)
+        }
+
+        /// The sensors found on the bus by the last scan.
+        pub fn addresses(&self, bus: BusId) -> &[Address] {
+            self.buses
+                .get(bus.0 as usize)
+                .map_or(&[], |bus| &bus.addresses)
+        }
+
+        /// The bus the sensor was found on.
+        pub fn locate(&self, address: &Address) -> Option<BusId> {
+            self.ids()
+                .find(|&bus| self.addresses(bus).contains(address))
+        }
+
+        /// Scans all buses concurrently. A failed bus is logged and keeps
+        /// its previous sensors, a bus with more than its
+        /// [`max_devices`](Ds18b20Driver::max_devices) keeps the first ones
+        /// found; the error of the first failed bus is returned.
+        pub fn scan(&mut self) -> Result<()> {
+            self.scan_with(|_| {})
+        }
+
+        /// Scans all buses concurrently, reporting the combined progress
+        /// after every search pass.
+        pub fn scan_with(&mut self, progress: impl FnMut(&ScanProgress)) -> Result<()> {
+            let start = Instant::now();
+            let searches = self
+                .buses
+                .iter_mut()
+                .map(|bus| {
+                    let driver = &mut bus.driver;
+                    let max = driver.max_devices;
+                    let search = move || {
+                        let driver = driver;
+                        driver.scan_iter()
+                    };
+                    (search, max)
+                })
+                .collect();
+            let searched = search_all(searches, progress);
+            let mut result = Ok(());
+            let mut slowest = Duration::ZERO;
+            for (bus, mut searched) in self.buses.iter_mut().zip(searched) {
+                slowest = slowest.max(searched.duration);
+                if searched.result.is_ok() && searched.count > searched.found.len() {
+                    searched.result = Err(Error::TooManyDevices {
+                        found: searched.count,
+                        max: searched.found.len(),
+                    });
+                }
+                bus.health.record_scan(&searched.result);
+                match searched.result {
+                    Ok(()) => {
+                        log!(
+                            Subsystem::Bus,
+                            Level::Debug,
+                            "Scan of {} found {} sensors in {:?}",
+                            bus.label,
+                            searched.count,
+                            searched.duration,
+                        );
+                        bus.addresses = searched.found;
+                    }
+                    // The first sensors are kept.
+                    Err(error @ Error::TooManyDevices { .. }) => {
+                        log!(
+                            Subsystem::Bus,
+                            Level::Error,
+                            "Scan of {} failed: {error}",
+                            bus.label,
+                        );
+                        bus.addresses = searched.found;
+                        result = result.and(Err(error));
+                    }
+                    Err(error) => {
+                        log!(
+                            Subsystem::Bus,
+                            Level::Warn,
+                            "Scan of {} failed: {error}",
+                            bus.label,
+                        );
+                        result = result.and(Err(error));
+                    }
+                }
+            }
+            log!(
+                Subsystem::Bus,
+                Level::Info,
+                "Scanned {} buses in {:?}, the slowest in {slowest:?}",
+                self.buses.len(),
+                start.elapsed(),
+            );
+            result
+        }
+
+        /// Reads the sensor on the bus it was found on.
+        pub fn read(&mut self, address: &Address) -> Result<Reading> {
+            let bus = self.locate(address).ok_or(Error::DeviceNotFound)?;
+            self.read_from(bus, address)
+        }
+
+        /// Reads all sensors on all buses.
+        pub fn read_all(&mut self) -> Vec<(BusId, Result<Reading>)> {
+            let mut readings = Vec::new();
+            for bus in self.ids().collect::<Vec<_>>() {
+                for address in self.addresses(bus).to_vec() {
+                    readings.push((bus, self.read_from(bus, &address)));
+                }
+            }
+            readings
+        }
+
+        fn read_from(&mut self, id: BusId, address: &Address) -> Result<Reading> {
+            let bus = &mut self.buses[id.0 as usize];
+            let reading = bus.driver.read(address).map(|reading| Reading {
+                bus: Some(id),
+                ..reading
+            });
+            bus.health.record(&reading);
+            if let Err(error) = &reading {
+                log!(
+                    Subsystem::Bus,
+                    Level::Warn,
+                    "Read of {address} on {} failed: {error}",
+                    bus.label,
+                );
+            }
+            reading
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn health() {
+        let mut health = Health::default();
+        health.record(&Ok(()));
+        health.record::<()>(&Err(Error::DeviceNotFound));
+        health.record::<()>(&Err(Error::ConversionTimeout));
+        assert_eq!(health.reads, 1);
+        assert_eq!(health.consecutive_failures, 2);
+        assert_eq!(health.last_error, Some(Error::ConversionTimeout));
+        assert!((health.failure_rate() - 2.0 / 3.0).abs() < 1e-6);
+        health.record(&Ok(()));
+        assert_eq!(health.consecutive_failures, 0);
+        // Scans don't skew the read failure rate.
+        health.record_scan::<()>(&Err(Error::TooManyDevices { found: 65, max: 64 }));
+        health.record_scan(&Ok(()));
+        assert_eq!((health.scans, health.scan_failures), (2, 1));
+        assert_eq!((health.reads, health.failures), (2, 2));
+        assert_eq!(health.failure_rate(), 0.5);
+    }
+
+    /// Four buses of five sensors, 20 ms per search pass: 400 ms one bus
+    /// after another.
+    #[cfg(feature = "std")]
+    #[test]
+    fn search_all() {
+        let pass = Duration::from_millis(20);
+        let search = |bus: u64| {
+            move || {
+                Ok((0..5).map(move |sensor| {
+                    thread::sleep(pass);
+                    Ok(Address(bus << 8 | sensor))
+                }))
+            }
+        };
+        let start = Instant::now();
+        let mut progress = Vec::new();
+        let searched = super::search_all((0..4).map(|bus| (search(bus), 3)).collect(), |state| {
+            progress.push(*state)
+        });
+        assert!(start.elapsed() < pass * 5 * 3);
+        assert_eq!(searched.len(), 4);
+        for (bus, searched) in searched.iter().enumerate() {
+            assert_eq!(searched.count, 5);
+            assert_eq!(
+                searched.found,
+                [0, 1, 2].map(|sensor| Address((bus as u64) << 8 | sensor))
+            );
+            assert_eq!(searched.result, Ok(()));
+        }
+        assert_eq!(
+            progress.last(),
+            Some(&ScanProgress {
+                buses: 4,
+                finished: 4,
+                passes: 20,
+                found: 12,
+            })
+        );
+        // Buses progress side by side.
+        assert!(progress[..8].iter().all(|state| state.finished == 0));
+
+        let searched = super::search_all(
+            vec![(
+                || Err::<core::iter::Empty<_>, _>(Error::ConversionPending),
+                3,
+            )],
+            |_| {},
+        );
+        assert_eq!(searched[0].result, Err(Error::ConversionPending));
+    }
+}
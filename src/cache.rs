@@ -0,0 +1,149 @@
+//! Last-known-good cache
+//!
+//! Keeps the last good reading of every sensor. When a read fails after its
+//! retries the cached reading is served instead, tagged with its age in
+//! [`Reading::stale`], so control loops can decide how to degrade:
+//!
+//! ```ignore
+//! let mut cache = Cache::new().max_age(Duration::from_secs(60));
+//! let reading = thermometer.read_cached(&address, &mut cache)?;
+//! if reading.is_stale() {
+//!     // ...
+//! }
+//! ```
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::Result,
+    logging::{Subsystem, log},
+    pipeline::Reading,
+};
+use log::Level;
+use std::time::{Duration, Instant};
+
+/// Last-known-good cache
+#[derive(Clone, Debug, Default)]
+pub struct Cache<const N: usize = CAPACITY> {
+    readings: Map<Address, (Reading, Instant), N>,
+    max_age: Option<Duration>,
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const N: usize> Cache<N> {
+    /// Sets the maximum number of cached sensors.
+    pub fn capacity<const M: usize>(self) -> Cache<M> {
+        Cache {
+            readings: self.readings.into_capacity(),
+            max_age: self.max_age,
+        }
+    }
+
+    /// Sets the maximum age of a served reading. Older readings are not
+    /// served and the read error is returned instead.
+    pub fn max_age(self, max_age: Duration) -> Self {
+        Self {
+            max_age: Some(max_age),
+            ..self
+        }
+    }
+
+    /// The cached reading of the sensor, tagged with its age.
+    pub fn get(&self, address: &Address) -> Option<Reading> {
+        let (reading, instant) = self.readings.get(address)?;
+        let age = instant.elapsed();
+        if self.max_age.is_some_and(|max_age| age > max_age) {
+            return None;
+        }
+        Some(Reading {
+            stale: Some(age),
+            ..*reading
+        })
+    }
+
+    /// Caches a good reading or, if the read failed, serves the cached one.
+    pub fn resolve(&mut self, address: &Address, result: Result<Reading>) -> Result<Reading> {
+        match result {
+            Ok(reading) => {
+                if let Err(error) = self.readings.insert(*address, (reading, Instant::now())) {
+                    log!(
+                        Subsystem::Sampler,
+                        Level::Warn,
+                        "Reading of {address:x?} not cached: {error}"
+                    );
+                }
+                Ok(reading)
+            }
+            Err(error) => {
+                let reading = self.get(address).ok_or(error)?;
+                log!(
+                    Subsystem::Sampler,
+                    Level::Warn,
+                    "Read of {address:x?} failed ({error}), serving a reading {:?} old",
+                    reading.stale.unwrap_or_default(),
+                );
+                Ok(reading)
+            }
+        }
+    }
+
+    /// Forgets the cached reading of the sensor.
+    pub fn clear(&mut self, address: &Address) {
+        self.readings.remove(address);
+    }
+}
+
+#[cfg(feature = "esp-idf")]
+impl<'a> crate::Ds18b20Driver<'a> {
+    /// Receive reading, falling back on the cache if the read fails.
+    pub fn read_cached<const N: usize>(
+        &mut self,
+        address: &Address,
+        cache: &mut Cache<N>,
+    ) -> Result<Reading> {
+        let result = self.read(address);
+        cache.resolve(address, result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn resolve() {
+        let address = Address(1);
+        let mut cache = Cache::new();
+        assert_eq!(
+            cache.resolve(&address, Err(Error::DeviceNotFound)),
+            Err(Error::DeviceNotFound),
+        );
+        let reading = Reading::new(address, 21.5);
+        assert_eq!(cache.resolve(&address, Ok(reading)), Ok(reading));
+        let stale = cache.resolve(&address, Err(Error::DeviceNotFound)).unwrap();
+        assert_eq!(stale.temperature, 21.5);
+        assert!(stale.is_stale());
+        cache.clear(&address);
+        assert_eq!(cache.get(&address), None);
+    }
+
+    #[test]
+    fn max_age() {
+        let address = Address(1);
+        let mut cache = Cache::new().max_age(Duration::ZERO);
+        cache
+            .resolve(&address, Ok(Reading::new(address, 21.5)))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(
+            cache.resolve(&address, Err(Error::DeviceNotFound)),
+            Err(Error::DeviceNotFound),
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! Cooperative cancellation
+//!
+//! Blocking waits of the driver (conversions, sweeps, queued operations)
+//! sleep in chunks and check a [`Cancellation`] token in between, so another
+//! task can interrupt them promptly, e.g. on shutdown:
+//!
+//! ```ignore
+//! let cancellation = thermometer.cancellation();
+//! thread::spawn(move || {
+//!     wait_for_shutdown();
+//!     cancellation.cancel();
+//! });
+//! match thermometer.temperature(&address) {
+//!     Err(Error::Cancelled) => return,
+//!     // ...
+//! }
+//! ```
+
+use crate::{Error, Result};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The longest uninterrupted sleep.
+pub const CHUNK: Duration = Duration::from_millis(10);
+
+/// Cancellation token
+///
+/// Clones share the state.
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Interrupts the current and all further waits until reset.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Sleeps for the duration in chunks, failing with [`Error::Cancelled`]
+    /// as soon as the token is cancelled.
+    pub fn sleep(&self, duration: Duration) -> Result<()> {
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            thread::sleep(remaining.min(CHUNK));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sleep() {
+        let cancellation = Cancellation::new();
+        assert_eq!(cancellation.sleep(Duration::from_millis(1)), Ok(()));
+        let start = Instant::now();
+        let cancel = cancellation.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            cancel.cancel();
+        });
+        assert_eq!(
+            cancellation.sleep(Duration::from_secs(10)),
+            Err(Error::Cancelled)
+        );
+        assert!(start.elapsed() < Duration::from_secs(1));
+        handle.join().unwrap();
+        cancellation.reset();
+        assert_eq!(cancellation.sleep(Duration::ZERO), Ok(()));
+    }
+}
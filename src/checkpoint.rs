@@ -0,0 +1,216 @@
+//! Deep-sleep checkpoints
+//!
+//! A duty-cycled node boots from scratch on every wake. The filter state of
+//! the pipeline (the smoothed values, the last values passed by the
+//! deadband) is checkpointed to RTC slow memory before sleeping and resumed
+//! after the wake, so the filters don't re-converge every cycle. Anything
+//! else small, e.g. a telemetry sequence number, fits alongside:
+//!
+//! ```ignore
+//! #[unsafe(link_section = ".rtc.data")]
+//! static mut CHECKPOINT: RtcStore = RtcStore::new();
+//!
+//! let checkpoint = unsafe { &mut *addr_of_mut!(CHECKPOINT) };
+//! pipeline.resume(checkpoint)?;
+//! let sequence = match checkpoint.load("sequence")? {
+//!     Some(bytes) => u32::from_le_bytes(bytes.try_into().unwrap()),
+//!     None => 0,
+//! };
+//! publish(sequence, pipeline.process(thermometer.read(&address)?));
+//! pipeline.checkpoint(checkpoint)?;
+//! checkpoint.save("sequence", &(sequence + 1).to_le_bytes())?;
+//! deep_sleep(interval);
+//! ```
+//!
+//! Each stage is checkpointed under its own key, two smoothing stages, or two
+//! pipelines sharing the store, need distinct ones:
+//!
+//! ```ignore
+//! let pipeline = Pipeline::new()
+//!     .stage(Smoothing::new(0.5).key("boiler.smoothing"))
+//!     .stage(Smoothing::new(0.1).key("trend.smoothing"));
+//! ```
+//!
+//! RTC memory survives deep sleep, not a power loss or a reset. The
+//! checkpoint carries a CRC, a store without a valid one (e.g. after a cold
+//! boot) reads as empty.
+//!
+//! Layout, little-endian:
+//!
+//! | bytes | content |
+//! |-------|---------|
+//! | 4     | magic   |
+//! | 2     | the length of the entries |
+//! | ...   | per entry the key length (`u8`), the key, the value length (`u16`) and the value |
+//! | 1     | CRC of the bytes before |
+
+use crate::{
+    crc8,
+    error::{Error, Result},
+    persistence::Store,
+};
+use alloc::vec::Vec;
+
+/// Default checkpoint size (bytes)
+pub const CHECKPOINT: usize = 2048;
+
+const MAGIC: [u8; 4] = *b"TCKP";
+/// Magic and length
+const HEADER: usize = 6;
+
+/// Store in a fixed buffer, e.g. in RTC memory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RtcStore<const N: usize = CHECKPOINT> {
+    buffer: [u8; N],
+}
+
+impl<const N: usize> RtcStore<N> {
+    pub const fn new() -> Self {
+        Self { buffer: [0; N] }
+    }
+
+    /// Forgets all entries.
+    pub fn clear(&mut self) {
+        self.buffer = [0; N];
+    }
+
+    /// The entries of a valid checkpoint.
+    fn entries(&self) -> Option<&[u8]> {
+        let header = self.buffer.get(..HEADER)?;
+        if header[..4] != MAGIC {
+            return None;
+        }
+        let end = HEADER + u16::from_le_bytes([header[4], header[5]]) as usize;
+        crc8::check(self.buffer.get(..=end)?).ok()?;
+        Some(&self.buffer[HEADER..end])
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        let mut entries = self.entries().unwrap_or_default();
+        core::iter::from_fn(move || {
+            let (&length, rest) = entries.split_first()?;
+            let (key, rest) = rest.split_at_checked(length as _)?;
+            let (length, rest) = rest.split_first_chunk()?;
+            let (value, rest) = rest.split_at_checked(u16::from_le_bytes(*length) as _)?;
+            entries = rest;
+            Some((key, value))
+        })
+    }
+}
+
+impl<const N: usize> Default for RtcStore<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fails with [`Error::Capacity`] if the entries don't fit, the store is
+/// unchanged then.
+impl<const N: usize> Store for RtcStore<N> {
+    fn save(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        let mut entries = Vec::new();
+        let mut push = |key: &[u8], value: &[u8]| -> Result<()> {
+            let key_length = u8::try_from(key.len()).map_err(|_| Error::Capacity(N))?;
+            let value_length = u16::try_from(value.len()).map_err(|_| Error::Capacity(N))?;
+            entries.push(key_length);
+            entries.extend_from_slice(key);
+            entries.extend_from_slice(&value_length.to_le_bytes());
+            entries.extend_from_slice(value);
+            Ok(())
+        };
+        for (saved, saved_value) in self.iter() {
+            if saved != key.as_bytes() {
+                push(saved, saved_value)?;
+            }
+        }
+        push(key.as_bytes(), value)?;
+        let end = HEADER + entries.len();
+        let length = u16::try_from(entries.len()).map_err(|_| Error::Capacity(N))?;
+        if end >= N {
+            return Err(Error::Capacity(N));
+        }
+        self.buffer[..4].copy_from_slice(&MAGIC);
+        self.buffer[4..HEADER].copy_from_slice(&length.to_le_bytes());
+        self.buffer[HEADER..end].copy_from_slice(&entries);
+        self.buffer[end] = crc8::calculate(&self.buffer[..end]);
+        Ok(())
+    }
+
+    fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .iter()
+            .find(|(saved, _)| *saved == key.as_bytes())
+            .map(|(_, value)| value.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        address::Address,
+        pipeline::{Deadband, Pipeline, Reading, Smoothing, Stage},
+    };
+
+    #[test]
+    fn store() {
+        let mut store = RtcStore::<32>::new();
+        assert_eq!(store.load("sequence"), Ok(None));
+        store.save("sequence", &1u32.to_le_bytes()).unwrap();
+        store.save("a", &[1, 2]).unwrap();
+        store.save("sequence", &2u32.to_le_bytes()).unwrap();
+        assert_eq!(
+            store.load("sequence"),
+            Ok(Some(2u32.to_le_bytes().to_vec()))
+        );
+        assert_eq!(store.load("a"), Ok(Some([1, 2].to_vec())));
+        assert_eq!(store.save("b", &[0; 16]), Err(Error::Capacity(32)));
+        assert_eq!(store.load("b"), Ok(None));
+        // Garbage after a cold boot
+        store.buffer[HEADER] ^= 1;
+        assert_eq!(store.load("a"), Ok(None));
+    }
+
+    #[test]
+    fn resume() {
+        let address = Address(1);
+        let pipeline = || {
+            Pipeline::new()
+                .stage(Smoothing::new(0.5))
+                .stage(Deadband::new(1.0))
+        };
+        let mut store = RtcStore::<256>::new();
+        let mut before = pipeline();
+        before.process(Reading::new(address, 20.0));
+        before.process(Reading::new(address, 22.0));
+        before.checkpoint(&mut store).unwrap();
+        // Woken up: smoothed from 21 °C, 21.5 °C is within the deadband of
+        // the 21 °C passed last.
+        let mut after = pipeline();
+        after.resume(&mut store).unwrap();
+        assert_eq!(after.process(Reading::new(address, 22.0)), None);
+        assert_eq!(
+            after.process(Reading::new(address, 24.0)),
+            Some(Reading::new(address, 22.75))
+        );
+
+        // Two smoothing stages keep their own state.
+        let pipeline = || {
+            Pipeline::new()
+                .stage(Smoothing::new(0.5).key("fast"))
+                .stage(Smoothing::new(0.5).key("slow"))
+        };
+        let mut store = RtcStore::<256>::new();
+        let mut before = pipeline();
+        before.process(Reading::new(address, 20.0));
+        before.process(Reading::new(address, 24.0));
+        before.checkpoint(&mut store).unwrap();
+        let mut after = pipeline();
+        after.resume(&mut store).unwrap();
+        // Smoothed to 24 °C from 22 °C, then to 22.5 °C from 21 °C.
+        assert_eq!(
+            after.process(Reading::new(address, 26.0)),
+            Some(Reading::new(address, 22.5))
+        );
+    }
+}
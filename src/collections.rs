@@ -0,0 +1,282 @@
+//! Fixed-capacity collections
+//!
+//! The capacity `N` is a hard limit regardless of the backing storage. With
+//! the `heapless` feature the entries are stored inline in `heapless`
+//! collections, so the structures built on top of them never allocate;
+//! otherwise they are stored on the heap and allocated on demand.
+
+use crate::error::{Error, Result};
+#[cfg(not(feature = "heapless"))]
+use alloc::{collections::VecDeque, vec::Vec};
+
+/// Default capacity, the maximum number of sensors tracked by a collection.
+pub const CAPACITY: usize = 64;
+
+/// Linear map
+///
+/// Keyed by equality only, which is fast enough for the number of sensors on
+/// a bus.
+#[derive(Clone, Debug)]
+pub struct Map<K, V, const N: usize = CAPACITY> {
+    #[cfg(feature = "heapless")]
+    entries: heapless::Vec<(K, V), N>,
+    #[cfg(not(feature = "heapless"))]
+    entries: Vec<(K, V)>,
+}
+
+impl<K: PartialEq, V, const N: usize> Map<K, V, N> {
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(feature = "heapless")]
+            entries: heapless::Vec::new(),
+            #[cfg(not(feature = "heapless"))]
+            entries: Vec::new(),
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.entries.len() >= N
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries
+            .iter()
+            .find_map(|(k, v)| (k == key).then_some(v))
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.entries
+            .iter_mut()
+            .find_map(|(k, v)| (k == key).then_some(v))
+    }
+
+    /// Inserts the value, returning the previous one. Fails with
+    /// [`Error::Capacity`] if the key is new and the map is full.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>> {
+        if let Some(previous) = self.get_mut(&key) {
+            return Ok(Some(core::mem::replace(previous, value)));
+        }
+        self.push(key, value)?;
+        Ok(None)
+    }
+
+    /// Returns the value, inserting `value` if the key is new. Fails with
+    /// [`Error::Capacity`] if the key is new and the map is full.
+    pub fn get_or_insert(&mut self, key: K, value: V) -> Result<&mut V> {
+        let index = match self.entries.iter().position(|(k, _)| *k == key) {
+            Some(index) => index,
+            None => {
+                self.push(key, value)?;
+                self.entries.len() - 1
+            }
+        };
+        Ok(&mut self.entries[index].1)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.swap_remove(index).1)
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        self.entries.retain_mut(|(k, v)| f(k, v));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.entries.iter_mut().map(|(k, v)| (&*k, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    /// Moves the entries into a map of another capacity, dropping the entries
+    /// that don't fit.
+    pub fn into_capacity<const M: usize>(self) -> Map<K, V, M> {
+        let mut map = Map::new();
+        for (key, value) in self.entries {
+            if map.push(key, value).is_err() {
+                break;
+            }
+        }
+        map
+    }
+
+    fn push(&mut self, key: K, value: V) -> Result<()> {
+        if self.is_full() {
+            return Err(Error::Capacity(N));
+        }
+        #[cfg(feature = "heapless")]
+        let _ = self.entries.push((key, value));
+        #[cfg(not(feature = "heapless"))]
+        self.entries.push((key, value));
+        Ok(())
+    }
+}
+
+impl<K: PartialEq, V, const N: usize> Default for Map<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Double-ended queue
+#[derive(Clone, Debug)]
+pub struct Deque<T, const N: usize = CAPACITY> {
+    #[cfg(feature = "heapless")]
+    items: heapless::Deque<T, N>,
+    #[cfg(not(feature = "heapless"))]
+    items: VecDeque<T>,
+}
+
+impl<T, const N: usize> Deque<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(feature = "heapless")]
+            items: heapless::Deque::new(),
+            #[cfg(not(feature = "heapless"))]
+            items: VecDeque::new(),
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() >= N
+    }
+
+    /// Appends the item, giving it back if the deque is full.
+    pub fn push_back(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+        #[cfg(feature = "heapless")]
+        return self.items.push_back(item);
+        #[cfg(not(feature = "heapless"))]
+        {
+            self.items.push_back(item);
+            Ok(())
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.items.pop_front()
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.items.front()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+}
+
+impl<T, const N: usize> Default for Deque<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert() {
+        let mut map = Map::<u8, u8, 2>::new();
+        assert_eq!(map.insert(1, 10), Ok(None));
+        assert_eq!(map.insert(2, 20), Ok(None));
+        assert_eq!(map.insert(1, 11), Ok(Some(10)));
+        assert_eq!(map.insert(3, 30), Err(Error::Capacity(2)));
+        assert_eq!(map.len(), 2);
+        assert!(map.is_full());
+        assert_eq!(map.get(&1), Some(&11));
+        assert_eq!(map.get(&3), None);
+    }
+
+    #[test]
+    fn get_or_insert() {
+        let mut map = Map::<u8, u8, 1>::new();
+        *map.get_or_insert(1, 10).unwrap() += 1;
+        *map.get_or_insert(1, 10).unwrap() += 1;
+        assert_eq!(map.get(&1), Some(&12));
+        assert_eq!(map.get_or_insert(2, 20), Err(Error::Capacity(1)));
+    }
+
+    #[test]
+    fn remove() {
+        let mut map = Map::<u8, u8, 2>::new();
+        map.insert(1, 10).unwrap();
+        map.insert(2, 20).unwrap();
+        assert_eq!(map.remove(&1), Some(10));
+        assert_eq!(map.remove(&1), None);
+        assert_eq!(map.insert(3, 30), Ok(None));
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), [2, 3]);
+    }
+
+    #[test]
+    fn into_capacity() {
+        let mut map = Map::<u8, u8, 3>::new();
+        map.insert(1, 10).unwrap();
+        map.insert(2, 20).unwrap();
+        map.insert(3, 30).unwrap();
+        let map = map.into_capacity::<2>();
+        assert_eq!(map.capacity(), 2);
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn deque() {
+        let mut deque = Deque::<u8, 2>::new();
+        assert_eq!(deque.push_back(1), Ok(()));
+        assert_eq!(deque.push_back(2), Ok(()));
+        assert_eq!(deque.push_back(3), Err(3));
+        assert!(deque.is_full());
+        assert_eq!(deque.pop_front(), Some(1));
+        assert_eq!(deque.push_back(3), Ok(()));
+        assert_eq!(deque.iter().copied().collect::<Vec<_>>(), [2, 3]);
+    }
+}
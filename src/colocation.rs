@@ -0,0 +1,164 @@
+//! Co-location calibration
+//!
+//! All the probes are put in the same bath and sampled together. The offset
+//! of every sensor is the mean difference between the reference sensor and
+//! the sensor over the sweeps, and is stored in the [`Calibration`] table:
+//!
+//! ```ignore
+//! let colocation = thermometer.co_locate(reference, &addresses, 16)?;
+//! colocation.apply(&mut calibration)?;
+//! ```
+//!
+//! The readings must be uncalibrated. The reference sensor keeps its own
+//! offset and the other sensors are calibrated against its calibrated
+//! temperature.
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::Result,
+    pipeline::{Calibration, Reading},
+};
+
+/// Co-location calibration run
+#[derive(Clone, Debug)]
+pub struct CoLocation<const N: usize = CAPACITY> {
+    reference: Address,
+    differences: Map<Address, Difference, N>,
+}
+
+impl CoLocation {
+    pub fn new(reference: Address) -> Self {
+        Self {
+            reference,
+            differences: Map::new(),
+        }
+    }
+}
+
+impl<const N: usize> CoLocation<N> {
+    /// Sets the maximum number of calibrated sensors.
+    pub fn capacity<const M: usize>(self) -> CoLocation<M> {
+        CoLocation {
+            reference: self.reference,
+            differences: self.differences.into_capacity(),
+        }
+    }
+
+    pub fn reference(&self) -> Address {
+        self.reference
+    }
+
+    /// Records the readings of one sweep. Sweeps without a reading of the
+    /// reference sensor are ignored.
+    pub fn push<'a>(
+        &mut self,
+        readings: impl IntoIterator<Item = &'a Reading> + Clone,
+    ) -> Result<()> {
+        let Some(reference) = readings
+            .clone()
+            .into_iter()
+            .find(|reading| reading.address == self.reference)
+        else {
+            return Ok(());
+        };
+        for reading in readings {
+            if reading.address == self.reference {
+                continue;
+            }
+            let difference = self
+                .differences
+                .get_or_insert(reading.address, Difference::default())?;
+            difference.sum += reference.temperature - reading.temperature;
+            difference.count += 1;
+        }
+        Ok(())
+    }
+
+    /// The offsets (°C) relative to the uncalibrated reference temperature.
+    pub fn offsets(&self) -> impl Iterator<Item = (Address, f32)> + '_ {
+        self.differences
+            .iter()
+            .map(|(address, difference)| (*address, difference.sum / difference.count as f32))
+    }
+
+    /// Stores the offsets in the calibration table.
+    pub fn apply<const M: usize>(&self, calibration: &mut Calibration<M>) -> Result<()> {
+        let reference = calibration.get(&self.reference).unwrap_or(0.0);
+        for (address, offset) in self.offsets() {
+            calibration.set_offset(address, reference + offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Accumulated difference
+#[derive(Clone, Copy, Debug, Default)]
+struct Difference {
+    sum: f32,
+    count: usize,
+}
+
+#[cfg(feature = "esp-idf")]
+impl<'a> crate::Ds18b20Driver<'a> {
+    /// Runs a co-location calibration over the number of sweeps. Failed
+    /// readings are skipped.
+    pub fn co_locate(
+        &mut self,
+        reference: Address,
+        addresses: &[Address],
+        sweeps: usize,
+    ) -> Result<CoLocation> {
+        let mut colocation = CoLocation::new(reference);
+        for _ in 0..sweeps {
+            let sweep = self.read_all(addresses, Default::default())?;
+            let readings: alloc::vec::Vec<_> = sweep.readings.into_iter().flatten().collect();
+            colocation.push(&readings)?;
+        }
+        Ok(colocation)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline::Stage;
+
+    fn reading(address: u64, temperature: f32) -> Reading {
+        Reading::new(Address(address), temperature)
+    }
+
+    #[test]
+    fn offsets() {
+        let mut colocation = CoLocation::new(Address(1));
+        colocation
+            .push(&[reading(1, 20.0), reading(2, 19.5), reading(3, 21.0)])
+            .unwrap();
+        colocation
+            .push(&[reading(1, 20.5), reading(2, 20.5)])
+            .unwrap();
+        // No reference reading.
+        colocation.push(&[reading(2, 0.0)]).unwrap();
+        assert_eq!(
+            colocation.offsets().collect::<Vec<_>>(),
+            [(Address(2), 0.25), (Address(3), -1.0)],
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let mut colocation = CoLocation::new(Address(1));
+        colocation
+            .push(&[reading(1, 20.0), reading(2, 19.5)])
+            .unwrap();
+        let mut calibration = Calibration::new()
+            .offset(Address(1), 0.25)
+            .offset(Address(2), 1.0);
+        colocation.apply(&mut calibration).unwrap();
+        assert_eq!(calibration.get(&Address(2)), Some(0.75));
+        assert_eq!(
+            calibration.process(reading(2, 19.5)).unwrap().temperature,
+            calibration.process(reading(1, 20.0)).unwrap().temperature,
+        );
+    }
+}
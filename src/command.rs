@@ -0,0 +1,170 @@
+//! Command codes
+//!
+//! The ROM commands of the 1-Wire protocol and the function commands of the
+//! DS18B20 in one vocabulary, shared by the driver, the raw transactions and
+//! diagnostics output:
+//!
+//! ```ignore
+//! thermometer.raw_transaction(|bus| {
+//!     bus.write_bytes(&command::match_rom(&address))?;
+//!     bus.command(CommandCode::ReadPowerSupply)?;
+//!     // ...
+//! })?;
+//! ```
+
+use crate::{address::Address, error::Error, scratchpad::AlarmThreshold};
+use core::fmt::{self, Display, Formatter};
+
+/// Command code
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CommandCode {
+    /// Enumerates the ROM codes of all devices.
+    SearchRom = 0xF0,
+    /// Reads the ROM code of the only device on the bus.
+    ReadRom = 0x33,
+    /// Addresses the device with the following ROM code.
+    MatchRom = 0x55,
+    /// Addresses all devices.
+    SkipRom = 0xCC,
+    /// Enumerates the ROM codes of the devices with an alarm.
+    AlarmSearch = 0xEC,
+    /// Starts a temperature conversion.
+    ConvertTemperature = 0x44,
+    /// Writes TH, TL and the configuration register to the scratchpad.
+    WriteScratchpad = 0x4E,
+    /// Reads the scratchpad, CRC last.
+    ReadScratchpad = 0xBE,
+    /// Copies TH, TL and the configuration register to EEPROM.
+    CopyScratchpad = 0x48,
+    /// Recalls TH, TL and the configuration register from EEPROM.
+    RecallE2 = 0xB8,
+    /// Reads whether the device is parasite-powered.
+    ReadPowerSupply = 0xB4,
+}
+
+impl CommandCode {
+    pub const ALL: [Self; 11] = [
+        Self::SearchRom,
+        Self::ReadRom,
+        Self::MatchRom,
+        Self::SkipRom,
+        Self::AlarmSearch,
+        Self::ConvertTemperature,
+        Self::WriteScratchpad,
+        Self::ReadScratchpad,
+        Self::CopyScratchpad,
+        Self::RecallE2,
+        Self::ReadPowerSupply,
+    ];
+
+    /// Whether it is a ROM command, which follows the reset pulse.
+    pub const fn is_rom(&self) -> bool {
+        matches!(
+            self,
+            Self::SearchRom | Self::ReadRom | Self::MatchRom | Self::SkipRom | Self::AlarmSearch
+        )
+    }
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::SearchRom => "Search ROM",
+            Self::ReadRom => "Read ROM",
+            Self::MatchRom => "Match ROM",
+            Self::SkipRom => "Skip ROM",
+            Self::AlarmSearch => "Alarm Search",
+            Self::ConvertTemperature => "Convert T",
+            Self::WriteScratchpad => "Write Scratchpad",
+            Self::ReadScratchpad => "Read Scratchpad",
+            Self::CopyScratchpad => "Copy Scratchpad",
+            Self::RecallE2 => "Recall E2",
+            Self::ReadPowerSupply => "Read Power Supply",
+        }
+    }
+}
+
+impl From<CommandCode> for u8 {
+    fn from(code: CommandCode) -> Self {
+        code as _
+    }
+}
+
+impl TryFrom<u8> for CommandCode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|code| *code as u8 == value)
+            .ok_or(Error::Command(value))
+    }
+}
+
+/// `Match ROM (0x55)`
+impl Display for CommandCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:#04x})", self.name(), *self as u8)
+    }
+}
+
+/// Match ROM followed by the ROM code.
+pub fn match_rom(address: &Address) -> [u8; 9] {
+    let mut bytes = [CommandCode::MatchRom as _; 9];
+    bytes[1..].copy_from_slice(&address.to_bytes());
+    bytes
+}
+
+/// Write Scratchpad followed by TH, TL and the configuration register.
+pub fn write_scratchpad(
+    alarm_high: AlarmThreshold,
+    alarm_low: AlarmThreshold,
+    configuration_register: u8,
+) -> [u8; 4] {
+    [
+        CommandCode::WriteScratchpad as _,
+        alarm_high.get() as _,
+        alarm_low.get() as _,
+        configuration_register,
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn codes() {
+        let codes = CommandCode::ALL.map(u8::from);
+        assert_eq!(
+            codes,
+            [
+                0xF0, 0x33, 0x55, 0xCC, 0xEC, 0x44, 0x4E, 0xBE, 0x48, 0xB8, 0xB4
+            ]
+        );
+        for code in CommandCode::ALL {
+            assert_eq!(CommandCode::try_from(code as u8), Ok(code));
+        }
+        assert_eq!(CommandCode::try_from(0x00), Err(Error::Command(0x00)));
+        assert_eq!(
+            CommandCode::ALL.iter().filter(|code| code.is_rom()).count(),
+            5
+        );
+        assert_eq!(CommandCode::MatchRom.to_string(), "Match ROM (0x55)");
+    }
+
+    #[test]
+    fn transactions() {
+        assert_eq!(
+            match_rom(&Address(0x1E00_0000_0000_0028)),
+            [0x55, 0x28, 0, 0, 0, 0, 0, 0, 0x1E]
+        );
+        assert_eq!(
+            write_scratchpad(
+                AlarmThreshold::clamped(30),
+                AlarmThreshold::clamped(-5),
+                0x7F
+            ),
+            [0x4E, 30, 0xFB, 0x7F]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! EEPROM commit queue
+//!
+//! A Copy Scratchpad holds the bus for the EEPROM write of every sensor,
+//! behind the strong pull-up on parasite-powered buses. The commits are
+//! queued instead and run while the bus is idle, as many as fit before the
+//! next sample, and each one is reported as an event:
+//!
+//! ```ignore
+//! thermometer.set_resolution(&address, Resolution::Eleven)?;
+//! sampler.commits().push(address)?;
+//! loop {
+//!     sampler.poll()?;
+//!     for event in sampler.commits().events().drain() {
+//!         info!("{event:?}");
+//!     }
+//! }
+//! ```
+//!
+//! Without a [`Sampler`](crate::sampler::Sampler) the application runs the
+//! queue in its own idle time:
+//!
+//! ```ignore
+//! commits.run(idle, |address| thermometer.commit(address));
+//! ```
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Deque},
+    error::{Error, Result},
+    event::{EventQueue, Overflow},
+    logging::{Subsystem, log},
+    timing,
+};
+use core::time::Duration;
+use log::Level;
+
+/// The bus time of a commit: the Match ROM and Copy Scratchpad, then the
+/// EEPROM write.
+pub const COMMIT: Duration = timing::transaction(10, 0).saturating_add(timing::EEPROM_WRITE);
+
+/// Commit event
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommitEvent {
+    Committed(Address),
+    Failed(Address, Error),
+}
+
+/// Queue of pending EEPROM commits
+#[derive(Clone, Debug)]
+pub struct CommitQueue<const N: usize = CAPACITY> {
+    pending: Deque<Address, N>,
+    events: EventQueue<CommitEvent, N>,
+}
+
+impl CommitQueue {
+    pub fn new(overflow: Overflow) -> Self {
+        Self {
+            pending: Deque::new(),
+            events: EventQueue::new(overflow),
+        }
+    }
+}
+
+impl Default for CommitQueue {
+    fn default() -> Self {
+        Self::new(Overflow::default())
+    }
+}
+
+impl<const N: usize> CommitQueue<N> {
+    /// Sets the maximum number of pending commits and queued events.
+    pub fn capacity<const M: usize>(self) -> CommitQueue<M> {
+        CommitQueue {
+            pending: Deque::new(),
+            events: self.events.capacity(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// The sensors waiting for their commit, oldest first.
+    pub fn pending(&self) -> impl Iterator<Item = &Address> {
+        self.pending.iter()
+    }
+
+    /// Queues the commit of the scratchpad of the sensor, unless it is
+    /// queued already. Fails with [`Error::Capacity`] if the queue is full.
+    pub fn push(&mut self, address: Address) -> Result<()> {
+        if self.pending.iter().any(|pending| *pending == address) {
+            return Ok(());
+        }
+        self.pending
+            .push_back(address)
+            .map_err(|_| Error::Capacity(N))
+    }
+
+    /// The queued events.
+    pub fn events(&mut self) -> &mut EventQueue<CommitEvent, N> {
+        &mut self.events
+    }
+
+    /// Runs the pending commits that fit into the idle time, oldest first.
+    /// Returns the number of commits run. A failed commit isn't retried.
+    pub fn run(&mut self, idle: Duration, mut commit: impl FnMut(&Address) -> Result<()>) -> usize {
+        let count = (idle.as_micros() / COMMIT.as_micros()) as usize;
+        let mut run = 0;
+        while run < count
+            && let Some(address) = self.pending.pop_front()
+        {
+            run += 1;
+            let event = match commit(&address) {
+                Ok(()) => CommitEvent::Committed(address),
+                Err(error) => {
+                    log!(
+                        Subsystem::Bus,
+                        Level::Warn,
+                        "Commit of {address} failed: {error}"
+                    );
+                    CommitEvent::Failed(address, error)
+                }
+            };
+            if !self.events.push_or_drop(event) {
+                log!(
+                    Subsystem::Bus,
+                    Level::Warn,
+                    "Commit event {event:?} dropped, the event queue is full"
+                );
+            }
+        }
+        run
+    }
+}
+
+#[cfg(feature = "esp-idf")]
+impl<'a> crate::Ds18b20Driver<'a> {
+    /// Commits the scratchpad of the sensor to EEPROM.
+    pub fn commit(&mut self, address: &Address) -> Result<()> {
+        crate::driver::preflight(address)?;
+        self.retry(|this| this.initialization()?.match_rom(address)?.load_scratchpad())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn run() {
+        let mut commits = CommitQueue::default().capacity::<3>();
+        for address in [1, 2, 1, 3] {
+            commits.push(Address(address)).unwrap();
+        }
+        assert_eq!(commits.push(Address(4)), Err(Error::Capacity(3)));
+        // Not enough time for a commit.
+        assert_eq!(commits.run(COMMIT / 2, |_| unreachable!()), 0);
+        let run = commits.run(COMMIT * 2, |address| match address {
+            Address(2) => Err(Error::DeviceNotFound),
+            _ => Ok(()),
+        });
+        assert_eq!(run, 2);
+        assert_eq!(commits.pending().collect::<Vec<_>>(), [&Address(3)]);
+        assert_eq!(
+            commits.events().drain().collect::<Vec<_>>(),
+            [
+                CommitEvent::Committed(Address(1)),
+                CommitEvent::Failed(Address(2), Error::DeviceNotFound),
+            ]
+        );
+    }
+}
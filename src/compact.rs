@@ -0,0 +1,137 @@
+//! Compact binary readings
+//!
+//! For LoRa or ESP-NOW payloads where every byte counts: each reading takes
+//! three bytes, the index of its sensor in an address table both ends agree
+//! on and the temperature in tenths of a degree (`i16`, little endian):
+//!
+//! ```ignore
+//! let mut payload = [0; 3 * 8];
+//! let length = compact::encode(&readings, &addresses, &mut payload)?;
+//! radio.send(&payload[..length])?;
+//! // ... on the receiver:
+//! for record in compact::decode(&payload)? {
+//!     let address = addresses[record.index as usize];
+//!     let temperature = record.celsius();
+//! }
+//! ```
+
+use crate::{
+    address::Address,
+    error::{Error, Result},
+    pipeline::Reading,
+    unit::Celsius,
+};
+
+/// Encoded reading
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// The index of the sensor in the address table.
+    pub index: u8,
+    /// Temperature (0.1 °C)
+    pub deci_celsius: i16,
+}
+
+impl Record {
+    /// Encoded size (bytes)
+    pub const SIZE: usize = 3;
+
+    pub fn new(index: u8, temperature: Celsius) -> Self {
+        Self {
+            index,
+            deci_celsius: temperature.deci_celsius(),
+        }
+    }
+
+    pub fn celsius(&self) -> Celsius {
+        Celsius::from_deci_celsius(self.deci_celsius)
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let [low, high] = self.deci_celsius.to_le_bytes();
+        [self.index, low, high]
+    }
+
+    pub fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        Self {
+            index: bytes[0],
+            deci_celsius: i16::from_le_bytes([bytes[1], bytes[2]]),
+        }
+    }
+}
+
+/// Encodes the readings into the buffer, returning the encoded length.
+///
+/// Fails with [`Error::InvalidAddress`] on a reading of a sensor not among
+/// the first 256 addresses, and with [`Error::Capacity`] if the buffer is too
+/// small.
+pub fn encode(readings: &[Reading], addresses: &[Address], buffer: &mut [u8]) -> Result<usize> {
+    let length = readings.len() * Record::SIZE;
+    if length > buffer.len() {
+        return Err(Error::Capacity(buffer.len() / Record::SIZE));
+    }
+    let (records, _) = buffer.as_chunks_mut::<{ Record::SIZE }>();
+    for (reading, bytes) in readings.iter().zip(records) {
+        let index = addresses
+            .iter()
+            .position(|address| *address == reading.address)
+            .and_then(|index| u8::try_from(index).ok())
+            .ok_or(Error::InvalidAddress)?;
+        *bytes = Record::new(index, Celsius(reading.temperature)).to_bytes();
+    }
+    Ok(length)
+}
+
+/// Decodes the payload. Fails with [`Error::Decode`] at the trailing bytes
+/// of a truncated payload.
+pub fn decode(payload: &[u8]) -> Result<impl Iterator<Item = Record> + '_> {
+    let (records, remainder) = payload.as_chunks::<{ Record::SIZE }>();
+    if !remainder.is_empty() {
+        return Err(Error::Decode {
+            offset: payload.len() - remainder.len(),
+        });
+    }
+    Ok(records.iter().map(|bytes| Record::from_bytes(*bytes)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn round_trip() {
+        let addresses = [Address(1), Address(2)];
+        let readings = [
+            Reading::new(Address(2), 21.4375),
+            Reading::new(Address(1), -10.25),
+        ];
+        let mut buffer = [0; 8];
+        assert_eq!(
+            encode(&readings, &addresses, &mut buffer[..5]),
+            Err(Error::Capacity(1))
+        );
+        let length = encode(&readings, &addresses, &mut buffer).unwrap();
+        assert_eq!(&buffer[..length], [1, 214, 0, 0, 153, 255]);
+        assert_eq!(
+            decode(&buffer[..length]).unwrap().collect::<Vec<_>>(),
+            [
+                Record {
+                    index: 1,
+                    deci_celsius: 214
+                },
+                Record {
+                    index: 0,
+                    deci_celsius: -103
+                },
+            ]
+        );
+        assert!(matches!(
+            decode(&buffer[..4]),
+            Err(Error::Decode { offset: 3 })
+        ));
+        assert_eq!(
+            encode(&[Reading::new(Address(3), 0.0)], &addresses, &mut buffer),
+            Err(Error::InvalidAddress)
+        );
+    }
+}
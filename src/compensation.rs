@@ -0,0 +1,85 @@
+//! Ambient compensation
+//!
+//! Adjusts the readings by external context the sensor can't see, e.g. the
+//! self-heating of a board-mounted sensor estimated from the CPU load. The
+//! model is a closure returning the [`Compensation`] of a reading, which is
+//! applied and recorded in the reading, so every adjusted temperature can be
+//! traced back to its input:
+//!
+//! ```ignore
+//! let pipeline = Pipeline::new().stage(AmbientCompensation::new(|reading: &Reading| {
+//!     (reading.address == BOARD).then(|| {
+//!         let load = cpu_load();
+//!         Compensation::new(load, -0.8 * load)
+//!     })
+//! }));
+//! ```
+
+use crate::pipeline::{Reading, Stage};
+
+/// Compensation applied to a reading
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Compensation {
+    /// The context the adjustment was computed from, in the unit of the
+    /// model.
+    pub input: f32,
+    /// The adjustment (°C) added to the temperature.
+    pub adjustment: f32,
+}
+
+impl Compensation {
+    pub fn new(input: f32, adjustment: f32) -> Self {
+        Self { input, adjustment }
+    }
+}
+
+/// Ambient compensation stage
+///
+/// Readings the model returns `None` for pass unchanged. A reading that is
+/// compensated again keeps the adjustments summed and the last input.
+pub struct AmbientCompensation<F> {
+    model: F,
+}
+
+impl<F: FnMut(&Reading) -> Option<Compensation>> AmbientCompensation<F> {
+    pub fn new(model: F) -> Self {
+        Self { model }
+    }
+}
+
+impl<F: FnMut(&Reading) -> Option<Compensation>> Stage for AmbientCompensation<F> {
+    fn process(&mut self, mut reading: Reading) -> Option<Reading> {
+        if let Some(mut compensation) = (self.model)(&reading) {
+            reading.temperature += compensation.adjustment;
+            if let Some(previous) = reading.compensation {
+                compensation.adjustment += previous.adjustment;
+            }
+            reading.compensation = Some(compensation);
+        }
+        Some(reading)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::address::Address;
+
+    #[test]
+    fn compensation() {
+        let board = Address(1);
+        let load = 0.5;
+        let mut stage = AmbientCompensation::new(|reading: &Reading| {
+            (reading.address == board).then(|| Compensation::new(load, -2.0 * load))
+        });
+        let reading = stage.process(Reading::new(board, 30.0)).unwrap();
+        assert_eq!(reading.temperature, 29.0);
+        assert_eq!(reading.compensation, Some(Compensation::new(0.5, -1.0)));
+        let reading = stage.process(reading).unwrap();
+        assert_eq!(reading.temperature, 28.0);
+        assert_eq!(reading.compensation, Some(Compensation::new(0.5, -2.0)));
+        let reading = stage.process(Reading::new(Address(2), 30.0)).unwrap();
+        assert_eq!(reading.temperature, 30.0);
+        assert_eq!(reading.compensation, None);
+    }
+}
@@ -0,0 +1,1000 @@
+//! Configuration snapshot
+//!
+//! The complete configuration (registry, labels, calibration, alarm limits,
+//! zone policies and the sampling schedule) as a single TOML document, for
+//! backups and for cloning the configuration to other devices:
+//!
+//! ```ignore
+//! let mut config = Config::capture(&registry, &calibration, &alarms);
+//! sampler.capture(&mut config);
+//! storage.write(config.export().as_bytes())?;
+//! // ... on another device:
+//! let config = Config::import(&text)?;
+//! config.apply(&mut registry, &mut calibration, &mut alarms)?;
+//! sampler.apply(&config)?;
+//! ```
+//!
+//! A running device can be reconfigured remotely, e.g. over MQTT, without
+//! restarting the sampler: only the settings that differ from the active
+//! configuration are applied, and the changes are reported back:
+//!
+//! ```ignore
+//! let changes = active.apply_live(Config::import(&payload)?, &mut registry, &mut calibration, &mut alarms, &mut sampler)?;
+//! for change in &changes {
+//!     info!("{change:?}");
+//! }
+//! ```
+//!
+//! A reload is transactional: if a sensor's resolution can't be written, the
+//! sensors written before it are restored to the resolution read back before
+//! their write, the previous settings stay active and the [`ApplyError`]
+//! reports the outcome per sensor.
+//!
+//! The export is canonical: sensors are sorted by address, policies by zone,
+//! and absent settings are left out.
+//!
+//! ```toml
+//! [sampling]
+//! interval_ms = 10000
+//! jitter_ms = 0
+//!
+//! [[sensor]]
+//! address = "230000046eafbc28"
+//! label = "boiler"
+//! zone = "tank"
+//! offset = -0.25
+//! low = 10
+//! high = 80
+//! release_ms = 500
+//! resolution = 12
+//!
+//! [[policy]]
+//! zone = "tank"
+//! high = 65
+//! ```
+//!
+//! Only the subset above is read: one `key = value` per line, strings,
+//! numbers and comments on lines of their own. Sinks are code and aren't part
+//! of the configuration.
+
+#[cfg(feature = "esp-idf")]
+use crate::{
+    Ds18b20Driver,
+    sampler::{Sampler, State},
+};
+use crate::{
+    address::{Address, Validation},
+    alarm::{Alarms, Limits},
+    error::{Error, Result},
+    pipeline::Calibration,
+    registry::{AlarmPolicy, Registry, Sensor},
+    scratchpad::Resolution,
+};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Display, Formatter, Write},
+    time::Duration,
+};
+
+/// Sampling schedule
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sampling {
+    pub interval: Duration,
+    pub jitter: Duration,
+}
+
+/// Sensor configuration
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SensorConfig {
+    pub address: Address,
+    pub label: Option<String>,
+    pub zone: Option<String>,
+    /// Calibration offset (°C)
+    pub offset: Option<f32>,
+    pub limits: Limits,
+    /// The time the reading is released after the conversion.
+    pub release: Option<Duration>,
+    /// Written to the scratchpad of the sensor, if set.
+    pub resolution: Option<Resolution>,
+}
+
+/// Zone policy
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PolicyConfig {
+    pub zone: String,
+    pub policy: AlarmPolicy,
+}
+
+/// Difference between two configurations
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Sampling(Option<Sampling>),
+    Added(Address),
+    Removed(Address),
+    Label(Address),
+    Zone(Address),
+    Offset(Address),
+    Limits(Address),
+    Release(Address),
+    Resolution {
+        address: Address,
+        resolution: Resolution,
+    },
+    /// The policy of the zone was added, changed or removed.
+    Policy(String),
+}
+
+/// The resolution writes of a reload
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WriteReport {
+    /// The sensors written, in the order of the writes.
+    pub written: Vec<Address>,
+    /// The sensor whose read-back or write failed.
+    pub failed: Option<(Address, Error)>,
+    /// The sensors restored to their previous resolution.
+    pub rolled_back: Vec<Address>,
+    /// The sensors whose restore failed, possibly left with the new
+    /// resolution.
+    pub stuck: Vec<(Address, Error)>,
+}
+
+impl WriteReport {
+    /// Whether all the resolutions were written.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_none()
+    }
+}
+
+/// Failed reload
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApplyError {
+    pub error: Error,
+    /// Empty if the reload failed before writing a resolution.
+    pub report: Box<WriteReport>,
+}
+
+impl From<Error> for ApplyError {
+    fn from(error: Error) -> Self {
+        Self {
+            error,
+            report: Box::default(),
+        }
+    }
+}
+
+impl Display for ApplyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.report.failed {
+            Some((address, _)) => write!(
+                f,
+                "sensor {address}: {} ({} rolled back, {} stuck)",
+                self.error,
+                self.report.rolled_back.len(),
+                self.report.stuck.len(),
+            ),
+            None => self.error.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for ApplyError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Writes the resolutions, reading back each sensor's resolution first. On
+/// the first failure the sensors written, and the failed one if it was read
+/// back, are restored.
+#[cfg_attr(not(feature = "esp-idf"), allow(dead_code))]
+fn write_resolutions<T: ?Sized>(
+    bus: &mut T,
+    resolutions: &[(Address, Resolution)],
+    read: impl Fn(&mut T, &Address) -> Result<Resolution>,
+    write: impl Fn(&mut T, &Address, Resolution) -> Result<()>,
+) -> WriteReport {
+    let mut report = WriteReport::default();
+    let mut previous = Vec::with_capacity(resolutions.len());
+    for &(address, resolution) in resolutions {
+        let written = read(bus, &address).and_then(|old| {
+            previous.push((address, old));
+            write(bus, &address, resolution)
+        });
+        if let Err(error) = written {
+            report.failed = Some((address, error));
+            break;
+        }
+        report.written.push(address);
+    }
+    if report.failed.is_none() {
+        return report;
+    }
+    for (address, resolution) in previous.into_iter().rev() {
+        match write(bus, &address, resolution) {
+            Ok(()) => report.rolled_back.push(address),
+            Err(error) => report.stuck.push((address, error)),
+        }
+    }
+    report
+}
+
+/// Configuration snapshot
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub sampling: Option<Sampling>,
+    pub sensors: Vec<SensorConfig>,
+    pub policies: Vec<PolicyConfig>,
+}
+
+impl Config {
+    /// Captures the configuration of the registered sensors and the zone
+    /// policies.
+    pub fn capture<const N: usize>(
+        registry: &Registry<N>,
+        calibration: &Calibration<N>,
+        alarms: &Alarms<N>,
+    ) -> Self {
+        let mut sensors: Vec<_> = registry
+            .iter()
+            .map(|(address, sensor)| SensorConfig {
+                address: *address,
+                label: sensor.label.clone(),
+                zone: sensor.zone.clone(),
+                offset: calibration.get(address),
+                limits: alarms.get_limits(address).unwrap_or_default(),
+                release: None,
+                resolution: None,
+            })
+            .collect();
+        sensors.sort_by_key(|sensor| sensor.address.0);
+        let mut policies: Vec<_> = registry
+            .policies()
+            .map(|(zone, policy)| PolicyConfig {
+                zone: zone.into(),
+                policy: *policy,
+            })
+            .collect();
+        policies.sort_by(|a, b| a.zone.cmp(&b.zone));
+        Self {
+            sampling: None,
+            sensors,
+            policies,
+        }
+    }
+
+    /// Replaces the sensors, calibration, alarm limits and zone policies with
+    /// the configuration. Nothing is changed if any of it fails, e.g. on a
+    /// duplicate address or label.
+    pub fn apply<const N: usize>(
+        &self,
+        registry: &mut Registry<N>,
+        calibration: &mut Calibration<N>,
+        alarms: &mut Alarms<N>,
+    ) -> Result<()> {
+        let mut new_registry = registry.clone();
+        new_registry.clear();
+        let mut new_calibration = calibration.clone();
+        new_calibration.clear();
+        let mut new_alarms = alarms.clone();
+        new_alarms.clear_limits();
+        for sensor in &self.sensors {
+            new_registry.add(
+                sensor.address,
+                Sensor {
+                    zone: sensor.zone.clone(),
+                    label: sensor.label.clone(),
+                },
+            )?;
+            if let Some(offset) = sensor.offset {
+                new_calibration.set_offset(sensor.address, offset)?;
+            }
+            if sensor.limits != Limits::default() {
+                new_alarms.set_limits(sensor.address, sensor.limits)?;
+            }
+        }
+        for policy in &self.policies {
+            new_registry.set_policy(policy.zone.clone(), policy.policy)?;
+        }
+        *registry = new_registry;
+        *calibration = new_calibration;
+        *alarms = new_alarms;
+        Ok(())
+    }
+
+    /// Applies the changes to this configuration to the sensors,
+    /// calibration, alarm limits and zone policies, leaving the settings they
+    /// don't name as they are. Nothing is changed if any of it fails.
+    pub fn apply_changes<const N: usize>(
+        &self,
+        changes: &[Change],
+        registry: &mut Registry<N>,
+        calibration: &mut Calibration<N>,
+        alarms: &mut Alarms<N>,
+    ) -> Result<()> {
+        let mut new_registry = registry.clone();
+        let mut new_calibration = calibration.clone();
+        let mut new_alarms = alarms.clone();
+        // Labels are released first, so they can move between sensors.
+        for change in changes {
+            match change {
+                Change::Removed(address) => {
+                    new_registry.remove(address);
+                    new_calibration.remove_offset(address);
+                    new_alarms.remove_limits(address);
+                }
+                Change::Label(address) => {
+                    if let Some(sensor) = new_registry.get_mut(address) {
+                        sensor.label = None;
+                    }
+                }
+                _ => {}
+            }
+        }
+        for change in changes {
+            let address = match change {
+                Change::Added(address)
+                | Change::Label(address)
+                | Change::Zone(address)
+                | Change::Offset(address)
+                | Change::Limits(address) => address,
+                Change::Policy(zone) => {
+                    match self.policies.iter().find(|policy| policy.zone == *zone) {
+                        Some(policy) => new_registry.set_policy(zone.clone(), policy.policy)?,
+                        None => {
+                            new_registry.remove_policy(zone);
+                        }
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            let Some(sensor) = self.sensor(address) else {
+                continue;
+            };
+            // An added sensor gets all its settings.
+            let added = matches!(change, Change::Added(_));
+            if added {
+                new_registry.insert(*address)?;
+            }
+            if (added || matches!(change, Change::Label(_)))
+                && let Some(label) = &sensor.label
+            {
+                new_registry.set_label(*address, label.clone())?;
+            }
+            if added || matches!(change, Change::Zone(_)) {
+                match &sensor.zone {
+                    Some(zone) => new_registry.set_zone(*address, zone.clone())?,
+                    None => {
+                        if let Some(sensor) = new_registry.get_mut(address) {
+                            sensor.zone = None;
+                        }
+                    }
+                }
+            }
+            if added || matches!(change, Change::Offset(_)) {
+                match sensor.offset {
+                    Some(offset) => {
+                        new_calibration.set_offset(*address, offset)?;
+                    }
+                    None => {
+                        new_calibration.remove_offset(address);
+                    }
+                }
+            }
+            if added || matches!(change, Change::Limits(_)) {
+                if sensor.limits == Limits::default() {
+                    new_alarms.remove_limits(address);
+                } else {
+                    new_alarms.set_limits(*address, sensor.limits)?;
+                }
+            }
+        }
+        *registry = new_registry;
+        *calibration = new_calibration;
+        *alarms = new_alarms;
+        Ok(())
+    }
+
+    /// The changes from this configuration to the new one. A resolution is
+    /// reported whenever it is set to a new value, including on added
+    /// sensors.
+    pub fn diff(&self, new: &Config) -> Vec<Change> {
+        let mut changes = Vec::new();
+        if self.sampling != new.sampling {
+            changes.push(Change::Sampling(new.sampling));
+        }
+        for sensor in &new.sensors {
+            let address = sensor.address;
+            let old = self.sensor(&address);
+            match old {
+                None => changes.push(Change::Added(address)),
+                Some(old) => {
+                    for (changed, change) in [
+                        (old.label != sensor.label, Change::Label(address)),
+                        (old.zone != sensor.zone, Change::Zone(address)),
+                        (old.offset != sensor.offset, Change::Offset(address)),
+                        (old.limits != sensor.limits, Change::Limits(address)),
+                        (old.release != sensor.release, Change::Release(address)),
+                    ] {
+                        if changed {
+                            changes.push(change);
+                        }
+                    }
+                }
+            }
+            if let Some(resolution) = sensor.resolution
+                && old.and_then(|old| old.resolution) != Some(resolution)
+            {
+                changes.push(Change::Resolution {
+                    address,
+                    resolution,
+                });
+            }
+        }
+        for sensor in &self.sensors {
+            if new.sensor(&sensor.address).is_none() {
+                changes.push(Change::Removed(sensor.address));
+            }
+        }
+        for policy in &new.policies {
+            if !self.policies.contains(policy) {
+                changes.push(Change::Policy(policy.zone.clone()));
+            }
+        }
+        for policy in &self.policies {
+            if !new.policies.iter().any(|new| new.zone == policy.zone) {
+                changes.push(Change::Policy(policy.zone.clone()));
+            }
+        }
+        changes
+    }
+
+    /// Applies the changes to the new configuration without restarting the
+    /// sampler, and makes it the active one. Returns the changes applied.
+    /// The settings that didn't change are left as they are.
+    ///
+    /// The reload is applied as a whole or not at all. The resolutions are
+    /// written last, a failed write rolls back the written ones and the
+    /// software settings. Sensors whose rollback fails keep the new
+    /// resolution, recorded in the active configuration.
+    ///
+    /// Fails with [`Error::ConversionPending`] if a resolution changes while
+    /// the sampler is converting; retry once it is idle.
+    #[cfg(feature = "esp-idf")]
+    pub fn apply_live<const N: usize>(
+        &mut self,
+        new: Config,
+        registry: &mut Registry<N>,
+        calibration: &mut Calibration<N>,
+        alarms: &mut Alarms<N>,
+        sampler: &mut Sampler<Ds18b20Driver>,
+    ) -> Result<Vec<Change>, ApplyError> {
+        let changes = self.diff(&new);
+        if changes.is_empty() {
+            return Ok(changes);
+        }
+        let resolutions: Vec<_> = changes
+            .iter()
+            .filter_map(|change| match *change {
+                Change::Resolution {
+                    address,
+                    resolution,
+                } => Some((address, resolution)),
+                _ => None,
+            })
+            .collect();
+        // Writing the scratchpad would disturb the conversion.
+        if !resolutions.is_empty() && matches!(sampler.state(), State::Converting { .. }) {
+            return Err(Error::ConversionPending.into());
+        }
+        let mut previous = self.clone();
+        sampler.capture(&mut previous);
+        let backup = (registry.clone(), calibration.clone(), alarms.clone());
+        sampler.apply_changes(&new, &changes)?;
+        if let Err(error) = new.apply_changes(&changes, registry, calibration, alarms) {
+            // The previous settings were applied before, they fit.
+            let _ = sampler.apply_changes(&previous, &new.diff(&previous));
+            return Err(error.into());
+        }
+        let report = write_resolutions(
+            sampler.driver(),
+            &resolutions,
+            |driver, address| Ok(driver.info(address)?.resolution),
+            |driver, address, resolution| driver.set_resolution(address, resolution),
+        );
+        let Some((_, error)) = report.failed else {
+            *self = new;
+            return Ok(changes);
+        };
+        let _ = sampler.apply_changes(&previous, &new.diff(&previous));
+        (*registry, *calibration, *alarms) = backup;
+        for (address, _) in &report.stuck {
+            let resolution = new.sensor(address).and_then(|sensor| sensor.resolution);
+            for sensor in &mut self.sensors {
+                if sensor.address == *address {
+                    sensor.resolution = resolution;
+                }
+            }
+        }
+        Err(ApplyError {
+            error,
+            report: Box::new(report),
+        })
+    }
+
+    pub(crate) fn sensor(&self, address: &Address) -> Option<&SensorConfig> {
+        self.sensors
+            .iter()
+            .find(|sensor| sensor.address == *address)
+    }
+
+    /// The TOML document.
+    pub fn export(&self) -> String {
+        let mut text = String::new();
+        // Writing to a string doesn't fail.
+        let _ = self.write(&mut text);
+        text
+    }
+
+    fn write(&self, text: &mut String) -> core::fmt::Result {
+        if let Some(sampling) = &self.sampling {
+            writeln!(text, "[sampling]")?;
+            writeln!(text, "interval_ms = {}", sampling.interval.as_millis())?;
+            writeln!(text, "jitter_ms = {}", sampling.jitter.as_millis())?;
+        }
+        for sensor in &self.sensors {
+            if !text.is_empty() {
+                writeln!(text)?;
+            }
+            writeln!(text, "[[sensor]]")?;
+            writeln!(text, "address = \"{}\"", sensor.address)?;
+            if let Some(label) = &sensor.label {
+                writeln!(text, "label = {}", quote(label))?;
+            }
+            if let Some(zone) = &sensor.zone {
+                writeln!(text, "zone = {}", quote(zone))?;
+            }
+            if let Some(offset) = sensor.offset {
+                writeln!(text, "offset = {offset}")?;
+            }
+            if let Some(low) = sensor.limits.low {
+                writeln!(text, "low = {low}")?;
+            }
+            if let Some(high) = sensor.limits.high {
+                writeln!(text, "high = {high}")?;
+            }
+            if let Some(release) = sensor.release {
+                writeln!(text, "release_ms = {}", release.as_millis())?;
+            }
+            if let Some(resolution) = sensor.resolution {
+                writeln!(text, "resolution = {}", resolution.bits())?;
+            }
+        }
+        for policy in &self.policies {
+            if !text.is_empty() {
+                writeln!(text)?;
+            }
+            writeln!(text, "[[policy]]")?;
+            writeln!(text, "zone = {}", quote(&policy.zone))?;
+            if let Some(low) = policy.policy.low {
+                writeln!(text, "low = {low}")?;
+            }
+            if let Some(high) = policy.policy.high {
+                writeln!(text, "high = {high}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the TOML document. Fails with [`Error::Syntax`] on the first
+    /// line that can't be read, including tables missing a required key
+    /// (`address`, `zone`, `interval_ms`).
+    pub fn import(text: &str) -> Result<Self> {
+        let mut config = Config::default();
+        let mut table = Table::None;
+        // The line of the current table, while a required key is missing.
+        let mut missing = None;
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let error = Error::Syntax { line: number };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                if let Some(line) = missing {
+                    return Err(Error::Syntax { line });
+                }
+                table = match line {
+                    "[sampling]" if config.sampling.is_none() => {
+                        config.sampling = Some(Sampling::default());
+                        Table::Sampling
+                    }
+                    "[[sensor]]" => {
+                        config.sensors.push(SensorConfig::default());
+                        Table::Sensor
+                    }
+                    "[[policy]]" => {
+                        config.policies.push(PolicyConfig::default());
+                        Table::Policy
+                    }
+                    _ => return Err(error),
+                };
+                missing = Some(number);
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or(error)?;
+            let value = Value::parse(value.trim()).ok_or(error)?;
+            match (&table, key.trim()) {
+                (Table::Sampling, "interval_ms") => {
+                    config.sampling.as_mut().ok_or(error)?.interval = value.duration(error)?;
+                    missing = None;
+                }
+                (Table::Sampling, "jitter_ms") => {
+                    config.sampling.as_mut().ok_or(error)?.jitter = value.duration(error)?;
+                }
+                (Table::Sensor, key) => {
+                    let sensor = config.sensors.last_mut().ok_or(error)?;
+                    match key {
+                        "address" => {
+                            sensor.address =
+                                Address::from_hex(&value.string(error)?, Validation::Strict)
+                                    .map_err(|_| error)?;
+                            missing = None;
+                        }
+                        "label" => sensor.label = Some(value.string(error)?),
+                        "zone" => sensor.zone = Some(value.string(error)?),
+                        "offset" => sensor.offset = Some(value.number(error)?),
+                        "low" => sensor.limits.low = Some(value.number(error)?),
+                        "high" => sensor.limits.high = Some(value.number(error)?),
+                        "release_ms" => sensor.release = Some(value.duration(error)?),
+                        "resolution" => {
+                            sensor.resolution =
+                                Some(Resolution::from_bits(value.integer(error)?).ok_or(error)?)
+                        }
+                        _ => return Err(error),
+                    }
+                }
+                (Table::Policy, key) => {
+                    let policy = config.policies.last_mut().ok_or(error)?;
+                    match key {
+                        "zone" => {
+                            policy.zone = value.string(error)?;
+                            missing = None;
+                        }
+                        "low" => policy.policy.low = Some(value.number(error)?),
+                        "high" => policy.policy.high = Some(value.number(error)?),
+                        _ => return Err(error),
+                    }
+                }
+                _ => return Err(error),
+            }
+        }
+        match missing {
+            Some(line) => Err(Error::Syntax { line }),
+            None => Ok(config),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Table {
+    None,
+    Sampling,
+    Sensor,
+    Policy,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value<'a> {
+    String(String),
+    Number(&'a str),
+}
+
+impl<'a> Value<'a> {
+    fn parse(text: &'a str) -> Option<Self> {
+        let Some(quoted) = text.strip_prefix('"') else {
+            return Some(Self::Number(text));
+        };
+        let mut string = String::new();
+        let mut characters = quoted.chars();
+        loop {
+            match characters.next()? {
+                '"' => break,
+                '\\' => match characters.next()? {
+                    character @ ('"' | '\\') => string.push(character),
+                    _ => return None,
+                },
+                character => string.push(character),
+            }
+        }
+        characters
+            .as_str()
+            .is_empty()
+            .then_some(Self::String(string))
+    }
+
+    fn string(self, error: Error) -> Result<String> {
+        match self {
+            Self::String(string) => Ok(string),
+            Self::Number(_) => Err(error),
+        }
+    }
+
+    fn number(self, error: Error) -> Result<f32> {
+        match self {
+            Self::Number(number) => number
+                .parse()
+                .ok()
+                .filter(|number: &f32| number.is_finite()),
+            Self::String(_) => None,
+        }
+        .ok_or(error)
+    }
+
+    fn integer(self, error: Error) -> Result<u8> {
+        match self {
+            Self::Number(number) => number.parse().ok(),
+            Self::String(_) => None,
+        }
+        .ok_or(error)
+    }
+
+    fn duration(self, error: Error) -> Result<Duration> {
+        match self {
+            Self::Number(number) => number.parse().map(Duration::from_millis).ok(),
+            Self::String(_) => None,
+        }
+        .ok_or(error)
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = "\"".to_string();
+    for character in text.chars() {
+        if matches!(character, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(character);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::Overflow;
+
+    const BOILER: Address = Address(0x2300_0004_6EAF_BC28);
+
+    fn config() -> Config {
+        Config {
+            sampling: Some(Sampling {
+                interval: Duration::from_secs(10),
+                jitter: Duration::ZERO,
+            }),
+            sensors: vec![SensorConfig {
+                address: BOILER,
+                label: Some("boiler \"main\"".into()),
+                zone: Some("tank".into()),
+                offset: Some(-0.25),
+                limits: Limits {
+                    low: Some(10.0),
+                    high: Some(80.5),
+                },
+                release: Some(Duration::from_millis(500)),
+                resolution: Some(Resolution::Ten),
+            }],
+            policies: vec![PolicyConfig {
+                zone: "tank".into(),
+                policy: AlarmPolicy {
+                    low: None,
+                    high: Some(65.0),
+                },
+            }],
+        }
+    }
+
+    #[test]
+    fn export() {
+        let text = config().export();
+        assert_eq!(
+            text,
+            "[sampling]\ninterval_ms = 10000\njitter_ms = 0\n\n[[sensor]]\naddress = \"230000046eafbc28\"\nlabel = \"boiler \\\"main\\\"\"\nzone = \"tank\"\noffset = -0.25\nlow = 10\nhigh = 80.5\nrelease_ms = 500\nresolution = 10\n\n[[policy]]\nzone = \"tank\"\nhigh = 65\n"
+        );
+        assert_eq!(Config::import(&text), Ok(config()));
+    }
+
+    #[test]
+    fn import() {
+        assert_eq!(
+            Config::import("# backup\n[[sensor]]\nlabel = \"a\"\n"),
+            Err(Error::Syntax { line: 2 })
+        );
+        assert_eq!(
+            Config::import("[[policy]]\nzone = \"tank\"\nmiddle = 3\n"),
+            Err(Error::Syntax { line: 3 })
+        );
+        assert_eq!(
+            Config::import("[[sensor]]\naddress = \"0000000000000028\"\n"),
+            Err(Error::Syntax { line: 2 })
+        );
+        assert_eq!(
+            Config::import("[[sensor]]\naddress = \"230000046eafbc28\"\nresolution = 8\n"),
+            Err(Error::Syntax { line: 3 })
+        );
+        assert_eq!(
+            Config::import("[[sensor]]\naddress = \"230000046eafbc28\"\nresolution = 9.5\n"),
+            Err(Error::Syntax { line: 3 })
+        );
+        assert_eq!(Config::import(""), Ok(Config::default()));
+    }
+
+    #[test]
+    fn diff() {
+        let active = config();
+        assert_eq!(active.diff(&active), []);
+        let mut new = active.clone();
+        new.sampling = None;
+        new.sensors[0].label = Some("boiler".into());
+        new.sensors[0].resolution = Some(Resolution::Twelve);
+        new.sensors.push(SensorConfig {
+            address: Address(1),
+            resolution: Some(Resolution::Nine),
+            ..Default::default()
+        });
+        new.policies[0].policy.low = Some(5.0);
+        assert_eq!(
+            active.diff(&new),
+            [
+                Change::Sampling(None),
+                Change::Label(BOILER),
+                Change::Resolution {
+                    address: BOILER,
+                    resolution: Resolution::Twelve
+                },
+                Change::Added(Address(1)),
+                Change::Resolution {
+                    address: Address(1),
+                    resolution: Resolution::Nine
+                },
+                Change::Policy("tank".into()),
+            ]
+        );
+        // Unsetting the resolution leaves the sensor as it is.
+        new = active.clone();
+        new.sensors[0].resolution = None;
+        new.policies.clear();
+        assert_eq!(active.diff(&new), [Change::Policy("tank".into())]);
+        new.sensors.clear();
+        assert_eq!(
+            active.diff(&new),
+            [Change::Removed(BOILER), Change::Policy("tank".into())]
+        );
+    }
+
+    #[test]
+    fn write_resolutions() {
+        use Resolution::{Nine, Twelve};
+        use alloc::collections::BTreeMap;
+
+        // The sensors and their resolutions, the second can't be written.
+        let mut bus = BTreeMap::from([(Address(1), Twelve), (Address(2), Twelve)]);
+        let read = |bus: &mut BTreeMap<_, _>, address: &Address| {
+            bus.get(address).copied().ok_or(Error::DeviceNotFound)
+        };
+        let write = |bus: &mut BTreeMap<_, Resolution>, address: &Address, resolution| match address
+        {
+            Address(2) if resolution != Twelve => Err(Error::Verification),
+            _ => {
+                bus.insert(*address, resolution);
+                Ok(())
+            }
+        };
+        let report = super::write_resolutions(&mut bus, &[(Address(1), Nine)], read, write);
+        assert!(report.is_complete());
+        assert_eq!(report.written, [Address(1)]);
+        assert_eq!(bus[&Address(1)], Nine);
+        let changes = [(Address(1), Twelve), (Address(2), Nine), (Address(3), Nine)];
+        assert_eq!(
+            super::write_resolutions(&mut bus, &changes, read, write),
+            WriteReport {
+                written: vec![Address(1)],
+                failed: Some((Address(2), Error::Verification)),
+                rolled_back: vec![Address(2), Address(1)],
+                stuck: vec![],
+            }
+        );
+        assert_eq!(bus[&Address(1)], Nine);
+    }
+
+    #[test]
+    fn apply() {
+        let mut registry = Registry::new();
+        let mut calibration = Calibration::new();
+        let mut alarms = Alarms::new(Overflow::default());
+        registry.set_zone(Address(1), "room").unwrap();
+        let mut config = config();
+        config
+            .apply(&mut registry, &mut calibration, &mut alarms)
+            .unwrap();
+        assert!(!registry.contains(&Address(1)));
+        let mut captured = Config::capture(&registry, &calibration, &alarms);
+        captured.sampling = config.sampling;
+        captured.sensors[0].release = config.sensors[0].release;
+        captured.sensors[0].resolution = config.sensors[0].resolution;
+        assert_eq!(captured, config);
+
+        // A duplicate address fails the whole configuration.
+        config.sensors.push(config.sensors[0].clone());
+        config.sensors[0].offset = Some(1.0);
+        assert_eq!(
+            config.apply(&mut registry, &mut calibration, &mut alarms),
+            Err(Error::DuplicateAddress(BOILER))
+        );
+        assert_eq!(calibration.get(&BOILER), Some(-0.25));
+    }
+
+    #[test]
+    fn apply_changes() {
+        let mut registry = Registry::new();
+        let mut calibration = Calibration::new();
+        let mut alarms = Alarms::new(Overflow::default());
+        let active = config();
+        active
+            .apply(&mut registry, &mut calibration, &mut alarms)
+            .unwrap();
+        // Registered since, e.g. by a scan.
+        registry.set_zone(Address(1), "room").unwrap();
+        let mut new = active.clone();
+        new.sensors[0].label = Some("boiler".into());
+        new.sensors[0].offset = None;
+        new.sensors.push(SensorConfig {
+            address: Address(2),
+            label: Some("boiler \"main\"".into()),
+            limits: Limits {
+                low: None,
+                high: Some(30.0),
+            },
+            ..Default::default()
+        });
+        new.policies.clear();
+        new.apply_changes(
+            &active.diff(&new),
+            &mut registry,
+            &mut calibration,
+            &mut alarms,
+        )
+        .unwrap();
+        assert_eq!(
+            registry.get(&Address(1)).unwrap().zone.as_deref(),
+            Some("room")
+        );
+        assert_eq!(registry.find("boiler"), Some(&BOILER));
+        assert_eq!(registry.find("boiler \"main\""), Some(&Address(2)));
+        assert_eq!(registry.get(&BOILER).unwrap().zone.as_deref(), Some("tank"));
+        assert_eq!(calibration.get(&BOILER), None);
+        assert_eq!(alarms.get_limits(&Address(2)).unwrap().high, Some(30.0));
+        assert!(registry.policy("tank").is_none());
+
+        // A label taken by a sensor the changes don't name fails them all.
+        let mut failing = new.clone();
+        failing.sensors[0].label = Some("boiler \"main\"".into());
+        failing.sensors[1].label = None;
+        failing.sensors[1].offset = Some(1.0);
+        let mut changes = new.diff(&failing);
+        changes.retain(|change| *change != Change::Label(Address(2)));
+        assert_eq!(
+            failing.apply_changes(&changes, &mut registry, &mut calibration, &mut alarms),
+            Err(Error::DuplicateLabel {
+                address: Address(2)
+            })
+        );
+        assert_eq!(calibration.get(&Address(2)), None);
+    }
+}
@@ -0,0 +1,216 @@
+//! Non-blocking conversion
+//!
+//! Starting a conversion returns a [`ConversionTicket`] that has to be
+//! redeemed to read the temperature. External schedulers (RTOS timers, async
+//! executors) can schedule the read at [`ready_at`](ConversionTicket::ready_at),
+//! and a read can't be issued before the conversion is done:
+//!
+//! ```ignore
+//! let ticket = thermometer.start_conversion(&address)?;
+//! timer.after(ticket.remaining())?;
+//! // ...
+//! let reading = thermometer.redeem(ticket)?;
+//! ```
+//!
+//! The whole bus converts at once in two phases, with the wait in between
+//! left to the caller, e.g. to yield the RMT channel to other users:
+//!
+//! ```ignore
+//! let conversion = thermometer.convert_all()?;
+//! thermometer.on_waiting(&conversion, |remaining| {
+//!     display.refresh(); // Called again until the conversion is done.
+//! })?;
+//! let readings = thermometer.collect_all(conversion, &addresses)?;
+//! ```
+
+use crate::{
+    Ds18b20Driver, Error, Result, address::Address, driver::preflight, pipeline::Reading, timing,
+};
+use alloc::vec::Vec;
+use std::time::{Duration, Instant};
+
+/// Conversion ticket
+///
+/// Can't be created or copied outside of the driver and is consumed by the
+/// read.
+#[derive(Debug, PartialEq, Eq)]
+#[must_use = "the temperature can only be read by redeeming the ticket"]
+pub struct ConversionTicket {
+    address: Address,
+    started_at: Instant,
+    ready_at: Instant,
+}
+
+impl ConversionTicket {
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    pub fn ready_at(&self) -> Instant {
+        self.ready_at
+    }
+
+    /// The time until the conversion is done.
+    pub fn remaining(&self) -> Duration {
+        self.ready_at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_ready(&self) -> bool {
+        Instant::now() >= self.ready_at
+    }
+
+    /// The reading of the converted temperature, stamped with the start of
+    /// the conversion.
+    fn reading(&self, temperature: f32) -> Reading {
+        Reading::new(self.address, temperature).converted_at(self.started_at)
+    }
+}
+
+/// Conversion of the whole bus
+///
+/// Consumed by collecting the readings.
+#[derive(Debug, PartialEq, Eq)]
+#[must_use = "the temperatures can only be read by collecting the conversion"]
+pub struct BusConversion {
+    started_at: Instant,
+    ready_at: Instant,
+}
+
+impl BusConversion {
+    pub fn started_at(&self) -> Instant {
+        self.started_at
+    }
+
+    pub fn ready_at(&self) -> Instant {
+        self.ready_at
+    }
+
+    /// The time until the conversion is done.
+    pub fn remaining(&self) -> Duration {
+        self.ready_at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_ready(&self) -> bool {
+        Instant::now() >= self.ready_at
+    }
+}
+
+impl<'a> Ds18b20Driver<'a> {
+    /// Starts a conversion of the sensor without waiting for it.
+    pub fn start_conversion(&mut self, address: &Address) -> Result<ConversionTicket> {
+        preflight(address)?;
+        self.retry(|this| {
+            this.initialization()?
+                .match_rom(address)?
+                .start_conversion()
+        })?;
+        let started_at = Instant::now();
+        Ok(ConversionTicket {
+            address: *address,
+            started_at,
+            ready_at: started_at + timing::CONVERSION,
+        })
+    }
+
+    /// Starts a conversion of all the sensors (Skip ROM + Convert T) without
+    /// waiting for it.
+    pub fn convert_all(&mut self) -> Result<BusConversion> {
+        self.retry(|this| this.initialization()?.skip_rom()?.start_conversion())?;
+        let started_at = Instant::now();
+        Ok(BusConversion {
+            started_at,
+            ready_at: started_at + timing::CONVERSION,
+        })
+    }
+
+    /// Calls the hook with the remaining time until the conversion is done.
+    /// The hook is called again right away, so it should do a slice of work
+    /// or sleep. Fails with [`Error::Cancelled`] between the calls once the
+    /// driver's cancellation token is cancelled.
+    pub fn on_waiting(
+        &self,
+        conversion: &BusConversion,
+        mut hook: impl FnMut(Duration),
+    ) -> Result<()> {
+        loop {
+            let remaining = conversion.remaining();
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            if self.cancellation.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            hook(remaining);
+        }
+    }
+
+    /// Reads the converted temperatures of the sensors, waiting for the
+    /// conversion if it isn't done yet. Failures of a single sensor are
+    /// reported in its reading.
+    pub fn collect_all(
+        &mut self,
+        conversion: BusConversion,
+        addresses: &[Address],
+    ) -> Result<Vec<Result<Reading>>> {
+        self.cancellation.sleep(conversion.remaining())?;
+        Ok(addresses
+            .iter()
+            .map(|address| {
+                preflight(address)?;
+                let scratchpad = self
+                    .retry(|this| this.initialization()?.match_rom(address)?.read_scratchpad())?;
+                Ok(Reading::new(*address, scratchpad.temperature)
+                    .converted_at(conversion.started_at))
+            })
+            .collect())
+    }
+
+    /// Reads the converted temperature, waiting for the conversion if it
+    /// isn't done yet.
+    pub fn redeem(&mut self, ticket: ConversionTicket) -> Result<Reading> {
+        self.cancellation.sleep(ticket.remaining())?;
+        let address = ticket.address;
+        let scratchpad = self.retry(|this| {
+            this.initialization()?
+                .match_rom(&address)?
+                .read_scratchpad()
+        })?;
+        Ok(ticket.reading(scratchpad.temperature))
+    }
+
+    /// Reads the converted temperature if the conversion is done, otherwise
+    /// gives the ticket back.
+    pub fn try_redeem(
+        &mut self,
+        ticket: ConversionTicket,
+    ) -> Result<Result<Reading>, ConversionTicket> {
+        if !ticket.is_ready() {
+            return Err(ticket);
+        }
+        Ok(self.redeem(ticket))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reading() {
+        let started_at = Instant::now() - Duration::from_secs(1);
+        let ticket = ConversionTicket {
+            address: Address(1),
+            started_at,
+            ready_at: started_at + timing::CONVERSION,
+        };
+        let reading = ticket.reading(21.0);
+        assert_eq!(reading.age_at(started_at), Some(Duration::ZERO));
+        assert!(reading.age().unwrap() >= Duration::from_secs(1));
+        assert!(reading.is_valid_for(Duration::from_secs(60)));
+        assert!(!reading.is_valid_for(Duration::from_millis(500)));
+    }
+}
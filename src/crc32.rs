@@ -0,0 +1,84 @@
+//! CRC-32 (ISO-HDLC, as in Ethernet and zlib)
+//!
+//! Protects the [`frame`](crate::frame)s, which outgrow the 8-bit 1-Wire CRC.
+
+/// Calculates the crc32 of the input data.
+pub fn calculate(data: &[u8]) -> u32 {
+    Crc32::new().update(data.iter().copied()).finish()
+}
+
+/// Running crc32
+///
+/// ```
+/// # use thermometer::crc32::Crc32;
+/// let crc = Crc32::new().update(*b"1234").update(*b"56789").finish();
+/// assert_eq!(crc, 0xCBF4_3926);
+/// ```
+///
+/// Reflected polynomial `0xEDB88320`, initial value and final XOR `0xFFFFFFFF`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Crc32 {
+    pub const fn new() -> Self {
+        Self { crc: u32::MAX }
+    }
+
+    /// Feeds the data.
+    pub fn update(mut self, data: impl IntoIterator<Item = u8>) -> Self {
+        for byte in data {
+            self.push(byte);
+        }
+        self
+    }
+
+    /// Feeds a single byte.
+    pub const fn push(&mut self, byte: u8) {
+        let mut crc = self.crc ^ byte as u32;
+        let mut index = 0;
+        while index < u8::BITS {
+            let bit = crc & 0b1;
+            crc >>= 1;
+            if bit != 0 {
+                crc ^= 0xEDB8_8320;
+            }
+            index += 1;
+        }
+        self.crc = crc;
+    }
+
+    /// The crc32 of the fed data.
+    pub const fn finish(self) -> u32 {
+        !self.crc
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn calculate() {
+        use super::calculate;
+
+        assert_eq!(calculate(b""), 0);
+        assert_eq!(calculate(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            calculate(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+        let mut crc = Crc32::new();
+        for byte in *b"123456789" {
+            crc.push(byte);
+        }
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+    }
+}
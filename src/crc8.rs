@@ -6,16 +6,55 @@ pub fn calculate(data: &[u8]) -> u8 {
 }
 
 /// Calculates the crc8 of the input data with init value.
+pub fn calculate_with_initial(crc: u8, data: &[u8]) -> u8 {
+    Crc8::with_initial(crc)
+        .update(data.iter().copied())
+        .finish()
+}
+
+/// Running crc8
+///
+/// Computes the crc8 over data that arrives in parts, without collecting it
+/// into a slice first:
+///
+/// ```
+/// # use thermometer::crc8::Crc8;
+/// let crc = Crc8::new().update([99, 1, 75, 70]).update([127, 255, 13, 16]).finish();
+/// assert_eq!(crc, 21);
+/// ```
 ///
 /// Feedback polynomial: `X^8 + X^5 + X^4 + X^0`
 /// LFSR (Galois configuration):
 ///                                                         v     input bit
 /// [7]->[6]->[5]->[4]->(XOR)->[3]->(XOR)->[2]->[1]->[0]->(XOR)-> feedback bit
 ///  ^                    ^           ^                           feedback mask
-pub fn calculate_with_initial(mut crc: u8, data: &[u8]) -> u8 {
-    for byte in data {
-        crc ^= byte;
-        for _ in 0..u8::BITS {
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Crc8 {
+    crc: u8,
+}
+
+impl Crc8 {
+    pub const fn new() -> Self {
+        Self::with_initial(0)
+    }
+
+    pub const fn with_initial(crc: u8) -> Self {
+        Self { crc }
+    }
+
+    /// Feeds the data.
+    pub fn update(mut self, data: impl IntoIterator<Item = u8>) -> Self {
+        for byte in data {
+            self.push(byte);
+        }
+        self
+    }
+
+    /// Feeds a single byte.
+    pub const fn push(&mut self, byte: u8) {
+        let mut crc = self.crc ^ byte;
+        let mut index = 0;
+        while index < u8::BITS {
             // feedback bit at each iteration step
             let bit = crc & 0b1;
             crc >>= 1;
@@ -23,9 +62,15 @@ pub fn calculate_with_initial(mut crc: u8, data: &[u8]) -> u8 {
             if bit != 0 {
                 crc ^= 0b1000_1100;
             }
+            index += 1;
         }
+        self.crc = crc;
+    }
+
+    /// The crc8 of the fed data.
+    pub const fn finish(self) -> u8 {
+        self.crc
     }
-    crc
 }
 
 /// Checks to see if data (including the crc byte) passes the crc check.
@@ -76,4 +121,24 @@ mod test {
         );
         assert!(check(&[95, 1, 75, 70, 127, 255, 1, 16, 155]).is_ok());
     }
+
+    #[test]
+    fn streaming() {
+        let data = [95, 1, 75, 70, 127, 255, 1, 16];
+        let mut crc = Crc8::new();
+        for byte in data {
+            crc.push(byte);
+        }
+        assert_eq!(crc.finish(), 155);
+        assert_eq!(
+            Crc8::new()
+                .update(data[..3].iter().copied())
+                .update(data[3..].iter().copied())
+                .finish(),
+            155
+        );
+        assert_eq!(Crc8::new().update(data).update([155]).finish(), 0);
+        assert_eq!(Crc8::with_initial(0x12).finish(), 0x12);
+        assert_eq!(Crc8::new().update([]).finish(), 0);
+    }
 }
@@ -0,0 +1,41 @@
+//! CSV export
+//!
+//! One reading per record: the ROM code as 16 hex digits and the temperature
+//! in degrees Celsius, with a `.` decimal separator and as many decimal places
+//! as needed to round-trip.
+//!
+//! ```text
+//! address,temperature
+//! 230000046eafbc28,21.4375
+//! ```
+
+use crate::pipeline::Reading;
+use alloc::{format, string::String};
+
+/// Header record
+pub const HEADER: &str = "address,temperature";
+/// Field separator
+pub const SEPARATOR: char = ',';
+
+/// Encodes the reading as a CSV record (without line terminator).
+pub fn encode(reading: &Reading) -> String {
+    format!("{}{SEPARATOR}{}", reading.address, reading.temperature)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::address::Address;
+
+    #[test]
+    fn encode() {
+        assert_eq!(
+            super::encode(&Reading::new(Address(0x2300_0004_6EAF_BC28), 21.4375)),
+            "230000046eafbc28,21.4375",
+        );
+        assert_eq!(
+            super::encode(&Reading::new(Address(0x28), -10.125)),
+            "0000000000000028,-10.125",
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Differential channels
+//!
+//! A channel computes the difference `T_a - T_b` of a pair of sensors, e.g.
+//! the delta-T of a heat exchanger or the inlet/outlet difference of a loop.
+//! As a pipeline [`Stage`] it passes the readings unchanged and pairs them
+//! up; every pair yields a delta, which passes through the channel's own
+//! filter and is checked against its own limits:
+//!
+//! ```ignore
+//! let exchanger = DifferentialChannel::new(inlet, outlet)
+//!     .filter(Pipeline::new().stage(Smoothing::new(0.5)))
+//!     .limits(Limits { low: Some(2.0), high: Some(15.0) });
+//! let mut pipeline = Pipeline::new().stage(exchanger);
+//! ```
+
+use crate::{
+    address::Address,
+    alarm::{AlarmKind, Limits},
+    collections::CAPACITY,
+    event::{EventQueue, Overflow},
+    logging::{Subsystem, log},
+    pipeline::{Pipeline, Reading, Stage},
+};
+use log::Level;
+
+/// Channel alarm event
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Raised { kind: AlarmKind, delta: f32 },
+    Cleared { kind: AlarmKind, delta: f32 },
+}
+
+/// Differential channel
+pub struct DifferentialChannel<const N: usize = CAPACITY> {
+    pub a: Address,
+    pub b: Address,
+    filter: Pipeline,
+    limits: Limits,
+    pending: (Option<f32>, Option<f32>),
+    delta: Option<f32>,
+    active: Option<AlarmKind>,
+    events: EventQueue<Event, N>,
+}
+
+impl DifferentialChannel {
+    pub fn new(a: Address, b: Address) -> Self {
+        Self {
+            a,
+            b,
+            filter: Pipeline::new(),
+            limits: Limits::default(),
+            pending: (None, None),
+            delta: None,
+            active: None,
+            events: EventQueue::new(Overflow::default()),
+        }
+    }
+}
+
+impl<const N: usize> DifferentialChannel<N> {
+    /// Sets the maximum number of queued events.
+    pub fn capacity<const M: usize>(self) -> DifferentialChannel<M> {
+        DifferentialChannel {
+            a: self.a,
+            b: self.b,
+            filter: self.filter,
+            limits: self.limits,
+            pending: self.pending,
+            delta: self.delta,
+            active: self.active,
+            events: self.events.capacity(),
+        }
+    }
+
+    /// Sets the filter of the delta. The delta passes through it as a
+    /// reading of sensor `a`; a dropped delta isn't checked.
+    pub fn filter(self, filter: Pipeline) -> Self {
+        Self { filter, ..self }
+    }
+
+    /// Sets the limits (°C) of the delta.
+    pub fn limits(self, limits: Limits) -> Self {
+        Self { limits, ..self }
+    }
+
+    /// The last filtered delta (°C).
+    pub fn delta(&self) -> Option<f32> {
+        self.delta
+    }
+
+    /// The active alarm of the channel.
+    pub fn active(&self) -> Option<AlarmKind> {
+        self.active
+    }
+
+    /// The queued events.
+    pub fn events(&mut self) -> &mut EventQueue<Event, N> {
+        &mut self.events
+    }
+
+    fn update(&mut self, delta: f32) {
+        let Some(reading) = self.filter.process(Reading::new(self.a, delta)) else {
+            return;
+        };
+        let delta = reading.temperature;
+        self.delta = Some(delta);
+        let alarm = self.limits.check(delta);
+        if alarm == self.active {
+            return;
+        }
+        // The active alarm changes only with an accepted event.
+        if let Some(kind) = self.active {
+            if !self.emit(Event::Cleared { kind, delta }) {
+                return;
+            }
+            self.active = None;
+        }
+        if let Some(kind) = alarm
+            && self.emit(Event::Raised { kind, delta })
+        {
+            self.active = alarm;
+        }
+    }
+
+    /// Queues the event, returns whether it was accepted.
+    fn emit(&mut self, event: Event) -> bool {
+        let accepted = self.events.push_or_drop(event);
+        if !accepted {
+            log!(
+                Subsystem::Pipeline,
+                Level::Warn,
+                "Channel event {event:?} dropped, the event queue is full"
+            );
+        }
+        accepted
+    }
+}
+
+impl<const N: usize> Stage for DifferentialChannel<N> {
+    fn process(&mut self, reading: Reading) -> Option<Reading> {
+        if reading.address == self.a {
+            self.pending.0 = Some(reading.temperature);
+        } else if reading.address == self.b {
+            self.pending.1 = Some(reading.temperature);
+        } else {
+            return Some(reading);
+        }
+        if let (Some(a), Some(b)) = self.pending {
+            self.pending = (None, None);
+            self.update(a - b);
+        }
+        Some(reading)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delta() {
+        let (a, b) = (Address(1), Address(2));
+        let mut channel = DifferentialChannel::new(a, b).limits(Limits {
+            low: Some(2.0),
+            high: None,
+        });
+        channel.process(Reading::new(a, 60.0));
+        assert_eq!(channel.delta(), None);
+        channel.process(Reading::new(Address(3), 0.0));
+        channel.process(Reading::new(b, 50.0));
+        assert_eq!(channel.delta(), Some(10.0));
+        // Pairs are taken in either order.
+        channel.process(Reading::new(b, 59.0));
+        channel.process(Reading::new(a, 60.0));
+        assert_eq!(channel.delta(), Some(1.0));
+        assert_eq!(channel.active(), Some(AlarmKind::Low));
+        assert_eq!(
+            channel.events().pop(),
+            Some(Event::Raised {
+                kind: AlarmKind::Low,
+                delta: 1.0,
+            })
+        );
+    }
+
+    #[test]
+    fn filter() {
+        let (a, b) = (Address(1), Address(2));
+        let mut channel =
+            DifferentialChannel::new(a, b).filter(Pipeline::new().stage(|mut reading: Reading| {
+                reading.temperature = reading.temperature.abs();
+                Some(reading)
+            }));
+        channel.process(Reading::new(a, 40.0));
+        channel.process(Reading::new(b, 50.0));
+        assert_eq!(channel.delta(), Some(10.0));
+    }
+}
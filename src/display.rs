@@ -0,0 +1,137 @@
+//! Display panel
+//!
+//! Renders a compact multi-sensor panel into any `embedded-graphics`
+//! [`DrawTarget`], one row per sensor formatted with its [`Label`]:
+//!
+//! ```text
+//! Boiler 72.4 °F
+//! Ambient 21.44 °C
+//! ```
+//!
+//! The alarm icon (a filled triangle) is drawn at the right edge of the row,
+//! the trend arrow (an outlined triangle pointing up or down) next to it.
+//!
+//! [`Label`]: crate::label::Label
+
+use crate::{label::Labels, pipeline::Reading, trend::Trend};
+use embedded_graphics::{
+    mono_font::{MonoFont, MonoTextStyle, iso_8859_1::FONT_6X10},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Triangle},
+    text::{Baseline, Text},
+};
+
+/// Panel row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Row {
+    pub reading: Reading,
+    /// Whether the alarm icon is shown.
+    pub alarm: bool,
+}
+
+/// Panel
+#[derive(Clone, Copy, Debug)]
+pub struct Panel<'a> {
+    style: MonoTextStyle<'a, BinaryColor>,
+}
+
+impl<'a> Panel<'a> {
+    pub fn new(font: &'a MonoFont<'a>) -> Self {
+        Self {
+            style: MonoTextStyle::new(font, BinaryColor::On),
+        }
+    }
+
+    /// Draws the rows from the top of the target. Rows that don't fit are
+    /// skipped.
+    pub fn draw<D, const N: usize>(
+        &self,
+        target: &mut D,
+        labels: &Labels<N>,
+        rows: impl IntoIterator<Item = Row>,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let bounds = target.bounding_box();
+        let height = self.style.font.character_size.height as i32;
+        let mut position = bounds.top_left;
+        for row in rows {
+            if position.y + height > bounds.top_left.y + bounds.size.height as i32 {
+                break;
+            }
+            let text = labels.format_reading(&row.reading);
+            Text::with_baseline(&text, position, self.style, Baseline::Top).draw(target)?;
+            let right = bounds.top_left.x + bounds.size.width as i32 - 1;
+            let size = height - 2;
+            let (top, bottom) = (position.y, position.y + size);
+            let left = right - 2 * size - 2;
+            let arrow = match row.reading.trend {
+                Some(Trend::Rising) => Some((bottom, top)),
+                Some(Trend::Falling) => Some((top, bottom)),
+                _ => None,
+            };
+            if let Some((base, tip)) = arrow {
+                Triangle::new(
+                    Point::new(left, base),
+                    Point::new(left + size / 2, tip),
+                    Point::new(left + size, base),
+                )
+                .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+                .draw(target)?;
+            }
+            if row.alarm {
+                Triangle::new(
+                    Point::new(right - size, position.y + size),
+                    Point::new(right - size / 2, position.y),
+                    Point::new(right, position.y + size),
+                )
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(target)?;
+            }
+            position.y += height;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Panel<'_> {
+    fn default() -> Self {
+        Self::new(&FONT_6X10)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::address::Address;
+    use embedded_graphics::mock_display::MockDisplay;
+
+    fn row(address: u64, alarm: bool) -> Row {
+        Row {
+            reading: Reading::new(Address(address), 21.5),
+            alarm,
+        }
+    }
+
+    #[test]
+    fn draw() {
+        let mut display = MockDisplay::<BinaryColor>::new();
+        display.set_allow_out_of_bounds_drawing(true);
+        display.set_allow_overdraw(true);
+        Panel::default()
+            .draw(
+                &mut display,
+                &Labels::new(),
+                [row(1, true), row(2, false), row(3, true), row(4, false)],
+            )
+            .unwrap();
+        let area = display.affected_area();
+        // The mock display is 64x64, so only 6 rows of 10 pixels fit.
+        assert!(area.size.height <= 60);
+        // The alarm icon of the first row is at the right edge.
+        assert_eq!(display.get_pixel(Point::new(63, 8)), Some(BinaryColor::On));
+        assert_eq!(display.get_pixel(Point::new(63, 18)), None);
+    }
+}
@@ -0,0 +1,181 @@
+//! Sensor drift tracking
+//!
+//! Records the offsets of every calibration run with its time, so probes
+//! drifting beyond tolerance can be scheduled for replacement:
+//!
+//! ```ignore
+//! let colocation = thermometer.co_locate(reference, &addresses, 16)?;
+//! drift.record(now, colocation.offsets())?;
+//! for report in drift.exceeding(0.5) {
+//!     warn!("Replace {}: drifted {} °C", report.address, report.drift);
+//! }
+//! ```
+//!
+//! The time is whatever the application uses as a clock across reboots, e.g.
+//! the time since the Unix epoch.
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Deque, Map},
+    error::Result,
+};
+use core::time::Duration;
+
+/// Default number of recent runs kept per sensor.
+pub const RUNS: usize = 16;
+
+const DAY: f32 = 86_400.0;
+
+/// Calibration run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Run {
+    pub at: Duration,
+    /// Offset (°C)
+    pub offset: f32,
+}
+
+#[derive(Clone, Debug)]
+struct Runs<const R: usize> {
+    first: Run,
+    recent: Deque<Run, R>,
+}
+
+/// Drift report of a sensor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DriftReport {
+    pub address: Address,
+    /// The number of recorded runs
+    pub runs: usize,
+    /// The first run
+    pub first: Run,
+    /// The last run
+    pub last: Run,
+    /// The change of the offset (°C) since the first run
+    pub drift: f32,
+}
+
+impl DriftReport {
+    /// The mean drift rate (°C per day).
+    pub fn rate(&self) -> Option<f32> {
+        let days =
+            (self.last.at.checked_sub(self.first.at)?.as_secs_f32() / DAY).max(f32::MIN_POSITIVE);
+        (self.runs > 1).then_some(self.drift / days)
+    }
+}
+
+/// Drift log
+///
+/// Keeps the first run and the `R` most recent runs of every sensor.
+#[derive(Clone, Debug)]
+pub struct Drift<const N: usize = CAPACITY, const R: usize = RUNS> {
+    sensors: Map<Address, (usize, Runs<R>), N>,
+}
+
+impl Drift {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const N: usize, const R: usize> Default for Drift<N, R> {
+    fn default() -> Self {
+        Self {
+            sensors: Map::new(),
+        }
+    }
+}
+
+impl<const N: usize, const R: usize> Drift<N, R> {
+    /// Sets the maximum number of sensors.
+    pub fn capacity<const M: usize>(self) -> Drift<M, R> {
+        Drift {
+            sensors: self.sensors.into_capacity(),
+        }
+    }
+
+    /// Records the offsets of a calibration run.
+    pub fn record(
+        &mut self,
+        at: Duration,
+        offsets: impl IntoIterator<Item = (Address, f32)>,
+    ) -> Result<()> {
+        for (address, offset) in offsets {
+            let run = Run { at, offset };
+            let (count, runs) = self.sensors.get_or_insert(
+                address,
+                (
+                    0,
+                    Runs {
+                        first: run,
+                        recent: Deque::new(),
+                    },
+                ),
+            )?;
+            *count += 1;
+            if runs.recent.is_full() {
+                runs.recent.pop_front();
+            }
+            // There is room after the pop.
+            let _ = runs.recent.push_back(run);
+        }
+        Ok(())
+    }
+
+    /// The recent runs of the sensor, oldest first.
+    pub fn runs(&self, address: &Address) -> impl Iterator<Item = &Run> {
+        self.sensors
+            .get(address)
+            .into_iter()
+            .flat_map(|(_, runs)| runs.recent.iter())
+    }
+
+    pub fn report(&self) -> impl Iterator<Item = DriftReport> + '_ {
+        self.sensors.iter().filter_map(|(address, (count, runs))| {
+            let last = *runs.recent.iter().last()?;
+            Some(DriftReport {
+                address: *address,
+                runs: *count,
+                first: runs.first,
+                last,
+                drift: last.offset - runs.first.offset,
+            })
+        })
+    }
+
+    /// The sensors which drifted more than the tolerance (°C).
+    pub fn exceeding(&self, tolerance: f32) -> impl Iterator<Item = DriftReport> + '_ {
+        self.report()
+            .filter(move |report| report.drift.abs() > tolerance)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DAYS: u64 = 86_400;
+
+    #[test]
+    fn drift() {
+        let mut drift = Drift::<4, 2>::default();
+        drift
+            .record(Duration::ZERO, [(Address(1), 0.25), (Address(2), 0.0)])
+            .unwrap();
+        drift
+            .record(Duration::from_secs(10 * DAYS), [(Address(1), 0.5)])
+            .unwrap();
+        drift
+            .record(Duration::from_secs(20 * DAYS), [(Address(1), 1.25)])
+            .unwrap();
+        assert_eq!(drift.runs(&Address(1)).count(), 2);
+        let report = drift.exceeding(0.5).collect::<Vec<_>>();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].address, Address(1));
+        assert_eq!(report[0].runs, 3);
+        assert_eq!(report[0].first.offset, 0.25);
+        assert_eq!(report[0].drift, 1.0);
+        assert_eq!(report[0].rate(), Some(0.05));
+        let report = drift.report().find(|report| report.address == Address(2));
+        assert_eq!(report.unwrap().rate(), None);
+    }
+}
@@ -0,0 +1,914 @@
+use crate::{
+    Error, FAMILY_CODE, Result,
+    address::{Address, Validation},
+    backoff::Backoff,
+    cancellation::Cancellation,
+    command::{self, CommandCode},
+    crc8, instrument,
+    logging::{Subsystem, log},
+    pipeline::Reading,
+    power::{BusPower, PowerMode, StrongPullUp},
+    scratchpad::{Precision, Resolution, Scratchpad, SensorInfo},
+    search::{BitBus, Pass, Search},
+    stats::{BusStats, Operation},
+    timing,
+    transactions::{Transaction, TransactionLog},
+};
+use esp_idf_svc::hal::{
+    delay::Delay,
+    gpio::IOPin,
+    onewire::{DeviceSearch, OWDriver},
+    peripheral::Peripheral,
+    rmt::RmtChannel,
+};
+use esp_idf_svc::sys::{EspError, esp, onewire_bus_read_bit, onewire_bus_write_bit};
+use log::Level;
+use std::{
+    collections::BTreeSet,
+    iter, thread,
+    time::{Duration, Instant},
+};
+
+/// Default number of retries of a failed operation.
+const RETRIES: usize = 3;
+/// Default maximum number of devices kept by a scan.
+pub const MAX_DEVICES: usize = 64;
+
+const HIGH: i8 = 30;
+const LOW: i8 = 19;
+const RESOLUTION: Resolution = Resolution::Twelve;
+
+/// The ds18b20 driver for esp32
+pub struct Ds18b20Driver<'a> {
+    pub driver: OWDriver<'a>,
+    /// The number of retries of a failed conversion or scratchpad read.
+    pub retries: usize,
+    /// The delay before a retry.
+    pub backoff: Backoff,
+    /// The maximum number of devices a scan keeps, more fail it with
+    /// [`Error::TooManyDevices`], e.g. on accidentally bridged buses.
+    pub max_devices: usize,
+    pub(crate) pending: Pending,
+    pub(crate) cancellation: Cancellation,
+    pub(crate) power: Option<BusPower<'a>>,
+    pub(crate) strong_pull_up: Option<StrongPullUp<'a>>,
+    /// Whether a sensor on the bus is parasite-powered, detected at the
+    /// first transaction.
+    pub(crate) power_mode: Option<PowerMode>,
+    /// The sensors written in [`WriteMode::Paranoid`].
+    paranoid: BTreeSet<Address>,
+    stats: BusStats,
+    transactions: TransactionLog,
+    /// The last failed bus operation.
+    failed: Option<Operation>,
+}
+
+impl<'a> Ds18b20Driver<'a> {
+    pub fn new(
+        pin: impl Peripheral<P = impl IOPin> + 'a,
+        channel: impl Peripheral<P = impl RmtChannel> + 'a,
+    ) -> Result<Self> {
+        let driver: OWDriver = OWDriver::new(pin, channel)?;
+        // let delay = Delay::new_default();
+        Ok(Self {
+            driver,
+            retries: RETRIES,
+            backoff: Backoff::default(),
+            max_devices: MAX_DEVICES,
+            pending: Pending::default(),
+            cancellation: Cancellation::new(),
+            power: None,
+            strong_pull_up: None,
+            power_mode: None,
+            paranoid: BTreeSet::new(),
+            stats: BusStats::new(),
+            transactions: TransactionLog::new(),
+            failed: None,
+        })
+    }
+
+    /// The token interrupting the blocking waits of the driver.
+    pub fn cancellation(&self) -> Cancellation {
+        self.cancellation.clone()
+    }
+
+    /// Receive temperature
+    ///
+    /// The conversion and the scratchpad read are retried independently: the
+    /// converted temperature stays in the scratchpad, so a failed read (e.g.
+    /// CRC mismatch) only repeats the read, not the conversion.
+    pub fn temperature(&mut self, address: &Address) -> Result<f32> {
+        self.temperature_with(address, WaitStrategy::Block)
+    }
+
+    /// Receive temperature, waiting for the conversion with the strategy
+    pub fn temperature_with(&mut self, address: &Address, wait: WaitStrategy) -> Result<f32> {
+        instrument::operation("temperature", Some(address), || {
+            preflight(address)?;
+            self.retry(|this| {
+                this.initialization()?
+                    .match_rom(address)?
+                    .convert_temperature_with(wait)
+            })?;
+            let scratchpad =
+                self.retry(|this| this.initialization()?.match_rom(address)?.read_scratchpad())?;
+            Ok(scratchpad.temperature)
+        })
+    }
+
+    /// Receive reading
+    pub fn read(&mut self, address: &Address) -> Result<Reading> {
+        let converted = Instant::now();
+        Ok(Reading::new(*address, self.temperature(address)?).converted_at(converted))
+    }
+
+    /// Reads back the configuration of the sensor.
+    pub fn info(&mut self, address: &Address) -> Result<SensorInfo> {
+        instrument::operation("info", Some(address), || {
+            preflight(address)?;
+            let scratchpad =
+                self.retry(|this| this.initialization()?.match_rom(address)?.read_scratchpad())?;
+            Ok(SensorInfo::new(*address, &scratchpad))
+        })
+    }
+
+    /// Reads the power supply mode of the sensor, e.g. to tell which
+    /// sensor of a parasite-powered bus needs the strong pull-up.
+    pub fn power_mode(&mut self, address: &Address) -> Result<PowerMode> {
+        instrument::operation("power_mode", Some(address), || {
+            preflight(address)?;
+            self.retry(|this| {
+                this.initialization()?
+                    .match_rom(address)?
+                    .read_power_supply()
+            })
+        })
+    }
+
+    /// Sets the fastest resolution of the sensor that reads to the
+    /// precision (°C), keeping the alarm thresholds. The resolution is set in
+    /// the scratchpad only, it isn't committed to EEPROM.
+    pub fn request_precision(&mut self, address: &Address, precision: f32) -> Result<Precision> {
+        let resolution = Resolution::for_precision(precision);
+        self.set_resolution(address, resolution)?;
+        Ok(resolution.into())
+    }
+
+    /// Sets the resolution of the sensor, keeping the alarm thresholds. The
+    /// resolution is set in the scratchpad only, it isn't committed to
+    /// EEPROM.
+    pub fn set_resolution(&mut self, address: &Address, resolution: Resolution) -> Result<()> {
+        instrument::operation("set_resolution", Some(address), || {
+            preflight(address)?;
+            let mut scratchpad =
+                self.retry(|this| this.initialization()?.match_rom(address)?.read_scratchpad())?;
+            if scratchpad.configuration_register.resolution != resolution {
+                scratchpad.configuration_register.resolution = resolution;
+                self.write_scratchpad(address, &scratchpad)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Sets how the scratchpad of the sensor is written, e.g. paranoid for a
+    /// suspected clone.
+    pub fn set_write_mode(&mut self, address: Address, mode: WriteMode) {
+        match mode {
+            WriteMode::Plain => self.paranoid.remove(&address),
+            WriteMode::Paranoid => self.paranoid.insert(address),
+        };
+    }
+
+    pub fn write_mode(&self, address: &Address) -> WriteMode {
+        if self.paranoid.contains(address) {
+            WriteMode::Paranoid
+        } else {
+            WriteMode::Plain
+        }
+    }
+
+    /// Writes TH, TL and the configuration register of the sensor in its
+    /// [`WriteMode`].
+    ///
+    /// A paranoid write fails with [`Error::Verification`] if the sensor
+    /// still reads back other values after the retries.
+    pub fn write_scratchpad(&mut self, address: &Address, scratchpad: &Scratchpad) -> Result<()> {
+        if self.write_mode(address) == WriteMode::Plain {
+            return self.retry(|this| {
+                this.initialization()?
+                    .match_rom(address)?
+                    .write_scratchpad(scratchpad)
+            });
+        }
+        let expected = registers(scratchpad);
+        for attempt in 1..=self.retries + 1 {
+            self.retry(|this| {
+                this.initialization()?
+                    .match_rom(address)?
+                    .write_scratchpad_chunked(scratchpad)
+            })?;
+            // Read back raw: a dropped configuration byte can leave an
+            // invalid configuration register.
+            let read_back = self.retry(|this| {
+                let mut buffer = [0; 9];
+                this.initialization()?
+                    .match_rom(address)?
+                    .read_scratchpad_bytes(&mut buffer)?;
+                crc8::check(&buffer)?;
+                Ok(buffer)
+            })?;
+            let Some(byte) = mismatch(&expected, &read_back) else {
+                return Ok(());
+            };
+            log!(
+                Subsystem::Bus,
+                Level::Warn,
+                "Scratchpad byte {byte} of {address} reads {:#04x}, expected {:#04x} (attempt {attempt})",
+                read_back[byte],
+                expected[byte - 2],
+            );
+        }
+        Err(Error::Verification)
+    }
+
+    /// Start a search for devices attached to the OneWire bus
+    ///
+    /// Fails with [`Error::ConversionPending`] while a conversion started
+    /// without waiting is in flight: the search traffic would corrupt the
+    /// results of parasite-powered sensors.
+    pub fn search(&mut self) -> Result<impl Iterator<Item = Result<Address>>> {
+        self.pending.check(Instant::now())?;
+        let (stats, transactions) = (&mut self.stats, &mut self.transactions);
+        let mut search = self.driver.search()?;
+        let passes = iter::from_fn(move || {
+            let start = Instant::now();
+            let address = search.next()?;
+            let error = address.as_ref().err().map(EspError::code);
+            stats.record(Operation::Search, 0, start.elapsed(), error);
+            transactions.record(Operation::Search, &[], start.elapsed(), error);
+            Some(address)
+        });
+        Ok(passes.map(|address| {
+            let address = Address::from(address?);
+            let family_code = address.family_code();
+            if family_code != FAMILY_CODE {
+                return Err(Error::FamilyCode(family_code));
+            }
+            Ok(address)
+        }))
+    }
+
+    /// Start a search reporting the branches of the search tree, for
+    /// diagnostics
+    ///
+    /// Runs the crate's own search algorithm rather than the driver's, one
+    /// pass per item. Fails like [`search`](Self::search) while a conversion
+    /// is in flight.
+    pub fn trace_search(&mut self) -> Result<impl Iterator<Item = Result<Pass>> + '_> {
+        self.pending.check(Instant::now())?;
+        let mut search = Search::new(CommandCode::SearchRom);
+        Ok(iter::from_fn(move || {
+            let start = Instant::now();
+            let pass = search.next_pass(self)?;
+            self.record_search(start, &pass);
+            Some(pass)
+        }))
+    }
+
+    /// Start an Alarm Search for the sensors whose last conversion was out
+    /// of their TH/TL range
+    ///
+    /// The search is lazy, every item is one search pass, so stopping after
+    /// the first hit saves enumerating the whole bus. Fails like
+    /// [`search`](Self::search) while a conversion is in flight.
+    ///
+    /// On a large bus only the alarming sensors need to be read:
+    ///
+    /// ```ignore
+    /// let alarming: Vec<_> = thermometer.alarms()?.collect::<Result<_>>()?;
+    /// for address in &alarming {
+    ///     warn!("{address}: {} °C", thermometer.temperature(address)?);
+    /// }
+    /// ```
+    pub fn alarms(&mut self) -> Result<AlarmSearch<'_, 'a>> {
+        self.pending.check(Instant::now())?;
+        Ok(AlarmSearch {
+            driver: self,
+            search: Search::new(CommandCode::AlarmSearch),
+        })
+    }
+
+    /// Counts the sensors in alarm.
+    pub fn count_alarms(&mut self) -> Result<usize> {
+        instrument::operation("count_alarms", None, || {
+            self.alarms()?
+                .try_fold(0, |count, address| address.map(|_| count + 1))
+        })
+    }
+
+    // pub fn device(&mut self) -> Result<Address> {
+    //     let search = self.search()?;
+    //     let address = search.next().ok_or(Error::DeviceNotFound)?;
+    //     Ok(address)
+    // }
+    pub fn initialization(&mut self) -> Result<Rom<&mut Self>> {
+        if let Some(power) = &mut self.power {
+            power.on(&self.cancellation)?;
+        }
+        self.release()?;
+        self.reset_pulse()?;
+        if self.power_mode.is_none() {
+            self.power_mode = Some(self.detect_power_mode()?);
+            self.reset_pulse()?;
+        }
+        Ok(Rom(self))
+    }
+
+    pub(crate) fn reset_pulse(&mut self) -> Result<()> {
+        let start = Instant::now();
+        let reset = self.driver.reset();
+        self.record(Operation::Reset, &[], start, &reset);
+        Ok(reset?)
+    }
+
+    /// Reads whether any sensor on the bus is parasite-powered.
+    fn detect_power_mode(&mut self) -> Result<PowerMode> {
+        let power_mode = Rom(&mut *self).skip_rom()?.read_power_supply()?;
+        if power_mode == PowerMode::Parasite {
+            log!(
+                Subsystem::Bus,
+                Level::Info,
+                "Parasite-powered sensors on the bus (strong pull-up {})",
+                if self.strong_pull_up.is_some() {
+                    "configured"
+                } else {
+                    "missing"
+                },
+            );
+        }
+        Ok(power_mode)
+    }
+
+    /// The statistics of the bus operations since the start or the last
+    /// reset.
+    pub fn bus_stats(&self) -> &BusStats {
+        &self.stats
+    }
+
+    pub fn reset_bus_stats(&mut self) {
+        self.stats.clear();
+    }
+
+    /// The last bus transactions, oldest first.
+    pub fn last_transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.transactions.iter()
+    }
+
+    /// Enables or disables keeping the last bus transactions, enabled by
+    /// default.
+    pub fn log_transactions(&mut self, enabled: bool) {
+        self.transactions.set_enabled(enabled);
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let start = Instant::now();
+        let write = self.driver.write(bytes);
+        self.record(Operation::Write, bytes, start, &write);
+        Ok(write?)
+    }
+
+    pub(crate) fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<()> {
+        let start = Instant::now();
+        let read = self.driver.read(buffer);
+        self.record(Operation::Read, buffer, start, &read);
+        Ok(read?)
+    }
+
+    fn record<T>(
+        &mut self,
+        operation: Operation,
+        bytes: &[u8],
+        start: Instant,
+        result: &Result<T, EspError>,
+    ) {
+        let duration = start.elapsed();
+        let error = result.as_ref().err().map(EspError::code);
+        if error.is_some() {
+            self.failed = Some(operation);
+        }
+        self.stats.record(operation, bytes.len(), duration, error);
+        self.transactions.record(operation, bytes, duration, error);
+    }
+
+    /// Records a pass of the crate's own search.
+    fn record_search<T>(&mut self, start: Instant, pass: &Result<T>) {
+        let duration = start.elapsed();
+        let error = match pass {
+            Err(Error::Esp(error)) => Some(error.code()),
+            _ => None,
+        };
+        self.stats.record(Operation::Search, 0, duration, error);
+        self.transactions
+            .record(Operation::Search, &[], duration, error);
+    }
+
+    /// Runs the operation, retrying it up to `retries` times on failure
+    /// after the backoff.
+    pub(crate) fn retry<T>(
+        &mut self,
+        mut operation: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let mut retry = 0;
+        loop {
+            self.failed = None;
+            let result = operation(self);
+            let Err(error) = result else {
+                return result;
+            };
+            // Failures detected after the transfer follow a read.
+            let failed = self.failed.unwrap_or(Operation::Read);
+            if retry >= self.retries {
+                self.stats.record_exhausted(failed);
+                return Err(error);
+            }
+            self.stats.record_retry(failed);
+            let delay = self.backoff.delay(retry as _);
+            retry += 1;
+            log!(
+                Subsystem::Bus,
+                Level::Debug,
+                "Retry {retry}/{} in {delay:?}: {error}",
+                self.retries
+            );
+            self.cancellation.sleep(delay)?;
+        }
+    }
+}
+
+/// Validates the family code and the ROM CRC of a user-provided address, so
+/// no bus transaction is issued to an address no DS18B20 can answer to.
+pub(crate) fn preflight(address: &Address) -> Result<()> {
+    if address.family_code() != FAMILY_CODE || address.validate(Validation::Strict).is_err() {
+        log!(
+            Subsystem::Bus,
+            Level::Warn,
+            "Invalid address {address} (family code {:#04x})",
+            address.family_code(),
+        );
+        return Err(Error::InvalidAddress);
+    }
+    Ok(())
+}
+
+/// TH, TL and the configuration register, in the order of a Write
+/// Scratchpad.
+fn registers(scratchpad: &Scratchpad) -> [u8; 3] {
+    [
+        scratchpad.alarm_high_trigger_register.get() as _,
+        scratchpad.alarm_low_trigger_register.get() as _,
+        scratchpad.configuration_register.into(),
+    ]
+}
+
+/// The first scratchpad byte (2 to 4) differing from the written registers.
+fn mismatch(registers: &[u8; 3], scratchpad: &[u8; 9]) -> Option<usize> {
+    (2..5).find(|&byte| scratchpad[byte] != registers[byte - 2])
+}
+
+/// Scratchpad write mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WriteMode {
+    /// The three bytes in one transfer.
+    #[default]
+    Plain,
+    /// Byte by byte, read back and rewritten until the sensor holds all
+    /// three, for marginal clones dropping the configuration byte. A Write
+    /// Scratchpad always starts at TH, so a retry rewrites every byte.
+    Paranoid,
+}
+
+/// Conversion wait strategy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WaitStrategy {
+    /// Sleep the maximum conversion time.
+    #[default]
+    Block,
+    /// Read time slots every `interval` until the sensor reports the
+    /// conversion complete, failing after `timeout`. Returns as soon as the
+    /// conversion is done, but doesn't work on parasite-powered buses, where
+    /// the bus has to be held high during the conversion.
+    Poll {
+        interval: Duration,
+        timeout: Duration,
+    },
+}
+
+impl WaitStrategy {
+    /// Polls every 10 ms up to the maximum conversion time.
+    pub const POLL: Self = Self::Poll {
+        interval: Duration::from_millis(10),
+        timeout: timing::CONVERSION,
+    };
+}
+
+pub struct Rom<T>(T);
+
+/// ROM function commands
+impl<'a, 'b> Rom<&'a mut Ds18b20Driver<'b>> {
+    /// Read ROM command
+    ///
+    /// This command allows the bus master to read the DS18B20’s 8-bit family
+    /// code, unique 48-bit serial number, and 8-bit CRC. This command can only
+    /// be used if there is a single DS18B20 on the bus. If more than one slave
+    /// is present on the bus, a data collision will occur when all slaves try
+    /// to transmit at the same time (open drain will produce a wired AND
+    /// result).
+    pub fn read_rom(self) -> Result<Address> {
+        self.0.write_bytes(&[CommandCode::ReadRom as _])?;
+        let mut buffer = [0u8; 8];
+        self.0.read_bytes(&mut buffer)?;
+        Address::from_bytes(buffer, Validation::Strict)
+    }
+
+    /// Match ROM command
+    ///
+    /// The match ROM command, followed by a 64-bit ROM sequence, allows the bus
+    /// master to address a specific DS18B20 on a multidrop bus. Only the
+    /// DS18B20 that exactly matches the 64-bit ROM sequence will respond to the
+    /// following memory function command. All slaves that do not match the
+    /// 64-bit ROM sequence will wait for a reset pulse. This command can be
+    /// used with a single or multiple devices on the bus.
+    pub fn match_rom(self, address: &Address) -> Result<Ram<&'a mut Ds18b20Driver<'b>>> {
+        self.0.write_bytes(&command::match_rom(address))?;
+        Ok(Ram(self.0, Some(*address)))
+    }
+
+    /// Skip ROM command
+    ///
+    /// This command can save time in a single drop bus system by allowing the
+    /// bus master to access the memory functions without providing the 64-bit
+    /// ROM code. If more than one slave is present on the bus and a Read
+    /// command is issued following the Skip ROM command, data collision will
+    /// occur on the bus as multiple slaves transmit simultaneously (open drain
+    /// pulldowns will produce a wired AND result).
+    pub fn skip_rom(self) -> Result<Ram<&'a mut Ds18b20Driver<'b>>> {
+        self.0.write_bytes(&[CommandCode::SkipRom as _])?;
+        Ok(Ram(self.0, None))
+    }
+
+    // /// Search ROM command
+    // ///
+    // /// When a system is initially brought up, the bus master might not know the
+    // /// number of devices on the 1-Wire bus or their 64-bit ROM codes. The
+    // /// search ROM command allows the bus master to use a process of elimination
+    // /// to identify the 64-bit ROM codes of all slave devices on the bus.
+    // pub fn search_rom(self) -> Result<DeviceSearch<'a, 'a>> {
+    //     Ok(self.0.driver.search()?)
+    // }
+
+    /// Alarm search command
+    ///
+    /// The operation of this command is identical to the operation of the
+    /// search ROM command except that only slaves with a set alarm flag will
+    /// respond. Every search pass issues its own reset pulse.
+    ///
+    /// Unlike [`Ds18b20Driver::alarms`] the search isn't guarded against a
+    /// conversion in flight.
+    pub fn search_alarm(self) -> AlarmSearch<'a, 'b> {
+        AlarmSearch {
+            driver: self.0,
+            search: Search::new(CommandCode::AlarmSearch),
+        }
+    }
+}
+
+/// Alarm Search
+///
+/// Yields the sensors in alarm one search pass at a time.
+pub struct AlarmSearch<'a, 'b> {
+    driver: &'a mut Ds18b20Driver<'b>,
+    search: Search,
+}
+
+impl Iterator for AlarmSearch<'_, '_> {
+    type Item = Result<Address>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = Instant::now();
+        let address = self.search.next(self.driver)?;
+        self.driver.record_search(start, &address);
+        Some(address.and_then(|address| match address.family_code() {
+            FAMILY_CODE => Ok(address),
+            family_code => Err(Error::FamilyCode(family_code)),
+        }))
+    }
+}
+
+/// Bit-level access for the search algorithm
+impl BitBus for Ds18b20Driver<'_> {
+    fn reset(&mut self) -> Result<()> {
+        self.initialization().map(|_| ())
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<()> {
+        self.write_bytes(&[byte])
+    }
+
+    fn read_bit(&mut self) -> Result<bool> {
+        let mut bit = 0;
+        esp!(unsafe { onewire_bus_read_bit(self.driver.bus(), &mut bit) })?;
+        Ok(bit != 0)
+    }
+
+    fn write_bit(&mut self, bit: bool) -> Result<()> {
+        esp!(unsafe { onewire_bus_write_bit(self.driver.bus(), bit as _) })?;
+        Ok(())
+    }
+}
+
+/// RAM commands
+///
+/// Holds the matched address, if any.
+pub struct Ram<T>(T, Option<Address>);
+
+/// RAM commands
+impl<'a> Ram<&mut Ds18b20Driver<'a>> {
+    /// Reads the entire scratchpad including the CRC byte.
+    pub fn read_scratchpad(self) -> Result<Scratchpad> {
+        self.0.write_bytes(&[CommandCode::ReadScratchpad as _])?;
+        let mut buffer = [0u8; 9];
+        self.0.read_bytes(&mut buffer)?;
+        Scratchpad::from_bytes(buffer).map_err(|error| match error {
+            Error::ScratchpadCrc { buffer, crc, .. } => Error::ScratchpadCrc {
+                address: self.1,
+                buffer,
+                crc,
+            },
+            error => error,
+        })
+    }
+
+    /// Reads the first bytes of the scratchpad without the CRC check. The
+    /// master resets the bus after the last byte.
+    pub fn read_scratchpad_bytes(self, buffer: &mut [u8]) -> Result<()> {
+        self.0.write_bytes(&[CommandCode::ReadScratchpad as _])?;
+        self.0.read_bytes(buffer)
+    }
+
+    /// Writes TH, TL, and configuration register data into scratchpad.
+    pub fn write_scratchpad(self, scratchpad: &Scratchpad) -> Result<()> {
+        self.0.write_bytes(&[CommandCode::WriteScratchpad as _])?;
+        self.0.write_bytes(&registers(scratchpad))
+    }
+
+    /// Writes TH, TL, and configuration register data into scratchpad, one
+    /// byte per transfer.
+    pub fn write_scratchpad_chunked(self, scratchpad: &Scratchpad) -> Result<()> {
+        self.0.write_bytes(&[CommandCode::WriteScratchpad as _])?;
+        for byte in registers(scratchpad) {
+            self.0.write_bytes(&[byte])?;
+        }
+        Ok(())
+    }
+
+    /// Load TH, TL, and configuration register data from the scratchpad to
+    /// EEPROM.
+    ///
+    /// Issues Copy Scratchpad (0x48) and waits out the EEPROM write
+    /// ([`timing::EEPROM_WRITE`]), on parasite-powered buses behind the
+    /// strong pull-up enabled right after the command (within
+    /// [`timing::STRONG_PULL_UP_ON`]). The settings survive a power cycle
+    /// from then on.
+    ///
+    /// Fails with [`Error::ParasitePower`] on parasite-powered buses without
+    /// a strong pull-up, which would power the write.
+    pub fn load_scratchpad(self) -> Result<()> {
+        let parasite = self.0.parasite()?;
+        self.0.write_bytes(&[CommandCode::CopyScratchpad as _])?;
+        if parasite {
+            self.0.hold()?;
+        }
+        // The EEPROM write mustn't be interrupted.
+        thread::sleep(timing::EEPROM_WRITE);
+        self.0.release()
+    }
+
+    /// Save TH, TL, and configuration register data from EEPROM to the
+    /// scratchpad.
+    pub fn save_scratchpad(self) -> Result<()> {
+        self.0.write_bytes(&[CommandCode::RecallE2 as _])?;
+        thread::sleep(timing::RECALL);
+        Ok(())
+    }
+
+    /// This command begins a temperature conversion. No further data is
+    /// required. The temperature conversion will be performed and then the
+    /// DS18B20 will remain idle. If the bus master issues read time slots
+    /// following this command, the DS18B20 will output 0 on the bus as long as
+    /// it is busy making a temperature conversion; it will return a 1 when the
+    /// temperature conversion is complete. If parasite-powered, the bus master
+    /// has to enable a strong pullup for a period greater than tconv
+    /// immediately after issuing this command.
+    ///
+    /// You should wait for the measurement to finish before reading the
+    /// measurement. The amount of time you need to wait depends on the current
+    /// resolution configuration
+    ///
+    /// Fails with [`Error::ParasitePower`] on parasite-powered buses without
+    /// a strong pull-up, rather than reading the power-on value.
+    pub fn convert_temperature(self) -> Result<()> {
+        self.convert_temperature_with(WaitStrategy::Block)
+    }
+
+    /// Begins a temperature conversion and waits for it to finish with the
+    /// strategy. Parasite-powered buses always block: the strong pull-up
+    /// holds the bus high, so it can't be polled.
+    pub fn convert_temperature_with(self, wait: WaitStrategy) -> Result<()> {
+        let parasite = self.0.parasite()?;
+        self.0
+            .write_bytes(&[CommandCode::ConvertTemperature as _])?;
+        if parasite {
+            self.0.hold()?;
+        }
+        match wait {
+            WaitStrategy::Poll { interval, timeout } if !parasite => {
+                let start = Instant::now();
+                let mut buffer = [0u8];
+                loop {
+                    // Read time slots return 0 while the conversion is in
+                    // progress.
+                    self.0.read_bytes(&mut buffer)?;
+                    if buffer[0] != 0 {
+                        break;
+                    }
+                    if start.elapsed() >= timeout {
+                        return Err(Error::ConversionTimeout);
+                    }
+                    self.0.cancellation.sleep(interval)?;
+                }
+            }
+            _ => {
+                // delay proper time for temp conversion, assume max resolution
+                // (12-bits)
+                self.0.cancellation.sleep(timing::CONVERSION)?;
+            }
+        }
+        self.0.release()
+    }
+
+    /// Begins a temperature conversion without waiting for it to finish.
+    ///
+    /// The caller is responsible for waiting the conversion time before
+    /// reading the scratchpad. On parasite-powered buses the strong pull-up
+    /// holds the bus until the next transaction, which cuts the conversion
+    /// short if issued earlier.
+    pub fn start_conversion(self) -> Result<()> {
+        self.start_conversion_for(timing::CONVERSION)
+    }
+
+    /// Begins a conversion taking the time, e.g. of a lower resolution. The
+    /// bus is guarded only that long.
+    pub(crate) fn start_conversion_for(self, conversion: Duration) -> Result<()> {
+        let parasite = self.0.parasite()?;
+        self.0
+            .write_bytes(&[CommandCode::ConvertTemperature as _])?;
+        if parasite {
+            self.0.hold()?;
+        }
+        self.0.pending.start(Instant::now(), conversion);
+        Ok(())
+    }
+
+    /// Signals the mode of DS18B20 power supply to the master. After Skip
+    /// ROM the bus reads [`PowerMode::Parasite`] if any sensor is
+    /// parasite-powered.
+    pub fn read_power_supply(self) -> Result<PowerMode> {
+        self.0.write_bytes(&[CommandCode::ReadPowerSupply as _])?;
+        Ok(PowerMode::from_bit(self.0.read_bit()?))
+    }
+}
+
+/// The conversion in flight on the bus, if any.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Pending {
+    until: Option<Instant>,
+}
+
+impl Pending {
+    pub(crate) fn start(&mut self, now: Instant, duration: Duration) {
+        let until = now + duration;
+        self.until = Some(self.until.map_or(until, |pending| pending.max(until)));
+    }
+
+    /// Fails while the conversion is in flight.
+    pub(crate) fn check(&mut self, now: Instant) -> Result<()> {
+        match self.until {
+            Some(until) if now < until => {
+                log!(
+                    Subsystem::Bus,
+                    Level::Debug,
+                    "Bus access rejected, conversion pending for {:?}",
+                    until - now,
+                );
+                Err(Error::ConversionPending)
+            }
+            _ => {
+                self.until = None;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scratchpad::AlarmThreshold;
+    use esp_idf_svc::hal::{gpio::AnyIOPin, rmt::CHANNEL0};
+
+    #[test]
+    fn preflight() {
+        assert_eq!(super::preflight(&Address(0x1E00_0000_0000_0028)), Ok(()));
+        // CRC
+        assert_eq!(
+            super::preflight(&Address(0x1F00_0000_0000_0028)),
+            Err(Error::InvalidAddress)
+        );
+        // Family code
+        assert_eq!(
+            super::preflight(&Address(0xFB00_0000_0000_0010)),
+            Err(Error::InvalidAddress)
+        );
+    }
+
+    #[test]
+    fn mismatch() {
+        let scratchpad = Scratchpad {
+            temperature: 21.5,
+            alarm_high_trigger_register: AlarmThreshold::clamped(30),
+            alarm_low_trigger_register: AlarmThreshold::clamped(-10),
+            ..Default::default()
+        };
+        let registers = registers(&scratchpad);
+        let mut buffer = scratchpad.to_bytes();
+        assert_eq!(super::mismatch(&registers, &buffer), None);
+        // The configuration byte dropped.
+        buffer[4] = 0x1F;
+        assert_eq!(super::mismatch(&registers, &buffer), Some(4));
+    }
+
+    #[test]
+    fn pending() {
+        let now = Instant::now();
+        let conversion = Duration::from_millis(750);
+        let mut pending = Pending::default();
+        assert_eq!(pending.check(now), Ok(()));
+        // Search during a conversion.
+        pending.start(now, conversion);
+        assert_eq!(pending.check(now), Err(Error::ConversionPending));
+        assert_eq!(
+            pending.check(now + conversion / 2),
+            Err(Error::ConversionPending)
+        );
+        // A second conversion started meanwhile extends the guard.
+        pending.start(now + conversion / 2, conversion);
+        assert_eq!(
+            pending.check(now + conversion),
+            Err(Error::ConversionPending)
+        );
+        assert_eq!(pending.check(now + conversion * 3 / 2), Ok(()));
+        assert_eq!(pending, Pending::default());
+        // An earlier-ending conversion doesn't shorten it.
+        pending.start(now, conversion);
+        pending.start(now, conversion / 2);
+        assert_eq!(
+            pending.check(now + conversion / 2),
+            Err(Error::ConversionPending)
+        );
+    }
+
+    /// The guard fails before any bus traffic, no sensor is needed on the
+    /// bus.
+    #[test]
+    fn conversion_pending() {
+        let pin = unsafe { AnyIOPin::new(4) };
+        let mut driver = Ds18b20Driver::new(pin, unsafe { CHANNEL0::new() }).unwrap();
+        driver.pending.start(Instant::now(), timing::CONVERSION);
+        assert!(matches!(driver.search(), Err(Error::ConversionPending)));
+        assert!(matches!(
+            driver.trace_search(),
+            Err(Error::ConversionPending)
+        ));
+        assert!(matches!(driver.alarms(), Err(Error::ConversionPending)));
+        assert_eq!(driver.count_alarms(), Err(Error::ConversionPending));
+        assert_eq!(driver.power_down(), Err(Error::ConversionPending));
+        assert_eq!(
+            driver.raw_transaction(|_| Ok(())),
+            Err(Error::ConversionPending)
+        );
+        assert_eq!(driver.last_transactions().count(), 0);
+        // Once the conversion is done
+        driver.pending = Pending::default();
+        assert_eq!(driver.power_down(), Ok(()));
+    }
+}
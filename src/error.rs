@@ -1,7 +1,25 @@
+//! Errors
+//!
+//! [`Error`] implements `core::error::Error`, also without `std`. Failures
+//! can carry the sensor and the bus they happened on, and convert into
+//! `anyhow::Error` and other boxed errors with `?`; the context and the
+//! error are separate links of the chain (`{:#}` prints both):
+//!
+//! ```ignore
+//! fn boiler(thermometer: &mut Ds18b20Driver) -> anyhow::Result<f32> {
+//!     Ok(thermometer.temperature(&BOILER).sensor(BOILER)?)
+//! }
+//! ```
+
 use crate::{
     FAMILY_CODE,
+    address::Address,
+    bus::BusId,
+    max31850::Faults,
     scratchpad::{ELEVEN, NINE, TEN, TWELVE},
 };
+use core::fmt::{self, Display, Formatter};
+#[cfg(feature = "esp-idf")]
 use esp_idf_svc::sys::EspError;
 use thiserror::Error;
 
@@ -11,10 +29,21 @@ pub type Result<T, E = Error> = core::result::Result<T, E>;
 /// Error
 #[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
 pub enum Error {
+    #[cfg(feature = "esp-idf")]
     #[error(transparent)]
     Esp(#[from] EspError),
     #[error("device not found")]
     DeviceNotFound,
+    #[error("invalid address")]
+    InvalidAddress,
+    #[error("conversion timed out")]
+    ConversionTimeout,
+    #[error("conversion in progress")]
+    ConversionPending,
+    #[error("parasite-powered sensors without a strong pull-up")]
+    ParasitePower,
+    #[error("cancelled")]
+    Cancelled,
     #[error("unexpected family code {{ family_code={0}, expected={FAMILY_CODE:x} }}")]
     FamilyCode(u8),
     #[error(
@@ -23,6 +52,50 @@ pub enum Error {
     ConfigurationRegister { configuration_register: u8 },
     #[error(transparent)]
     Crc(#[from] CrcError),
+    #[error("unexpected frame CRC {{ crc={crc:#010x}, expected={expected:#010x} }}")]
+    FrameCrc { crc: u32, expected: u32 },
+    #[error(
+        "unexpected scratchpad CRC {{ crc={crc}, expected=0, buffer={buffer:02x?}, address={address:?} }}"
+    )]
+    ScratchpadCrc {
+        /// The matched address, `None` after Skip ROM.
+        address: Option<Address>,
+        buffer: [u8; 9],
+        crc: u8,
+    },
+    #[error("duplicate address {0}")]
+    DuplicateAddress(Address),
+    #[error("duplicate label {{ address={address} }}")]
+    DuplicateLabel {
+        /// The sensor already holding the label.
+        address: Address,
+    },
+    #[error("unknown command code {0:#04x}")]
+    Command(u8),
+    #[error("verification failed")]
+    Verification,
+    #[error("capacity exceeded {{ capacity={0} }}")]
+    Capacity(usize),
+    #[error("no slot {0}")]
+    Slot(usize),
+    #[error("alarm threshold {0} °C out of the range -55..=125 °C")]
+    AlarmThreshold(i32),
+    #[error("search timed out {{ found={found} }}")]
+    SearchTimedOut { found: usize },
+    #[error("too many devices {{ found={found}, max={max} }}")]
+    TooManyDevices { found: usize, max: usize },
+    #[error("invalid configuration {{ line={line} }}")]
+    Syntax { line: usize },
+    #[error("unexpected telemetry format {{ line={line} }}")]
+    Format { line: usize },
+    #[error("malformed data {{ offset={offset} }}")]
+    Decode { offset: usize },
+    #[error("thermocouple faults: {0}")]
+    Thermocouple(Faults),
+    #[error("malformed request")]
+    Request,
+    #[error("unauthorized")]
+    Unauthorized,
 }
 
 /// The CRC error
@@ -31,3 +104,86 @@ pub enum Error {
 pub struct CrcError {
     pub(crate) crc: u8,
 }
+
+/// Error of a sensor or a bus
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SensorError {
+    pub address: Option<Address>,
+    pub bus: Option<BusId>,
+    pub error: Error,
+}
+
+impl From<Error> for SensorError {
+    fn from(error: Error) -> Self {
+        Self {
+            address: None,
+            bus: None,
+            error,
+        }
+    }
+}
+
+/// Displays the context only, the error is the [`source`](core::error::Error::source).
+impl Display for SensorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.address, self.bus) {
+            (Some(address), Some(bus)) => write!(f, "sensor {address} on {bus}"),
+            (Some(address), None) => write!(f, "sensor {address}"),
+            (None, Some(bus)) => write!(f, "{bus}"),
+            (None, None) => write!(f, "unknown sensor"),
+        }
+    }
+}
+
+impl core::error::Error for SensorError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Sensor and bus context of failures
+pub trait ResultExt<T> {
+    /// Attaches the sensor to the error.
+    fn sensor(self, address: Address) -> Result<T, SensorError>;
+
+    /// Attaches the bus to the error.
+    fn bus(self, bus: BusId) -> Result<T, SensorError>;
+}
+
+impl<T, E: Into<SensorError>> ResultExt<T> for Result<T, E> {
+    fn sensor(self, address: Address) -> Result<T, SensorError> {
+        self.map_err(|error| SensorError {
+            address: Some(address),
+            ..error.into()
+        })
+    }
+
+    fn bus(self, bus: BusId) -> Result<T, SensorError> {
+        self.map_err(|error| SensorError {
+            bus: Some(bus),
+            ..error.into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{boxed::Box, string::ToString};
+
+    #[test]
+    fn context() {
+        let result: Result<()> = Err(Error::DeviceNotFound);
+        let error = result.sensor(Address(0x28)).bus(BusId(1)).unwrap_err();
+        assert_eq!(error.to_string(), "sensor 0000000000000028 on bus1");
+        let boxed: Box<dyn core::error::Error + Send + Sync> = error.into();
+        assert_eq!(
+            boxed.source().map(ToString::to_string).as_deref(),
+            Some("device not found")
+        );
+        assert_eq!(
+            SensorError::from(Error::Cancelled).to_string(),
+            "unknown sensor"
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Event queue
+//!
+//! A bounded queue between the producers of events (the alarm engine) and
+//! the application. A burst of events on a large bus can't exhaust memory:
+//! when the queue is full the [`Overflow`] policy decides which event is
+//! lost, and every loss is counted in the [`Counters`].
+
+use crate::{
+    collections::{CAPACITY, Deque},
+    error::Result,
+    logging::{Subsystem, log},
+};
+use log::Level;
+
+/// Overflow policy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// The oldest queued event is dropped to make room.
+    #[default]
+    DropOldest,
+    /// The new event is dropped.
+    DropNewest,
+    /// The new event is refused and given back to the producer, which has
+    /// to wait for the consumer.
+    Block,
+}
+
+/// Queue counters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// Events accepted by the queue, including the dropped ones.
+    pub pushed: usize,
+    pub popped: usize,
+    /// Events dropped by [`Overflow::DropOldest`].
+    pub dropped_oldest: usize,
+    /// Events dropped by [`Overflow::DropNewest`].
+    pub dropped_newest: usize,
+    /// Events refused by [`Overflow::Block`].
+    pub blocked: usize,
+    /// Refused events the producer couldn't wait with, see
+    /// [`EventQueue::push_or_drop`].
+    pub dropped_blocked: usize,
+    /// The maximum number of queued events.
+    pub high_watermark: usize,
+}
+
+impl Counters {
+    /// The number of lost events.
+    pub fn dropped(&self) -> usize {
+        self.dropped_oldest + self.dropped_newest + self.dropped_blocked
+    }
+}
+
+/// Event queue
+#[derive(Clone, Debug)]
+pub struct EventQueue<T, const N: usize = CAPACITY> {
+    events: Deque<T, N>,
+    overflow: Overflow,
+    counters: Counters,
+}
+
+impl<T> EventQueue<T> {
+    pub fn new(overflow: Overflow) -> Self {
+        Self {
+            events: Deque::new(),
+            overflow,
+            counters: Counters::default(),
+        }
+    }
+}
+
+impl<T> Default for EventQueue<T> {
+    fn default() -> Self {
+        Self::new(Overflow::default())
+    }
+}
+
+impl<T, const N: usize> EventQueue<T, N> {
+    /// Sets the maximum number of queued events, dropping the newest events
+    /// that don't fit.
+    pub fn capacity<const M: usize>(mut self) -> EventQueue<T, M> {
+        let mut events = Deque::new();
+        while let Some(event) = self.events.pop_front() {
+            if events.push_back(event).is_err() {
+                break;
+            }
+        }
+        EventQueue {
+            events,
+            overflow: self.overflow,
+            counters: self.counters,
+        }
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
+
+    pub fn reset_counters(&mut self) {
+        self.counters = Counters::default();
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.events.is_full()
+    }
+
+    /// Queues the event. Fails, giving the event back, only if the queue is
+    /// full and the policy is [`Overflow::Block`].
+    pub fn push(&mut self, event: T) -> Result<(), T> {
+        if let Err(event) = self.events.push_back(event) {
+            match self.overflow {
+                Overflow::DropOldest => {
+                    self.events.pop_front();
+                    let _ = self.events.push_back(event);
+                    self.counters.dropped_oldest += 1;
+                }
+                Overflow::DropNewest => self.counters.dropped_newest += 1,
+                Overflow::Block => {
+                    self.counters.blocked += 1;
+                    return Err(event);
+                }
+            }
+            log!(
+                Subsystem::Pipeline,
+                Level::Warn,
+                "Event queue overflow ({:?}): {} events dropped",
+                self.overflow,
+                self.counters.dropped(),
+            );
+        }
+        self.counters.pushed += 1;
+        self.counters.high_watermark = self.counters.high_watermark.max(self.events.len());
+        Ok(())
+    }
+
+    /// Queues the event for a producer that can't wait: an event refused by
+    /// [`Overflow::Block`] is dropped and counted. Returns whether the event
+    /// was queued.
+    pub fn push_or_drop(&mut self, event: T) -> bool {
+        let refused = self.push(event).is_err();
+        if refused {
+            self.counters.dropped_blocked += 1;
+        }
+        !refused
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let event = self.events.pop_front()?;
+        self.counters.popped += 1;
+        Some(event)
+    }
+
+    /// Pops all the queued events.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        core::iter::from_fn(|| self.pop())
+    }
+}
+
+#[cfg(feature = "std")]
+pub use self::blocking::BlockingQueue;
+
+#[cfg(feature = "std")]
+mod blocking {
+    use super::*;
+    use std::sync::{Condvar, Mutex, PoisonError};
+
+    /// Event queue shared between threads
+    ///
+    /// With [`Overflow::Block`] the producer waits until the consumer makes
+    /// room.
+    #[derive(Debug)]
+    pub struct BlockingQueue<T, const N: usize = CAPACITY> {
+        queue: Mutex<EventQueue<T, N>>,
+        popped: Condvar,
+    }
+
+    impl<T, const N: usize> BlockingQueue<T, N> {
+        pub fn new(queue: EventQueue<T, N>) -> Self {
+            Self {
+                queue: Mutex::new(queue),
+                popped: Condvar::new(),
+            }
+        }
+
+        /// Queues the event, waiting for room with [`Overflow::Block`].
+        pub fn push(&self, mut event: T) {
+            let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+            while let Err(refused) = queue.push(event) {
+                event = refused;
+                queue = self
+                    .popped
+                    .wait(queue)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+
+        pub fn pop(&self) -> Option<T> {
+            let event = self
+                .queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop();
+            if event.is_some() {
+                self.popped.notify_all();
+            }
+            event
+        }
+
+        pub fn counters(&self) -> Counters {
+            self.queue
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .counters()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn queue(overflow: Overflow) -> EventQueue<u8, 2> {
+        let mut queue = EventQueue::new(overflow).capacity::<2>();
+        for event in 1..=3 {
+            let _ = queue.push(event);
+        }
+        queue
+    }
+
+    #[test]
+    fn drop_oldest() {
+        let mut queue = queue(Overflow::DropOldest);
+        assert_eq!(queue.drain().collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(
+            queue.counters(),
+            Counters {
+                pushed: 3,
+                popped: 2,
+                dropped_oldest: 1,
+                high_watermark: 2,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn drop_newest() {
+        let mut queue = queue(Overflow::DropNewest);
+        assert_eq!(queue.drain().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(queue.counters().dropped_newest, 1);
+        assert_eq!(queue.counters().dropped(), 1);
+    }
+
+    #[test]
+    fn block() {
+        let mut queue = queue(Overflow::Block);
+        assert_eq!(queue.push(4), Err(4));
+        assert_eq!(queue.counters().blocked, 2);
+        assert_eq!(queue.counters().dropped(), 0);
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.push(4), Ok(()));
+        assert_eq!(queue.drain().collect::<Vec<_>>(), [2, 4]);
+        // A producer that can't wait
+        assert!(queue.push_or_drop(5) && queue.push_or_drop(6));
+        assert!(!queue.push_or_drop(7));
+        assert_eq!(queue.counters().blocked, 3);
+        assert_eq!(queue.counters().dropped(), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn blocking() {
+        let queue = BlockingQueue::new(EventQueue::new(Overflow::Block).capacity::<1>());
+        std::thread::scope(|scope| {
+            scope.spawn(|| (1..=3).for_each(|event| queue.push(event)));
+            let mut events = Vec::new();
+            while events.len() < 3 {
+                events.extend(queue.pop());
+            }
+            assert_eq!(events, [1, 2, 3]);
+        });
+        assert_eq!(queue.counters().dropped(), 0);
+    }
+}
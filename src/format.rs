@@ -0,0 +1,216 @@
+//! Numeric output
+//!
+//! Formatting and scaling helpers for the export paths (CSV, MQTT, Modbus).
+//! The output doesn't depend on any locale: the decimal separator is always
+//! `.`, there is no digit grouping and no exponent.
+//!
+//! Where regulations require a specific rounding, it is selected explicitly
+//! with a [`RoundingMode`] rather than left to `format!("{:.1}")`:
+//!
+//! ```ignore
+//! let text = Fixed::new(21.4375, 1).rounding(RoundingMode::Floor).to_string();
+//! let register = Scale::Ten.encode_with(21.4375, RoundingMode::Truncation);
+//! ```
+
+use core::fmt::{self, Display, Formatter};
+
+/// Rounding mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RoundingMode {
+    /// To the nearest, half away from zero
+    #[default]
+    Nearest,
+    /// Towards negative infinity
+    Floor,
+    /// Towards positive infinity
+    Ceil,
+    /// Towards zero
+    Truncation,
+}
+
+impl RoundingMode {
+    /// Rounds the value to an integer.
+    pub fn round(&self, value: f32) -> f32 {
+        // Values this large have no fractional part.
+        if !value.is_finite() || value.abs() >= 8_388_608.0 {
+            return value;
+        }
+        let truncated = value as i32 as f32;
+        let fraction = value - truncated;
+        match self {
+            RoundingMode::Nearest if fraction >= 0.5 => truncated + 1.0,
+            RoundingMode::Nearest if fraction <= -0.5 => truncated - 1.0,
+            RoundingMode::Floor if fraction < 0.0 => truncated - 1.0,
+            RoundingMode::Ceil if fraction > 0.0 => truncated + 1.0,
+            _ => truncated,
+        }
+    }
+}
+
+/// Fixed-point decimal
+///
+/// Displays the value rounded with the rounding mode to exactly `precision`
+/// decimal places. A value that rounds to zero is displayed without a sign,
+/// non-finite values as `NaN`, `inf` and `-inf`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fixed {
+    pub value: f32,
+    pub precision: usize,
+    pub rounding: RoundingMode,
+}
+
+impl Fixed {
+    pub const fn new(value: f32, precision: usize) -> Self {
+        Self {
+            value,
+            precision,
+            rounding: RoundingMode::Nearest,
+        }
+    }
+
+    pub const fn rounding(self, rounding: RoundingMode) -> Self {
+        Self { rounding, ..self }
+    }
+}
+
+impl Display for Fixed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let factor =
+            (0..self.precision.min(f32::MAX_10_EXP as _)).fold(1.0, |factor, _| factor * 10.0);
+        let scaled = self.value * factor;
+        let mut value = match scaled.is_finite() {
+            true => self.rounding.round(scaled) / factor,
+            false => self.value,
+        };
+        // `-0.001` rounds to `-0.00`.
+        if value == 0.0 {
+            value = 0.0;
+        }
+        write!(f, "{:.*}", self.precision, value)
+    }
+}
+
+/// Integer scale
+///
+/// Industrial protocols commonly transfer temperatures as integers in tenths
+/// or hundredths of a degree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scale {
+    /// ×10 (0.1 °C)
+    #[default]
+    Ten,
+    /// ×100 (0.01 °C)
+    Hundred,
+}
+
+impl Scale {
+    pub const fn factor(&self) -> i32 {
+        match self {
+            Scale::Ten => 10,
+            Scale::Hundred => 100,
+        }
+    }
+
+    /// Scales and rounds the value to the nearest integer. Returns `None` if
+    /// the value is not finite or doesn't fit into an `i16` register.
+    pub fn encode(&self, value: f32) -> Option<i16> {
+        self.encode_with(value, RoundingMode::Nearest)
+    }
+
+    /// Scales and rounds the value with the rounding mode. Returns `None` if
+    /// the value is not finite or doesn't fit into an `i16` register.
+    pub fn encode_with(&self, value: f32, rounding: RoundingMode) -> Option<i16> {
+        i16::try_from(self.encode_i32_with(value, rounding)?).ok()
+    }
+
+    /// Scales and rounds the value to the nearest integer. Returns `None` if
+    /// the value is not finite or doesn't fit.
+    pub fn encode_i32(&self, value: f32) -> Option<i32> {
+        self.encode_i32_with(value, RoundingMode::Nearest)
+    }
+
+    /// Scales and rounds the value with the rounding mode. Returns `None` if
+    /// the value is not finite or doesn't fit.
+    pub fn encode_i32_with(&self, value: f32, rounding: RoundingMode) -> Option<i32> {
+        let scaled = rounding.round(value * self.factor() as f32);
+        (scaled.is_finite() && scaled >= i32::MIN as f32 && scaled <= i32::MAX as f32)
+            .then_some(scaled as _)
+    }
+
+    /// The value of the scaled integer.
+    pub fn decode(&self, scaled: i32) -> f32 {
+        scaled as f32 / self.factor() as f32
+    }
+}
+
+/// Rounds half away from zero, like `f32::round`, which isn't available
+/// without `std`.
+pub(crate) fn round(value: f32) -> f32 {
+    RoundingMode::Nearest.round(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fixed() {
+        assert_eq!(Fixed::new(21.4375, 2).to_string(), "21.44");
+        assert_eq!(Fixed::new(-10.125, 1).to_string(), "-10.1");
+        assert_eq!(Fixed::new(125.0, 3).to_string(), "125.000");
+        assert_eq!(Fixed::new(1e-7, 4).to_string(), "0.0000");
+        assert_eq!(Fixed::new(-0.004, 2).to_string(), "0.00");
+        assert_eq!(Fixed::new(-0.0, 0).to_string(), "0");
+        assert_eq!(Fixed::new(-0.006, 2).to_string(), "-0.01");
+        assert_eq!(Fixed::new(f32::NAN, 2).to_string(), "NaN");
+        assert_eq!(Fixed::new(f32::NEG_INFINITY, 2).to_string(), "-inf");
+        assert!(Fixed::new(100.0, 40).to_string().starts_with("100.000"));
+    }
+
+    #[test]
+    fn rounding() {
+        let fixed = |value, rounding| Fixed::new(value, 1).rounding(rounding).to_string();
+        for (value, nearest, floor, ceil, truncation) in [
+            (21.4375, "21.4", "21.4", "21.5", "21.4"),
+            (21.5625, "21.6", "21.5", "21.6", "21.5"),
+            (-10.125, "-10.1", "-10.2", "-10.1", "-10.1"),
+            (-0.0625, "-0.1", "-0.1", "0.0", "0.0"),
+            (125.0, "125.0", "125.0", "125.0", "125.0"),
+        ] {
+            assert_eq!(fixed(value, RoundingMode::Nearest), nearest);
+            assert_eq!(fixed(value, RoundingMode::Floor), floor);
+            assert_eq!(fixed(value, RoundingMode::Ceil), ceil);
+            assert_eq!(fixed(value, RoundingMode::Truncation), truncation);
+        }
+        assert_eq!(
+            Scale::Ten.encode_with(21.4375, RoundingMode::Ceil),
+            Some(215)
+        );
+        assert_eq!(
+            Scale::Hundred.encode_with(-55.03125, RoundingMode::Truncation),
+            Some(-5503),
+        );
+    }
+
+    #[test]
+    fn scale() {
+        assert_eq!(Scale::Ten.encode(21.4375), Some(214));
+        assert_eq!(Scale::Hundred.encode(21.4375), Some(2144));
+        assert_eq!(Scale::Hundred.encode(-55.0), Some(-5500));
+        assert_eq!(Scale::Hundred.encode(400.0), None);
+        assert_eq!(Scale::Hundred.encode_i32(400.0), Some(40000));
+        assert_eq!(Scale::Ten.encode(f32::NAN), None);
+        assert_eq!(Scale::Ten.decode(-103), -10.3);
+        assert_eq!(Scale::Hundred.decode(2144), 21.44);
+    }
+
+    #[test]
+    fn round() {
+        for value in [0.0, 0.5, -0.5, 1.49, -1.5, 2.5, 342.9375, -55.03125, 1e9] {
+            assert_eq!(super::round(value), value.round());
+            assert_eq!(RoundingMode::Floor.round(value), value.floor());
+            assert_eq!(RoundingMode::Ceil.round(value), value.ceil());
+            assert_eq!(RoundingMode::Truncation.round(value), value.trunc());
+        }
+    }
+}
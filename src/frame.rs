@@ -0,0 +1,94 @@
+//! Binary telemetry frame
+//!
+//! A self-describing frame of the readings of one device, parsed on the
+//! gateway by [`host::parse_frame`](crate::host::parse_frame):
+//!
+//! ```ignore
+//! let frame = frame::encode_frame(device_id, &readings)?;
+//! client.publish(TOPIC, QoS::AtLeastOnce, false, &frame)?;
+//! ```
+//!
+//! Layout, little-endian:
+//!
+//! | bytes          | content |
+//! |----------------|---------|
+//! | 2              | magic `TF` |
+//! | 1              | schema version |
+//! | 8              | device id |
+//! | 1              | reading count |
+//! | 1              | record size |
+//! | count × size   | per reading the address (`u64`) and temperature (`f32`, °C) |
+//! | 4              | CRC-32 of the bytes before |
+//!
+//! Schema versions only append fields to the record, so a parser reads the
+//! fields it knows and skips the rest of each record by the record size: a
+//! gateway keeps parsing the frames of newer firmware. An incompatible
+//! layout gets a new magic.
+
+use crate::{
+    crc32,
+    error::{Error, Result},
+    pipeline::Reading,
+};
+use alloc::vec::Vec;
+
+/// Frame magic
+pub const MAGIC: [u8; 2] = *b"TF";
+/// Schema version
+pub const VERSION: u8 = 1;
+/// Header size (bytes)
+pub const HEADER: usize = 13;
+/// Record size of [`VERSION`] (bytes)
+pub const RECORD: usize = 12;
+/// Trailer size (bytes)
+pub const TRAILER: usize = 4;
+
+/// Encodes the readings of the device into a frame.
+///
+/// Fails with [`Error::Capacity`] on more than 255 readings.
+pub fn encode_frame(device: u64, readings: &[Reading]) -> Result<Vec<u8>> {
+    let count = u8::try_from(readings.len()).map_err(|_| Error::Capacity(u8::MAX as _))?;
+    let mut frame = Vec::with_capacity(HEADER + readings.len() * RECORD + TRAILER);
+    frame.extend_from_slice(&MAGIC);
+    frame.push(VERSION);
+    frame.extend_from_slice(&device.to_le_bytes());
+    frame.push(count);
+    frame.push(RECORD as _);
+    for reading in readings {
+        frame.extend_from_slice(&reading.address.0.to_le_bytes());
+        frame.extend_from_slice(&reading.temperature.to_le_bytes());
+    }
+    let crc = crc32::calculate(&frame);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::address::Address;
+
+    #[test]
+    fn encode_frame() {
+        let reading = Reading::new(Address(0x2300_0004_6EAF_BC28), 21.5);
+        let frame = super::encode_frame(0x0102, &[reading]).unwrap();
+        assert_eq!(frame.len(), HEADER + RECORD + TRAILER);
+        assert_eq!(
+            frame[..HEADER],
+            [b'T', b'F', 1, 2, 1, 0, 0, 0, 0, 0, 0, 1, 12]
+        );
+        assert_eq!(
+            frame[HEADER..HEADER + 8],
+            0x2300_0004_6EAF_BC28u64.to_le_bytes()
+        );
+        assert_eq!(frame[HEADER + 8..HEADER + 12], 21.5f32.to_le_bytes());
+        assert_eq!(
+            frame[HEADER + RECORD..],
+            crc32::calculate(&frame[..HEADER + RECORD]).to_le_bytes()
+        );
+        assert_eq!(
+            super::encode_frame(0, &[reading; 256]),
+            Err(Error::Capacity(255))
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Temperature histograms
+//!
+//! Counts the readings of every sensor in fixed buckets over a range, so
+//! dashboards can show the temperature distribution over days without
+//! storing the samples:
+//!
+//! ```ignore
+//! let mut histograms = Histograms::new(-10.0, 40.0).buckets::<50>();
+//! // ...
+//! for (index, count) in histograms.get(&address)?.counts().iter().enumerate() {
+//!     // ...
+//! }
+//! ```
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    pipeline::{Reading, Stage},
+};
+use core::ops::Range;
+
+/// Default number of buckets.
+pub const BUCKETS: usize = 32;
+
+/// Fixed-bucket histogram
+///
+/// Readings outside the range are counted below or above it, NaN readings
+/// apart. `B` must not be zero, which fails at compile time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram<const B: usize = BUCKETS> {
+    range: Range<f32>,
+    counts: [u32; B],
+    below: u32,
+    above: u32,
+    nan: u32,
+}
+
+impl<const B: usize> Histogram<B> {
+    const NONEMPTY: () = assert!(B > 0, "a histogram needs at least one bucket");
+
+    /// Splits the range (°C) into `B` equal buckets.
+    pub fn new(range: Range<f32>) -> Self {
+        let () = Self::NONEMPTY;
+        Self {
+            range,
+            counts: [0; B],
+            below: 0,
+            above: 0,
+            nan: 0,
+        }
+    }
+
+    pub fn push(&mut self, temperature: f32) {
+        let Range { start, end } = self.range;
+        if temperature.is_nan() {
+            self.nan = self.nan.saturating_add(1);
+        } else if temperature < start {
+            self.below = self.below.saturating_add(1);
+        } else if temperature >= end {
+            self.above = self.above.saturating_add(1);
+        } else {
+            let index = ((temperature - start) / (end - start) * B as f32) as usize;
+            let count = &mut self.counts[index.min(B - 1)];
+            *count = count.saturating_add(1);
+        }
+    }
+
+    pub fn counts(&self) -> &[u32; B] {
+        &self.counts
+    }
+
+    /// The readings below the range.
+    pub fn below(&self) -> u32 {
+        self.below
+    }
+
+    /// The readings above the range.
+    pub fn above(&self) -> u32 {
+        self.above
+    }
+
+    /// The NaN readings, in no bucket.
+    pub fn nan(&self) -> u32 {
+        self.nan
+    }
+
+    /// All counted readings.
+    pub fn total(&self) -> u64 {
+        self.counts
+            .iter()
+            .chain([&self.below, &self.above, &self.nan])
+            .map(|count| *count as u64)
+            .sum()
+    }
+
+    /// The temperature range (°C) of the bucket.
+    pub fn bucket(&self, index: usize) -> Range<f32> {
+        let width = (self.range.end - self.range.start) / B as f32;
+        let start = self.range.start + width * index as f32;
+        start..start + width
+    }
+
+    pub fn reset(&mut self) {
+        self.counts = [0; B];
+        self.below = 0;
+        self.above = 0;
+        self.nan = 0;
+    }
+}
+
+/// Histogram stage
+///
+/// Keeps a histogram per sensor. Readings of sensors beyond the capacity and
+/// stale readings aren't counted.
+#[derive(Clone, Debug)]
+pub struct Histograms<const N: usize = CAPACITY, const B: usize = BUCKETS> {
+    range: Range<f32>,
+    histograms: Map<Address, Histogram<B>, N>,
+}
+
+impl Histograms {
+    /// Histograms over the range (°C).
+    pub fn new(min: f32, max: f32) -> Self {
+        Self {
+            range: min..max,
+            histograms: Map::new(),
+        }
+    }
+}
+
+impl<const N: usize, const B: usize> Histograms<N, B> {
+    /// Sets the maximum number of tracked sensors.
+    pub fn capacity<const M: usize>(self) -> Histograms<M, B> {
+        Histograms {
+            range: self.range,
+            histograms: self.histograms.into_capacity(),
+        }
+    }
+
+    /// Sets the number of buckets, resetting the histograms.
+    pub fn buckets<const C: usize>(self) -> Histograms<N, C> {
+        let () = Histogram::<C>::NONEMPTY;
+        Histograms {
+            range: self.range,
+            histograms: Map::new(),
+        }
+    }
+
+    pub fn get(&self, address: &Address) -> Option<&Histogram<B>> {
+        self.histograms.get(address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, &Histogram<B>)> {
+        self.histograms.iter()
+    }
+
+    /// Resets the histogram of the sensor.
+    pub fn reset(&mut self, address: &Address) -> Option<Histogram<B>> {
+        self.histograms.remove(address)
+    }
+
+    /// Resets the histograms of all sensors.
+    pub fn clear(&mut self) {
+        self.histograms.clear();
+    }
+}
+
+impl<const N: usize, const B: usize> Stage for Histograms<N, B> {
+    fn process(&mut self, reading: Reading) -> Option<Reading> {
+        if !reading.is_stale()
+            && let Ok(histogram) = self
+                .histograms
+                .get_or_insert(reading.address, Histogram::new(self.range.clone()))
+        {
+            histogram.push(reading.temperature);
+        }
+        Some(reading)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::time::Duration;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = Histogram::<4>::new(0.0..20.0);
+        for temperature in [-0.1, 0.0, 4.9, 5.0, 12.5, 19.99, 20.0, 35.0, f32::NAN] {
+            histogram.push(temperature);
+        }
+        assert_eq!(histogram.counts(), &[2, 1, 1, 1]);
+        assert_eq!((histogram.below(), histogram.above()), (1, 2));
+        assert_eq!(histogram.nan(), 1);
+        assert_eq!(histogram.total(), 9);
+        assert_eq!(histogram.bucket(2), 10.0..15.0);
+        histogram.reset();
+        assert_eq!(histogram.total(), 0);
+    }
+
+    #[test]
+    fn histograms() {
+        let mut histograms = Histograms::new(0.0, 40.0).buckets::<8>().capacity::<1>();
+        histograms.process(Reading::new(Address(1), 21.0));
+        histograms.process(Reading {
+            stale: Some(Duration::from_secs(1)),
+            ..Reading::new(Address(1), 21.0)
+        });
+        histograms.process(Reading::new(Address(2), 21.0));
+        let histogram = histograms.get(&Address(1)).unwrap();
+        assert_eq!(histogram.counts()[4], 1);
+        assert_eq!(histogram.total(), 1);
+        assert!(histograms.get(&Address(2)).is_none());
+        assert!(histograms.reset(&Address(1)).is_some());
+        assert!(histograms.get(&Address(1)).is_none());
+    }
+}
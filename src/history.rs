@@ -0,0 +1,192 @@
+//! Temperature history
+//!
+//! Keeps the last `L` temperatures of every sensor in fixed-size ring
+//! buffers. As a pipeline [`Stage`] it records each passing reading and
+//! annotates it with the sensor's [`Trend`].
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::Result,
+    pipeline::{Reading, Stage},
+    trend::{Trend, TrendConfig},
+};
+
+/// Default history length.
+pub const LENGTH: usize = 16;
+
+/// History
+#[derive(Clone, Debug)]
+pub struct History<const L: usize = LENGTH, const N: usize = CAPACITY> {
+    buffers: Map<Address, Buffer<L>, N>,
+    trend: TrendConfig,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            buffers: Map::new(),
+            trend: TrendConfig::default(),
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const L: usize, const N: usize> History<L, N> {
+    /// Sets the maximum number of tracked sensors.
+    pub fn capacity<const M: usize>(self) -> History<L, M> {
+        History {
+            buffers: self.buffers.into_capacity(),
+            trend: self.trend,
+        }
+    }
+
+    /// Sets the number of temperatures kept per sensor.
+    pub fn length<const M: usize>(self) -> History<M, N> {
+        let mut buffers = Map::new();
+        for (address, buffer) in self.buffers.iter() {
+            let mut resized = Buffer::new();
+            buffer.iter().for_each(|value| resized.push(value));
+            let _ = buffers.insert(*address, resized);
+        }
+        History {
+            buffers,
+            trend: self.trend,
+        }
+    }
+
+    /// Sets the trend classification parameters.
+    pub fn trend_config(self, trend: TrendConfig) -> Self {
+        Self { trend, ..self }
+    }
+
+    /// Records the temperature of the sensor. Fails with
+    /// [`Error::Capacity`](crate::Error::Capacity) if the sensor is new and
+    /// the history is full.
+    pub fn push(&mut self, address: Address, temperature: f32) -> Result<()> {
+        self.buffers
+            .get_or_insert(address, Buffer::new())?
+            .push(temperature);
+        Ok(())
+    }
+
+    /// The recorded temperatures of the sensor, oldest first.
+    pub fn temperatures(&self, address: &Address) -> impl Iterator<Item = f32> + '_ {
+        self.buffers.get(address).into_iter().flat_map(Buffer::iter)
+    }
+
+    /// The latest recorded temperature of the sensor.
+    pub fn latest(&self, address: &Address) -> Option<f32> {
+        self.temperatures(address).last()
+    }
+
+    /// The trend of the sensor over the configured window.
+    pub fn trend(&self, address: &Address) -> Option<Trend> {
+        let buffer = self.buffers.get(address)?;
+        let skip = buffer.len.saturating_sub(self.trend.window);
+        Trend::classify(buffer.iter().skip(skip), &self.trend)
+    }
+
+    /// Forgets the recorded temperatures of the sensor.
+    pub fn clear(&mut self, address: &Address) {
+        self.buffers.remove(address);
+    }
+}
+
+impl<const L: usize, const N: usize> Stage for History<L, N> {
+    fn process(&mut self, mut reading: Reading) -> Option<Reading> {
+        if self.push(reading.address, reading.temperature).is_ok() {
+            reading.trend = self.trend(&reading.address);
+        }
+        Some(reading)
+    }
+}
+
+/// Ring buffer
+#[derive(Clone, Copy, Debug)]
+struct Buffer<const L: usize> {
+    values: [f32; L],
+    start: usize,
+    len: usize,
+}
+
+impl<const L: usize> Buffer<L> {
+    fn new() -> Self {
+        Self {
+            values: [0.0; L],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, value: f32) {
+        if L == 0 {
+            return;
+        }
+        self.values[(self.start + self.len) % L] = value;
+        if self.len < L {
+            self.len += 1;
+        } else {
+            self.start = (self.start + 1) % L;
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = f32> + '_ {
+        (0..self.len).map(|index| self.values[(self.start + index) % L])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push() {
+        let mut history = History::new().length::<3>();
+        for temperature in [1.0, 2.0, 3.0, 4.0] {
+            history.push(Address(1), temperature).unwrap();
+        }
+        assert_eq!(
+            history.temperatures(&Address(1)).collect::<Vec<_>>(),
+            [2.0, 3.0, 4.0]
+        );
+        assert_eq!(history.latest(&Address(1)), Some(4.0));
+        assert_eq!(history.temperatures(&Address(2)).count(), 0);
+        history.clear(&Address(1));
+        assert_eq!(history.latest(&Address(1)), None);
+    }
+
+    #[test]
+    fn length() {
+        let mut history = History::new();
+        for temperature in [1.0, 2.0, 3.0] {
+            history.push(Address(1), temperature).unwrap();
+        }
+        let history = history.length::<2>();
+        assert_eq!(
+            history.temperatures(&Address(1)).collect::<Vec<_>>(),
+            [2.0, 3.0]
+        );
+    }
+
+    #[test]
+    fn trend() {
+        let mut history = History::new().trend_config(TrendConfig {
+            window: 3,
+            threshold: 0.1,
+        });
+        let mut process = |temperature| history.process(Reading::new(Address(1), temperature));
+        assert_eq!(process(20.0).unwrap().trend, None);
+        assert_eq!(process(20.5).unwrap().trend, Some(Trend::Rising));
+        assert_eq!(process(21.0).unwrap().trend, Some(Trend::Rising));
+        // The window only covers the latest three temperatures.
+        assert_eq!(process(21.0).unwrap().trend, Some(Trend::Rising));
+        assert_eq!(process(21.0).unwrap().trend, Some(Trend::Stable));
+        assert_eq!(process(20.0).unwrap().trend, Some(Trend::Falling));
+    }
+}
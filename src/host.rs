@@ -0,0 +1,153 @@
+//! Host-side telemetry parsing
+//!
+//! Decodes the telemetry exported by the firmware back into readings, so
+//! gateway software can use the same crate (without esp-idf) on both ends of
+//! the link.
+
+use crate::{
+    Error, Result,
+    address::{Address, Validation},
+    crc32, csv,
+    frame::{HEADER, MAGIC, RECORD, TRAILER},
+    pipeline::Reading,
+};
+
+/// Parses CSV telemetry. The header record and blank lines are skipped.
+pub fn parse_csv(input: &str) -> impl Iterator<Item = Result<Reading>> + '_ {
+    input
+        .lines()
+        .enumerate()
+        .map(|(index, record)| (index + 1, record.trim()))
+        .filter(|(_, record)| !record.is_empty() && *record != csv::HEADER)
+        .map(|(line, record)| parse_csv_record(record).ok_or(Error::Format { line }))
+}
+
+/// Parses a CSV record.
+pub fn parse_csv_record(record: &str) -> Option<Reading> {
+    let (address, temperature) = record.split_once(csv::SEPARATOR)?;
+    Some(Reading::new(
+        Address::from_hex(address, Validation::Lenient).ok()?,
+        temperature.trim().parse().ok()?,
+    ))
+}
+
+/// Decoded telemetry frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// The schema version the frame was encoded with.
+    pub version: u8,
+    pub device: u64,
+    pub readings: Vec<Reading>,
+}
+
+/// Parses a [`frame`](crate::frame) of any schema version, skipping the record fields
+/// appended after the known ones.
+///
+/// Fails with [`Error::FrameCrc`] on a corrupted frame and with
+/// [`Error::Decode`] at the first malformed byte.
+pub fn parse_frame(bytes: &[u8]) -> Result<Frame> {
+    let Some(header) = bytes.first_chunk::<HEADER>() else {
+        return Err(Error::Decode {
+            offset: bytes.len(),
+        });
+    };
+    if header[..2] != MAGIC {
+        return Err(Error::Decode { offset: 0 });
+    }
+    if header[2] == 0 {
+        return Err(Error::Decode { offset: 2 });
+    }
+    let (count, size) = (header[11] as usize, header[12] as usize);
+    if size < RECORD {
+        return Err(Error::Decode { offset: 12 });
+    }
+    let length = HEADER + count * size + TRAILER;
+    if bytes.len() != length {
+        return Err(Error::Decode {
+            offset: bytes.len().min(length),
+        });
+    }
+    let (body, trailer) = bytes.split_at(length - TRAILER);
+    let (crc, expected) = (
+        crc32::calculate(body),
+        u32::from_le_bytes(trailer.try_into().unwrap()),
+    );
+    if crc != expected {
+        return Err(Error::FrameCrc { crc, expected });
+    }
+    let readings = body[HEADER..]
+        .chunks_exact(size)
+        .map(|record| {
+            Reading::new(
+                Address(u64::from_le_bytes(record[..8].try_into().unwrap())),
+                f32::from_le_bytes(record[8..12].try_into().unwrap()),
+            )
+        })
+        .collect();
+    Ok(Frame {
+        version: header[2],
+        device: u64::from_le_bytes(header[3..11].try_into().unwrap()),
+        readings,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame;
+
+    #[test]
+    fn parse_csv() {
+        let reading = Reading::new(Address(0x2300_0004_6EAF_BC28), 21.4375);
+        let input = format!("{}\n{}\n\n{}\n", csv::HEADER, csv::encode(&reading), "28,x");
+        let mut readings = super::parse_csv(&input);
+        assert_eq!(readings.next(), Some(Ok(reading)));
+        assert_eq!(readings.next(), Some(Err(Error::Format { line: 4 })));
+        assert_eq!(readings.next(), None);
+    }
+
+    #[test]
+    fn parse_frame() {
+        let readings = [
+            Reading::new(Address(0x2300_0004_6EAF_BC28), 21.4375),
+            Reading::new(Address(0x28), -10.125),
+        ];
+        let mut bytes = frame::encode_frame(7, &readings).unwrap();
+        assert_eq!(
+            super::parse_frame(&bytes),
+            Ok(Frame {
+                version: frame::VERSION,
+                device: 7,
+                readings: readings.to_vec(),
+            })
+        );
+        assert!(matches!(
+            super::parse_frame(&bytes[..bytes.len() - 1]),
+            Err(Error::Decode { .. })
+        ));
+        bytes[HEADER] ^= 1;
+        assert!(matches!(
+            super::parse_frame(&bytes),
+            Err(Error::FrameCrc { .. })
+        ));
+        // A newer version with a field appended to the record.
+        let mut newer = vec![b'T', b'F', 2, 7, 0, 0, 0, 0, 0, 0, 0, 1, 14];
+        newer.extend_from_slice(&0x28u64.to_le_bytes());
+        newer.extend_from_slice(&20.0f32.to_le_bytes());
+        newer.extend_from_slice(&[0xAA, 0x55]);
+        newer.extend_from_slice(&crc32::calculate(&newer).to_le_bytes());
+        let frame = super::parse_frame(&newer).unwrap();
+        assert_eq!(frame.version, 2);
+        assert_eq!(frame.readings, [Reading::new(Address(0x28), 20.0)]);
+    }
+
+    #[test]
+    fn parse_csv_record() {
+        assert_eq!(
+            super::parse_csv_record("0000000000000028,-10.125"),
+            Some(Reading::new(Address(0x28), -10.125)),
+        );
+        assert_eq!(super::parse_csv_record("0000000000000028"), None);
+        assert_eq!(super::parse_csv_record("address,21.5"), None);
+    }
+}
@@ -0,0 +1,99 @@
+//! Probe finding
+//!
+//! Which physical probe is this address? The sensor is sampled back to back
+//! and every reading reports its change, so warming the probe in a hand
+//! shows up within a conversion or two:
+//!
+//! ```ignore
+//! thermometer.identify(&address, WaitStrategy::Block, |probe| {
+//!     println!("{:+.2} °C", probe.rise);
+//!     probe.rise < 1.0
+//! })?;
+//! println!("Found it");
+//! ```
+//!
+//! The readings come at the rate of the sensor's resolution. Polling for the
+//! end of the conversion (on an externally powered bus) shortens the round
+//! trip further.
+
+use crate::{Ds18b20Driver, Result, WaitStrategy, address::Address, driver::preflight};
+
+/// Identify reading (°C)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Probe {
+    pub temperature: f32,
+    /// The change since the previous reading.
+    pub delta: f32,
+    /// The change since the first reading.
+    pub rise: f32,
+}
+
+/// The changes of the readings
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Watch {
+    baseline: f32,
+    previous: f32,
+}
+
+impl Watch {
+    fn new(baseline: f32) -> Self {
+        Self {
+            baseline,
+            previous: baseline,
+        }
+    }
+
+    fn update(&mut self, temperature: f32) -> Probe {
+        let probe = Probe {
+            temperature,
+            delta: temperature - self.previous,
+            rise: temperature - self.baseline,
+        };
+        self.previous = temperature;
+        probe
+    }
+}
+
+impl Ds18b20Driver<'_> {
+    /// Samples the sensor back to back, passing every reading to the
+    /// callback until it returns `false`. Cancellable through the driver's
+    /// cancellation token.
+    pub fn identify(
+        &mut self,
+        address: &Address,
+        wait: WaitStrategy,
+        mut on_probe: impl FnMut(&Probe) -> bool,
+    ) -> Result<()> {
+        preflight(address)?;
+        let mut watch = Watch::new(self.temperature_with(address, wait)?);
+        while on_probe(&watch.update(self.temperature_with(address, wait)?)) {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn watch() {
+        let mut watch = Watch::new(21.0);
+        assert_eq!(
+            watch.update(21.0),
+            Probe {
+                temperature: 21.0,
+                delta: 0.0,
+                rise: 0.0,
+            }
+        );
+        watch.update(22.5);
+        assert_eq!(
+            watch.update(23.0),
+            Probe {
+                temperature: 23.0,
+                delta: 0.5,
+                rise: 2.0,
+            }
+        );
+    }
+}
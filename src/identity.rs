@@ -0,0 +1,228 @@
+//! Sensor identity
+//!
+//! Binds the sensors to their physical positions (slot, zone) and learns the
+//! temperature profile of every position. After maintenance, the current
+//! temperatures are checked against the profiles: a probe that no longer
+//! matches its position, e.g. the "boiler" probe suddenly tracking ambient,
+//! was likely swapped, and the position it matches instead is reported:
+//!
+//! ```ignore
+//! let mut identity = Identity::new();
+//! identity.bind(boiler, "boiler")?;
+//! identity.bind(room, "room")?;
+//! for reading in readings {
+//!     identity.process(reading);
+//! }
+//! // ... after maintenance:
+//! identity.learning = false;
+//! for mismatch in identity.check(|address| history.latest(address)) {
+//!     warn!("{} isn't at {}, likely at {:?}", mismatch.address, mismatch.expected, mismatch.likely);
+//! }
+//! ```
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::Result,
+    pipeline::{Reading, Stage},
+};
+use alloc::{string::String, vec::Vec};
+
+/// The smallest standard deviation (°C) of a profile, so a position with a
+/// very steady history doesn't flag ordinary noise.
+pub const MIN_SPREAD: f32 = 0.5;
+
+/// Temperature profile of a position
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub count: usize,
+    /// Mean (°C)
+    pub mean: f32,
+    m2: f32,
+}
+
+impl Profile {
+    pub fn push(&mut self, temperature: f32) {
+        // Welford
+        self.count += 1;
+        let delta = temperature - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (temperature - self.mean);
+    }
+
+    /// Standard deviation (°C), at least [`MIN_SPREAD`].
+    pub fn spread(&self) -> f32 {
+        let variance = match self.count {
+            0 | 1 => 0.0,
+            count => self.m2 / (count - 1) as f32,
+        };
+        variance.sqrt().max(MIN_SPREAD)
+    }
+
+    /// The distance of the temperature from the mean in standard deviations.
+    pub fn distance(&self, temperature: f32) -> f32 {
+        (temperature - self.mean).abs() / self.spread()
+    }
+}
+
+/// Bound position
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Position {
+    pub name: String,
+    pub profile: Profile,
+}
+
+/// Identity mismatch
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    pub address: Address,
+    /// The bound position.
+    pub expected: String,
+    /// The position the temperature matches instead, if any.
+    pub likely: Option<String>,
+}
+
+/// Identity binding
+#[derive(Clone, Debug)]
+pub struct Identity<const N: usize = CAPACITY> {
+    positions: Map<Address, Position, N>,
+    /// The distance (in standard deviations) beyond which a temperature
+    /// doesn't match a profile.
+    pub tolerance: f32,
+    /// Whether passing readings are learned; turn it off during maintenance.
+    pub learning: bool,
+}
+
+impl Identity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const N: usize> Default for Identity<N> {
+    fn default() -> Self {
+        Self {
+            positions: Map::new(),
+            tolerance: 3.0,
+            learning: true,
+        }
+    }
+}
+
+impl<const N: usize> Identity<N> {
+    /// Sets the maximum number of bound sensors.
+    pub fn capacity<const M: usize>(self) -> Identity<M> {
+        Identity {
+            positions: self.positions.into_capacity(),
+            tolerance: self.tolerance,
+            learning: self.learning,
+        }
+    }
+
+    /// Binds the sensor to the position, forgetting the learned profile if
+    /// the position changes.
+    pub fn bind(&mut self, address: Address, position: impl Into<String>) -> Result<()> {
+        let name = position.into();
+        let position = self.positions.get_or_insert(address, Position::default())?;
+        if position.name != name {
+            *position = Position {
+                name,
+                profile: Profile::default(),
+            };
+        }
+        Ok(())
+    }
+
+    pub fn unbind(&mut self, address: &Address) -> Option<Position> {
+        self.positions.remove(address)
+    }
+
+    pub fn position(&self, address: &Address) -> Option<&Position> {
+        self.positions.get(address)
+    }
+
+    /// Checks the current temperatures of the bound sensors against the
+    /// profiles of their positions. Sensors without a temperature or a
+    /// learned profile are skipped.
+    pub fn check(&self, temperature: impl Fn(&Address) -> Option<f32>) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        for (address, position) in self.positions.iter() {
+            let Some(temperature) = temperature(address) else {
+                continue;
+            };
+            if position.profile.count == 0
+                || position.profile.distance(temperature) <= self.tolerance
+            {
+                continue;
+            }
+            let likely = self
+                .positions
+                .values()
+                .filter(|other| other.name != position.name && other.profile.count != 0)
+                .map(|other| (other, other.profile.distance(temperature)))
+                .filter(|(_, distance)| *distance <= self.tolerance)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(other, _)| other.name.clone());
+            mismatches.push(Mismatch {
+                address: *address,
+                expected: position.name.clone(),
+                likely,
+            });
+        }
+        mismatches
+    }
+}
+
+/// Learns the profiles from fresh readings of the bound sensors.
+impl<const N: usize> Stage for Identity<N> {
+    fn process(&mut self, reading: Reading) -> Option<Reading> {
+        if self.learning
+            && !reading.is_stale()
+            && let Some(position) = self.positions.get_mut(&reading.address)
+        {
+            position.profile.push(reading.temperature);
+        }
+        Some(reading)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn swap() {
+        let (boiler, room) = (Address(1), Address(2));
+        let mut identity = Identity::new();
+        identity.bind(boiler, "boiler").unwrap();
+        identity.bind(room, "room").unwrap();
+        for temperature in [60.0, 61.0, 59.0, 60.0] {
+            identity.process(Reading::new(boiler, temperature));
+            identity.process(Reading::new(room, temperature - 39.0));
+        }
+        let current = |boiler_temperature, room_temperature| {
+            move |address: &Address| match address.0 {
+                1 => Some(boiler_temperature),
+                2 => Some(room_temperature),
+                _ => None,
+            }
+        };
+        assert_eq!(identity.check(current(60.5, 21.0)), []);
+        assert_eq!(
+            identity.check(current(21.0, 60.0)),
+            [
+                Mismatch {
+                    address: boiler,
+                    expected: "boiler".into(),
+                    likely: Some("room".into()),
+                },
+                Mismatch {
+                    address: room,
+                    expected: "room".into(),
+                    likely: Some("boiler".into()),
+                },
+            ]
+        );
+        assert_eq!(identity.check(current(40.0, 21.0))[0].likely, None);
+    }
+}
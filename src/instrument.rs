@@ -0,0 +1,43 @@
+//! `tracing` instrumentation
+//!
+//! With the `tracing` feature every driver operation runs in a `ds18b20`
+//! span with the operation and the address, and closes with an event
+//! carrying its duration and result. Without it the operations run as they
+//! are.
+
+use crate::{Result, address::Address};
+
+/// Runs the driver operation in its span.
+#[cfg(feature = "tracing")]
+pub(crate) fn operation<T>(
+    operation: &'static str,
+    address: Option<&Address>,
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    use tracing::{Level, debug_span, event, field};
+
+    let span = debug_span!("ds18b20", operation, address = field::Empty);
+    if let Some(address) = address {
+        span.record("address", field::display(address));
+    }
+    let _entered = span.enter();
+    let start = std::time::Instant::now();
+    let result = f();
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    match &result {
+        Ok(_) => event!(Level::DEBUG, elapsed_us, "ok"),
+        Err(error) => event!(Level::WARN, elapsed_us, %error, "failed"),
+    }
+    result
+}
+
+/// Runs the driver operation.
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn operation<T>(
+    _operation: &'static str,
+    _address: Option<&Address>,
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    f()
+}
@@ -0,0 +1,315 @@
+//! Thermal runaway interlock
+//!
+//! A safety net independent of the application logic. The interlock trips
+//! and drives its [`SafeState`] output (e.g. a heater off GPIO) when any
+//! sensor exceeds the hard limit, a watched sensor hasn't reported for too
+//! long or the sampler stalls. A trip is latched: the output stays in
+//! the safe state until [`Interlock::reset_interlock`] is called.
+//!
+//! ```ignore
+//! let pin = SafeStatePin::new(PinDriver::output(peripherals.pins.gpio5)?, Level::Low);
+//! let mut interlock = Interlock::new(pin, InterlockConfig::default());
+//! interlock.watch(address)?;
+//! loop {
+//!     for reading in sweep.readings.iter().flatten() {
+//!         interlock.feed(reading)?;
+//!     }
+//!     interlock.heartbeat();
+//!     interlock.check()?;
+//! }
+//! ```
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::Result,
+    logging::{Subsystem, log},
+    pipeline::{Reading, Stage},
+};
+use log::Level;
+use std::time::{Duration, Instant};
+
+/// Safe state output
+pub trait SafeState {
+    /// Drives the output into the safe state.
+    fn engage(&mut self) -> Result<()>;
+
+    /// Releases the output from the safe state.
+    fn release(&mut self) -> Result<()>;
+}
+
+/// Interlock parameters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InterlockConfig {
+    /// The hard temperature limit (°C).
+    pub limit: f32,
+    /// The maximum time without a reading of a watched sensor.
+    pub communication_timeout: Duration,
+    /// The maximum time without a sampler heartbeat.
+    pub stall_timeout: Duration,
+}
+
+impl Default for InterlockConfig {
+    fn default() -> Self {
+        Self {
+            limit: 85.0,
+            communication_timeout: Duration::from_secs(30),
+            stall_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Trip cause
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trip {
+    OverTemperature { address: Address, temperature: f32 },
+    CommunicationLost { address: Address },
+    SamplerStalled,
+}
+
+/// Interlock
+#[derive(Debug)]
+pub struct Interlock<T, const N: usize = CAPACITY> {
+    output: T,
+    config: InterlockConfig,
+    watched: Map<Address, Instant, N>,
+    heartbeat: Instant,
+    trip: Option<Trip>,
+}
+
+impl<T: SafeState> Interlock<T> {
+    /// The output is not driven until the interlock trips.
+    pub fn new(output: T, config: InterlockConfig) -> Self {
+        Self {
+            output,
+            config,
+            watched: Map::new(),
+            heartbeat: Instant::now(),
+            trip: None,
+        }
+    }
+}
+
+impl<T: SafeState, const N: usize> Interlock<T, N> {
+    /// Sets the maximum number of watched sensors.
+    pub fn capacity<const M: usize>(self) -> Interlock<T, M> {
+        Interlock {
+            output: self.output,
+            config: self.config,
+            watched: self.watched.into_capacity(),
+            heartbeat: self.heartbeat,
+            trip: self.trip,
+        }
+    }
+
+    /// Watches the communication with the sensor.
+    pub fn watch(&mut self, address: Address) -> Result<()> {
+        self.watched.get_or_insert(address, Instant::now())?;
+        Ok(())
+    }
+
+    pub fn unwatch(&mut self, address: &Address) {
+        self.watched.remove(address);
+    }
+
+    /// The latched trip cause.
+    pub fn trip(&self) -> Option<Trip> {
+        self.trip
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.trip.is_some()
+    }
+
+    /// Checks the reading against the hard limit. Readings of any sensor are
+    /// checked, watched or not. Stale readings don't count as communication.
+    pub fn feed(&mut self, reading: &Reading) -> Result<()> {
+        if !reading.is_stale()
+            && let Some(seen) = self.watched.get_mut(&reading.address)
+        {
+            *seen = Instant::now();
+        }
+        if reading.temperature > self.config.limit {
+            self.engage(Trip::OverTemperature {
+                address: reading.address,
+                temperature: reading.temperature,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Signals that the sampler is alive.
+    pub fn heartbeat(&mut self) {
+        self.heartbeat = Instant::now();
+    }
+
+    /// Checks the communication and sampler timeouts. Returns the latched
+    /// trip cause.
+    pub fn check(&mut self) -> Result<Option<Trip>> {
+        if self.heartbeat.elapsed() > self.config.stall_timeout {
+            self.engage(Trip::SamplerStalled)?;
+        }
+        let lost = self
+            .watched
+            .iter()
+            .find(|(_, seen)| seen.elapsed() > self.config.communication_timeout)
+            .map(|(address, _)| *address);
+        if let Some(address) = lost {
+            self.engage(Trip::CommunicationLost { address })?;
+        }
+        Ok(self.trip)
+    }
+
+    /// Clears the latched trip and releases the output. The timeouts start
+    /// over, the conditions are checked again by the next feed or check.
+    pub fn reset_interlock(&mut self) -> Result<()> {
+        let now = Instant::now();
+        self.heartbeat = now;
+        self.watched.iter_mut().for_each(|(_, seen)| *seen = now);
+        if let Some(trip) = self.trip.take() {
+            log!(
+                Subsystem::Sampler,
+                Level::Warn,
+                "Interlock reset after {trip:?}"
+            );
+        }
+        self.output.release()
+    }
+
+    /// Drives the output into the safe state. The first cause is latched.
+    fn engage(&mut self, trip: Trip) -> Result<()> {
+        if self.trip.is_none() {
+            log!(
+                Subsystem::Sampler,
+                Level::Error,
+                "Interlock tripped: {trip:?}"
+            );
+            self.trip = Some(trip);
+        }
+        // Driven on every trip, in case the previous attempt failed.
+        self.output.engage()
+    }
+}
+
+impl<T: SafeState, const N: usize> Stage for Interlock<T, N> {
+    fn process(&mut self, reading: Reading) -> Option<Reading> {
+        if let Err(error) = self.feed(&reading) {
+            log!(
+                Subsystem::Sampler,
+                Level::Error,
+                "Interlock output failed: {error}"
+            );
+        }
+        Some(reading)
+    }
+}
+
+#[cfg(feature = "esp-idf")]
+pub use self::pin::SafeStatePin;
+
+#[cfg(feature = "esp-idf")]
+mod pin {
+    use super::*;
+    use esp_idf_svc::hal::gpio::{self, Output, OutputPin, PinDriver};
+
+    /// Safe state GPIO
+    pub struct SafeStatePin<'d, T: OutputPin> {
+        pin: PinDriver<'d, T, Output>,
+        safe: gpio::Level,
+    }
+
+    impl<'d, T: OutputPin> SafeStatePin<'d, T> {
+        /// The pin is driven to the `safe` level in the safe state and to the
+        /// opposite level otherwise.
+        pub fn new(pin: PinDriver<'d, T, Output>, safe: gpio::Level) -> Self {
+            Self { pin, safe }
+        }
+    }
+
+    impl<T: OutputPin> SafeState for SafeStatePin<'_, T> {
+        fn engage(&mut self) -> Result<()> {
+            Ok(self.pin.set_level(self.safe)?)
+        }
+
+        fn release(&mut self) -> Result<()> {
+            let level = match self.safe {
+                gpio::Level::Low => gpio::Level::High,
+                gpio::Level::High => gpio::Level::Low,
+            };
+            Ok(self.pin.set_level(level)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Output {
+        safe: bool,
+    }
+
+    impl SafeState for &mut Output {
+        fn engage(&mut self) -> Result<()> {
+            self.safe = true;
+            Ok(())
+        }
+
+        fn release(&mut self) -> Result<()> {
+            self.safe = false;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn over_temperature() {
+        let mut output = Output::default();
+        let mut interlock = Interlock::new(&mut output, InterlockConfig::default());
+        interlock.feed(&Reading::new(Address(1), 80.0)).unwrap();
+        assert_eq!(interlock.check(), Ok(None));
+        interlock.feed(&Reading::new(Address(1), 90.0)).unwrap();
+        // Latched, the first cause is kept.
+        interlock.feed(&Reading::new(Address(2), 95.0)).unwrap();
+        interlock.feed(&Reading::new(Address(1), 20.0)).unwrap();
+        let trip = Trip::OverTemperature {
+            address: Address(1),
+            temperature: 90.0,
+        };
+        assert_eq!(interlock.check(), Ok(Some(trip)));
+        interlock.reset_interlock().unwrap();
+        assert!(!interlock.is_tripped());
+        drop(interlock);
+        assert!(!output.safe);
+    }
+
+    #[test]
+    fn timeouts() {
+        let mut output = Output::default();
+        let config = InterlockConfig {
+            communication_timeout: Duration::ZERO,
+            stall_timeout: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let mut interlock = Interlock::new(&mut output, config);
+        interlock.watch(Address(1)).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        interlock.heartbeat();
+        assert_eq!(
+            interlock.check(),
+            Ok(Some(Trip::CommunicationLost {
+                address: Address(1)
+            }))
+        );
+        drop(interlock);
+        assert!(output.safe);
+
+        let config = InterlockConfig {
+            stall_timeout: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut interlock = Interlock::new(&mut output, config);
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(interlock.check(), Ok(Some(Trip::SamplerStalled)));
+    }
+}
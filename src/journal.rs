@@ -0,0 +1,328 @@
+//! Error journal
+//!
+//! A compact journal of significant events (sensor down and up, CRC bursts,
+//! interlock trips) for post-mortem analysis after unexpected resets, where
+//! the volatile diagnostics are gone. At most `N` entries are kept, the
+//! oldest ones being dropped first. The journal is written to a [`Store`]
+//! when it has changed:
+//!
+//! ```ignore
+//! let mut journal = Journal::new();
+//! journal.restore(&mut nvs)?;
+//! loop {
+//!     let sweep = thermometer.read_all(&addresses, PowerBudget::Unlimited)?;
+//!     for (address, result) in addresses.iter().zip(&sweep.readings) {
+//!         journal.observe(now(), *address, result);
+//!     }
+//!     journal.commit(&mut nvs)?;
+//! }
+//! ```
+//!
+//! Entry format, 14 bytes, little-endian:
+//!
+//! ```text
+//! entry = kind time address data
+//! ```
+//!
+//! - `kind`: byte, 1 sensor down, 2 sensor up, 3 CRC burst, 4 interlock trip
+//! - `time`: u32, seconds (Unix time or uptime, as supplied)
+//! - `address`: u64
+//! - `data`: byte, the fault code of a sensor down, the count of a CRC burst
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Deque, Map},
+    error::{Error, Result},
+    persistence::Store,
+    trace::fault_code,
+};
+use alloc::vec::Vec;
+
+/// Default number of kept entries.
+pub const JOURNAL: usize = 32;
+/// The number of consecutive failures after which a sensor is down.
+pub const DOWN_AFTER: u8 = 3;
+/// The number of consecutive CRC errors making a burst.
+pub const CRC_BURST: u8 = 3;
+
+const ENTRY: usize = 14;
+const KEY: &str = "journal";
+
+/// Journal event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Failed [`DOWN_AFTER`] times in a row, with the fault code of the last
+    /// failure, see [`fault_code`].
+    SensorDown {
+        address: Address,
+        code: u8,
+    },
+    /// Read again after being down.
+    SensorUp {
+        address: Address,
+    },
+    /// Recovered from at least [`CRC_BURST`] consecutive CRC errors.
+    CrcBurst {
+        address: Address,
+        count: u8,
+    },
+    InterlockTrip {
+        address: Address,
+    },
+}
+
+/// Journal entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Seconds
+    pub time: u32,
+    pub event: Event,
+}
+
+impl Entry {
+    pub fn to_bytes(&self) -> [u8; ENTRY] {
+        let (kind, address, data) = match self.event {
+            Event::SensorDown { address, code } => (1, address, code),
+            Event::SensorUp { address } => (2, address, 0),
+            Event::CrcBurst { address, count } => (3, address, count),
+            Event::InterlockTrip { address } => (4, address, 0),
+        };
+        let mut bytes = [0; ENTRY];
+        bytes[0] = kind;
+        bytes[1..5].copy_from_slice(&self.time.to_le_bytes());
+        bytes[5..13].copy_from_slice(&address.0.to_le_bytes());
+        bytes[13] = data;
+        bytes
+    }
+
+    pub fn from_bytes(bytes: [u8; ENTRY]) -> Option<Self> {
+        let time = u32::from_le_bytes(bytes[1..5].try_into().ok()?);
+        let address = Address(u64::from_le_bytes(bytes[5..13].try_into().ok()?));
+        let data = bytes[13];
+        let event = match bytes[0] {
+            1 => Event::SensorDown {
+                address,
+                code: data,
+            },
+            2 => Event::SensorUp { address },
+            3 => Event::CrcBurst {
+                address,
+                count: data,
+            },
+            4 => Event::InterlockTrip { address },
+            _ => return None,
+        };
+        Some(Self { time, event })
+    }
+}
+
+/// Failure streak of a sensor
+#[derive(Clone, Copy, Debug, Default)]
+struct Streak {
+    failures: u8,
+    crc: u8,
+    down: bool,
+}
+
+/// Error journal
+#[derive(Clone, Debug)]
+pub struct Journal<const N: usize = JOURNAL> {
+    entries: Deque<Entry, N>,
+    streaks: Map<Address, Streak, CAPACITY>,
+    dirty: bool,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const N: usize> Default for Journal<N> {
+    fn default() -> Self {
+        Self {
+            entries: Deque::new(),
+            streaks: Map::new(),
+            dirty: false,
+        }
+    }
+}
+
+impl<const N: usize> Journal<N> {
+    /// Sets the maximum number of kept entries.
+    pub fn capacity<const M: usize>(self) -> Journal<M> {
+        let mut journal = Journal::<M> {
+            streaks: self.streaks,
+            dirty: self.dirty,
+            ..Default::default()
+        };
+        for entry in self.entries.iter() {
+            journal.push(*entry);
+        }
+        journal
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub fn record(&mut self, time: u32, event: Event) {
+        self.push(Entry { time, event });
+        self.dirty = true;
+    }
+
+    /// Tracks the result of a read of the sensor, recording the sensor going
+    /// down and up and CRC bursts.
+    pub fn observe<T>(&mut self, time: u32, address: Address, result: &Result<T>) {
+        let Ok(streak) = self.streaks.get_or_insert(address, Streak::default()) else {
+            return;
+        };
+        let streak = match result {
+            Ok(_) => core::mem::take(streak),
+            Err(error) => {
+                streak.failures = streak.failures.saturating_add(1);
+                if matches!(error, Error::Crc(_) | Error::ScratchpadCrc { .. }) {
+                    streak.crc = streak.crc.saturating_add(1);
+                } else {
+                    streak.crc = 0;
+                }
+                if streak.failures < DOWN_AFTER || streak.down {
+                    return;
+                }
+                streak.down = true;
+                let code = fault_code(error);
+                self.record(time, Event::SensorDown { address, code });
+                return;
+            }
+        };
+        if streak.crc >= CRC_BURST {
+            let count = streak.crc;
+            self.record(time, Event::CrcBurst { address, count });
+        }
+        if streak.down {
+            self.record(time, Event::SensorUp { address });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.dirty = true;
+    }
+
+    /// Writes the journal to the store if it has changed. Returns whether it
+    /// was written.
+    pub fn commit(&mut self, store: &mut dyn Store) -> Result<bool> {
+        if !self.dirty {
+            return Ok(false);
+        }
+        store.save(KEY, &self.to_bytes())?;
+        self.dirty = false;
+        Ok(true)
+    }
+
+    /// Restores the entries written to the store, keeping the newest ones.
+    pub fn restore(&mut self, store: &mut dyn Store) -> Result<()> {
+        self.entries.clear();
+        if let Some(bytes) = store.load(KEY)? {
+            for (index, chunk) in bytes.chunks(ENTRY).enumerate() {
+                let entry =
+                    chunk
+                        .try_into()
+                        .ok()
+                        .and_then(Entry::from_bytes)
+                        .ok_or(Error::Decode {
+                            offset: index * ENTRY,
+                        })?;
+                self.push(entry);
+            }
+        }
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flat_map(Entry::to_bytes).collect()
+    }
+
+    fn push(&mut self, entry: Entry) {
+        if self.entries.is_full() {
+            self.entries.pop_front();
+        }
+        let _ = self.entries.push_back(entry);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::CrcError;
+    use alloc::{collections::BTreeMap, string::String};
+
+    #[test]
+    fn observe() {
+        let address = Address(1);
+        let crc = Err::<(), _>(Error::Crc(CrcError { crc: 1 }));
+        let mut journal = Journal::new();
+        for time in 0..4 {
+            journal.observe(time, address, &crc);
+        }
+        journal.observe(4, address, &Ok(()));
+        journal.observe(5, address, &Err::<(), _>(Error::DeviceNotFound));
+        assert_eq!(
+            journal.entries().copied().collect::<Vec<_>>(),
+            [
+                Entry {
+                    time: 2,
+                    event: Event::SensorDown { address, code: 2 },
+                },
+                Entry {
+                    time: 4,
+                    event: Event::CrcBurst { address, count: 4 },
+                },
+                Entry {
+                    time: 4,
+                    event: Event::SensorUp { address },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn persist() {
+        let mut store = BTreeMap::<String, Vec<u8>>::new();
+        let mut journal = Journal::new().capacity::<2>();
+        for time in 0..3 {
+            journal.record(
+                time,
+                Event::InterlockTrip {
+                    address: Address(1),
+                },
+            );
+        }
+        assert_eq!(journal.commit(&mut store), Ok(true));
+        assert_eq!(journal.commit(&mut store), Ok(false));
+        let mut restored = Journal::new();
+        restored.restore(&mut store).unwrap();
+        assert_eq!(
+            restored
+                .entries()
+                .map(|entry| entry.time)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+        store.insert(KEY.into(), vec![5; ENTRY]);
+        assert_eq!(
+            restored.restore(&mut store),
+            Err(Error::Decode { offset: 0 })
+        );
+    }
+}
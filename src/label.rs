@@ -0,0 +1,192 @@
+//! Sensor labels
+//!
+//! A label carries the display preferences of a sensor, so sinks can format
+//! readings per sensor, e.g. "Boiler 72.4 °F" and "Ambient 21.44 °C".
+//!
+//! An alias names one sensor only, like the labels of the
+//! [`Registry`](crate::registry::Registry), so a lookup by alias is never
+//! ambiguous.
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::{Error, Result},
+    format::{Fixed, RoundingMode},
+    pipeline::Reading,
+    unit::Unit,
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+
+/// Default number of decimal places.
+pub const PRECISION: usize = 2;
+
+/// Label
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    /// Display name
+    pub alias: String,
+    /// Display unit
+    pub unit: Unit,
+    /// Number of decimal places
+    pub precision: usize,
+    pub rounding: RoundingMode,
+}
+
+impl Label {
+    pub fn new(alias: impl Into<String>) -> Self {
+        Self {
+            alias: alias.into(),
+            unit: Unit::default(),
+            precision: PRECISION,
+            rounding: RoundingMode::Nearest,
+        }
+    }
+
+    pub fn unit(self, unit: Unit) -> Self {
+        Self { unit, ..self }
+    }
+
+    pub fn precision(self, precision: usize) -> Self {
+        Self { precision, ..self }
+    }
+
+    pub fn rounding(self, rounding: RoundingMode) -> Self {
+        Self { rounding, ..self }
+    }
+
+    /// Formats the temperature (°C) as `"{alias} {value} {unit}"`.
+    pub fn format(&self, celsius: f32) -> String {
+        format!(
+            "{} {} {}",
+            self.alias,
+            Fixed::new(self.unit.from_celsius(celsius), self.precision).rounding(self.rounding),
+            self.unit,
+        )
+    }
+}
+
+/// Labels
+#[derive(Clone, Debug, Default)]
+pub struct Labels<const N: usize = CAPACITY> {
+    labels: Map<Address, Label, N>,
+}
+
+impl Labels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const N: usize> Labels<N> {
+    /// Sets the label of the sensor, returning the previous one. Fails with
+    /// [`Error::DuplicateLabel`] if another sensor holds the alias.
+    pub fn insert(&mut self, address: Address, label: Label) -> Result<Option<Label>> {
+        match self.find(&label.alias) {
+            Some(holder) if *holder != address => Err(Error::DuplicateLabel { address: *holder }),
+            _ => self.labels.insert(address, label),
+        }
+    }
+
+    /// The sensor with the alias.
+    pub fn find(&self, alias: &str) -> Option<&Address> {
+        self.labels
+            .iter()
+            .find(|(_, label)| label.alias == alias)
+            .map(|(address, _)| address)
+    }
+
+    pub fn remove(&mut self, address: &Address) -> Option<Label> {
+        self.labels.remove(address)
+    }
+
+    pub fn get(&self, address: &Address) -> Option<&Label> {
+        self.labels.get(address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, &Label)> {
+        self.labels.iter()
+    }
+
+    /// Formats the reading with the display preferences of its sensor.
+    /// Unlabeled sensors are shown by address in degrees Celsius.
+    pub fn format_reading(&self, reading: &Reading) -> String {
+        match self.get(&reading.address) {
+            Some(label) => label.format(reading.temperature),
+            None => Label::new(reading.address.to_string()).format(reading.temperature),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn reading(address: u64, temperature: f32) -> Reading {
+        Reading::new(Address(address), temperature)
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(Label::new("Ambient").format(21.4375), "Ambient 21.44 °C");
+        assert_eq!(
+            Label::new("Boiler")
+                .unit(Unit::Fahrenheit)
+                .precision(1)
+                .format(22.4375),
+            "Boiler 72.4 °F",
+        );
+        assert_eq!(
+            Label::new("Tank")
+                .unit(Unit::Kelvin)
+                .precision(0)
+                .format(0.0),
+            "Tank 273 K",
+        );
+        assert_eq!(
+            Label::new("Freezer")
+                .precision(1)
+                .rounding(RoundingMode::Ceil)
+                .format(-18.0625),
+            "Freezer -18.0 °C",
+        );
+    }
+
+    #[test]
+    fn format_reading() {
+        let mut labels = Labels::new();
+        labels
+            .insert(reading(0x28, 0.0).address, Label::new("Ambient"))
+            .unwrap();
+        assert_eq!(
+            labels.format_reading(&reading(0x28, 21.4375)),
+            "Ambient 21.44 °C",
+        );
+        assert_eq!(
+            labels.format_reading(&reading(0x1e00000000000028, 21.4375)),
+            "1e00000000000028 21.44 °C",
+        );
+    }
+
+    #[test]
+    fn duplicate() {
+        let mut labels = Labels::new();
+        labels.insert(Address(1), Label::new("Boiler")).unwrap();
+        assert_eq!(
+            labels.insert(Address(2), Label::new("Boiler")),
+            Err(Error::DuplicateLabel {
+                address: Address(1)
+            })
+        );
+        assert_eq!(labels.get(&Address(2)), None);
+        // Relabeling the holder
+        let boiler = Label::new("Boiler").unit(Unit::Fahrenheit);
+        assert_eq!(
+            labels.insert(Address(1), boiler),
+            Ok(Some(Label::new("Boiler")))
+        );
+        assert_eq!(labels.find("Boiler"), Some(&Address(1)));
+    }
+}
@@ -0,0 +1,97 @@
+//! Logging
+//!
+//! Every subsystem logs to its own target (`thermometer::bus`,
+//! `thermometer::sampler`, ...) and has its own runtime verbosity, checked
+//! before the record reaches the logger:
+//!
+//! ```ignore
+//! logging::set_level(Subsystem::Bus, LevelFilter::Off);
+//! logging::set_level(Subsystem::Sampler, LevelFilter::Debug);
+//! ```
+//!
+//! The global `log` level and the logger's own filters still apply.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+use log::{Level, LevelFilter};
+
+/// Subsystem
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    /// Bus transactions, search and retries
+    Bus,
+    /// Sweeps
+    Sampler,
+    /// Reading pipeline
+    Pipeline,
+}
+
+impl Subsystem {
+    /// The log target
+    pub const fn target(&self) -> &'static str {
+        match self {
+            Subsystem::Bus => "thermometer::bus",
+            Subsystem::Sampler => "thermometer::sampler",
+            Subsystem::Pipeline => "thermometer::pipeline",
+        }
+    }
+}
+
+static LEVELS: [AtomicU8; 3] = [
+    AtomicU8::new(LevelFilter::Trace as _),
+    AtomicU8::new(LevelFilter::Trace as _),
+    AtomicU8::new(LevelFilter::Trace as _),
+];
+
+/// Sets the maximum verbosity of the subsystem.
+pub fn set_level(subsystem: Subsystem, level: LevelFilter) {
+    LEVELS[subsystem as usize].store(level as _, Ordering::Relaxed);
+}
+
+/// The maximum verbosity of the subsystem.
+pub fn level(subsystem: Subsystem) -> LevelFilter {
+    match LEVELS[subsystem as usize].load(Ordering::Relaxed) {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Returns `true` if the subsystem logs at the level.
+pub fn enabled(subsystem: Subsystem, level: Level) -> bool {
+    level <= self::level(subsystem)
+}
+
+/// Logs to the target of the subsystem if it is enabled at the level.
+macro_rules! log {
+    ($subsystem:expr, $level:expr, $($arg:tt)+) => {{
+        let subsystem: $crate::logging::Subsystem = $subsystem;
+        let level: ::log::Level = $level;
+        if $crate::logging::enabled(subsystem, level) {
+            ::log::log!(target: subsystem.target(), level, $($arg)+);
+        }
+    }};
+}
+
+pub(crate) use log;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn level() {
+        assert_eq!(super::level(Subsystem::Pipeline), LevelFilter::Trace);
+        set_level(Subsystem::Pipeline, LevelFilter::Warn);
+        assert_eq!(super::level(Subsystem::Pipeline), LevelFilter::Warn);
+        assert!(enabled(Subsystem::Pipeline, Level::Error));
+        assert!(enabled(Subsystem::Pipeline, Level::Warn));
+        assert!(!enabled(Subsystem::Pipeline, Level::Info));
+        assert!(enabled(Subsystem::Bus, Level::Trace));
+        set_level(Subsystem::Pipeline, LevelFilter::Off);
+        assert!(!enabled(Subsystem::Pipeline, Level::Error));
+        set_level(Subsystem::Pipeline, LevelFilter::Trace);
+    }
+}
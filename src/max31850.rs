@@ -0,0 +1,294 @@
+//! MAX31850 thermocouple interface
+//!
+//! The MAX31850 shares the bus protocol of the DS18B20 (Convert T, Read
+//! Scratchpad) but reports a thermocouple temperature, its own cold-junction
+//! temperature and the thermocouple faults. A DS18B20 mounted next to the
+//! terminals cross-checks the cold junction, which a thermocouple reading
+//! silently depends on:
+//!
+//! ```ignore
+//! let thermocouple = Thermocouple::from_bytes(buffer)?;
+//! if let Some(deviation) = thermocouple.cross_check(&terminals, COLD_JUNCTION) {
+//!     warn!("Cold junction off by {deviation} °C");
+//! }
+//! let reading = thermocouple.reading(address);
+//! if let Some(status) = reading.thermocouple && !status.faults.is_empty() {
+//!     warn!("Thermocouple {address}: {}", status.faults);
+//! }
+//! ```
+//!
+//! A faulty thermocouple still yields a reading, with a NaN temperature and
+//! the faults attached; [`Thermocouple::check`] fails on them instead.
+//!
+//! Scratchpad layout, CRC byte last:
+//!
+//! | byte | content |
+//! |------|---------|
+//! | 0-1  | thermocouple temperature, 0.25 °C, fault bit 0 |
+//! | 2-3  | cold-junction temperature, 0.0625 °C, fault flags bits 0-2 |
+//! | 4    | configuration, the address pins AD0-AD3 |
+
+use crate::{
+    address::Address,
+    crc8,
+    error::{CrcError, Error, Result},
+    pipeline::Reading,
+};
+use core::fmt::{self, Display, Formatter};
+
+/// The MAX31850 family code
+pub const FAMILY_CODE: u8 = 0x3B;
+/// Default cold-junction cross-check tolerance (°C)
+pub const COLD_JUNCTION: f32 = 2.0;
+
+/// Thermocouple fault
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Fault {
+    /// The thermocouple is open (OC).
+    Open,
+    /// The thermocouple is shorted to GND (SCG).
+    ShortToGround,
+    /// The thermocouple is shorted to VDD (SCV).
+    ShortToVdd,
+}
+
+impl Fault {
+    /// The flag in the scratchpad byte 2.
+    pub const fn bit(&self) -> u8 {
+        match self {
+            Self::Open => 0b001,
+            Self::ShortToGround => 0b010,
+            Self::ShortToVdd => 0b100,
+        }
+    }
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Open => "open",
+            Self::ShortToGround => "short to GND",
+            Self::ShortToVdd => "short to VDD",
+        })
+    }
+}
+
+/// Thermocouple faults
+///
+/// The fault flags, any number of them can be set at once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Faults(u8);
+
+impl Faults {
+    const ALL: [Fault; 3] = [Fault::Open, Fault::ShortToGround, Fault::ShortToVdd];
+
+    /// The flags of the scratchpad byte 2, other bits are ignored.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits & 0b111)
+    }
+
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(&self, fault: Fault) -> bool {
+        self.0 & fault.bit() != 0
+    }
+
+    pub fn insert(&mut self, fault: Fault) {
+        self.0 |= fault.bit();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Fault> {
+        let faults = *self;
+        Self::ALL
+            .into_iter()
+            .filter(move |fault| faults.contains(*fault))
+    }
+}
+
+impl FromIterator<Fault> for Faults {
+    fn from_iter<T: IntoIterator<Item = Fault>>(iter: T) -> Self {
+        let mut faults = Self::default();
+        for fault in iter {
+            faults.insert(fault);
+        }
+        faults
+    }
+}
+
+impl Display for Faults {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("none");
+        }
+        for (index, fault) in self.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            fault.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Thermocouple status of a reading
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Status {
+    /// Cold-junction temperature (°C)
+    pub cold_junction: f32,
+    pub faults: Faults,
+}
+
+/// Thermocouple reading
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Thermocouple {
+    /// Hot junction temperature (°C), cold-junction compensated. Invalid
+    /// while any fault is set.
+    pub temperature: f32,
+    /// Cold-junction temperature (°C)
+    pub cold_junction: f32,
+    pub faults: Faults,
+    /// The state of the address pins AD0-AD3.
+    pub pins: u8,
+}
+
+impl Thermocouple {
+    /// Decodes the scratchpad, CRC byte included.
+    ///
+    /// Fails with [`Error::ScratchpadCrc`] without an address.
+    pub fn from_bytes(buffer: [u8; 9]) -> Result<Self> {
+        crc8::check(&buffer).map_err(|CrcError { crc }| Error::ScratchpadCrc {
+            address: None,
+            buffer,
+            crc,
+        })?;
+        Ok(Self {
+            temperature: (i16::from_le_bytes([buffer[0], buffer[1]]) >> 2) as f32 / 4.0,
+            cold_junction: (i16::from_le_bytes([buffer[2], buffer[3]]) >> 4) as f32 / 16.0,
+            faults: Faults::from_bits(buffer[2]),
+            pins: buffer[4] & 0x0F,
+        })
+    }
+
+    /// The difference of the cold junction from a reference sensor, if it is
+    /// beyond the tolerance (°C). Stale references aren't checked.
+    pub fn cross_check(&self, reference: &Reading, tolerance: f32) -> Option<f32> {
+        let deviation = self.cold_junction - reference.temperature;
+        // NaN is never within the tolerance.
+        if reference.is_stale() || deviation.abs() <= tolerance {
+            return None;
+        }
+        Some(deviation)
+    }
+
+    /// The thermocouple temperature as a reading, with the cold junction and
+    /// the faults attached. The temperature is NaN on a fault.
+    pub fn reading(&self, address: Address) -> Reading {
+        let temperature = match self.faults.is_empty() {
+            true => self.temperature,
+            false => f32::NAN,
+        };
+        Reading {
+            thermocouple: Some(Status {
+                cold_junction: self.cold_junction,
+                faults: self.faults,
+            }),
+            ..Reading::new(address, temperature)
+        }
+    }
+
+    /// Fails with [`Error::Thermocouple`] on a fault.
+    pub fn check(&self) -> Result<()> {
+        match self.faults.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Thermocouple(self.faults)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scratchpad(bytes: [u8; 8]) -> [u8; 9] {
+        let mut buffer = [0; 9];
+        buffer[..8].copy_from_slice(&bytes);
+        buffer[8] = crc8::calculate(&bytes);
+        buffer
+    }
+
+    #[test]
+    fn decode() {
+        // 100.25 °C, cold junction 21.5625 °C, pins AD0 and AD2 high.
+        let thermocouple =
+            Thermocouple::from_bytes(scratchpad([0x44, 0x06, 0x90, 0x15, 0xF5, 0xFF, 0xFF, 0xFF]))
+                .unwrap();
+        assert_eq!(
+            thermocouple,
+            Thermocouple {
+                temperature: 100.25,
+                cold_junction: 21.5625,
+                faults: Faults::default(),
+                pins: 0b0101,
+            }
+        );
+        let address = Address(0x1E00_0000_0000_0028);
+        let reading = thermocouple.reading(address);
+        assert_eq!(reading.temperature, 100.25);
+        assert_eq!(
+            reading.thermocouple,
+            Some(Status {
+                cold_junction: 21.5625,
+                faults: Faults::default(),
+            })
+        );
+        assert_eq!(thermocouple.check(), Ok(()));
+        // -0.25 °C
+        let thermocouple =
+            Thermocouple::from_bytes(scratchpad([0xFC, 0xFF, 0x00, 0x00, 0xF0, 0xFF, 0xFF, 0xFF]))
+                .unwrap();
+        assert_eq!(thermocouple.temperature, -0.25);
+        // Open and shorted to VDD
+        let thermocouple =
+            Thermocouple::from_bytes(scratchpad([0x01, 0x00, 0x95, 0x15, 0xF0, 0xFF, 0xFF, 0xFF]))
+                .unwrap();
+        let faults = [Fault::Open, Fault::ShortToVdd].into_iter().collect();
+        assert_eq!(thermocouple.faults, faults);
+        assert_eq!(faults.to_string(), "open, short to VDD");
+        assert_eq!(thermocouple.cold_junction, 21.5625);
+        let reading = thermocouple.reading(address);
+        assert!(reading.temperature.is_nan());
+        assert_eq!(
+            reading.thermocouple.map(|status| status.faults),
+            Some(faults)
+        );
+        assert_eq!(thermocouple.check(), Err(Error::Thermocouple(faults)));
+        let mut buffer = scratchpad([0; 8]);
+        buffer[8] ^= 1;
+        assert!(matches!(
+            Thermocouple::from_bytes(buffer),
+            Err(Error::ScratchpadCrc { .. })
+        ));
+    }
+
+    #[test]
+    fn cross_check() {
+        let thermocouple = Thermocouple {
+            temperature: 100.0,
+            cold_junction: 24.0,
+            faults: Faults::default(),
+            pins: 0,
+        };
+        let address = Address(0x1E00_0000_0000_0028);
+        let reference = Reading::new(address, 23.0);
+        assert_eq!(thermocouple.cross_check(&reference, COLD_JUNCTION), None);
+        assert_eq!(thermocouple.cross_check(&reference, 0.5), Some(1.0));
+        let nan = Reading::new(address, f32::NAN);
+        assert!(thermocouple.cross_check(&nan, COLD_JUNCTION).is_some());
+    }
+}
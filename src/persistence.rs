@@ -0,0 +1,50 @@
+//! Persistence
+//!
+//! State that has to survive a reboot (calibration, accumulated totals, the
+//! registry) is saved to a [`Store`] as one blob per key, e.g. an NVS
+//! namespace on the ESP32:
+//!
+//! ```ignore
+//! impl Store for EspNvs<NvsDefault> {
+//!     fn save(&mut self, key: &str, value: &[u8]) -> Result<()> {
+//!         self.set_blob(key, value)?;
+//!         Ok(())
+//!     }
+//!
+//!     fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+//!         let mut buffer = vec![0; self.blob_len(key)?.unwrap_or_default()];
+//!         Ok(self.get_blob(key, &mut buffer)?.map(<[u8]>::to_vec))
+//!     }
+//! }
+//! sampler.shutdown(&mut nvs)?;
+//! registry.shutdown(&mut nvs)?;
+//! // After the reboot
+//! sampler.restore(&mut nvs)?;
+//! registry.restore(&mut nvs)?;
+//! ```
+//!
+//! Keys are at most 15 characters, the NVS limit.
+
+use crate::error::Result;
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+/// Key-value store
+pub trait Store {
+    /// Saves the value, replacing the previous one.
+    fn save(&mut self, key: &str, value: &[u8]) -> Result<()>;
+
+    /// The saved value, `None` if there is none.
+    fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>>;
+}
+
+/// In-memory store, e.g. for tests and host tools.
+impl Store for BTreeMap<String, Vec<u8>> {
+    fn save(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        self.insert(key.into(), value.into());
+        Ok(())
+    }
+
+    fn load(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.get(key).cloned())
+    }
+}
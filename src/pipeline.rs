@@ -0,0 +1,482 @@
+//! Reading pipeline
+//!
+//! A pipeline is an ordered list of [`Stage`]s. Each stage receives the
+//! reading produced by the previous one and may transform it or drop it by
+//! returning `None`, so calibration, filtering, deadband and sinks can be
+//! composed in whatever order the application needs:
+//!
+//! ```ignore
+//! let mut pipeline = Pipeline::new()
+//!     .stage(Calibration::new().offset(address, -0.25))
+//!     .stage(Smoothing::new(0.5))
+//!     .stage(Deadband::new(0.1))
+//!     .stage(|reading: Reading| {
+//!         info!("{reading:?}");
+//!         Some(reading)
+//!     });
+//! pipeline.process(thermometer.read(&address)?);
+//! ```
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::{Error, Result},
+    logging::{Subsystem, log},
+    persistence::Store,
+};
+use alloc::{boxed::Box, vec::Vec};
+use log::Level;
+
+pub use crate::types::Reading;
+
+/// Pipeline stage
+pub trait Stage {
+    /// Processes the reading. Returns `None` to drop it.
+    fn process(&mut self, reading: Reading) -> Option<Reading>;
+
+    /// Saves the state that has to survive a reboot.
+    fn persist(&mut self, _store: &mut dyn Store) -> Result<()> {
+        Ok(())
+    }
+
+    /// Restores the state saved by [`persist`](Self::persist).
+    fn restore(&mut self, _store: &mut dyn Store) -> Result<()> {
+        Ok(())
+    }
+
+    /// Saves the filter state carried across a deep sleep, e.g. to an
+    /// [`RtcStore`](crate::checkpoint::RtcStore).
+    fn checkpoint(&mut self, _store: &mut dyn Store) -> Result<()> {
+        Ok(())
+    }
+
+    /// Restores the filter state saved by [`checkpoint`](Self::checkpoint).
+    fn resume(&mut self, _store: &mut dyn Store) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<F: FnMut(Reading) -> Option<Reading>> Stage for F {
+    fn process(&mut self, reading: Reading) -> Option<Reading> {
+        self(reading)
+    }
+}
+
+/// Pipeline
+///
+/// Stages are applied in the order they were added. The pipeline is a stage
+/// itself, so pipelines can be nested.
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Stage>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the stage to the end of the pipeline.
+    pub fn stage(mut self, stage: impl Stage + 'static) -> Self {
+        self.push(stage);
+        self
+    }
+
+    /// Appends the stage to the end of the pipeline.
+    pub fn push(&mut self, stage: impl Stage + 'static) {
+        self.stages.push(Box::new(stage));
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl Stage for Pipeline {
+    fn process(&mut self, reading: Reading) -> Option<Reading> {
+        self.stages
+            .iter_mut()
+            .try_fold(reading, |reading, stage| stage.process(reading))
+    }
+
+    /// Persists all stages, even if one of them fails, and returns the first
+    /// failure.
+    fn persist(&mut self, store: &mut dyn Store) -> Result<()> {
+        self.stages
+            .iter_mut()
+            .map(|stage| stage.persist(store))
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Restores all stages, even if one of them fails, and returns the first
+    /// failure.
+    fn restore(&mut self, store: &mut dyn Store) -> Result<()> {
+        self.stages
+            .iter_mut()
+            .map(|stage| stage.restore(store))
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Checkpoints all stages, even if one of them fails, and returns the
+    /// first failure.
+    fn checkpoint(&mut self, store: &mut dyn Store) -> Result<()> {
+        self.stages
+            .iter_mut()
+            .map(|stage| stage.checkpoint(store))
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Resumes all stages, even if one of them fails, and returns the first
+    /// failure.
+    fn resume(&mut self, store: &mut dyn Store) -> Result<()> {
+        self.stages
+            .iter_mut()
+            .map(|stage| stage.resume(store))
+            .fold(Ok(()), Result::and)
+    }
+}
+
+/// Encodes the values as little-endian address and value pairs.
+fn encode<const N: usize>(values: &Map<Address, f32, N>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(values.len() * 12);
+    for (address, value) in values.iter() {
+        bytes.extend_from_slice(&address.0.to_le_bytes());
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+}
+
+/// Decodes the address and value pairs of [`encode`].
+fn decode<const N: usize>(value: &[u8]) -> Result<Map<Address, f32, N>> {
+    let (chunks, remainder) = value.as_chunks::<12>();
+    if !remainder.is_empty() {
+        return Err(Error::Decode {
+            offset: value.len() - remainder.len(),
+        });
+    }
+    let mut values = Map::new();
+    for chunk in chunks {
+        let (address, value) = chunk.split_at(8);
+        values.insert(
+            Address(u64::from_le_bytes(address.try_into().unwrap())),
+            f32::from_le_bytes(value.try_into().unwrap()),
+        )?;
+    }
+    Ok(values)
+}
+
+/// Calibration stage
+///
+/// Adds a per-sensor offset to the temperature. Readings of sensors without
+/// an offset pass unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct Calibration<const N: usize = CAPACITY> {
+    offsets: Map<Address, f32, N>,
+}
+
+impl Calibration {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const N: usize> Calibration<N> {
+    /// Sets the maximum number of calibrated sensors.
+    pub fn capacity<const M: usize>(self) -> Calibration<M> {
+        Calibration {
+            offsets: self.offsets.into_capacity(),
+        }
+    }
+
+    /// Sets the offset (°C) of the sensor.
+    pub fn offset(mut self, address: Address, offset: f32) -> Self {
+        if let Err(error) = self.offsets.insert(address, offset) {
+            log!(
+                Subsystem::Pipeline,
+                Level::Warn,
+                "Calibration offset of {address:x?} ignored: {error}"
+            );
+        }
+        self
+    }
+
+    /// Sets the offset (°C) of the sensor, returning the previous one.
+    pub fn set_offset(&mut self, address: Address, offset: f32) -> Result<Option<f32>> {
+        self.offsets.insert(address, offset)
+    }
+
+    /// Removes the offset of the sensor, returning it.
+    pub fn remove_offset(&mut self, address: &Address) -> Option<f32> {
+        self.offsets.remove(address)
+    }
+
+    /// The offset (°C) of the sensor.
+    pub fn get(&self, address: &Address) -> Option<f32> {
+        self.offsets.get(address).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, &f32)> {
+        self.offsets.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.offsets.clear();
+    }
+}
+
+impl<const N: usize> Stage for Calibration<N> {
+    fn process(&mut self, mut reading: Reading) -> Option<Reading> {
+        if let Some(offset) = self.offsets.get(&reading.address) {
+            reading.temperature += offset;
+        }
+        Some(reading)
+    }
+
+    /// Saves the offsets under `calibration` as little-endian address and
+    /// offset pairs.
+    fn persist(&mut self, store: &mut dyn Store) -> Result<()> {
+        store.save("calibration", &encode(&self.offsets))
+    }
+
+    /// Replaces the offsets with the saved ones, if any.
+    fn restore(&mut self, store: &mut dyn Store) -> Result<()> {
+        if let Some(value) = store.load("calibration")? {
+            self.offsets = decode(&value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Exponential smoothing stage
+///
+/// `smoothed = alpha * temperature + (1 - alpha) * smoothed`, tracked per
+/// sensor. An `alpha` of `1.0` disables smoothing. Readings of sensors beyond
+/// the capacity pass unchanged.
+#[derive(Clone, Debug)]
+pub struct Smoothing<const N: usize = CAPACITY> {
+    alpha: f32,
+    key: &'static str,
+    values: Map<Address, f32, N>,
+}
+
+impl Smoothing {
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            key: "smoothing",
+            values: Map::new(),
+        }
+    }
+}
+
+impl<const N: usize> Smoothing<N> {
+    /// Sets the maximum number of tracked sensors.
+    pub fn capacity<const M: usize>(self) -> Smoothing<M> {
+        Smoothing {
+            alpha: self.alpha,
+            key: self.key,
+            values: self.values.into_capacity(),
+        }
+    }
+
+    /// Sets the checkpoint key, `smoothing` by default. Stages sharing a
+    /// store need distinct keys.
+    pub fn key(mut self, key: &'static str) -> Self {
+        self.key = key;
+        self
+    }
+}
+
+impl<const N: usize> Stage for Smoothing<N> {
+    fn process(&mut self, mut reading: Reading) -> Option<Reading> {
+        if let Ok(value) = self
+            .values
+            .get_or_insert(reading.address, reading.temperature)
+        {
+            *value += self.alpha * (reading.temperature - *value);
+            reading.temperature = *value;
+        }
+        Some(reading)
+    }
+
+    /// Saves the smoothed values under the [key](Self::key) like the
+    /// calibration.
+    fn checkpoint(&mut self, store: &mut dyn Store) -> Result<()> {
+        store.save(self.key, &encode(&self.values))
+    }
+
+    fn resume(&mut self, store: &mut dyn Store) -> Result<()> {
+        if let Some(value) = store.load(self.key)? {
+            self.values = decode(&value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Deadband stage
+///
+/// Drops readings that differ from the last passed reading of the same sensor
+/// by less than the threshold (°C). Readings of sensors beyond the capacity
+/// pass unchanged.
+#[derive(Clone, Debug)]
+pub struct Deadband<const N: usize = CAPACITY> {
+    threshold: f32,
+    key: &'static str,
+    values: Map<Address, f32, N>,
+}
+
+impl Deadband {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            key: "deadband",
+            values: Map::new(),
+        }
+    }
+}
+
+impl<const N: usize> Deadband<N> {
+    /// Sets the maximum number of tracked sensors.
+    pub fn capacity<const M: usize>(self) -> Deadband<M> {
+        Deadband {
+            threshold: self.threshold,
+            key: self.key,
+            values: self.values.into_capacity(),
+        }
+    }
+
+    /// Sets the checkpoint key, `deadband` by default. Stages sharing a
+    /// store need distinct keys.
+    pub fn key(mut self, key: &'static str) -> Self {
+        self.key = key;
+        self
+    }
+}
+
+impl<const N: usize> Stage for Deadband<N> {
+    fn process(&mut self, reading: Reading) -> Option<Reading> {
+        match self.values.get_mut(&reading.address) {
+            Some(value) if (reading.temperature - *value).abs() < self.threshold => None,
+            Some(value) => {
+                *value = reading.temperature;
+                Some(reading)
+            }
+            None => {
+                let _ = self.values.insert(reading.address, reading.temperature);
+                Some(reading)
+            }
+        }
+    }
+
+    /// Saves the last passed values under the [key](Self::key) like the
+    /// calibration.
+    fn checkpoint(&mut self, store: &mut dyn Store) -> Result<()> {
+        store.save(self.key, &encode(&self.values))
+    }
+
+    fn resume(&mut self, store: &mut dyn Store) -> Result<()> {
+        if let Some(value) = store.load(self.key)? {
+            self.values = decode(&value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn reading(address: u64, temperature: f32) -> Reading {
+        Reading::new(Address(address), temperature)
+    }
+
+    #[test]
+    fn pipeline() {
+        let mut pipeline = Pipeline::new()
+            .stage(|mut reading: Reading| {
+                reading.temperature *= 2.0;
+                Some(reading)
+            })
+            .stage(|mut reading: Reading| {
+                reading.temperature += 1.0;
+                Some(reading)
+            });
+        assert_eq!(pipeline.len(), 2);
+        assert_eq!(pipeline.process(reading(1, 10.0)), Some(reading(1, 21.0)));
+
+        let mut pipeline = Pipeline::new()
+            .stage(|_| None)
+            .stage(|_: Reading| -> Option<Reading> { unreachable!() });
+        assert_eq!(pipeline.process(reading(1, 10.0)), None);
+
+        assert_eq!(
+            Pipeline::new().process(reading(1, 10.0)),
+            Some(reading(1, 10.0))
+        );
+    }
+
+    #[test]
+    fn persist() {
+        let mut store = alloc::collections::BTreeMap::new();
+        let mut pipeline = Pipeline::new()
+            .stage(Calibration::new().offset(Address(1), -0.5))
+            .stage(Smoothing::new(0.5));
+        pipeline.persist(&mut store).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(
+            store["calibration"],
+            [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xBF]
+        );
+        let mut restored = Pipeline::new().stage(Calibration::new().offset(Address(2), 1.0));
+        restored.restore(&mut store).unwrap();
+        assert_eq!(restored.process(reading(1, 10.0)), Some(reading(1, 9.5)));
+        assert_eq!(restored.process(reading(2, 10.0)), Some(reading(2, 10.0)));
+    }
+
+    #[test]
+    fn calibration() {
+        let mut calibration = Calibration::new()
+            .offset(reading(1, 0.0).address, -0.5)
+            .offset(reading(1, 0.0).address, 0.25);
+        assert_eq!(
+            calibration.process(reading(1, 10.0)),
+            Some(reading(1, 10.25))
+        );
+        assert_eq!(
+            calibration.process(reading(2, 10.0)),
+            Some(reading(2, 10.0))
+        );
+    }
+
+    #[test]
+    fn smoothing() {
+        let mut smoothing = Smoothing::new(0.5);
+        assert_eq!(smoothing.process(reading(1, 10.0)), Some(reading(1, 10.0)));
+        assert_eq!(smoothing.process(reading(1, 20.0)), Some(reading(1, 15.0)));
+        assert_eq!(smoothing.process(reading(2, 0.0)), Some(reading(2, 0.0)));
+        assert_eq!(smoothing.process(reading(1, 15.0)), Some(reading(1, 15.0)));
+    }
+
+    #[test]
+    fn capacity() {
+        let mut smoothing = Smoothing::new(0.5).capacity::<1>();
+        assert_eq!(smoothing.process(reading(1, 10.0)), Some(reading(1, 10.0)));
+        assert_eq!(smoothing.process(reading(2, 20.0)), Some(reading(2, 20.0)));
+        assert_eq!(smoothing.process(reading(2, 10.0)), Some(reading(2, 10.0)));
+        assert_eq!(smoothing.process(reading(1, 20.0)), Some(reading(1, 15.0)));
+    }
+
+    #[test]
+    fn deadband() {
+        let mut deadband = Deadband::new(0.5);
+        assert_eq!(deadband.process(reading(1, 10.0)), Some(reading(1, 10.0)));
+        assert_eq!(deadband.process(reading(1, 10.25)), None);
+        assert_eq!(deadband.process(reading(2, 10.25)), Some(reading(2, 10.25)));
+        assert_eq!(deadband.process(reading(1, 10.5)), Some(reading(1, 10.5)));
+        assert_eq!(deadband.process(reading(1, 10.75)), None);
+    }
+}
@@ -0,0 +1,212 @@
+//! Bus power gating
+//!
+//! A GPIO switching the supply rail of the sensors, e.g. through a MOSFET.
+//! Power-cycling recovers clones that wedge the bus, and battery nodes can
+//! depower the sensors between infrequent samples:
+//!
+//! ```ignore
+//! let power = BusPower::new(pins.gpio5.downgrade_output(), Level::High)?;
+//! thermometer.set_power(power);
+//! thermometer.power_down()?;
+//! // The next transaction powers the rail up and waits for the sensors.
+//! let temperature = thermometer.temperature(&address)?;
+//! ```
+//!
+//! A powered-up sensor reloads its scratchpad from EEPROM, settings only
+//! written to the scratchpad are lost.
+//!
+//! Parasite-powered sensors draw the current of a conversion or an EEPROM
+//! write from the data line, more than the pull-up resistor supplies. A GPIO
+//! switching a strong pull-up (e.g. a P-MOSFET from DQ to VDD) holds the line
+//! high meanwhile:
+//!
+//! ```ignore
+//! let pull_up = StrongPullUp::new(pins.gpio6.downgrade_output(), Level::Low)?;
+//! thermometer.set_strong_pull_up(pull_up);
+//! ```
+//!
+//! Without it, conversions on a bus with parasite-powered sensors fail with
+//! [`Error::ParasitePower`] instead of reading back the power-on 85 °C.
+
+use crate::{
+    Ds18b20Driver, Error, Result,
+    cancellation::Cancellation,
+    instrument,
+    logging::{Subsystem, log},
+};
+use esp_idf_svc::hal::{
+    gpio::{AnyOutputPin, Level, Output, PinDriver},
+    peripheral::Peripheral,
+};
+use log::Level as LogLevel;
+use std::time::{Duration, Instant};
+
+pub use crate::types::PowerMode;
+
+/// Default time for the sensors to start up after power-up.
+pub const WARM_UP: Duration = Duration::from_millis(10);
+/// Default time for the rail to discharge during a power cycle.
+pub const DISCHARGE: Duration = Duration::from_millis(100);
+
+/// Power switch of the sensor rail
+pub struct BusPower<'d> {
+    pin: PinDriver<'d, AnyOutputPin, Output>,
+    /// The level that powers the rail.
+    on: Level,
+    /// The wait after power-up before the first transaction.
+    pub warm_up: Duration,
+    /// The time the rail stays off during a power cycle.
+    pub discharge: Duration,
+}
+
+impl<'d> BusPower<'d> {
+    /// Powers the rail up, the sensors are ready after the warm-up.
+    pub fn new(pin: impl Peripheral<P = AnyOutputPin> + 'd, on: Level) -> Result<Self> {
+        let mut power = Self {
+            pin: PinDriver::output(pin)?,
+            on,
+            warm_up: WARM_UP,
+            discharge: DISCHARGE,
+        };
+        power.pin.set_level(on)?;
+        Ok(power)
+    }
+
+    pub fn is_on(&self) -> bool {
+        match self.on {
+            Level::High => self.pin.is_set_high(),
+            Level::Low => self.pin.is_set_low(),
+        }
+    }
+
+    /// Powers the rail up and waits the warm-up, unless it is on already.
+    pub(crate) fn on(&mut self, cancellation: &Cancellation) -> Result<()> {
+        if self.is_on() {
+            return Ok(());
+        }
+        self.pin.set_level(self.on)?;
+        log!(Subsystem::Bus, LogLevel::Debug, "Bus powered up");
+        cancellation.sleep(self.warm_up)
+    }
+
+    fn off(&mut self) -> Result<()> {
+        let off = match self.on {
+            Level::High => Level::Low,
+            Level::Low => Level::High,
+        };
+        self.pin.set_level(off)?;
+        log!(Subsystem::Bus, LogLevel::Debug, "Bus powered down");
+        Ok(())
+    }
+}
+
+/// Strong pull-up switch of the data line
+pub struct StrongPullUp<'d> {
+    pin: PinDriver<'d, AnyOutputPin, Output>,
+    /// The level that enables the pull-up.
+    on: Level,
+}
+
+impl<'d> StrongPullUp<'d> {
+    /// Starts with the pull-up disabled.
+    pub fn new(pin: impl Peripheral<P = AnyOutputPin> + 'd, on: Level) -> Result<Self> {
+        let mut pull_up = Self {
+            pin: PinDriver::output(pin)?,
+            on,
+        };
+        pull_up.off()?;
+        Ok(pull_up)
+    }
+
+    fn on(&mut self) -> Result<()> {
+        Ok(self.pin.set_level(self.on)?)
+    }
+
+    fn off(&mut self) -> Result<()> {
+        Ok(self.pin.set_level(match self.on {
+            Level::High => Level::Low,
+            Level::Low => Level::High,
+        })?)
+    }
+}
+
+impl<'a> Ds18b20Driver<'a> {
+    /// Powers the conversions and EEPROM writes of parasite-powered sensors
+    /// with the pull-up.
+    pub fn set_strong_pull_up(&mut self, pull_up: StrongPullUp<'a>) {
+        self.strong_pull_up = Some(pull_up);
+    }
+
+    /// Whether the strong pull-up has to power the next conversion. Fails
+    /// with [`Error::ParasitePower`] if it is needed but missing.
+    pub(crate) fn parasite(&self) -> Result<bool> {
+        match (self.power_mode, &self.strong_pull_up) {
+            (Some(PowerMode::Parasite), None) => {
+                log!(
+                    Subsystem::Bus,
+                    LogLevel::Error,
+                    "Parasite-powered sensors need a strong pull-up, see `set_strong_pull_up`",
+                );
+                Err(Error::ParasitePower)
+            }
+            (power_mode, _) => Ok(power_mode == Some(PowerMode::Parasite)),
+        }
+    }
+
+    /// Holds the data line high, until released.
+    pub(crate) fn hold(&mut self) -> Result<()> {
+        match &mut self.strong_pull_up {
+            Some(pull_up) => pull_up.on(),
+            None => Ok(()),
+        }
+    }
+
+    /// Releases the data line for the next transaction.
+    pub(crate) fn release(&mut self) -> Result<()> {
+        match &mut self.strong_pull_up {
+            Some(pull_up) => pull_up.off(),
+            None => Ok(()),
+        }
+    }
+
+    /// Gates the sensor rail with the switch.
+    pub fn set_power(&mut self, power: BusPower<'a>) {
+        self.power = Some(power);
+    }
+
+    /// Depowers the sensors. Fails with
+    /// [`Error::ConversionPending`](crate::Error::ConversionPending) while a
+    /// conversion is in flight.
+    pub fn power_down(&mut self) -> Result<()> {
+        self.pending.check(Instant::now())?;
+        match &mut self.power {
+            Some(power) => power.off(),
+            None => Ok(()),
+        }
+    }
+
+    /// Powers the sensors up and waits the warm-up. The bus powers up by
+    /// itself before the next transaction.
+    pub fn power_up(&mut self) -> Result<()> {
+        match &mut self.power {
+            Some(power) => power.on(&self.cancellation),
+            None => Ok(()),
+        }
+    }
+
+    /// Power-cycles the sensors, e.g. to recover a wedged bus. A conversion
+    /// in flight is lost, and the power mode is detected again.
+    pub fn power_cycle(&mut self) -> Result<()> {
+        let Some(power) = &mut self.power else {
+            return Ok(());
+        };
+        instrument::operation("power_cycle", None, || {
+            log!(Subsystem::Bus, LogLevel::Info, "Power-cycling the bus");
+            power.off()?;
+            self.pending = Default::default();
+            self.power_mode = None;
+            self.cancellation.sleep(power.discharge)?;
+            power.on(&self.cancellation)
+        })
+    }
+}
@@ -0,0 +1,183 @@
+//! Application presets
+//!
+//! Starting points for common applications: the resolution, sampling
+//! interval, alarm limits, smoothing and deadband that fit them. The
+//! configuration part goes into a [`Config`], the filtering into the
+//! pipeline, and either can be tweaked afterwards:
+//!
+//! ```ignore
+//! let mut config = Config::preset(Preset::Freezer, &addresses);
+//! config.sensors[0].label = Some("chest freezer".into());
+//! config.apply(&mut registry, &mut calibration, &mut alarms)?;
+//! let profile = Preset::Freezer.profile();
+//! let pipeline = Pipeline::new()
+//!     .stage(profile.smoothing())
+//!     .stage(profile.deadband());
+//! ```
+//!
+//! | Preset      | Resolution | Interval | Limits (°C)   | Alpha | Deadband (°C) |
+//! |-------------|------------|----------|---------------|-------|---------------|
+//! | `Freezer`   | 10 bits    | 60 s     | -30 to -12    | 0.3   | 0.5           |
+//! | `Fridge`    | 11 bits    | 30 s     | 0 to 8        | 0.3   | 0.25          |
+//! | `Incubator` | 12 bits    | 10 s     | 36 to 38.5    | 0.5   | 0.1           |
+//! | `SousVide`  | 12 bits    | 2 s      | up to 95      | 0.7   | 0.1           |
+
+use crate::{
+    address::Address,
+    alarm::Limits,
+    config::{Config, Sampling, SensorConfig},
+    pipeline::{Deadband, Smoothing},
+    scratchpad::Resolution,
+};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Application preset
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Preset {
+    /// Freezers and cold rooms around -18 °C.
+    Freezer,
+    /// Fridges and cold chains between 0 and 8 °C.
+    Fridge,
+    /// Egg and lab incubators around 37 °C.
+    Incubator,
+    /// Water baths up to 95 °C, sampled fast for the control loop.
+    SousVide,
+}
+
+impl Preset {
+    pub const ALL: [Self; 4] = [Self::Freezer, Self::Fridge, Self::Incubator, Self::SousVide];
+
+    pub const fn profile(&self) -> Profile {
+        match self {
+            Self::Freezer => Profile {
+                resolution: Resolution::Ten,
+                interval: Duration::from_secs(60),
+                limits: Limits {
+                    low: Some(-30.0),
+                    high: Some(-12.0),
+                },
+                alpha: 0.3,
+                deadband: 0.5,
+            },
+            Self::Fridge => Profile {
+                resolution: Resolution::Eleven,
+                interval: Duration::from_secs(30),
+                limits: Limits {
+                    low: Some(0.0),
+                    high: Some(8.0),
+                },
+                alpha: 0.3,
+                deadband: 0.25,
+            },
+            Self::Incubator => Profile {
+                resolution: Resolution::Twelve,
+                interval: Duration::from_secs(10),
+                limits: Limits {
+                    low: Some(36.0),
+                    high: Some(38.5),
+                },
+                alpha: 0.5,
+                deadband: 0.1,
+            },
+            Self::SousVide => Profile {
+                resolution: Resolution::Twelve,
+                interval: Duration::from_secs(2),
+                limits: Limits {
+                    low: None,
+                    high: Some(95.0),
+                },
+                alpha: 0.7,
+                deadband: 0.1,
+            },
+        }
+    }
+}
+
+/// Preset settings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Profile {
+    pub resolution: Resolution,
+    pub interval: Duration,
+    pub limits: Limits,
+    /// The smoothing factor, see [`Smoothing`].
+    pub alpha: f32,
+    /// The deadband threshold (°C)
+    pub deadband: f32,
+}
+
+impl Profile {
+    pub fn sampling(&self) -> Sampling {
+        Sampling {
+            interval: self.interval,
+            jitter: Duration::ZERO,
+        }
+    }
+
+    /// The configuration of a sensor.
+    pub fn sensor(&self, address: Address) -> SensorConfig {
+        SensorConfig {
+            address,
+            limits: self.limits,
+            resolution: Some(self.resolution),
+            ..Default::default()
+        }
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        Smoothing::new(self.alpha)
+    }
+
+    pub fn deadband(&self) -> Deadband {
+        Deadband::new(self.deadband)
+    }
+}
+
+impl Config {
+    /// The configuration of the sensors for the preset.
+    pub fn preset(preset: Preset, addresses: &[Address]) -> Self {
+        let profile = preset.profile();
+        let mut sensors: Vec<_> = addresses
+            .iter()
+            .map(|address| profile.sensor(*address))
+            .collect();
+        sensors.sort_by_key(|sensor| sensor.address.0);
+        sensors.dedup_by_key(|sensor| sensor.address);
+        Self {
+            sampling: Some(profile.sampling()),
+            sensors,
+            policies: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preset() {
+        for preset in Preset::ALL {
+            let profile = preset.profile();
+            assert!(profile.limits.low < profile.limits.high, "{preset:?}");
+            assert!((0.0..=1.0).contains(&profile.alpha), "{preset:?}");
+            // A deadband below the step of the resolution passes everything.
+            assert!(profile.deadband >= profile.resolution.step(), "{preset:?}");
+        }
+        let boiler = Address(0x2300_0004_6EAF_BC28);
+        let config = Config::preset(Preset::Fridge, &[boiler, boiler]);
+        assert_eq!(
+            config.sensors,
+            [SensorConfig {
+                address: boiler,
+                limits: Limits {
+                    low: Some(0.0),
+                    high: Some(8.0),
+                },
+                resolution: Some(Resolution::Eleven),
+                ..Default::default()
+            }]
+        );
+        assert_eq!(Config::import(&config.export()), Ok(config));
+    }
+}
@@ -0,0 +1,174 @@
+//! Fleet provisioning
+//!
+//! Identifies the sensors of a freshly wired bus: the installer is asked to
+//! warm one probe at a time (e.g. by holding it), the probe whose temperature
+//! jumps is matched to the label the installer asked for, and the complete
+//! mapping is persisted at the end:
+//!
+//! ```ignore
+//! let mapping = thermometer.provision(&mut labels, &mut prompt, &Config::default())?;
+//! ```
+//!
+//! Sensors are programmed before installation with [`factory`].
+
+use crate::{address::Address, pipeline::Reading};
+use alloc::string::String;
+use core::time::Duration;
+
+#[cfg(feature = "esp-idf")]
+pub mod factory;
+
+/// Provisioning prompt
+///
+/// The callbacks through which the provisioning talks to the installer.
+pub trait Prompt {
+    /// Asks the installer to warm the next probe and returns its label, or
+    /// `None` to end the provisioning.
+    fn next(&mut self, unidentified: &[Address]) -> Option<String>;
+
+    /// The warmed probe was identified.
+    fn on_identified(&mut self, _address: &Address, _label: &str) {}
+
+    /// No probe warmed up within the timeout.
+    fn on_timeout(&mut self, _label: &str) {}
+
+    /// Persists the mapping of all the identified probes.
+    fn persist(&mut self, _mapping: &[(Address, String)]) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+/// Provisioning parameters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    /// The minimum temperature rise (°C) of a warmed probe.
+    pub jump: f32,
+    /// The time the installer has to warm a probe.
+    pub timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            jump: 1.0,
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Detects the warmed probe: the one with the largest rise over its baseline
+/// temperature, if the rise is at least `jump`.
+pub fn detect(baseline: &[Reading], readings: &[Reading], jump: f32) -> Option<Address> {
+    readings
+        .iter()
+        .filter_map(|reading| {
+            let baseline = baseline
+                .iter()
+                .find(|baseline| baseline.address == reading.address)?;
+            Some((reading.address, reading.temperature - baseline.temperature))
+        })
+        .filter(|(_, rise)| *rise >= jump)
+        .max_by(|(_, left), (_, right)| left.total_cmp(right))
+        .map(|(address, _)| address)
+}
+
+#[cfg(feature = "esp-idf")]
+mod driver {
+    use super::*;
+    use crate::{
+        Ds18b20Driver, Result,
+        label::{Label, Labels},
+        logging::{Subsystem, log},
+        sweep::PowerBudget,
+    };
+    use log::Level;
+    use std::time::Instant;
+
+    impl<'a> Ds18b20Driver<'a> {
+        /// Runs the provisioning over all the sensors on the bus. The
+        /// identified probes are labeled and the mapping is returned in the
+        /// identification order.
+        pub fn provision<const N: usize>(
+            &mut self,
+            labels: &mut Labels<N>,
+            prompt: &mut impl Prompt,
+            config: &Config,
+        ) -> Result<Vec<(Address, String)>> {
+            let mut unidentified = self.scan()?;
+            let mut mapping = Vec::new();
+            while !unidentified.is_empty() {
+                let Some(label) = prompt.next(&unidentified) else {
+                    break;
+                };
+                let baseline = self.sweep(&unidentified)?;
+                let start = Instant::now();
+                let address = loop {
+                    if start.elapsed() >= config.timeout {
+                        break None;
+                    }
+                    let readings = self.sweep(&unidentified)?;
+                    if let Some(address) = detect(&baseline, &readings, config.jump) {
+                        break Some(address);
+                    }
+                };
+                let Some(address) = address else {
+                    log!(
+                        Subsystem::Bus,
+                        Level::Warn,
+                        "No probe warmed up for {label}"
+                    );
+                    prompt.on_timeout(&label);
+                    continue;
+                };
+                log!(
+                    Subsystem::Bus,
+                    Level::Info,
+                    "Probe {address:x?} identified as {label}"
+                );
+                labels.insert(address, Label::new(label.clone()))?;
+                unidentified.retain(|unidentified| *unidentified != address);
+                prompt.on_identified(&address, &label);
+                mapping.push((address, label));
+            }
+            prompt.persist(&mapping)?;
+            Ok(mapping)
+        }
+
+        /// The good readings of the sensors.
+        fn sweep(&mut self, addresses: &[Address]) -> Result<Vec<Reading>> {
+            let sweep = self.read_all(addresses, PowerBudget::Unlimited)?;
+            Ok(sweep.readings.into_iter().flatten().collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn readings(temperatures: &[f32]) -> Vec<Reading> {
+        temperatures
+            .iter()
+            .enumerate()
+            .map(|(index, temperature)| Reading::new(Address(index as _), *temperature))
+            .collect()
+    }
+
+    #[test]
+    fn detect() {
+        let baseline = readings(&[20.0, 21.0, 22.0]);
+        assert_eq!(
+            super::detect(&baseline, &readings(&[20.5, 21.0, 22.0]), 1.0),
+            None
+        );
+        assert_eq!(
+            super::detect(&baseline, &readings(&[21.5, 23.0, 22.0]), 1.0),
+            Some(Address(1))
+        );
+        // Sensors without a baseline are ignored.
+        assert_eq!(
+            super::detect(&baseline[..1], &readings(&[20.0, 30.0]), 1.0),
+            None
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Production-line programming
+//!
+//! Programs the single sensor attached to a jig: reads its ROM, writes the
+//! configuration, commits it to EEPROM, verifies it and takes a test
+//! temperature. The resulting [`Record`] is meant to be logged per unit:
+//!
+//! ```ignore
+//! loop {
+//!     wait_for_next_unit();
+//!     match thermometer.program(&configuration) {
+//!         Ok(record) => println!("{record}"),
+//!         Err(error) => println!("reject: {error}"),
+//!     }
+//! }
+//! ```
+
+use crate::{
+    Ds18b20Driver, Error, Result,
+    address::Address,
+    audit::Configuration,
+    driver::preflight,
+    logging::{Subsystem, log},
+};
+use core::fmt::{self, Display, Formatter};
+use log::Level;
+
+/// Provisioning record
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record {
+    pub address: Address,
+    pub configuration: Configuration,
+    /// Test temperature (°C)
+    pub temperature: f32,
+}
+
+/// `address,th,tl,resolution,temperature`
+impl Display for Record {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{}",
+            self.address,
+            self.configuration.alarm_high,
+            self.configuration.alarm_low,
+            self.configuration.resolution.bits(),
+            self.temperature,
+        )
+    }
+}
+
+impl Ds18b20Driver<'_> {
+    /// Programs the only sensor on the bus with the configuration.
+    ///
+    /// Fails with [`Error::Verification`] if the EEPROM doesn't hold the
+    /// configuration after the commit.
+    pub fn program(&mut self, configuration: &Configuration) -> Result<Record> {
+        let address = self.retry(|this| this.initialization()?.read_rom())?;
+        preflight(&address)?;
+        self.write_scratchpad(&address, &(*configuration).into())?;
+        self.retry(|this| {
+            this.initialization()?
+                .match_rom(&address)?
+                .load_scratchpad()
+        })?;
+        // Read the EEPROM back through the scratchpad.
+        self.retry(|this| {
+            this.initialization()?
+                .match_rom(&address)?
+                .save_scratchpad()
+        })?;
+        let scratchpad = self.retry(|this| {
+            this.initialization()?
+                .match_rom(&address)?
+                .read_scratchpad()
+        })?;
+        let found = Configuration::from(&scratchpad);
+        if found != *configuration {
+            log!(
+                Subsystem::Bus,
+                Level::Warn,
+                "Verification of {address} failed: {found:?}",
+            );
+            return Err(Error::Verification);
+        }
+        let temperature = self.temperature(&address)?;
+        Ok(Record {
+            address,
+            configuration: *configuration,
+            temperature,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scratchpad::{AlarmThreshold, Resolution};
+
+    #[test]
+    fn record() {
+        let record = Record {
+            address: Address(0x1E00_0000_0000_0028),
+            configuration: Configuration {
+                alarm_high: AlarmThreshold::clamped(30),
+                alarm_low: AlarmThreshold::clamped(-5),
+                resolution: Resolution::Eleven,
+            },
+            temperature: 21.4375,
+        };
+        assert_eq!(record.to_string(), "1e00000000000028,30,-5,11,21.4375");
+    }
+}
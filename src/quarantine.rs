@@ -0,0 +1,162 @@
+//! Quarantine of failing sensors
+//!
+//! A sensor that keeps failing, e.g. on a damaged cable, costs the bus its
+//! retries on every sample. After a number of failures in a row the sensor is
+//! quarantined: it is skipped for a number of samples, then read once more. A
+//! successful read releases it, a failed one quarantines it again:
+//!
+//! ```ignore
+//! let mut sampler = Sampler::new(driver, addresses, Duration::from_secs(10))
+//!     .quarantine(Quarantine::new(3, 30));
+//! // ...
+//! for address in sampler.quarantined() {
+//!     warn!("{address} quarantined");
+//! }
+//! ```
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    logging::{Subsystem, log},
+};
+use log::Level;
+
+/// Failures of a sensor
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Record {
+    /// Failed reads since the last successful read
+    failures: usize,
+    /// The samples left to skip.
+    skip: usize,
+}
+
+/// Quarantine
+///
+/// Tracks the failures of the sensors; sensors beyond the capacity aren't
+/// quarantined.
+#[derive(Clone, Debug)]
+pub struct Quarantine<const N: usize = CAPACITY> {
+    failures: usize,
+    samples: usize,
+    records: Map<Address, Record, N>,
+}
+
+impl Quarantine {
+    /// Quarantines a sensor for the samples after the failures in a row.
+    pub fn new(failures: usize, samples: usize) -> Self {
+        Self {
+            failures: failures.max(1),
+            samples,
+            records: Map::new(),
+        }
+    }
+}
+
+impl<const N: usize> Quarantine<N> {
+    /// Sets the maximum number of tracked sensors.
+    pub fn capacity<const M: usize>(self) -> Quarantine<M> {
+        Quarantine {
+            failures: self.failures,
+            samples: self.samples,
+            records: self.records.into_capacity(),
+        }
+    }
+
+    /// Whether the sensor is skipped in this sample. Counts the sample off
+    /// its quarantine.
+    pub fn skip(&mut self, address: &Address) -> bool {
+        match self.records.get_mut(address) {
+            Some(record) if record.skip > 0 => {
+                record.skip -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Records a read of the sensor.
+    pub fn record<T, E>(&mut self, address: Address, result: &Result<T, E>) {
+        if result.is_ok() {
+            if let Some(record) = self.records.remove(&address)
+                && record.failures >= self.failures
+            {
+                log!(
+                    Subsystem::Sampler,
+                    Level::Info,
+                    "Sensor {address} released from quarantine"
+                );
+            }
+            return;
+        }
+        let Ok(record) = self.records.get_or_insert(address, Record::default()) else {
+            return;
+        };
+        record.failures += 1;
+        if record.failures >= self.failures {
+            record.skip = self.samples;
+            log!(
+                Subsystem::Sampler,
+                Level::Warn,
+                "Sensor {address} quarantined for {} samples after {} failures",
+                self.samples,
+                record.failures,
+            );
+        }
+    }
+
+    /// Whether the sensor failed too often, also while it is read again.
+    pub fn contains(&self, address: &Address) -> bool {
+        self.records
+            .get(address)
+            .is_some_and(|record| record.failures >= self.failures)
+    }
+
+    /// The quarantined sensors.
+    pub fn iter(&self) -> impl Iterator<Item = &Address> {
+        self.records
+            .iter()
+            .filter(|(_, record)| record.failures >= self.failures)
+            .map(|(address, _)| address)
+    }
+
+    /// Releases the sensor, e.g. after a repair.
+    pub fn release(&mut self, address: &Address) {
+        self.records.remove(address);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quarantine() {
+        let mut quarantine = Quarantine::new(2, 3);
+        let failed: Result<(), ()> = Err(());
+        quarantine.record(Address(1), &failed);
+        assert!(!quarantine.contains(&Address(1)));
+        assert!(!quarantine.skip(&Address(1)));
+        quarantine.record(Address(1), &failed);
+        assert!(quarantine.contains(&Address(1)));
+        assert_eq!(quarantine.iter().collect::<Vec<_>>(), [&Address(1)]);
+        assert_eq!(
+            (0..4)
+                .map(|_| quarantine.skip(&Address(1)))
+                .collect::<Vec<_>>(),
+            [true, true, true, false]
+        );
+        // Failing again
+        quarantine.record(Address(1), &failed);
+        assert!(quarantine.skip(&Address(1)));
+        quarantine.record(Address(1), &Ok::<_, ()>(()));
+        assert!(!quarantine.contains(&Address(1)));
+        assert!(!quarantine.skip(&Address(1)));
+
+        let mut quarantine = Quarantine::new(1, 1).capacity::<1>();
+        quarantine.record(Address(1), &failed);
+        quarantine.record(Address(2), &failed);
+        assert!(!quarantine.contains(&Address(2)));
+        quarantine.release(&Address(1));
+        assert_eq!(quarantine.iter().count(), 0);
+    }
+}
@@ -0,0 +1,263 @@
+//! Command queue
+//!
+//! Scripts sequences of high-level operations, e.g. for test benches and
+//! programming jigs. The queue executes them in order, waits for conversions
+//! before touching the converting sensors, keeps the number of simultaneous
+//! conversions within the [`PowerBudget`] and reports an [`Outcome`] per
+//! operation:
+//!
+//! ```ignore
+//! let mut queue = CommandQueue::new(PowerBudget::MaxSimultaneousConversions(2));
+//! queue
+//!     .convert(group_a.clone())
+//!     .write(address, Configuration::default())
+//!     .read(group_a)
+//!     .recall(address);
+//! for outcome in queue.run(&mut thermometer) {
+//!     // ...
+//! }
+//! ```
+//!
+//! The operations run in the order they were queued. Every one of them
+//! selects its sensors with Match ROM: the DS18B20 has no Resume command and
+//! every function command ends with a reset, so grouping the operations of a
+//! sensor wouldn't save any ROM selection.
+
+use crate::{
+    Ds18b20Driver, Error, Result, address::Address, audit::Configuration,
+    cancellation::Cancellation, driver::preflight, pipeline::Reading, sweep::PowerBudget, timing,
+};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Queued operation
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    /// Starts the conversion of the group.
+    Convert(Vec<Address>),
+    /// Reads the group, waiting for their conversions.
+    Read(Vec<Address>),
+    /// Writes the configuration to the scratchpad.
+    Write(Address, Configuration),
+    /// Recalls the EEPROM into the scratchpad.
+    Recall(Address),
+    Wait(Duration),
+}
+
+/// Operation outcome
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Done,
+    /// The readings of a [`Operation::Read`] in the order of the group.
+    Readings(Vec<Result<Reading>>),
+    Failed(Error),
+}
+
+/// Command queue
+#[derive(Clone, Debug, Default)]
+pub struct CommandQueue {
+    operations: VecDeque<Operation>,
+    budget: PowerBudget,
+}
+
+impl CommandQueue {
+    pub fn new(budget: PowerBudget) -> Self {
+        Self {
+            operations: VecDeque::new(),
+            budget,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    pub fn push(&mut self, operation: Operation) -> &mut Self {
+        self.operations.push_back(operation);
+        self
+    }
+
+    pub fn convert(&mut self, group: impl Into<Vec<Address>>) -> &mut Self {
+        self.push(Operation::Convert(group.into()))
+    }
+
+    pub fn read(&mut self, group: impl Into<Vec<Address>>) -> &mut Self {
+        self.push(Operation::Read(group.into()))
+    }
+
+    pub fn write(&mut self, address: Address, configuration: Configuration) -> &mut Self {
+        self.push(Operation::Write(address, configuration))
+    }
+
+    pub fn recall(&mut self, address: Address) -> &mut Self {
+        self.push(Operation::Recall(address))
+    }
+
+    pub fn wait(&mut self, duration: Duration) -> &mut Self {
+        self.push(Operation::Wait(duration))
+    }
+
+    /// Executes all queued operations.
+    pub fn run(&mut self, driver: &mut Ds18b20Driver) -> Vec<Outcome> {
+        let mut conversions = Conversions::default();
+        let mut outcomes = Vec::with_capacity(self.operations.len());
+        while let Some(operation) = self.operations.pop_front() {
+            let outcome = match operation {
+                Operation::Convert(group) => group
+                    .iter()
+                    .try_for_each(|address| {
+                        preflight(address)?;
+                        conversions.wait_for_budget(self.budget, &driver.cancellation)?;
+                        driver.retry(|this| {
+                            this.initialization()?
+                                .match_rom(address)?
+                                .start_conversion()
+                        })?;
+                        conversions.start(*address, Instant::now());
+                        Ok(())
+                    })
+                    .map(|_| Outcome::Done),
+                Operation::Read(group) => Ok(Outcome::Readings(
+                    group
+                        .iter()
+                        .map(|address| {
+                            preflight(address)?;
+                            conversions.wait_for(address, &driver.cancellation)?;
+                            let scratchpad = driver.retry(|this| {
+                                this.initialization()?.match_rom(address)?.read_scratchpad()
+                            })?;
+                            let reading = Reading::new(*address, scratchpad.temperature);
+                            Ok(match conversions.started(address) {
+                                Some(converted) => reading.converted_at(converted),
+                                None => reading,
+                            })
+                        })
+                        .collect(),
+                )),
+                Operation::Write(address, configuration) => preflight(&address)
+                    .and_then(|_| {
+                        conversions.wait_for(&address, &driver.cancellation)?;
+                        driver.write_scratchpad(&address, &configuration.into())
+                    })
+                    .map(|_| Outcome::Done),
+                Operation::Recall(address) => preflight(&address)
+                    .and_then(|_| {
+                        conversions.wait_for(&address, &driver.cancellation)?;
+                        driver.retry(|this| {
+                            this.initialization()?
+                                .match_rom(&address)?
+                                .save_scratchpad()
+                        })
+                    })
+                    .map(|_| Outcome::Done),
+                Operation::Wait(duration) => {
+                    driver.cancellation.sleep(duration).map(|_| Outcome::Done)
+                }
+            };
+            outcomes.push(outcome.unwrap_or_else(Outcome::Failed));
+        }
+        outcomes
+    }
+}
+
+/// Conversions in flight
+#[derive(Clone, Debug, Default)]
+struct Conversions {
+    ready_at: Vec<(Address, Instant)>,
+    /// The start of the last conversion of each sensor, kept after the
+    /// conversion leaves the budget, for stamping its readings.
+    started_at: Vec<(Address, Instant)>,
+}
+
+impl Conversions {
+    fn start(&mut self, address: Address, now: Instant) {
+        self.ready_at
+            .retain(|(converting, _)| *converting != address);
+        self.ready_at.push((address, now + timing::CONVERSION));
+        self.started_at
+            .retain(|(converting, _)| *converting != address);
+        self.started_at.push((address, now));
+    }
+
+    /// The start of the last conversion of the sensor.
+    fn started(&self, address: &Address) -> Option<Instant> {
+        self.started_at
+            .iter()
+            .find(|(converting, _)| converting == address)
+            .map(|(_, started_at)| *started_at)
+    }
+
+    /// The time until another conversion fits into the budget.
+    fn budget_delay(&mut self, budget: PowerBudget, now: Instant) -> Duration {
+        self.ready_at.retain(|(_, ready_at)| *ready_at > now);
+        let PowerBudget::MaxSimultaneousConversions(count) = budget else {
+            return Duration::ZERO;
+        };
+        let mut ready_at: Vec<_> = self
+            .ready_at
+            .iter()
+            .map(|(_, ready_at)| *ready_at)
+            .collect();
+        ready_at.sort();
+        match ready_at.len().checked_sub(count.max(1)) {
+            Some(index) => ready_at[index].saturating_duration_since(now),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// The time until the conversion of the sensor is done.
+    fn delay(&self, address: &Address, now: Instant) -> Duration {
+        self.ready_at
+            .iter()
+            .find(|(converting, _)| converting == address)
+            .map_or(Duration::ZERO, |(_, ready_at)| {
+                ready_at.saturating_duration_since(now)
+            })
+    }
+
+    fn wait_for_budget(&mut self, budget: PowerBudget, cancellation: &Cancellation) -> Result<()> {
+        cancellation.sleep(self.budget_delay(budget, Instant::now()))
+    }
+
+    fn wait_for(&self, address: &Address, cancellation: &Cancellation) -> Result<()> {
+        cancellation.sleep(self.delay(address, Instant::now()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        let now = Instant::now();
+        let conversion = timing::CONVERSION;
+        let mut conversions = Conversions::default();
+        let budget = PowerBudget::MaxSimultaneousConversions(2);
+        conversions.start(Address(1), now);
+        assert_eq!(conversions.budget_delay(budget, now), Duration::ZERO);
+        conversions.start(Address(2), now + conversion / 2);
+        // The first conversion has to finish.
+        assert_eq!(conversions.budget_delay(budget, now), conversion);
+        assert_eq!(
+            conversions.budget_delay(PowerBudget::Unlimited, now),
+            Duration::ZERO
+        );
+        assert_eq!(conversions.delay(&Address(2), now), conversion * 3 / 2);
+        assert_eq!(conversions.delay(&Address(3), now), Duration::ZERO);
+        assert_eq!(
+            conversions.budget_delay(budget, now + conversion),
+            Duration::ZERO
+        );
+        assert_eq!(conversions.ready_at.len(), 1);
+        // The finished conversion still stamps its readings.
+        assert_eq!(conversions.started(&Address(1)), Some(now));
+        assert_eq!(conversions.started(&Address(3)), None);
+    }
+}
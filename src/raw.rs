@@ -0,0 +1,78 @@
+//! Raw bus access
+//!
+//! Escape hatch for vendor-specific commands. The bus is handed out only for
+//! the duration of a closure and is reset afterwards, so whatever the closure
+//! did, the devices are idle and waiting for a reset pulse when the driver
+//! takes over again. The transfers go through the driver like its own, so
+//! they show in the [bus statistics](Ds18b20Driver::bus_stats) and the
+//! [last transactions](Ds18b20Driver::last_transactions).
+
+use crate::{
+    Ds18b20Driver, Result,
+    address::Address,
+    command::{self, CommandCode},
+    logging::{Subsystem, log},
+};
+use log::Level;
+use std::time::Instant;
+
+/// Raw bus
+pub struct RawBus<'a, 'b> {
+    driver: &'a mut Ds18b20Driver<'b>,
+}
+
+impl RawBus<'_, '_> {
+    /// Sends the reset pulse.
+    pub fn reset(&mut self) -> Result<()> {
+        log!(Subsystem::Bus, Level::Trace, "reset");
+        self.driver.reset_pulse()
+    }
+
+    /// Writes the bytes to the bus.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        log!(Subsystem::Bus, Level::Trace, "write {bytes:02x?}");
+        self.driver.write_bytes(bytes)
+    }
+
+    /// Writes the command code.
+    pub fn command(&mut self, code: CommandCode) -> Result<()> {
+        log!(Subsystem::Bus, Level::Trace, "{code}");
+        self.driver.write_bytes(&[code as _])
+    }
+
+    /// Reads bytes from the bus into the buffer.
+    pub fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<()> {
+        self.driver.read_bytes(buffer)?;
+        log!(Subsystem::Bus, Level::Trace, "read {buffer:02x?}");
+        Ok(())
+    }
+
+    /// Sends the reset pulse followed by the Match ROM command, so the next
+    /// bytes are addressed to the device only.
+    pub fn select(&mut self, address: &Address) -> Result<()> {
+        self.reset()?;
+        self.write_bytes(&command::match_rom(address))
+    }
+}
+
+impl<'a> Ds18b20Driver<'a> {
+    /// Runs a raw transaction on the bus.
+    ///
+    /// The bus is prepared like for any transaction (powered up, the strong
+    /// pull-up released, the reset pulse sent) and reset after the closure
+    /// returns, even if it failed. Fails with
+    /// [`Error::ConversionPending`](crate::Error::ConversionPending) while a
+    /// conversion is in flight.
+    pub fn raw_transaction<T>(
+        &mut self,
+        transaction: impl FnOnce(&mut RawBus<'_, 'a>) -> Result<T>,
+    ) -> Result<T> {
+        self.pending.check(Instant::now())?;
+        self.initialization()?;
+        let result = transaction(&mut RawBus { driver: self });
+        let reset = self.reset_pulse();
+        let value = result?;
+        reset?;
+        Ok(value)
+    }
+}
@@ -0,0 +1,235 @@
+//! Dual-bus redundancy
+//!
+//! For high-reliability installs the same probes are wired to two GPIO
+//! buses. Every sensor is read over both paths, the readings are
+//! cross-checked and the active path fails over to the other one when it
+//! degrades:
+//!
+//! ```ignore
+//! let mut bus = RedundantBus::new(
+//!     Ds18b20Driver::new(peripherals.pins.gpio4, peripherals.rmt.channel0)?,
+//!     Ds18b20Driver::new(peripherals.pins.gpio5, peripherals.rmt.channel1)?,
+//!     Redundancy::default(),
+//! );
+//! let resolved = bus.read(&address)?;
+//! ```
+
+use crate::{
+    error::Result,
+    logging::{Subsystem, log},
+    pipeline::Reading,
+};
+use log::Level;
+
+/// Bus path
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Path {
+    #[default]
+    Primary,
+    Secondary,
+}
+
+impl Path {
+    pub const fn other(&self) -> Self {
+        match self {
+            Path::Primary => Path::Secondary,
+            Path::Secondary => Path::Primary,
+        }
+    }
+}
+
+/// Resolved reading
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resolved {
+    pub reading: Reading,
+    /// The path the reading was taken from.
+    pub path: Path,
+    /// The difference (°C) between the paths, if both read successfully and
+    /// differ by more than the tolerance.
+    pub discrepancy: Option<f32>,
+}
+
+/// Redundancy state
+///
+/// Cross-checks the readings of both paths and tracks the health of the
+/// active path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Redundancy {
+    /// The maximum difference (°C) between the paths.
+    pub tolerance: f32,
+    /// The number of consecutive failures of the active path after which
+    /// it fails over.
+    pub failover_after: usize,
+    active: Path,
+    failures: usize,
+    discrepancies: usize,
+    failovers: usize,
+}
+
+impl Default for Redundancy {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.5,
+            failover_after: 3,
+            active: Path::Primary,
+            failures: 0,
+            discrepancies: 0,
+            failovers: 0,
+        }
+    }
+}
+
+impl Redundancy {
+    /// The active path.
+    pub fn active(&self) -> Path {
+        self.active
+    }
+
+    /// The number of reported discrepancies.
+    pub fn discrepancies(&self) -> usize {
+        self.discrepancies
+    }
+
+    /// The number of failovers.
+    pub fn failovers(&self) -> usize {
+        self.failovers
+    }
+
+    /// Resolves the readings of the same sensor over both paths. The reading
+    /// of the active path is preferred, the other path is used if the active
+    /// one failed. Fails with the error of the active path if both failed.
+    pub fn resolve(
+        &mut self,
+        primary: Result<Reading>,
+        secondary: Result<Reading>,
+    ) -> Result<Resolved> {
+        let (active, standby) = match self.active {
+            Path::Primary => (primary, secondary),
+            Path::Secondary => (secondary, primary),
+        };
+        match (active, standby) {
+            (Ok(reading), standby) => {
+                self.failures = 0;
+                let discrepancy = standby
+                    .ok()
+                    .map(|standby| reading.temperature - standby.temperature)
+                    .filter(|difference| difference.abs() > self.tolerance);
+                if let Some(discrepancy) = discrepancy {
+                    self.discrepancies += 1;
+                    log!(
+                        Subsystem::Bus,
+                        Level::Warn,
+                        "Paths of {} differ by {discrepancy} °C",
+                        reading.address,
+                    );
+                }
+                Ok(Resolved {
+                    reading,
+                    path: self.active,
+                    discrepancy,
+                })
+            }
+            (Err(error), standby) => {
+                let path = self.active.other();
+                self.failures += 1;
+                if self.failures >= self.failover_after && standby.is_ok() {
+                    log!(
+                        Subsystem::Bus,
+                        Level::Warn,
+                        "{:?} path degraded ({error}), failing over to {path:?}",
+                        self.active,
+                    );
+                    self.active = path;
+                    self.failures = 0;
+                    self.failovers += 1;
+                }
+                Ok(Resolved {
+                    reading: standby.map_err(|_| error)?,
+                    path,
+                    discrepancy: None,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(feature = "esp-idf")]
+pub use self::bus::RedundantBus;
+
+#[cfg(feature = "esp-idf")]
+mod bus {
+    use super::*;
+    use crate::{Ds18b20Driver, address::Address};
+
+    /// Redundant bus
+    pub struct RedundantBus<'a> {
+        pub primary: Ds18b20Driver<'a>,
+        pub secondary: Ds18b20Driver<'a>,
+        pub redundancy: Redundancy,
+    }
+
+    impl<'a> RedundantBus<'a> {
+        pub fn new(
+            primary: Ds18b20Driver<'a>,
+            secondary: Ds18b20Driver<'a>,
+            redundancy: Redundancy,
+        ) -> Self {
+            Self {
+                primary,
+                secondary,
+                redundancy,
+            }
+        }
+
+        /// Reads the sensor over both paths.
+        pub fn read(&mut self, address: &Address) -> Result<Resolved> {
+            let primary = self.primary.read(address);
+            let secondary = self.secondary.read(address);
+            self.redundancy.resolve(primary, secondary)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{address::Address, error::Error};
+
+    fn reading(temperature: f32) -> Result<Reading> {
+        Ok(Reading::new(Address(1), temperature))
+    }
+
+    #[test]
+    fn discrepancy() {
+        let mut redundancy = Redundancy::default();
+        let resolved = redundancy.resolve(reading(20.0), reading(20.25)).unwrap();
+        assert_eq!(resolved.discrepancy, None);
+        assert_eq!(resolved.path, Path::Primary);
+        let resolved = redundancy.resolve(reading(20.0), reading(21.0)).unwrap();
+        assert_eq!(resolved.discrepancy, Some(-1.0));
+        assert_eq!(resolved.reading.temperature, 20.0);
+        assert_eq!(redundancy.discrepancies(), 1);
+    }
+
+    #[test]
+    fn failover() {
+        let mut redundancy = Redundancy {
+            failover_after: 2,
+            ..Default::default()
+        };
+        let failure = || Err(Error::DeviceNotFound);
+        let resolved = redundancy.resolve(failure(), reading(21.0)).unwrap();
+        assert_eq!(resolved.path, Path::Secondary);
+        assert_eq!(redundancy.active(), Path::Primary);
+        redundancy.resolve(failure(), reading(21.0)).unwrap();
+        assert_eq!(redundancy.active(), Path::Secondary);
+        assert_eq!(redundancy.failovers(), 1);
+        // The secondary path is preferred now.
+        let resolved = redundancy.resolve(reading(20.0), reading(21.0)).unwrap();
+        assert_eq!(resolved.reading.temperature, 21.0);
+        assert_eq!(
+            redundancy.resolve(failure(), failure()),
+            Err(Error::DeviceNotFound)
+        );
+    }
+}
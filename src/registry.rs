@@ -0,0 +1,579 @@
+//! Sensor registry
+//!
+//! The set of known sensors, organized into named zones (room, tank, rack).
+//! Zone queries take the temperature source as a closure, so they work with
+//! the [`History`](crate::history::History), the readings of a sweep or any
+//! other source:
+//!
+//! ```ignore
+//! let statistics = registry.statistics("tank", |address| history.latest(address));
+//! ```
+//!
+//! Sensors loaded from a configuration are added with [`Registry::add`],
+//! which rejects an address listed twice and labels used by two sensors, so
+//! a mistake can't silently shadow a sensor. The [`Merge`] strategy decides
+//! how a repeated address is handled instead.
+
+use crate::{
+    address::Address,
+    collections::{CAPACITY, Map},
+    error::{Error, Result},
+    persistence::Store,
+};
+use alloc::{string::String, vec::Vec};
+
+/// Registry
+#[derive(Clone, Debug, Default)]
+pub struct Registry<const N: usize = CAPACITY> {
+    sensors: Map<Address, Sensor, N>,
+    policies: Map<String, AlarmPolicy, N>,
+    merge: Merge,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<const N: usize> Registry<N> {
+    /// Sets the maximum number of sensors and zone policies.
+    pub fn capacity<const M: usize>(self) -> Registry<M> {
+        Registry {
+            sensors: self.sensors.into_capacity(),
+            policies: self.policies.into_capacity(),
+            merge: self.merge,
+        }
+    }
+
+    /// Sets the handling of an address added twice.
+    pub fn merge(self, merge: Merge) -> Self {
+        Self { merge, ..self }
+    }
+
+    /// Adds the sensor.
+    ///
+    /// Fails with [`Error::DuplicateAddress`] if the sensor is already known
+    /// and the strategy is [`Merge::Reject`], and with
+    /// [`Error::DuplicateLabel`] if another sensor holds the label.
+    pub fn add(&mut self, address: Address, sensor: Sensor) -> Result<&mut Sensor> {
+        if let Some(label) = &sensor.label {
+            self.check_label(&address, label)?;
+        }
+        if let Some(existing) = self.sensors.get_mut(&address) {
+            match self.merge {
+                Merge::Reject => return Err(Error::DuplicateAddress(address)),
+                Merge::Keep => {
+                    existing.zone = existing.zone.take().or(sensor.zone);
+                    existing.label = existing.label.take().or(sensor.label);
+                }
+                Merge::Replace => *existing = sensor,
+            }
+            return self.insert(address);
+        }
+        self.sensors.insert(address, sensor)?;
+        self.insert(address)
+    }
+
+    /// Labels the sensor, registering the sensor if it is new. Fails with
+    /// [`Error::DuplicateLabel`] if another sensor holds the label.
+    pub fn set_label(&mut self, address: Address, label: impl Into<String>) -> Result<()> {
+        let label = label.into();
+        self.check_label(&address, &label)?;
+        self.insert(address)?.label = Some(label);
+        Ok(())
+    }
+
+    /// The sensor with the label.
+    pub fn find(&self, label: &str) -> Option<&Address> {
+        self.sensors
+            .iter()
+            .find(|(_, sensor)| sensor.label.as_deref() == Some(label))
+            .map(|(address, _)| address)
+    }
+
+    fn check_label(&self, address: &Address, label: &str) -> Result<()> {
+        match self.find(label) {
+            Some(holder) if holder != address => Err(Error::DuplicateLabel { address: *holder }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Registers the sensor, keeping its settings if it is already known.
+    pub fn insert(&mut self, address: Address) -> Result<&mut Sensor> {
+        self.sensors.get_or_insert(address, Sensor::default())
+    }
+
+    /// Brings the registry up to date with the sensors found on the bus:
+    /// the new ones are registered, the missing ones keep their settings.
+    /// Fails with [`Error::Capacity`] if the new sensors don't fit, nothing
+    /// is registered then.
+    pub fn sync(&mut self, found: &[Address]) -> Result<Presence> {
+        let mut added = Vec::new();
+        for address in found {
+            if !self.contains(address) && !added.contains(address) {
+                added.push(*address);
+            }
+        }
+        if self.len() + added.len() > N {
+            return Err(Error::Capacity(N));
+        }
+        for address in &added {
+            self.insert(*address)?;
+        }
+        let missing = self
+            .sensors
+            .iter()
+            .map(|(address, _)| *address)
+            .filter(|address| !found.contains(address))
+            .collect();
+        Ok(Presence { added, missing })
+    }
+
+    pub fn remove(&mut self, address: &Address) -> Option<Sensor> {
+        self.sensors.remove(address)
+    }
+
+    pub fn get(&self, address: &Address) -> Option<&Sensor> {
+        self.sensors.get(address)
+    }
+
+    pub fn get_mut(&mut self, address: &Address) -> Option<&mut Sensor> {
+        self.sensors.get_mut(address)
+    }
+
+    /// Saves the sensors, their zones and labels under `registry`: per
+    /// sensor the little-endian address, then the length (0 if unset) and the
+    /// bytes of the zone and of the label, each truncated to 255 bytes.
+    pub fn shutdown(&self, store: &mut dyn Store) -> Result<()> {
+        let mut value = Vec::new();
+        for (address, sensor) in self.sensors.iter() {
+            value.extend_from_slice(&address.0.to_le_bytes());
+            for text in [&sensor.zone, &sensor.label] {
+                let text = text.as_deref().unwrap_or_default();
+                let mut length = text.len().min(u8::MAX as _);
+                while !text.is_char_boundary(length) {
+                    length -= 1;
+                }
+                value.push(length as _);
+                value.extend_from_slice(&text.as_bytes()[..length]);
+            }
+        }
+        store.save("registry", &value)
+    }
+
+    /// Replaces the sensors with the ones saved by
+    /// [`shutdown`](Self::shutdown), if any. The zone policies are kept.
+    /// Nothing is changed on failure.
+    pub fn restore(&mut self, store: &mut dyn Store) -> Result<()> {
+        let Some(value) = store.load("registry")? else {
+            return Ok(());
+        };
+        let mut sensors = Map::new();
+        let mut rest = &value[..];
+        while !rest.is_empty() {
+            let error = Error::Decode {
+                offset: value.len() - rest.len(),
+            };
+            let (address, tail) = rest.split_first_chunk().ok_or(error)?;
+            rest = tail;
+            let mut texts = [None, None];
+            for text in &mut texts {
+                let (&length, tail) = rest.split_first().ok_or(error)?;
+                let (bytes, tail) = tail.split_at_checked(length as _).ok_or(error)?;
+                rest = tail;
+                if !bytes.is_empty() {
+                    *text = Some(str::from_utf8(bytes).map_err(|_| error)?.into());
+                }
+            }
+            let [zone, label] = texts;
+            sensors.insert(
+                Address(u64::from_le_bytes(*address)),
+                Sensor { zone, label },
+            )?;
+        }
+        self.sensors = sensors;
+        Ok(())
+    }
+
+    pub fn contains(&self, address: &Address) -> bool {
+        self.sensors.contains_key(address)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sensors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sensors.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, &Sensor)> {
+        self.sensors.iter()
+    }
+
+    /// Assigns the sensor to the zone, registering the sensor if it is new.
+    pub fn set_zone(&mut self, address: Address, zone: impl Into<String>) -> Result<()> {
+        self.insert(address)?.zone = Some(zone.into());
+        Ok(())
+    }
+
+    /// The sensors of the zone.
+    pub fn zone<'a>(&'a self, zone: &'a str) -> impl Iterator<Item = &'a Address> {
+        self.sensors
+            .iter()
+            .filter(move |(_, sensor)| sensor.zone.as_deref() == Some(zone))
+            .map(|(address, _)| address)
+    }
+
+    /// The distinct zones, in the order of their first sensor.
+    pub fn zones(&self) -> impl Iterator<Item = &str> {
+        self.sensors
+            .values()
+            .enumerate()
+            .filter_map(|(index, sensor)| {
+                let zone = sensor.zone.as_deref()?;
+                // Not allocating a set, the registry is small.
+                let first = self
+                    .sensors
+                    .values()
+                    .take(index)
+                    .all(|previous| previous.zone.as_deref() != Some(zone));
+                first.then_some(zone)
+            })
+    }
+
+    /// Sets the alarm policy of the zone.
+    pub fn set_policy(&mut self, zone: impl Into<String>, policy: AlarmPolicy) -> Result<()> {
+        self.policies.insert(zone.into(), policy)?;
+        Ok(())
+    }
+
+    /// Removes the alarm policy of the zone, returning it.
+    pub fn remove_policy(&mut self, zone: &str) -> Option<AlarmPolicy> {
+        let policy = self.policy(zone).copied();
+        self.policies.retain(|name, _| name != zone);
+        policy
+    }
+
+    /// The zones with a policy and their policies.
+    pub fn policies(&self) -> impl Iterator<Item = (&str, &AlarmPolicy)> {
+        self.policies
+            .iter()
+            .map(|(zone, policy)| (zone.as_str(), policy))
+    }
+
+    /// Removes all sensors and policies.
+    pub fn clear(&mut self) {
+        self.sensors.clear();
+        self.policies.clear();
+    }
+
+    pub fn policy(&self, zone: &str) -> Option<&AlarmPolicy> {
+        self.policies
+            .iter()
+            .find(|(name, _)| *name == zone)
+            .map(|(_, policy)| policy)
+    }
+
+    /// The statistics of the zone over the sensors with a known temperature.
+    pub fn statistics(
+        &self,
+        zone: &str,
+        temperature: impl Fn(&Address) -> Option<f32>,
+    ) -> Option<Statistics> {
+        Statistics::new(self.zone(zone).filter_map(temperature))
+    }
+
+    /// Checks the zone against its alarm policy.
+    pub fn alarm(
+        &self,
+        zone: &str,
+        temperature: impl Fn(&Address) -> Option<f32>,
+    ) -> Option<ZoneAlarm> {
+        let policy = self.policy(zone)?;
+        let statistics = self.statistics(zone, temperature)?;
+        policy.check(&statistics)
+    }
+}
+
+/// Registered sensor
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sensor {
+    pub zone: Option<String>,
+    /// Unique label
+    pub label: Option<String>,
+}
+
+/// The outcome of [`Registry::sync`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Presence {
+    /// Sensors found for the first time, now registered.
+    pub added: Vec<Address>,
+    /// Registered sensors missing from the bus.
+    pub missing: Vec<Address>,
+}
+
+/// Handling of an address added twice
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Merge {
+    #[default]
+    Reject,
+    /// Keeps the settings of the known sensor, taking only the unset ones.
+    Keep,
+    /// Replaces the settings of the known sensor.
+    Replace,
+}
+
+/// Zone alarm policy
+///
+/// The zone is in alarm when its hottest sensor is above `high` or its
+/// coldest sensor is below `low`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AlarmPolicy {
+    pub low: Option<f32>,
+    pub high: Option<f32>,
+}
+
+impl AlarmPolicy {
+    pub fn check(&self, statistics: &Statistics) -> Option<ZoneAlarm> {
+        if let Some(high) = self.high.filter(|high| statistics.max > *high) {
+            return Some(ZoneAlarm::High {
+                temperature: statistics.max,
+                limit: high,
+            });
+        }
+        if let Some(low) = self.low.filter(|low| statistics.min < *low) {
+            return Some(ZoneAlarm::Low {
+                temperature: statistics.min,
+                limit: low,
+            });
+        }
+        None
+    }
+}
+
+/// Zone alarm
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZoneAlarm {
+    High { temperature: f32, limit: f32 },
+    Low { temperature: f32, limit: f32 },
+}
+
+/// Temperature statistics
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Statistics {
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+}
+
+impl Statistics {
+    /// Returns `None` for no temperatures.
+    pub fn new(temperatures: impl IntoIterator<Item = f32>) -> Option<Self> {
+        let mut temperatures = temperatures.into_iter();
+        let first = temperatures.next()?;
+        let mut statistics = Self {
+            count: 1,
+            min: first,
+            max: first,
+            mean: first,
+        };
+        let mut sum = first;
+        for temperature in temperatures {
+            statistics.count += 1;
+            statistics.min = statistics.min.min(temperature);
+            statistics.max = statistics.max.max(temperature);
+            sum += temperature;
+        }
+        statistics.mean = sum / statistics.count as f32;
+        Some(statistics)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+        registry.set_zone(Address(1), "tank").unwrap();
+        registry.set_zone(Address(2), "tank").unwrap();
+        registry.set_zone(Address(3), "room").unwrap();
+        registry.insert(Address(4)).unwrap();
+        registry
+    }
+
+    fn temperature(address: &Address) -> Option<f32> {
+        match address.0 {
+            1 => Some(60.0),
+            2 => Some(50.0),
+            3 => Some(21.0),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn zone() {
+        let registry = registry();
+        assert_eq!(registry.len(), 4);
+        assert_eq!(
+            registry.zone("tank").copied().collect::<Vec<_>>(),
+            [Address(1), Address(2)],
+        );
+        assert_eq!(registry.zone("rack").count(), 0);
+        assert_eq!(registry.zones().collect::<Vec<_>>(), ["tank", "room"]);
+        assert_eq!(registry.get(&Address(4)), Some(&Sensor::default()));
+    }
+
+    #[test]
+    fn add() {
+        let mut registry = registry();
+        let boiler = Sensor {
+            zone: Some("room".into()),
+            label: Some("boiler".into()),
+        };
+        assert_eq!(
+            registry.add(Address(1), boiler.clone()),
+            Err(Error::DuplicateAddress(Address(1)))
+        );
+        registry.add(Address(5), boiler.clone()).unwrap();
+        assert_eq!(
+            registry.add(Address(6), boiler.clone()),
+            Err(Error::DuplicateLabel {
+                address: Address(5)
+            })
+        );
+        assert_eq!(
+            registry.set_label(Address(4), "boiler"),
+            Err(Error::DuplicateLabel {
+                address: Address(5)
+            })
+        );
+        assert_eq!(registry.find("boiler"), Some(&Address(5)));
+
+        let mut registry = registry.merge(Merge::Keep);
+        registry.add(Address(1), boiler.clone()).unwrap_err();
+        let sensor = Sensor {
+            label: Some("hot".into()),
+            ..boiler.clone()
+        };
+        assert_eq!(
+            registry.add(Address(1), sensor.clone()).cloned(),
+            Ok(Sensor {
+                zone: Some("tank".into()),
+                label: Some("hot".into()),
+            })
+        );
+        let mut registry = registry.merge(Merge::Replace);
+        assert_eq!(
+            registry.add(Address(1), sensor.clone()).cloned(),
+            Ok(sensor)
+        );
+    }
+
+    #[test]
+    fn sync() {
+        let mut registry = registry();
+        registry.set_label(Address(1), "boiler").unwrap();
+        let presence = registry
+            .sync(&[Address(2), Address(5), Address(1)])
+            .unwrap();
+        assert_eq!(presence.added, [Address(5)]);
+        assert_eq!(presence.missing, [Address(3), Address(4)]);
+        assert_eq!(registry.len(), 5);
+        assert_eq!(registry.find("boiler"), Some(&Address(1)));
+
+        let presence = registry.sync(&[]).unwrap();
+        assert!(presence.added.is_empty());
+        assert_eq!(presence.missing.len(), 5);
+        // Missing sensors keep their settings.
+        assert_eq!(registry.find("boiler"), Some(&Address(1)));
+
+        let mut registry = Registry::new().capacity::<2>();
+        registry.insert(Address(1)).unwrap();
+        assert_eq!(
+            registry.sync(&[Address(2), Address(3)]),
+            Err(Error::Capacity(2))
+        );
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn statistics() {
+        let registry = registry();
+        assert_eq!(
+            registry.statistics("tank", temperature),
+            Some(Statistics {
+                count: 2,
+                min: 50.0,
+                max: 60.0,
+                mean: 55.0,
+            }),
+        );
+        assert_eq!(registry.statistics("rack", temperature), None);
+        assert_eq!(registry.statistics("room", |_| None), None);
+    }
+
+    #[test]
+    fn alarm() {
+        let mut registry = registry();
+        assert_eq!(registry.alarm("tank", temperature), None);
+        registry
+            .set_policy(
+                "tank",
+                AlarmPolicy {
+                    low: Some(55.0),
+                    high: Some(65.0),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            registry.alarm("tank", temperature),
+            Some(ZoneAlarm::Low {
+                temperature: 50.0,
+                limit: 55.0,
+            }),
+        );
+        registry
+            .set_policy(
+                "tank",
+                AlarmPolicy {
+                    low: None,
+                    high: Some(55.0),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            registry.alarm("tank", temperature),
+            Some(ZoneAlarm::High {
+                temperature: 60.0,
+                limit: 55.0,
+            }),
+        );
+    }
+
+    #[test]
+    fn restore() {
+        let mut store = alloc::collections::BTreeMap::new();
+        let mut registry = registry();
+        registry.set_label(Address(3), "living room").unwrap();
+        registry.shutdown(&mut store).unwrap();
+        let mut restored = Registry::new();
+        restored.insert(Address(5)).unwrap();
+        restored.restore(&mut store).unwrap();
+        assert_eq!(
+            restored.iter().collect::<Vec<_>>(),
+            registry.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(restored.find("living room"), Some(&Address(3)));
+        // Truncated
+        store.get_mut("registry").unwrap().pop();
+        assert!(matches!(
+            restored.restore(&mut store),
+            Err(Error::Decode { .. })
+        ));
+        assert_eq!(restored.len(), 4);
+    }
+}
@@ -0,0 +1,500 @@
+//! Remote commands
+//!
+//! The command side of the telemetry: requests arriving e.g. over MQTT are
+//! parsed, authenticated, executed and acknowledged. The crate doesn't own
+//! the client, it maps topics and payloads:
+//!
+//! ```ignore
+//! let mut remote = Remote::new("thermometer", "boiler-room", TOKEN)?;
+//! remote.restore(&mut store)?;
+//! client.subscribe(&remote.subscription(), QoS::AtLeastOnce)?;
+//! // ... on a message:
+//! let Ok(request) = remote.parse(topic, payload, &mut store) else {
+//!     return; // Foreign, malformed or unauthenticated.
+//! };
+//! let outcome = remote.execute(&request.command, sampler.driver(), &mut alarms, &mut calibration);
+//! let ack = remote.ack(&request, &outcome);
+//! client.publish(&ack.topic, QoS::AtLeastOnce, false, ack.payload.as_bytes())?;
+//! ```
+//!
+//! The commands are published to `{prefix}/{device}/cmd/{command}`, with a
+//! payload of space-separated `key=value` pairs carrying a counter and an
+//! optional correlation id, and ending in the `mac`: the hex HMAC-SHA-256,
+//! keyed with the shared token, of the topic, a newline and the payload
+//! before ` mac=` ([`Remote::sign`]):
+//!
+//! ```text
+//! thermometer/boiler-room/cmd/set-resolution
+//! counter=17 id=42 address=230000046eafbc28 bits=10 mac=5c1f…
+//! ```
+//!
+//! The counter must increase from request to request, a request with a
+//! counter not above the last accepted one is rejected as a replay. The
+//! counter is saved before a request is accepted and restored with
+//! [`Remote::restore`], so a restart doesn't reopen the accepted requests to
+//! replays; a request is rejected if its counter can't be saved. A signed
+//! request that was never accepted, e.g. one held back by the broker, stays
+//! valid as long as no later counter is accepted.
+//!
+//! The acknowledgment is published to `{prefix}/{device}/ack/{command}`:
+//! `id=42 ok`, `id=42 ok found=3` or `id=42 error="device not found"`.
+//!
+//! | Command            | Keys                                 |
+//! |--------------------|--------------------------------------|
+//! | `rescan`           |                                      |
+//! | `set-resolution`   | `address`, `bits` (9 to 12)          |
+//! | `set-alarm`        | `address`, `low` and `high` (°C)     |
+//! | `calibrate-offset` | `address`, `offset` (°C)             |
+//! | `identify`         | `address`                            |
+//!
+//! An alarm limit left out is cleared.
+
+#[cfg(feature = "esp-idf")]
+use crate::{Ds18b20Driver, WaitStrategy, alarm::Alarms, pipeline::Calibration};
+use crate::{
+    address::{Address, Validation},
+    alarm::Limits,
+    error::{Error, Result},
+    persistence::Store,
+    scratchpad::Resolution,
+    sha256::{self, hmac},
+};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Remote command
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Rescan,
+    SetResolution {
+        address: Address,
+        resolution: Resolution,
+    },
+    SetAlarm {
+        address: Address,
+        limits: Limits,
+    },
+    CalibrateOffset {
+        address: Address,
+        offset: f32,
+    },
+    Identify {
+        address: Address,
+    },
+}
+
+impl Command {
+    /// The name in the topic.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Command::Rescan => "rescan",
+            Command::SetResolution { .. } => "set-resolution",
+            Command::SetAlarm { .. } => "set-alarm",
+            Command::CalibrateOffset { .. } => "calibrate-offset",
+            Command::Identify { .. } => "identify",
+        }
+    }
+}
+
+/// Authenticated request
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    /// The correlation id, echoed in the acknowledgment.
+    pub id: Option<String>,
+    pub command: Command,
+}
+
+/// The result of an executed command
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Done,
+    /// The sensors found by the rescan.
+    Rescanned(Vec<Address>),
+}
+
+/// Acknowledgment to publish
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ack {
+    pub topic: String,
+    pub payload: String,
+}
+
+/// Remote command channel of a device
+#[derive(Clone, Debug)]
+pub struct Remote {
+    prefix: String,
+    device: String,
+    token: String,
+    /// The counter of the last accepted request.
+    counter: Option<u64>,
+}
+
+impl Remote {
+    /// Fails with [`Error::Unauthorized`] if the token is empty.
+    pub fn new(
+        prefix: impl Into<String>,
+        device: impl Into<String>,
+        token: impl Into<String>,
+    ) -> Result<Self> {
+        let token = token.into();
+        if token.is_empty() {
+            return Err(Error::Unauthorized);
+        }
+        Ok(Self {
+            prefix: prefix.into(),
+            device: device.into(),
+            token,
+            counter: None,
+        })
+    }
+
+    /// Appends the `mac` of the command to the payload, e.g. in a host tool
+    /// sharing the token.
+    pub fn sign(&self, topic: &str, payload: &str) -> String {
+        let mac = hmac(
+            self.token.as_bytes(),
+            &[topic.as_bytes(), b"\n", payload.as_bytes()],
+        );
+        let mut signed = format!("{payload} mac=");
+        for byte in mac {
+            signed.push_str(&format!("{byte:02x}"));
+        }
+        signed
+    }
+
+    /// Restores the last accepted counter saved by [`parse`](Self::parse),
+    /// if any.
+    pub fn restore(&mut self, store: &mut dyn Store) -> Result<()> {
+        let Some(value) = store.load("remote")? else {
+            return Ok(());
+        };
+        let counter = value.try_into().map_err(|_| Error::Decode { offset: 0 })?;
+        self.counter = Some(u64::from_le_bytes(counter));
+        Ok(())
+    }
+
+    /// The topic filter of the commands.
+    pub fn subscription(&self) -> String {
+        format!("{}/{}/cmd/+", self.prefix, self.device)
+    }
+
+    /// Parses the message and saves its counter under `remote`,
+    /// little-endian. Fails with [`Error::Request`] if the topic isn't a
+    /// command of this device or the payload is malformed, with
+    /// [`Error::Unauthorized`] if the `mac` doesn't match or the counter
+    /// doesn't increase, and with the error of the store if the counter
+    /// can't be saved.
+    pub fn parse(&mut self, topic: &str, payload: &[u8], store: &mut dyn Store) -> Result<Request> {
+        let name = topic
+            .strip_prefix(self.prefix.as_str())
+            .and_then(|topic| topic.strip_prefix('/'))
+            .and_then(|topic| topic.strip_prefix(self.device.as_str()))
+            .and_then(|topic| topic.strip_prefix("/cmd/"))
+            .ok_or(Error::Request)?;
+        let payload = core::str::from_utf8(payload).map_err(|_| Error::Request)?;
+        // Checked before anything else is interpreted.
+        let (payload, mac) = payload.rsplit_once(" mac=").ok_or(Error::Unauthorized)?;
+        let expected = hmac(
+            self.token.as_bytes(),
+            &[topic.as_bytes(), b"\n", payload.as_bytes()],
+        );
+        if !sha256::verify(&decode(mac.trim_end()), &expected) {
+            return Err(Error::Unauthorized);
+        }
+        let mut pairs = Vec::new();
+        for pair in payload.split_whitespace() {
+            pairs.push(pair.split_once('=').ok_or(Error::Request)?);
+        }
+        let get = |key| {
+            pairs
+                .iter()
+                .find_map(|&(k, value)| (k == key).then_some(value))
+        };
+        let counter: u64 = get("counter")
+            .and_then(|counter| counter.parse().ok())
+            .ok_or(Error::Request)?;
+        if self.counter.is_some_and(|last| counter <= last) {
+            return Err(Error::Unauthorized);
+        }
+        store.save("remote", &counter.to_le_bytes())?;
+        self.counter = Some(counter);
+        let address = || {
+            Address::from_hex(get("address").ok_or(Error::Request)?, Validation::Strict)
+                .map_err(|_| Error::Request)
+        };
+        let number = |key| -> Result<Option<f32>> {
+            get(key)
+                .map(|value| value.parse().ok().filter(|value: &f32| value.is_finite()))
+                .map(|value| value.ok_or(Error::Request))
+                .transpose()
+        };
+        let command = match name {
+            "rescan" => Command::Rescan,
+            "set-resolution" => Command::SetResolution {
+                address: address()?,
+                resolution: get("bits")
+                    .and_then(|bits| bits.parse().ok())
+                    .and_then(Resolution::from_bits)
+                    .ok_or(Error::Request)?,
+            },
+            "set-alarm" => Command::SetAlarm {
+                address: address()?,
+                limits: Limits {
+                    low: number("low")?,
+                    high: number("high")?,
+                },
+            },
+            "calibrate-offset" => Command::CalibrateOffset {
+                address: address()?,
+                offset: number("offset")?.ok_or(Error::Request)?,
+            },
+            "identify" => Command::Identify {
+                address: address()?,
+            },
+            _ => return Err(Error::Request),
+        };
+        Ok(Request {
+            id: get("id").map(ToString::to_string),
+            command,
+        })
+    }
+
+    /// The acknowledgment of the request.
+    pub fn ack(&self, request: &Request, outcome: &Result<Outcome>) -> Ack {
+        let mut payload = String::new();
+        if let Some(id) = &request.id {
+            payload = format!("id={id} ");
+        }
+        match outcome {
+            Ok(Outcome::Done) => payload.push_str("ok"),
+            Ok(Outcome::Rescanned(addresses)) => {
+                payload.push_str(&format!("ok found={}", addresses.len()))
+            }
+            Err(error) => payload.push_str(&format!("error={:?}", error.to_string())),
+        }
+        Ack {
+            topic: format!(
+                "{}/{}/ack/{}",
+                self.prefix,
+                self.device,
+                request.command.name()
+            ),
+            payload,
+        }
+    }
+
+    /// Executes the command. Identify blinks the sensor with back-to-back
+    /// conversions, visible on a scope or a current probe.
+    #[cfg(feature = "esp-idf")]
+    pub fn execute<const N: usize>(
+        &self,
+        command: &Command,
+        driver: &mut Ds18b20Driver,
+        alarms: &mut Alarms<N>,
+        calibration: &mut Calibration<N>,
+    ) -> Result<Outcome> {
+        match *command {
+            Command::Rescan => return Ok(Outcome::Rescanned(driver.scan()?)),
+            Command::SetResolution {
+                address,
+                resolution,
+            } => driver.set_resolution(&address, resolution)?,
+            Command::SetAlarm { address, limits } => {
+                alarms.set_limits(address, limits)?;
+            }
+            Command::CalibrateOffset { address, offset } => {
+                calibration.set_offset(address, offset)?;
+            }
+            Command::Identify { address } => {
+                let mut blinks = 1;
+                driver.identify(&address, WaitStrategy::Block, |_| {
+                    blinks += 1;
+                    blinks < BLINKS
+                })?;
+            }
+        }
+        Ok(Outcome::Done)
+    }
+}
+
+/// The number of conversions identifying a sensor.
+#[cfg(feature = "esp-idf")]
+const BLINKS: usize = 5;
+
+/// Decodes the hex digits, empty if they aren't.
+fn decode(hex: &str) -> Vec<u8> {
+    if !hex.len().is_multiple_of(2) {
+        return Vec::new();
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect::<Option<_>>()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BOILER: Address = Address(0x2300_0004_6EAF_BC28);
+
+    fn remote() -> Remote {
+        Remote::new("thermometer", "boiler-room", "s3cret").unwrap()
+    }
+
+    const TOPIC: &str = "thermometer/boiler-room/cmd/";
+
+    type Memory = alloc::collections::BTreeMap<String, Vec<u8>>;
+
+    fn send(
+        remote: &mut Remote,
+        store: &mut Memory,
+        command: &str,
+        payload: &str,
+    ) -> Result<Request> {
+        let topic = format!("{TOPIC}{command}");
+        let signed = remote.sign(&topic, payload);
+        remote.parse(&topic, signed.as_bytes(), store)
+    }
+
+    #[test]
+    fn parse() {
+        let mut remote = remote();
+        let mut store = Memory::new();
+        assert_eq!(remote.subscription(), "thermometer/boiler-room/cmd/+");
+        assert_eq!(
+            send(
+                &mut remote,
+                &mut store,
+                "set-resolution",
+                "counter=1 id=42 address=230000046eafbc28 bits=10"
+            ),
+            Ok(Request {
+                id: Some("42".into()),
+                command: Command::SetResolution {
+                    address: BOILER,
+                    resolution: Resolution::Ten,
+                },
+            })
+        );
+        assert_eq!(
+            send(
+                &mut remote,
+                &mut store,
+                "set-alarm",
+                "address=230000046eafbc28 high=80 counter=2"
+            )
+            .map(|request| request.command),
+            Ok(Command::SetAlarm {
+                address: BOILER,
+                limits: Limits {
+                    low: None,
+                    high: Some(80.0),
+                },
+            })
+        );
+        assert_eq!(
+            send(
+                &mut remote,
+                &mut store,
+                "calibrate-offset",
+                "counter=3 address=230000046eafbc28 offset=NaN"
+            ),
+            Err(Error::Request)
+        );
+        assert_eq!(
+            send(&mut remote, &mut store, "reboot", "counter=4"),
+            Err(Error::Request)
+        );
+        assert_eq!(
+            send(&mut remote, &mut store, "rescan", ""),
+            Err(Error::Request)
+        );
+        assert_eq!(
+            remote.parse("thermometer/kitchen/cmd/rescan", b"counter=5", &mut store),
+            Err(Error::Request)
+        );
+    }
+
+    #[test]
+    fn authentication() {
+        assert_eq!(
+            Remote::new("thermometer", "boiler-room", "").map(|_| ()),
+            Err(Error::Unauthorized)
+        );
+        let mut remote = remote();
+        let mut store = Memory::new();
+        let topic = format!("{TOPIC}rescan");
+        let signed = remote.sign(&topic, "counter=7");
+        // Another token, topic or payload.
+        let other = Remote::new("thermometer", "boiler-room", "s3cre").unwrap();
+        let forged = other.sign(&topic, "counter=7");
+        assert_eq!(
+            remote.parse(&topic, forged.as_bytes(), &mut store),
+            Err(Error::Unauthorized)
+        );
+        let moved = format!("{TOPIC}identify");
+        assert_eq!(
+            remote.parse(&moved, signed.as_bytes(), &mut store),
+            Err(Error::Unauthorized)
+        );
+        let tampered = signed.replace("counter=7", "counter=8");
+        assert_eq!(
+            remote.parse(&topic, tampered.as_bytes(), &mut store),
+            Err(Error::Unauthorized)
+        );
+        assert_eq!(
+            remote.parse(&topic, b"counter=7", &mut store),
+            Err(Error::Unauthorized)
+        );
+        assert_eq!(
+            remote.parse(&topic, b"counter=7 mac=00", &mut store),
+            Err(Error::Unauthorized)
+        );
+        // Replays and stale counters.
+        assert!(remote.parse(&topic, signed.as_bytes(), &mut store).is_ok());
+        assert_eq!(
+            remote.parse(&topic, signed.as_bytes(), &mut store),
+            Err(Error::Unauthorized)
+        );
+        assert_eq!(
+            send(&mut remote, &mut store, "rescan", "counter=6"),
+            Err(Error::Unauthorized)
+        );
+        assert!(send(&mut remote, &mut store, "rescan", "counter=8").is_ok());
+
+        // The counter survives a restart.
+        let mut restarted = self::remote();
+        restarted.restore(&mut store).unwrap();
+        assert_eq!(
+            send(&mut restarted, &mut store, "rescan", "counter=8"),
+            Err(Error::Unauthorized)
+        );
+        assert!(send(&mut restarted, &mut store, "rescan", "counter=9").is_ok());
+    }
+
+    #[test]
+    fn ack() {
+        let remote = remote();
+        let mut request = Request {
+            id: Some("42".into()),
+            command: Command::Rescan,
+        };
+        assert_eq!(
+            remote.ack(&request, &Ok(Outcome::Rescanned(vec![BOILER]))),
+            Ack {
+                topic: "thermometer/boiler-room/ack/rescan".into(),
+                payload: "id=42 ok found=1".into(),
+            }
+        );
+        request.id = None;
+        assert_eq!(
+            remote.ack(&request, &Err(Error::DeviceNotFound)).payload,
+            "error=\"device not found\""
+        );
+    }
+}
@@ -0,0 +1,767 @@
+//! Periodic sampler
+//!
+//! The sampler converts all its sensors at once every interval and passes the
+//! readings through its [`Pipeline`]. [`Sampler::poll`] never blocks on a
+//! conversion, so it can be called from the main loop:
+//!
+//! ```ignore
+//! let mut sampler = Sampler::new(driver, addresses, Duration::from_secs(10))
+//!     .pipeline(Pipeline::new().stage(History::new()));
+//! loop {
+//!     if let Some(readings) = sampler.poll()? {
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! Around OTA updates or radio-heavy phases, where 1-Wire timing gets
+//! unreliable, the sampler can be paused. The schedule and the pipeline state
+//! are kept.
+//!
+//! Fleets of devices sampling on the same interval would otherwise all
+//! publish in the same instant. The schedule can be shifted by a phase and
+//! each sample delayed by a bounded random jitter, and the readings of single
+//! sensors can be released with an offset after the conversion:
+//!
+//! ```ignore
+//! let mut sampler = Sampler::new(driver, addresses, Duration::from_secs(60))
+//!     .phase(Duration::from_secs(7))
+//!     .jitter(Duration::from_secs(2));
+//! sampler.set_offset(boiler, Duration::from_millis(500))?;
+//! ```
+//!
+//! The readings are delivered to the [`Sink`]s according to their
+//! [`QoS`](crate::sink::QoS). At high sampling rates
+//! [`poll_into`](Sampler::poll_into) reuses the caller's buffer, so the hot
+//! path doesn't allocate:
+//!
+//! ```ignore
+//! let mut readings = Vec::with_capacity(addresses.len());
+//! loop {
+//!     if sampler.poll_into(&mut readings)? {
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! The schedule survives a short reboot: [`shutdown`](Sampler::shutdown)
+//! persists the wall-clock time of the next sample and
+//! [`restore`](Sampler::restore) resumes the same cadence and phase, missed
+//! samples skipped, instead of starting over on the first poll. With the
+//! clocks synchronized over SNTP, nodes sampling in step stay in step:
+//!
+//! ```ignore
+//! let mut sampler = Sampler::new(driver, addresses, Duration::from_secs(60));
+//! sampler.restore(&mut nvs)?;
+//! ```
+//!
+//! EEPROM commits queued in [`commits`](Sampler::commits) run between the
+//! samples, as many as fit before the next one, so a configuration change
+//! doesn't delay a sample.
+//!
+//! [`SimulatedSensor`]s are sampled alongside the real sensors, their
+//! readings follow those of the bus.
+//!
+//! Sensors failing in a row can be skipped for a while, see
+//! [`Quarantine`].
+//!
+//! Sensors plugged in or out at runtime are picked up by
+//! [`rescan`](Sampler::rescan), which keeps the [`Registry`] up to date:
+//!
+//! ```ignore
+//! let presence = sampler.rescan(&mut registry)?;
+//! for address in presence.missing {
+//!     // ...
+//! }
+//! ```
+//!
+//! The sampler reaches the bus through a [`Transport`], the
+//! [`Ds18b20Driver`](crate::Ds18b20Driver) on the device. Host tests put a
+//! scripted bus behind it.
+
+#[cfg(feature = "esp-idf")]
+use crate::{Ds18b20Driver, driver::preflight};
+use crate::{
+    Error, Result,
+    address::Address,
+    backoff::{Entropy, Xorshift},
+    cancellation::Cancellation,
+    collections::Map,
+    commit::CommitQueue,
+    config::{Change, Config, Sampling},
+    logging::{Subsystem, log},
+    persistence::Store,
+    pipeline::{Pipeline, Reading, Stage},
+    quarantine::Quarantine,
+    registry::{Presence, Registry},
+    scratchpad::Scratchpad,
+    simulation::SimulatedSensor,
+    sink::{Outlet, Sink, SinkStats},
+    timing,
+};
+use log::Level;
+use std::{
+    cmp::Reverse,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The store key of the schedule.
+const SCHEDULE: &str = "schedule";
+
+/// Sampler state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    /// Waiting for the next sample.
+    Idle,
+    /// Waiting for the conversion to complete and the offsets of the
+    /// sensors to pass.
+    Converting { ready_at: Instant },
+    /// Suspended, no bus activity.
+    Paused { since: Instant },
+}
+
+/// Bus access of the sampler
+pub trait Transport {
+    /// Starts the conversion of all sensors at once.
+    fn start_conversion(&mut self) -> Result<()>;
+
+    /// Reads the scratchpad of the sensor, retries included.
+    fn read_scratchpad(&mut self, address: &Address) -> Result<Scratchpad>;
+
+    /// Commits the scratchpad of the sensor to EEPROM.
+    fn commit(&mut self, address: &Address) -> Result<()>;
+
+    /// Depowers the sensors until the next transaction.
+    fn power_down(&mut self) -> Result<()>;
+
+    /// Searches the bus for the sensors present.
+    fn scan(&mut self) -> Result<Vec<Address>>;
+
+    /// The token interrupting the blocking waits.
+    fn cancellation(&self) -> Cancellation;
+
+    /// The time the conversion takes.
+    fn conversion_time(&self) -> Duration {
+        timing::CONVERSION
+    }
+}
+
+#[cfg(feature = "esp-idf")]
+impl Transport for Ds18b20Driver<'_> {
+    fn start_conversion(&mut self) -> Result<()> {
+        self.initialization()?.skip_rom()?.start_conversion()
+    }
+
+    fn read_scratchpad(&mut self, address: &Address) -> Result<Scratchpad> {
+        preflight(address)?;
+        self.retry(|this| this.initialization()?.match_rom(address)?.read_scratchpad())
+    }
+
+    fn commit(&mut self, address: &Address) -> Result<()> {
+        Ds18b20Driver::commit(self, address)
+    }
+
+    fn power_down(&mut self) -> Result<()> {
+        Ds18b20Driver::power_down(self)
+    }
+
+    fn scan(&mut self) -> Result<Vec<Address>> {
+        self.scan_iter()?.collect()
+    }
+
+    fn cancellation(&self) -> Cancellation {
+        self.cancellation.clone()
+    }
+}
+
+/// Sampler
+pub struct Sampler<T: Transport> {
+    driver: T,
+    addresses: Vec<Address>,
+    interval: Duration,
+    pipeline: Pipeline,
+    state: State,
+    next: Instant,
+    offsets: Map<Address, Duration>,
+    pending: Vec<(Duration, Address)>,
+    jitter: Duration,
+    delay: Duration,
+    entropy: Xorshift,
+    simulated: Vec<SimulatedSensor>,
+    started: Instant,
+    sinks: Vec<Outlet>,
+    /// The fresh readings of a sample, lent to the sinks.
+    scratch: Vec<Reading>,
+    depower: bool,
+    commits: CommitQueue,
+    quarantine: Option<Quarantine>,
+}
+
+impl<T: Transport> Sampler<T> {
+    /// The first sample is taken on the first poll.
+    pub fn new(driver: T, addresses: Vec<Address>, interval: Duration) -> Self {
+        Self {
+            driver,
+            interval,
+            pipeline: Pipeline::new(),
+            state: State::Idle,
+            next: Instant::now(),
+            offsets: Map::new(),
+            pending: Vec::new(),
+            jitter: Duration::ZERO,
+            delay: Duration::ZERO,
+            // Seeded per device, so a fleet doesn't jitter in lockstep.
+            entropy: Xorshift::new(addresses.iter().fold(0x9E37_79B9, |seed, address| {
+                seed ^ address.0 as u32 ^ (address.0 >> 32) as u32
+            })),
+            addresses,
+            simulated: Vec::new(),
+            started: Instant::now(),
+            sinks: Vec::new(),
+            scratch: Vec::new(),
+            depower: false,
+            commits: CommitQueue::default(),
+            quarantine: None,
+        }
+    }
+
+    /// Skips the sensors failing in a row for a while.
+    pub fn quarantine(mut self, quarantine: Quarantine) -> Self {
+        self.quarantine = Some(quarantine);
+        self
+    }
+
+    /// The quarantined sensors.
+    pub fn quarantined(&self) -> impl Iterator<Item = &Address> {
+        self.quarantine.iter().flat_map(Quarantine::iter)
+    }
+
+    /// Adds the simulated sensor.
+    pub fn simulate(mut self, sensor: SimulatedSensor) -> Self {
+        self.simulated.push(sensor);
+        self
+    }
+
+    /// Adds the sink.
+    pub fn sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sinks.push(Outlet::new(sink));
+        self
+    }
+
+    /// The delivery accounting of the sink, in the order they were added.
+    pub fn sink_stats(&self, index: usize) -> Option<SinkStats> {
+        self.sinks.get(index).map(Outlet::stats)
+    }
+
+    /// Depowers the sensors between the samples, see
+    /// [`BusPower`](crate::power::BusPower). The rail is powered up before
+    /// the next conversion.
+    pub fn depower(mut self, depower: bool) -> Self {
+        self.depower = depower;
+        self
+    }
+
+    /// Shifts the schedule, the first sample is taken after the phase.
+    pub fn phase(mut self, phase: Duration) -> Self {
+        self.next = Instant::now() + phase;
+        self
+    }
+
+    /// Delays each sample by a random time up to the jitter. The schedule
+    /// itself doesn't drift.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the time the reading of the sensor is released after the
+    /// conversion, returning the previous one. The next sample isn't started
+    /// before all readings are released.
+    pub fn set_offset(&mut self, address: Address, offset: Duration) -> Result<Option<Duration>> {
+        self.offsets.insert(address, offset)
+    }
+
+    /// Captures the sampling schedule and the release offsets of the
+    /// configured sensors into the configuration.
+    pub fn capture(&self, config: &mut Config) {
+        config.sampling = Some(Sampling {
+            interval: self.interval,
+            jitter: self.jitter,
+        });
+        for sensor in &mut config.sensors {
+            sensor.release = self.offsets.get(&sensor.address).copied();
+        }
+    }
+
+    /// Applies the sampling schedule and the release offsets of the
+    /// configuration, from the next sample on. Nothing is changed on
+    /// failure.
+    pub fn apply(&mut self, config: &Config) -> Result<()> {
+        let mut offsets = Map::new();
+        for sensor in &config.sensors {
+            if let Some(release) = sensor.release {
+                offsets.insert(sensor.address, release)?;
+            }
+        }
+        self.offsets = offsets;
+        if let Some(sampling) = config.sampling {
+            self.interval = sampling.interval;
+            self.jitter = sampling.jitter;
+        }
+        Ok(())
+    }
+
+    /// Applies the sampling schedule and the release offsets of the
+    /// configuration the changes name, leaving the others as they are.
+    /// Nothing is changed on failure.
+    pub fn apply_changes(&mut self, config: &Config, changes: &[Change]) -> Result<()> {
+        let mut offsets = self.offsets.clone();
+        let mut schedule = None;
+        for change in changes {
+            match change {
+                Change::Sampling(sampling) => schedule = *sampling,
+                Change::Added(address) | Change::Release(address) => {
+                    match config.sensor(address).and_then(|sensor| sensor.release) {
+                        Some(release) => {
+                            offsets.insert(*address, release)?;
+                        }
+                        None => {
+                            offsets.remove(address);
+                        }
+                    }
+                }
+                Change::Removed(address) => {
+                    offsets.remove(address);
+                }
+                _ => {}
+            }
+        }
+        self.offsets = offsets;
+        if let Some(sampling) = schedule {
+            self.interval = sampling.interval;
+            self.jitter = sampling.jitter;
+        }
+        Ok(())
+    }
+
+    /// Sets the pipeline the readings pass through.
+    pub fn pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    pub fn driver(&mut self) -> &mut T {
+        &mut self.driver
+    }
+
+    /// The EEPROM commits run while the sampler is idle.
+    pub fn commits(&mut self) -> &mut CommitQueue {
+        &mut self.commits
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Searches the bus and samples the sensors present from the next sample
+    /// on, registering the new ones. Unplugged sensors keep their settings in
+    /// the registry and are sampled again once they are back. Fails with
+    /// [`Error::ConversionPending`] while converting.
+    pub fn rescan<const N: usize>(&mut self, registry: &mut Registry<N>) -> Result<Presence> {
+        if let State::Converting { .. } = self.state {
+            return Err(Error::ConversionPending);
+        }
+        let found = self.driver.scan()?;
+        let presence = registry.sync(&found)?;
+        log!(
+            Subsystem::Sampler,
+            Level::Info,
+            "Rescanned {} sensors, {} added, {} missing",
+            found.len(),
+            presence.added.len(),
+            presence.missing.len(),
+        );
+        self.addresses = found;
+        Ok(presence)
+    }
+
+    /// Starts a conversion when a sample is due and collects the readings
+    /// once it has completed and their offsets have passed. Returns `None`
+    /// while there is nothing to collect.
+    ///
+    /// Failures of a single sensor are reported in its reading, readings
+    /// dropped by the pipeline are left out.
+    pub fn poll(&mut self) -> Result<Option<Vec<Result<Reading>>>> {
+        let mut readings = Vec::new();
+        Ok(self.poll_into(&mut readings)?.then_some(readings))
+    }
+
+    /// Polls like [`poll`](Self::poll), but replaces the contents of the
+    /// buffer with the collected readings. Returns whether there were any
+    /// to collect.
+    pub fn poll_into(&mut self, readings: &mut Vec<Result<Reading>>) -> Result<bool> {
+        let now = Instant::now();
+        match self.state {
+            State::Paused { .. } => Ok(false),
+            State::Idle if now < self.next + self.delay => {
+                let driver = &mut self.driver;
+                let run = self.commits.run(self.next + self.delay - now, |address| {
+                    driver.commit(address)
+                });
+                if run > 0
+                    && self.depower
+                    && let Err(error) = self.driver.power_down()
+                {
+                    log!(
+                        Subsystem::Sampler,
+                        Level::Warn,
+                        "Power-down failed: {error}"
+                    );
+                }
+                Ok(false)
+            }
+            State::Idle => {
+                // Simulated sensors only, there may be no bus.
+                if !self.addresses.is_empty() {
+                    self.driver.start_conversion()?;
+                }
+                let conversion = self.driver.conversion_time();
+                self.state = State::Converting {
+                    ready_at: now + conversion,
+                };
+                // Skip missed samples rather than catching up.
+                while self.next <= now {
+                    self.next += self.interval.max(conversion);
+                }
+                self.delay = jitter(&mut self.entropy, self.jitter);
+                self.pending.clear();
+                let quarantine = &mut self.quarantine;
+                self.pending.extend(
+                    self.addresses
+                        .iter()
+                        .filter(|address| {
+                            !quarantine
+                                .as_mut()
+                                .is_some_and(|quarantine| quarantine.skip(address))
+                        })
+                        .map(|address| {
+                            (
+                                self.offsets.get(address).copied().unwrap_or_default(),
+                                *address,
+                            )
+                        }),
+                );
+                // Released from the back.
+                self.pending.sort_by_key(|&(offset, _)| Reverse(offset));
+                Ok(false)
+            }
+            State::Converting { ready_at } if now < ready_at => Ok(false),
+            State::Converting { ready_at } => {
+                let elapsed = now - ready_at;
+                let due = self
+                    .pending
+                    .iter()
+                    .rev()
+                    .take_while(|(offset, _)| *offset <= elapsed)
+                    .count();
+                if due == 0 && !self.pending.is_empty() {
+                    return Ok(false);
+                }
+                readings.clear();
+                let converted = ready_at - self.driver.conversion_time();
+                for index in (self.pending.len() - due..self.pending.len()).rev() {
+                    let (_, address) = self.pending[index];
+                    self.collect(&address, converted, readings);
+                }
+                self.pending.truncate(self.pending.len() - due);
+                if self.pending.is_empty() {
+                    self.state = State::Idle;
+                    if self.depower
+                        && let Err(error) = self.driver.power_down()
+                    {
+                        log!(
+                            Subsystem::Sampler,
+                            Level::Warn,
+                            "Power-down failed: {error}"
+                        );
+                    }
+                    let at = ready_at - self.started;
+                    for sensor in &mut self.simulated {
+                        let reading = sensor.read(at).converted_at(converted);
+                        readings.extend(self.pipeline.process(reading).map(Ok));
+                    }
+                }
+                self.scratch.clear();
+                self.scratch.extend(
+                    readings
+                        .iter()
+                        .flatten()
+                        .filter(|reading| !reading.is_stale()),
+                );
+                let cancellation = self.driver.cancellation();
+                for sink in &mut self.sinks {
+                    sink.deliver(&self.scratch, &cancellation);
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    /// Pauses sampling. An in-flight conversion is waited out, so the bus is
+    /// idle on return, unless the wait is cancelled; its readings are
+    /// discarded.
+    pub fn pause(&mut self) {
+        match self.state {
+            State::Paused { .. } => return,
+            State::Converting { ready_at } => {
+                let _ = self
+                    .driver
+                    .cancellation()
+                    .sleep(ready_at.saturating_duration_since(Instant::now()));
+                self.pending.clear();
+            }
+            State::Idle => {}
+        }
+        self.state = State::Paused {
+            since: Instant::now(),
+        };
+        log!(Subsystem::Sampler, Level::Info, "Sampling paused");
+    }
+
+    /// Stops sampling without waiting for an in-flight conversion. Blocking
+    /// waits of the driver fail with [`Error::Cancelled`](crate::Error::Cancelled)
+    /// until sampling is resumed.
+    pub fn stop(&mut self) {
+        self.driver.cancellation().cancel();
+        self.pause();
+    }
+
+    /// Shuts sampling down before a planned reboot: waits out an in-flight
+    /// conversion, so the bus is idle, makes one more attempt to send the
+    /// buffered readings to the sinks and persists the pipeline state and
+    /// the schedule. Sampling stays paused.
+    ///
+    /// Dropping the sampler only sends the buffered readings, it doesn't
+    /// wait out a conversion.
+    pub fn shutdown(&mut self, store: &mut dyn Store) -> Result<()> {
+        self.pause();
+        for sink in &mut self.sinks {
+            sink.flush();
+        }
+        self.pipeline.persist(store)?;
+        self.persist(store)?;
+        log!(Subsystem::Sampler, Level::Info, "Sampling shut down");
+        Ok(())
+    }
+
+    /// Saves the schedule under `schedule` as the little-endian interval and
+    /// the Unix time of the next sample, both in milliseconds.
+    fn persist(&self, store: &mut dyn Store) -> Result<()> {
+        let next = SystemTime::now() + self.next.saturating_duration_since(Instant::now());
+        let next = next.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut value = [0; 16];
+        value[..8].copy_from_slice(&(self.interval.as_millis() as u64).to_le_bytes());
+        value[8..].copy_from_slice(&(next.as_millis() as u64).to_le_bytes());
+        store.save(SCHEDULE, &value)
+    }
+
+    /// Restores the persisted pipeline state and resumes the persisted
+    /// schedule, see [`shutdown`](Self::shutdown). Returns `false` if there
+    /// is no schedule or it was persisted with another interval, the
+    /// schedule is kept then.
+    pub fn restore(&mut self, store: &mut dyn Store) -> Result<bool> {
+        self.pipeline.restore(store)?;
+        let Some(value) = store.load(SCHEDULE)? else {
+            return Ok(false);
+        };
+        if value.len() != 16 {
+            return Err(Error::Decode {
+                offset: value.len().min(16),
+            });
+        }
+        let [interval, next] = [0, 8].map(|start| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&value[start..start + 8]);
+            Duration::from_millis(u64::from_le_bytes(bytes))
+        });
+        if interval != self.interval {
+            return Ok(false);
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let delay = phase(interval, next, now);
+        self.next = Instant::now() + delay;
+        log!(
+            Subsystem::Sampler,
+            Level::Info,
+            "Schedule restored, next sample in {delay:?}"
+        );
+        Ok(true)
+    }
+
+    /// Resumes sampling and returns how long it was suspended. The samples
+    /// missed meanwhile are skipped, the next one is taken on the schedule,
+    /// keeping the sampling phase.
+    pub fn resume(&mut self) -> Duration {
+        let State::Paused { since } = self.state else {
+            return Duration::ZERO;
+        };
+        self.driver.cancellation().reset();
+        let now = Instant::now();
+        let suspended = now - since;
+        if let Some(late) = now.checked_duration_since(self.next) {
+            let interval = self.interval.max(self.driver.conversion_time());
+            let missed = late.as_nanos() / interval.as_nanos().max(1) + 1;
+            self.next += Duration::from_nanos((interval.as_nanos() * missed) as u64);
+        }
+        self.state = State::Idle;
+        log!(
+            Subsystem::Sampler,
+            Level::Info,
+            "Sampling resumed after {suspended:?}",
+        );
+        suspended
+    }
+
+    fn collect(
+        &mut self,
+        address: &Address,
+        converted: Instant,
+        readings: &mut Vec<Result<Reading>>,
+    ) {
+        let reading = self.driver.read_scratchpad(address).map(|scratchpad| {
+            Reading::new(*address, scratchpad.temperature).converted_at(converted)
+        });
+        if let Some(quarantine) = &mut self.quarantine {
+            quarantine.record(*address, &reading);
+        }
+        match reading {
+            Ok(reading) => readings.extend(self.pipeline.process(reading).map(Ok)),
+            Err(error) => readings.push(Err(error)),
+        }
+    }
+}
+
+impl<T: Transport> Drop for Sampler<T> {
+    fn drop(&mut self) {
+        for sink in &mut self.sinks {
+            sink.flush();
+        }
+    }
+}
+
+/// The delay until the next sample of the schedule, from the Unix time of a
+/// due sample and the current one. Missed samples are skipped, a sample
+/// further ahead than the interval (the clock went back) is capped.
+fn phase(interval: Duration, next: Duration, now: Duration) -> Duration {
+    if next >= now {
+        return (next - now).min(interval);
+    }
+    let interval = interval.as_millis().max(1);
+    let missed = (now - next).as_millis() % interval;
+    Duration::from_millis(((interval - missed) % interval) as _)
+}
+
+/// A random delay up to the bound.
+fn jitter(entropy: &mut impl Entropy, bound: Duration) -> Duration {
+    if bound.is_zero() {
+        return Duration::ZERO;
+    }
+    bound.mul_f64(entropy.next_u32() as f64 / u32::MAX as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn phase() {
+        let interval = Duration::from_secs(60);
+        let at = |seconds: u64| Duration::from_secs(1_700_000_000 + seconds);
+        assert_eq!(
+            super::phase(interval, at(20), at(0)),
+            Duration::from_secs(20)
+        );
+        assert_eq!(super::phase(interval, at(20), at(20)), Duration::ZERO);
+        // Two samples missed
+        assert_eq!(
+            super::phase(interval, at(20), at(150)),
+            Duration::from_secs(50)
+        );
+        assert_eq!(super::phase(interval, at(20), at(140)), Duration::ZERO);
+        assert_eq!(super::phase(interval, at(3600), at(0)), interval);
+    }
+
+    /// Bus without sensors
+    #[derive(Default)]
+    struct Empty(Cancellation);
+
+    impl Transport for Empty {
+        fn start_conversion(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn read_scratchpad(&mut self, _address: &Address) -> Result<Scratchpad> {
+            Err(Error::DeviceNotFound)
+        }
+
+        fn commit(&mut self, _address: &Address) -> Result<()> {
+            Ok(())
+        }
+
+        fn power_down(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn scan(&mut self) -> Result<Vec<Address>> {
+            Ok(Vec::new())
+        }
+
+        fn cancellation(&self) -> Cancellation {
+            self.0.clone()
+        }
+
+        fn conversion_time(&self) -> Duration {
+            Duration::from_millis(1)
+        }
+    }
+
+    #[test]
+    fn resume() {
+        let interval = Duration::from_millis(50);
+        let mut sampler =
+            Sampler::new(Empty::default(), Vec::new(), interval).phase(Duration::from_millis(20));
+        let scheduled = sampler.next;
+        sampler.pause();
+        std::thread::sleep(Duration::from_millis(80));
+        let before = Instant::now();
+        assert!(sampler.resume() >= Duration::from_millis(80));
+        assert_eq!(sampler.state(), State::Idle);
+        // On the schedule, missed samples skipped.
+        assert!(sampler.next > before);
+        assert!(sampler.next <= Instant::now() + interval);
+        assert_eq!(
+            (sampler.next - scheduled).as_nanos() % interval.as_nanos(),
+            0
+        );
+        // A short pause keeps the next sample.
+        let scheduled = sampler.next;
+        sampler.pause();
+        sampler.resume();
+        assert_eq!(sampler.next, scheduled);
+    }
+
+    #[test]
+    fn jitter() {
+        let bound = Duration::from_secs(2);
+        let mut entropy = Xorshift::new(1);
+        let delays: Vec<_> = (0..100)
+            .map(|_| super::jitter(&mut entropy, bound))
+            .collect();
+        assert!(delays.iter().all(|delay| *delay <= bound));
+        assert!(delays.iter().any(|delay| *delay > bound / 2));
+        assert!(delays.iter().any(|delay| *delay < bound / 2));
+        assert_eq!(super::jitter(&mut entropy, Duration::ZERO), Duration::ZERO);
+    }
+}
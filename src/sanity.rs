@@ -0,0 +1,142 @@
+//! Startup sanity check
+//!
+//! Sensors in one installation usually read within a known spread at boot.
+//! A sensor far from the median of the first sweep is likely mis-wired,
+//! failing or mislabeled, and is flagged before the application starts
+//! trusting it:
+//!
+//! ```ignore
+//! let sweep = thermometer.read_all(&addresses, PowerBudget::Unlimited)?;
+//! let readings: Vec<_> = sweep.readings.into_iter().flatten().collect();
+//! for outlier in SanityCheck::new(15.0).check(&readings) {
+//!     untrusted.push(outlier.address);
+//! }
+//! ```
+//!
+//! The median needs a majority of sane sensors: with fewer than three
+//! readings nothing is flagged.
+
+use crate::{
+    address::Address,
+    logging::{Subsystem, log},
+    pipeline::Reading,
+};
+use alloc::vec::Vec;
+use log::Level;
+
+/// Default spread (°C)
+pub const SPREAD: f32 = 10.0;
+
+/// Sensor reading far from the others
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outlier {
+    pub address: Address,
+    /// Temperature (°C)
+    pub temperature: f32,
+    /// The difference from the median (°C).
+    pub deviation: f32,
+}
+
+/// Startup sanity check
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SanityCheck {
+    spread: f32,
+}
+
+impl Default for SanityCheck {
+    fn default() -> Self {
+        Self::new(SPREAD)
+    }
+}
+
+impl SanityCheck {
+    /// The readings may differ from the median by up to the spread (°C).
+    pub fn new(spread: f32) -> Self {
+        Self {
+            spread: spread.abs(),
+        }
+    }
+
+    pub fn spread(&self) -> f32 {
+        self.spread
+    }
+
+    /// The readings beyond the spread, in the order of the readings. Stale
+    /// readings aren't checked.
+    pub fn check(&self, readings: &[Reading]) -> Vec<Outlier> {
+        let fresh = || readings.iter().filter(|reading| !reading.is_stale());
+        let mut temperatures: Vec<_> = fresh().map(|reading| reading.temperature).collect();
+        if temperatures.len() < 3 {
+            return Vec::new();
+        }
+        temperatures.sort_by(f32::total_cmp);
+        let middle = temperatures.len() / 2;
+        let median = if temperatures.len() % 2 == 0 {
+            (temperatures[middle - 1] + temperatures[middle]) / 2.0
+        } else {
+            temperatures[middle]
+        };
+        fresh()
+            .filter_map(|reading| {
+                let deviation = reading.temperature - median;
+                // NaN is never within the spread.
+                if deviation.abs() <= self.spread {
+                    return None;
+                }
+                log!(
+                    Subsystem::Pipeline,
+                    Level::Warn,
+                    "Sensor {} reads {} °C, {deviation:+} °C from the median",
+                    reading.address,
+                    reading.temperature,
+                );
+                Some(Outlier {
+                    address: reading.address,
+                    temperature: reading.temperature,
+                    deviation,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::time::Duration;
+
+    fn readings(temperatures: &[f32]) -> Vec<Reading> {
+        temperatures
+            .iter()
+            .enumerate()
+            .map(|(index, temperature)| Reading::new(Address(index as _), *temperature))
+            .collect()
+    }
+
+    #[test]
+    fn check() {
+        let check = SanityCheck::new(5.0);
+        let readings = readings(&[21.0, 22.5, 85.0, 20.0, -0.0625]);
+        assert_eq!(
+            check.check(&readings),
+            [
+                Outlier {
+                    address: Address(2),
+                    temperature: 85.0,
+                    deviation: 64.0,
+                },
+                Outlier {
+                    address: Address(4),
+                    temperature: -0.0625,
+                    deviation: -21.0625,
+                },
+            ]
+        );
+        // No majority
+        assert_eq!(check.check(&readings[1..3]), []);
+        // Stale readings are left out.
+        let mut readings = readings;
+        readings[2].stale = Some(Duration::from_secs(1));
+        assert_eq!(check.check(&readings).len(), 1);
+    }
+}
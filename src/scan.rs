@@ -0,0 +1,413 @@
+//! Bus scan
+//!
+//! Enumerates the DS18B20 sensors on the bus. Every pass of the ROM search
+//! algorithm discovers one device, so on a large bus the scan can take a
+//! while; [`Progress`] reports the intermediate results.
+//!
+//! At boot the persisted addresses are usually all still there, so
+//! [`fast_scan`](Ds18b20Driver::fast_scan) checks them one by one first and
+//! only falls back to the full search if any is missing:
+//!
+//! ```ignore
+//! let addresses = thermometer.fast_scan(&persisted)?;
+//! ```
+//!
+//! The sensors found can differ from the previous scan, so every scan
+//! detects the power mode of the bus again.
+//!
+//! A failed scan keeps the sensors found until then, e.g. the first
+//! [`max_devices`](Ds18b20Driver::max_devices) ones on an overcrowded bus:
+//!
+//! ```ignore
+//! let addresses = match thermometer.scan() {
+//!     Ok(addresses) => addresses,
+//!     Err(ScanError { error: Error::TooManyDevices { .. }, addresses }) => addresses,
+//!     Err(error) => return Err(error.into()),
+//! };
+//! ```
+//!
+//! A damaged bus can keep the search going for a long time. A scan with a
+//! deadline stops there and keeps the sensors found so far, so the boot goes
+//! on:
+//!
+//! ```ignore
+//! let mut addresses = Vec::new();
+//! match thermometer.scan_until(Instant::now() + Duration::from_secs(5), &mut addresses) {
+//!     Ok(()) | Err(Error::SearchTimedOut { .. }) => {}
+//!     Err(error) => return Err(error),
+//! }
+//! ```
+//!
+//! Noise can make a search miss devices. Repeated scans take the union of
+//! what they found and report the devices not found by every scan as
+//! unstable:
+//!
+//! ```ignore
+//! let report = thermometer.scan_repeated(3)?;
+//! for address in report.unstable() {
+//!     warn!("{address} only answers some searches");
+//! }
+//! ```
+
+use crate::{
+    Ds18b20Driver, Error, Result,
+    address::Address,
+    driver::preflight,
+    instrument,
+    logging::{Subsystem, log},
+};
+use log::Level;
+use std::{
+    fmt::{self, Display, Formatter},
+    time::Instant,
+};
+
+/// Scan progress callbacks
+pub trait Progress {
+    /// Called before each search pass, `pass` starts from 1.
+    fn on_search_pass(&mut self, _pass: usize) {}
+
+    /// Called for each discovered sensor.
+    fn on_device_found(&mut self, _address: &Address) {}
+}
+
+/// No progress reporting
+impl Progress for () {}
+
+/// A scan failed midway
+///
+/// Converts into its [`Error`], so `?` works in functions returning
+/// [`Result`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanError {
+    pub error: Error,
+    /// The sensors found until the failure, at most
+    /// [`max_devices`](Ds18b20Driver::max_devices).
+    pub addresses: Vec<Address>,
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "scan failed after {} sensors", self.addresses.len())
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<ScanError> for Error {
+    fn from(error: ScanError) -> Self {
+        error.error
+    }
+}
+
+/// Repeated scan report
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScanReport {
+    /// The number of scans.
+    pub scans: usize,
+    /// The number of scans that failed midway. Their devices found until
+    /// then are counted.
+    pub failed: usize,
+    /// The devices and the number of scans that found them, in the order of
+    /// discovery.
+    pub devices: Vec<(Address, usize)>,
+}
+
+impl ScanReport {
+    /// All devices found.
+    pub fn addresses(&self) -> impl Iterator<Item = &Address> {
+        self.devices.iter().map(|(address, _)| address)
+    }
+
+    /// The devices found by every scan.
+    pub fn stable(&self) -> impl Iterator<Item = &Address> {
+        self.devices
+            .iter()
+            .filter(|(_, seen)| *seen == self.scans)
+            .map(|(address, _)| address)
+    }
+
+    /// The devices missed by some scans.
+    pub fn unstable(&self) -> impl Iterator<Item = &Address> {
+        self.devices
+            .iter()
+            .filter(|(_, seen)| *seen < self.scans)
+            .map(|(address, _)| address)
+    }
+
+    pub fn is_stable(&self) -> bool {
+        self.failed == 0 && self.unstable().next().is_none()
+    }
+
+    fn merge(&mut self, addresses: &[Address], failed: bool) {
+        self.scans += 1;
+        self.failed += failed as usize;
+        for address in addresses {
+            match self.devices.iter_mut().find(|(a, _)| a == address) {
+                Some((_, seen)) => *seen += 1,
+                None => self.devices.push((*address, 1)),
+            }
+        }
+    }
+}
+
+impl Ds18b20Driver<'_> {
+    /// Scans the bus for DS18B20 sensors.
+    ///
+    /// Devices of other families are skipped. A failed scan returns the
+    /// sensors found until then with the error, e.g. the first
+    /// [`max_devices`](Ds18b20Driver::max_devices) ones with
+    /// [`Error::TooManyDevices`].
+    pub fn scan(&mut self) -> Result<Vec<Address>, ScanError> {
+        self.scan_with(&mut ())
+    }
+
+    /// Scans the bus for DS18B20 sensors one at a time, without allocating.
+    ///
+    /// Devices of other families are skipped.
+    pub fn scan_iter(&mut self) -> Result<impl Iterator<Item = Result<Address>>> {
+        self.power_mode = None;
+        Ok(self.search()?.filter(|address| match address {
+            Err(Error::FamilyCode(family_code)) => {
+                log!(
+                    Subsystem::Bus,
+                    Level::Debug,
+                    "Skip device of family {family_code:x}"
+                );
+                false
+            }
+            _ => true,
+        }))
+    }
+
+    /// Scans the bus for DS18B20 sensors reporting the progress, see
+    /// [`scan`](Self::scan).
+    pub fn scan_with(&mut self, progress: &mut impl Progress) -> Result<Vec<Address>, ScanError> {
+        let mut addresses = Vec::new();
+        match self.scan_into(&mut addresses, progress) {
+            Ok(()) => Ok(addresses),
+            Err(error) => Err(ScanError { error, addresses }),
+        }
+    }
+
+    /// Scans the bus for DS18B20 sensors into the buffer, replacing its
+    /// contents.
+    ///
+    /// Keeps at most [`max_devices`](Ds18b20Driver::max_devices) sensors. If
+    /// there are more, the search still counts them and fails with
+    /// [`Error::TooManyDevices`], the buffer holds the first ones found.
+    pub fn scan_into(
+        &mut self,
+        addresses: &mut Vec<Address>,
+        progress: &mut impl Progress,
+    ) -> Result<()> {
+        instrument::operation("scan", None, || self.scan_before(None, addresses, progress))
+    }
+
+    /// Scans the bus for DS18B20 sensors into the buffer like
+    /// [`scan_into`](Self::scan_into), giving up at the deadline.
+    ///
+    /// Fails with [`Error::SearchTimedOut`] once the deadline has passed, the
+    /// buffer holds the sensors found until then. A device found twice, as
+    /// on a search looping over a faulty bus, is kept once.
+    pub fn scan_until(&mut self, deadline: Instant, addresses: &mut Vec<Address>) -> Result<()> {
+        instrument::operation("scan", None, || {
+            self.scan_before(Some(deadline), addresses, &mut ())
+        })
+    }
+
+    /// Scans the bus the number of times, see [`ScanReport`].
+    ///
+    /// A scan failing midway is counted as failed, unless it was cancelled
+    /// or found too many devices, which fails the whole run.
+    pub fn scan_repeated(&mut self, scans: usize) -> Result<ScanReport> {
+        instrument::operation("scan_repeated", None, || {
+            let mut report = ScanReport::default();
+            let mut addresses = Vec::new();
+            for _ in 0..scans {
+                let failed = match self.scan_before(None, &mut addresses, &mut ()) {
+                    Ok(()) => false,
+                    Err(error @ (Error::Cancelled | Error::TooManyDevices { .. })) => {
+                        return Err(error);
+                    }
+                    Err(error) => {
+                        log!(Subsystem::Bus, Level::Debug, "Scan failed: {error}");
+                        true
+                    }
+                };
+                report.merge(&addresses, failed);
+            }
+            if !report.is_stable() {
+                log!(
+                    Subsystem::Bus,
+                    Level::Warn,
+                    "Unstable scan: {} of {} devices missed by some of {} scans",
+                    report.unstable().count(),
+                    report.devices.len(),
+                    report.scans,
+                );
+            }
+            Ok(report)
+        })
+    }
+
+    fn scan_before(
+        &mut self,
+        deadline: Option<Instant>,
+        addresses: &mut Vec<Address>,
+        progress: &mut impl Progress,
+    ) -> Result<()> {
+        addresses.clear();
+        self.power_mode = None;
+        let max = self.max_devices;
+        let search = self.search()?;
+        collect(search, deadline, max, addresses, progress)
+    }
+
+    /// Checks that the sensor answers: Match ROM and the first two
+    /// scratchpad bytes. Nobody drives the bus for an absent sensor, so it
+    /// reads as all ones; as that is also -0.0625 °C, the whole scratchpad
+    /// is read to tell them apart.
+    pub fn is_present(&mut self, address: &Address) -> Result<bool> {
+        preflight(address)?;
+        let mut buffer = [0; 2];
+        self.initialization()?
+            .match_rom(address)?
+            .read_scratchpad_bytes(&mut buffer)?;
+        if buffer != [0xFF; 2] {
+            return Ok(true);
+        }
+        match self.initialization()?.match_rom(address)?.read_scratchpad() {
+            Ok(_) => Ok(true),
+            Err(Error::ScratchpadCrc { .. }) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Checks the known sensors and returns them if all are present,
+    /// otherwise (or if none are known) falls back to the full scan.
+    pub fn fast_scan(&mut self, known: &[Address]) -> Result<Vec<Address>, ScanError> {
+        if known.is_empty() {
+            return self.scan();
+        }
+        for address in known {
+            if !self.is_present(address).unwrap_or(false) {
+                log!(
+                    Subsystem::Bus,
+                    Level::Info,
+                    "Known sensor {address} missing, falling back to full scan"
+                );
+                return self.scan();
+            }
+        }
+        Ok(known.to_vec())
+    }
+}
+
+/// Collects the sensors found by the search, see
+/// [`scan_into`](Ds18b20Driver::scan_into).
+fn collect(
+    mut search: impl Iterator<Item = Result<Address>>,
+    deadline: Option<Instant>,
+    max: usize,
+    addresses: &mut Vec<Address>,
+    progress: &mut impl Progress,
+) -> Result<()> {
+    let mut found = 0;
+    for pass in 1.. {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            log!(
+                Subsystem::Bus,
+                Level::Warn,
+                "Search timed out after {} passes, found {} sensors",
+                pass - 1,
+                addresses.len(),
+            );
+            return Err(Error::SearchTimedOut {
+                found: addresses.len(),
+            });
+        }
+        progress.on_search_pass(pass);
+        match search.next() {
+            Some(Ok(address)) if addresses.contains(&address) => {
+                log!(Subsystem::Bus, Level::Debug, "Found {address} again");
+            }
+            Some(Ok(address)) => {
+                found += 1;
+                if found <= max {
+                    progress.on_device_found(&address);
+                    addresses.push(address);
+                }
+            }
+            Some(Err(Error::FamilyCode(family_code))) => {
+                log!(
+                    Subsystem::Bus,
+                    Level::Debug,
+                    "Skip device of family {family_code:x}"
+                );
+            }
+            Some(Err(error)) => return Err(error),
+            None => break,
+        }
+    }
+    if found > max {
+        log!(
+            Subsystem::Bus,
+            Level::Error,
+            "Found {found} sensors, kept the first {max}"
+        );
+        return Err(Error::TooManyDevices { found, max });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::power::PowerMode;
+    use esp_idf_svc::hal::{gpio::AnyIOPin, rmt::CHANNEL0};
+
+    #[test]
+    fn report() {
+        let mut report = ScanReport::default();
+        report.merge(&[Address(1), Address(2)], false);
+        report.merge(&[Address(2)], true);
+        report.merge(&[Address(3), Address(2)], false);
+        assert_eq!(report.scans, 3);
+        assert_eq!(report.failed, 1);
+        assert_eq!(
+            report.addresses().collect::<Vec<_>>(),
+            [&Address(1), &Address(2), &Address(3)]
+        );
+        assert_eq!(report.stable().collect::<Vec<_>>(), [&Address(2)]);
+        assert_eq!(
+            report.unstable().collect::<Vec<_>>(),
+            [&Address(1), &Address(3)]
+        );
+        assert!(!report.is_stable());
+    }
+
+    #[test]
+    fn too_many() {
+        let search = (1..=4).map(|address| Ok(Address(address)));
+        let mut addresses = Vec::new();
+        assert_eq!(
+            collect(search, None, 2, &mut addresses, &mut ()),
+            Err(Error::TooManyDevices { found: 4, max: 2 })
+        );
+        assert_eq!(addresses, [Address(1), Address(2)]);
+    }
+
+    #[test]
+    fn power_mode() {
+        let pin = unsafe { AnyIOPin::new(4) };
+        let mut driver = Ds18b20Driver::new(pin, unsafe { CHANNEL0::new() }).unwrap();
+        driver.power_mode = Some(PowerMode::Parasite);
+        assert_eq!(driver.scan(), Ok(Vec::new()));
+        assert_eq!(driver.power_mode, None);
+    }
+}
@@ -1,23 +1,198 @@
-use crate::{CONVERSION_TIME_NS, error::Error};
+use crate::{
+    address::Address,
+    crc8::{self, Crc8},
+    error::{CrcError, Error},
+    timing,
+    unit::Celsius,
+};
+use core::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+pub use crate::types::Resolution;
 
 pub(crate) const NINE: u8 = 0b00011111;
 pub(crate) const TEN: u8 = 0b00111111;
 pub(crate) const ELEVEN: u8 = 0b01011111;
 pub(crate) const TWELVE: u8 = 0b01111111;
 
+/// Reserved bytes 5 to 7 as the DS18B20 reports them after power-up.
+const RESERVED: [u8; 3] = [0xFF, 0x0C, 0x10];
+
 /// Scratchpad
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Scratchpad {
     pub temperature: f32,
     /// Alarm high trigger register (TH)
-    pub alarm_high_trigger_register: i8,
+    pub alarm_high_trigger_register: AlarmThreshold,
     /// Alarm low trigger register (TL)
-    pub alarm_low_trigger_register: i8,
+    pub alarm_low_trigger_register: AlarmThreshold,
     /// Configuration register
     pub configuration_register: ConfigurationRegister,
     pub crc: u8,
 }
 
+impl Scratchpad {
+    /// Decodes the scratchpad, CRC byte included.
+    ///
+    /// Fails with [`Error::ScratchpadCrc`] without an address.
+    pub fn from_bytes(buffer: [u8; 9]) -> Result<Self, Error> {
+        crc8::check(&buffer).map_err(|CrcError { crc }| Error::ScratchpadCrc {
+            address: None,
+            buffer,
+            crc,
+        })?;
+        let configuration_register = ConfigurationRegister::try_from(buffer[4])?;
+        Ok(Self {
+            temperature: temperature(buffer[1], buffer[0], configuration_register.resolution),
+            alarm_high_trigger_register: AlarmThreshold::from_register(buffer[2] as _),
+            alarm_low_trigger_register: AlarmThreshold::from_register(buffer[3] as _),
+            configuration_register,
+            crc: buffer[8],
+        })
+    }
+
+    /// Encodes the scratchpad as the sensor sends it, with the reserved bytes
+    /// and a freshly computed CRC byte.
+    ///
+    /// The temperature is rounded to 1/16 °C; `crc` is ignored.
+    pub fn to_bytes(&self) -> [u8; 9] {
+        let raw = self.temperature * 16.0;
+        let raw = (raw + if raw < 0.0 { -0.5 } else { 0.5 }) as i16;
+        let [lsb, msb] = raw.to_le_bytes();
+        let mut buffer = [
+            lsb,
+            msb,
+            self.alarm_high_trigger_register.get() as _,
+            self.alarm_low_trigger_register.get() as _,
+            self.configuration_register.into(),
+            RESERVED[0],
+            RESERVED[1],
+            RESERVED[2],
+            0,
+        ];
+        buffer[8] = Crc8::new().update(buffer[..8].iter().copied()).finish();
+        buffer
+    }
+
+    /// Alarm high threshold (TH)
+    pub fn alarm_high(&self) -> Celsius {
+        self.alarm_high_trigger_register.into()
+    }
+
+    /// Alarm low threshold (TL)
+    pub fn alarm_low(&self) -> Celsius {
+        self.alarm_low_trigger_register.into()
+    }
+}
+
+/// Alarm threshold (°C)
+///
+/// The value of an alarm trigger register (TH or TL). Thresholds set by the
+/// user are within the measuring range of the DS18B20, registers read back
+/// are kept as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AlarmThreshold(i8);
+
+impl AlarmThreshold {
+    pub const MIN: Self = Self(-55);
+    pub const MAX: Self = Self(125);
+
+    /// Fails with [`Error::AlarmThreshold`] outside the measuring range.
+    pub const fn try_new(celsius: i32) -> Result<Self, Error> {
+        if celsius < Self::MIN.0 as i32 || celsius > Self::MAX.0 as i32 {
+            return Err(Error::AlarmThreshold(celsius));
+        }
+        Ok(Self(celsius as _))
+    }
+
+    /// Clamps the threshold to the measuring range.
+    pub const fn clamped(celsius: i32) -> Self {
+        if celsius < Self::MIN.0 as i32 {
+            Self::MIN
+        } else if celsius > Self::MAX.0 as i32 {
+            Self::MAX
+        } else {
+            Self(celsius as _)
+        }
+    }
+
+    /// The threshold of the register, unchanged. Registers beyond the
+    /// measuring range, e.g. TH 127 and TL -128 disabling the alarm or bytes
+    /// used as EEPROM storage, are written back as they were read.
+    pub const fn from_register(register: i8) -> Self {
+        Self(register)
+    }
+
+    pub const fn get(&self) -> i8 {
+        self.0
+    }
+}
+
+impl TryFrom<i32> for AlarmThreshold {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Self::try_new(value)
+    }
+}
+
+impl From<AlarmThreshold> for i8 {
+    fn from(value: AlarmThreshold) -> Self {
+        value.0
+    }
+}
+
+impl From<AlarmThreshold> for Celsius {
+    fn from(value: AlarmThreshold) -> Self {
+        value.0.into()
+    }
+}
+
+impl Display for AlarmThreshold {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// Sensor information
+///
+/// The configuration of a sensor as read back from its scratchpad.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SensorInfo {
+    pub address: Address,
+    pub resolution: Resolution,
+    /// Alarm high threshold (TH)
+    pub alarm_high: Celsius,
+    /// Alarm low threshold (TL)
+    pub alarm_low: Celsius,
+}
+
+impl SensorInfo {
+    pub fn new(address: Address, scratchpad: &Scratchpad) -> Self {
+        Self {
+            address,
+            resolution: scratchpad.configuration_register.resolution,
+            alarm_high: scratchpad.alarm_high(),
+            alarm_low: scratchpad.alarm_low(),
+        }
+    }
+}
+
+impl Display for SensorInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}-bit TH {} TL {}",
+            self.address,
+            self.resolution.bits(),
+            self.alarm_high,
+            self.alarm_low,
+        )
+    }
+}
+
 /// Configuration register
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct ConfigurationRegister {
@@ -59,29 +234,22 @@ impl From<ConfigurationRegister> for u8 {
     }
 }
 
-/// Temperature resolution: 9, 10, 11 or 12 bits.
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-pub enum Resolution {
-    /// 9-bit, equates to a temperature resolution of 0.5°C
-    Nine,
-    /// 10-bit, equates to a temperature resolution of 0.25°C
-    Ten,
-    /// 11-bit, equates to a temperature resolution of 0.125°C
-    Eleven,
-    /// 12-bit, equates to a temperature resolution of 0.0625°C
-    #[default]
-    Twelve,
-}
-
-impl Resolution {
-    /// Conversion time (ns)
-    pub fn conversion_time(&self) -> u32 {
-        (match self {
-            Resolution::Nine => CONVERSION_TIME_NS / 8,
-            Resolution::Ten => CONVERSION_TIME_NS / 4,
-            Resolution::Eleven => CONVERSION_TIME_NS / 2,
-            Resolution::Twelve => CONVERSION_TIME_NS,
-        }) as _
+/// Achieved precision
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Precision {
+    pub resolution: Resolution,
+    /// Temperature step (°C)
+    pub step: f32,
+    pub conversion_time: Duration,
+}
+
+impl From<Resolution> for Precision {
+    fn from(resolution: Resolution) -> Self {
+        Self {
+            resolution,
+            step: resolution.step(),
+            conversion_time: timing::conversion(resolution),
+        }
     }
 }
 
@@ -93,6 +261,17 @@ pub fn temperature(msb: u8, lsb: u8, resolution: Resolution) -> f32 {
 mod test {
     use super::*;
 
+    #[test]
+    fn precision() {
+        assert_eq!(Resolution::for_precision(1.0), Resolution::Nine);
+        assert_eq!(Resolution::for_precision(0.3), Resolution::Ten);
+        assert_eq!(Resolution::for_precision(0.125), Resolution::Eleven);
+        assert_eq!(Resolution::for_precision(0.01), Resolution::Twelve);
+        let precision = Precision::from(Resolution::Ten);
+        assert_eq!(precision.step, 0.25);
+        assert_eq!(precision.conversion_time, Duration::from_micros(187_500));
+    }
+
     #[test]
     fn configuration_register() {
         assert_eq!(
@@ -133,6 +312,109 @@ mod test {
         );
     }
 
+    #[test]
+    fn bytes() {
+        let buffer = [99, 1, 75, 70, 127, 255, 13, 16, 21];
+        let scratchpad = Scratchpad::from_bytes(buffer).unwrap();
+        assert_eq!(scratchpad.temperature, 22.1875);
+        assert_eq!(scratchpad.alarm_high_trigger_register.get(), 75);
+        assert_eq!(scratchpad.alarm_low_trigger_register.get(), 70);
+        assert_eq!(scratchpad.crc, 21);
+        // The reserved byte 6 differs, so the CRC does too.
+        let bytes = scratchpad.to_bytes();
+        assert_eq!(bytes[..8], [99, 1, 75, 70, 127, 255, 12, 16]);
+        assert_eq!(crc8::calculate(&bytes), 0);
+        assert_eq!(
+            Scratchpad::from_bytes(bytes),
+            Ok(Scratchpad {
+                crc: bytes[8],
+                ..scratchpad
+            })
+        );
+        for temperature in [-55.0, -10.125, -0.0625, 0.0, 25.0625, 85.0, 125.0] {
+            for resolution in [Resolution::Nine, Resolution::Twelve] {
+                let scratchpad = Scratchpad {
+                    temperature,
+                    alarm_high_trigger_register: AlarmThreshold::MAX,
+                    alarm_low_trigger_register: AlarmThreshold::MIN,
+                    configuration_register: ConfigurationRegister { resolution },
+                    crc: 0,
+                };
+                let decoded = Scratchpad::from_bytes(scratchpad.to_bytes()).unwrap();
+                assert_eq!(
+                    decoded,
+                    Scratchpad {
+                        crc: decoded.crc,
+                        ..scratchpad
+                    }
+                );
+            }
+        }
+        let mut corrupted = bytes;
+        corrupted[0] ^= 1;
+        assert!(matches!(
+            Scratchpad::from_bytes(corrupted),
+            Err(Error::ScratchpadCrc { address: None, .. })
+        ));
+    }
+
+    #[test]
+    fn alarm_threshold() {
+        assert_eq!(AlarmThreshold::try_new(-55).map(|t| t.get()), Ok(-55));
+        assert_eq!(AlarmThreshold::try_new(125).map(|t| t.get()), Ok(125));
+        assert_eq!(AlarmThreshold::try_new(0).map(|t| t.get()), Ok(0));
+        assert_eq!(
+            AlarmThreshold::try_new(-56),
+            Err(Error::AlarmThreshold(-56))
+        );
+        assert_eq!(
+            AlarmThreshold::try_new(126),
+            Err(Error::AlarmThreshold(126))
+        );
+        // Would wrap to -126 as an `i8`.
+        assert_eq!(
+            AlarmThreshold::try_new(130),
+            Err(Error::AlarmThreshold(130))
+        );
+        assert_eq!(
+            AlarmThreshold::try_from(i32::MIN),
+            Err(Error::AlarmThreshold(i32::MIN))
+        );
+        assert_eq!(AlarmThreshold::clamped(130), AlarmThreshold::MAX);
+        assert_eq!(AlarmThreshold::clamped(-130), AlarmThreshold::MIN);
+        assert_eq!(AlarmThreshold::clamped(-10).get(), -10);
+        assert_eq!(AlarmThreshold::from_register(i8::MAX).get(), i8::MAX);
+        assert_eq!(AlarmThreshold::from_register(i8::MIN).get(), i8::MIN);
+        // Alarm disabled, round-trips unchanged.
+        let scratchpad = Scratchpad {
+            alarm_high_trigger_register: AlarmThreshold::from_register(127),
+            alarm_low_trigger_register: AlarmThreshold::from_register(-128),
+            ..Default::default()
+        };
+        let bytes = scratchpad.to_bytes();
+        assert_eq!(bytes[2..4], [0x7F, 0x80]);
+        let decoded = Scratchpad::from_bytes(bytes).unwrap();
+        assert_eq!(decoded.alarm_high_trigger_register.get(), 127);
+        assert_eq!(decoded.alarm_low_trigger_register.get(), -128);
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(AlarmThreshold::clamped(-5).to_string(), "-5");
+    }
+
+    #[test]
+    fn sensor_info() {
+        let scratchpad = Scratchpad {
+            alarm_high_trigger_register: AlarmThreshold::clamped(75),
+            alarm_low_trigger_register: AlarmThreshold::clamped(-10),
+            ..Default::default()
+        };
+        let info = SensorInfo::new(Address(0x1E00_0000_0000_0028), &scratchpad);
+        assert_eq!(info.alarm_low, Celsius(-10.0));
+        assert_eq!(
+            info.to_string(),
+            "1e00000000000028 12-bit TH 75 °C TL -10 °C",
+        );
+    }
+
     #[test]
     fn temperature() {
         use super::temperature;
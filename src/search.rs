@@ -0,0 +1,282 @@
+//! ROM search algorithm
+//!
+//! The binary tree search of the 1-Wire Search ROM and Alarm Search commands
+//! over a bit-level bus. Every pass discovers one device, so the caller can
+//! stop after the first hit instead of enumerating the whole bus:
+//!
+//! ```ignore
+//! let mut search = Search::new(CommandCode::AlarmSearch);
+//! if let Some(address) = search.next(&mut bus) {
+//!     // ...
+//! }
+//! ```
+//!
+//! The passes also report the branches of the search tree on the path to
+//! their device, the bits where devices answered both 0 and 1. A flaky
+//! device shows up as a branch that comes and goes between searches:
+//!
+//! ```ignore
+//! for pass in thermometer.trace_search()? {
+//!     println!("{}", pass?); // 28ff641e0c160382 branches 2:0 9:1
+//! }
+//! ```
+
+use crate::{
+    address::{Address, Validation},
+    command::CommandCode,
+    error::{Error, Result},
+};
+use core::{
+    cmp::Ordering,
+    fmt::{self, Display, Formatter},
+};
+
+/// Bit-level 1-Wire bus
+pub trait BitBus {
+    /// Issues the reset pulse, fails without a presence pulse.
+    fn reset(&mut self) -> Result<()>;
+
+    fn write_byte(&mut self, byte: u8) -> Result<()>;
+
+    fn read_bit(&mut self) -> Result<bool>;
+
+    fn write_bit(&mut self, bit: bool) -> Result<()>;
+}
+
+/// Search pass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pass {
+    pub address: Address,
+    /// The discrepancies: bit `n - 1` is set if the devices answered both 0
+    /// and 1 for ROM bit `n`.
+    pub discrepancies: u64,
+}
+
+impl Pass {
+    /// The branches on the path to the device: the ROM bit (1 to 64) and
+    /// the direction taken, in search order.
+    pub fn branches(&self) -> impl Iterator<Item = (u32, bool)> + '_ {
+        (1..=u64::BITS)
+            .filter(|bit| self.discrepancies >> (bit - 1) & 1 != 0)
+            .map(|bit| (bit, self.address.0 >> (bit - 1) & 1 != 0))
+    }
+}
+
+impl Display for Pass {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} branches", self.address)?;
+        for (bit, direction) in self.branches() {
+            write!(f, " {bit}:{}", direction as u8)?;
+        }
+        Ok(())
+    }
+}
+
+/// Search state between the passes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Search {
+    command: CommandCode,
+    rom: u64,
+    /// The bit (1 to 64) of the last branch taken towards the zeros, 0 if
+    /// none is left.
+    last_discrepancy: u32,
+    done: bool,
+}
+
+impl Search {
+    pub fn new(command: CommandCode) -> Self {
+        Self {
+            command,
+            rom: 0,
+            last_discrepancy: 0,
+            done: false,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Runs the next pass. The search ends with the last device or the first
+    /// error.
+    pub fn next<B: BitBus + ?Sized>(&mut self, bus: &mut B) -> Option<Result<Address>> {
+        Some(self.next_pass(bus)?.map(|pass| pass.address))
+    }
+
+    /// Runs the next pass, reporting its branches.
+    pub fn next_pass<B: BitBus + ?Sized>(&mut self, bus: &mut B) -> Option<Result<Pass>> {
+        if self.done {
+            return None;
+        }
+        let pass = self.pass(bus);
+        if !matches!(pass, Ok(Some(_))) || self.last_discrepancy == 0 {
+            self.done = true;
+        }
+        pass.transpose()
+    }
+
+    fn pass<B: BitBus + ?Sized>(&mut self, bus: &mut B) -> Result<Option<Pass>> {
+        bus.reset()?;
+        bus.write_byte(self.command as _)?;
+        let mut discrepancies = 0;
+        let mut last_zero = 0;
+        for bit in 1..=u64::BITS {
+            let mask = 1 << (bit - 1);
+            let direction = match (bus.read_bit()?, bus.read_bit()?) {
+                // Nobody answered: no device is left in the search, which
+                // midway means one was lost.
+                (true, true) if bit == 1 => return Ok(None),
+                (true, true) => return Err(Error::DeviceNotFound),
+                (id, complement) if id != complement => id,
+                _ => {
+                    discrepancies |= mask;
+                    let direction = match bit.cmp(&self.last_discrepancy) {
+                        Ordering::Less => self.rom & mask != 0,
+                        Ordering::Equal => true,
+                        Ordering::Greater => false,
+                    };
+                    if !direction {
+                        last_zero = bit;
+                    }
+                    direction
+                }
+            };
+            if direction {
+                self.rom |= mask;
+            } else {
+                self.rom &= !mask;
+            }
+            bus.write_bit(direction)?;
+        }
+        self.last_discrepancy = last_zero;
+        let address = Address::from_bytes(self.rom.to_le_bytes(), Validation::Strict)?;
+        Ok(Some(Pass {
+            address,
+            discrepancies,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{FAMILY_CODE, crc8::Crc8};
+    use alloc::{format, string::ToString, vec::Vec};
+
+    /// Devices answering the search, wired-AND
+    struct Bus {
+        roms: Vec<u64>,
+        active: Vec<u64>,
+        bit: u32,
+        complement: bool,
+        resets: usize,
+    }
+
+    impl Bus {
+        fn new(serials: &[u64]) -> Self {
+            let roms = serials
+                .iter()
+                .map(|serial| {
+                    let rom = serial << 8 | FAMILY_CODE as u64;
+                    let crc = Crc8::new().update(rom.to_le_bytes().into_iter().take(7));
+                    rom | (crc.finish() as u64) << 56
+                })
+                .collect();
+            Self {
+                roms,
+                active: Vec::new(),
+                bit: 0,
+                complement: false,
+                resets: 0,
+            }
+        }
+    }
+
+    impl BitBus for Bus {
+        fn reset(&mut self) -> Result<()> {
+            self.active = self.roms.clone();
+            self.bit = 0;
+            self.resets += 1;
+            Ok(())
+        }
+
+        fn write_byte(&mut self, byte: u8) -> Result<()> {
+            assert_eq!(byte, CommandCode::AlarmSearch as u8);
+            Ok(())
+        }
+
+        fn read_bit(&mut self) -> Result<bool> {
+            let complement = self.complement;
+            self.complement = !complement;
+            Ok(self
+                .active
+                .iter()
+                .all(|rom| (rom >> self.bit & 1 == 1) != complement))
+        }
+
+        fn write_bit(&mut self, bit: bool) -> Result<()> {
+            self.active.retain(|rom| (rom >> self.bit & 1 == 1) == bit);
+            self.bit += 1;
+            Ok(())
+        }
+    }
+
+    fn search(bus: &mut Bus) -> Vec<Result<Address>> {
+        let mut search = Search::new(CommandCode::AlarmSearch);
+        core::iter::from_fn(|| search.next(bus)).collect()
+    }
+
+    #[test]
+    fn all() {
+        let mut bus = Bus::new(&[3, 1, 2, 0xFFFF_FFFF_FFFF]);
+        let found = search(&mut bus);
+        assert_eq!(bus.resets, 4);
+        let mut roms = bus.roms.clone();
+        roms.sort_by_key(|rom| rom.reverse_bits());
+        assert_eq!(
+            found,
+            roms.into_iter()
+                .map(|rom| Ok(Address(rom)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn early_exit() {
+        let mut bus = Bus::new(&[1, 2, 3]);
+        let mut search = Search::new(CommandCode::AlarmSearch);
+        assert!(matches!(search.next(&mut bus), Some(Ok(_))));
+        assert!(!search.is_done());
+        assert_eq!(bus.resets, 1);
+    }
+
+    #[test]
+    fn branches() {
+        // Serial 2 branches off at ROM bit 9 (serial bit 0), serials 1 and 3
+        // at ROM bit 10.
+        let mut bus = Bus::new(&[1, 2, 3]);
+        let mut search = Search::new(CommandCode::AlarmSearch);
+        let passes: Vec<_> = core::iter::from_fn(|| search.next_pass(&mut bus))
+            .map(Result::unwrap)
+            .collect();
+        let branches: Vec<Vec<_>> = passes
+            .iter()
+            .map(|pass| pass.branches().collect())
+            .collect();
+        assert_eq!(branches[0], [(9, false)]);
+        assert_eq!(branches[1], [(9, true), (10, false)]);
+        assert_eq!(branches[2], [(9, true), (10, true)]);
+        assert_eq!(
+            passes[0].to_string(),
+            format!("{} branches 9:0", passes[0].address)
+        );
+    }
+
+    #[test]
+    fn none() {
+        let mut bus = Bus::new(&[]);
+        assert_eq!(search(&mut bus), []);
+        let mut bus = Bus::new(&[7]);
+        assert_eq!(search(&mut bus), [Ok(Address(bus.roms[0]))]);
+    }
+}
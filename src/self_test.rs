@@ -0,0 +1,314 @@
+//! Startup self-test
+//!
+//! Checks the bus from the transport up to every sensor, so the rest of the
+//! firmware boot can be gated on the result:
+//!
+//! ```ignore
+//! let report = thermometer.self_test();
+//! if !report.passed() {
+//!     error!("{report:?}");
+//! }
+//! ```
+//!
+//! The bus rise time can't be measured through the RMT transport, so the
+//! strength of the pull-up resistor is estimated from repeated scratchpad
+//! reads instead: a slow rising edge is sampled low, so a weak pull-up turns
+//! ones into zeros, while noise flips bits both ways.
+
+use crate::{
+    Ds18b20Driver, Error, FAMILY_CODE, Result,
+    address::{Address, Validation},
+    crc8,
+    logging::{Subsystem, log},
+};
+use core::fmt::{self, Display, Formatter};
+use log::Level;
+
+/// The scratchpad reads per device of the pull-up estimate.
+pub const SAMPLES: usize = 8;
+
+/// Self-test report
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelfTestReport {
+    /// The reset pulse. Fails if the RMT transport is broken or no device
+    /// answered with a presence pulse.
+    pub presence: Result<()>,
+    /// The bus enumeration.
+    pub search: Result<()>,
+    /// The found devices in the search order.
+    pub devices: Vec<DeviceReport>,
+    /// The estimated pull-up strength, `None` without a device to read.
+    pub pull_up: Option<PullUp>,
+}
+
+impl SelfTestReport {
+    /// Returns `true` if all the checks passed, at least one sensor was
+    /// found and the pull-up isn't weak.
+    pub fn passed(&self) -> bool {
+        self.pull_up != Some(PullUp::Weak)
+            && self.presence.is_ok()
+            && self.search.is_ok()
+            && !self.devices.is_empty()
+            && self.devices.iter().all(DeviceReport::passed)
+    }
+}
+
+/// Device report
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeviceReport {
+    pub address: Address,
+    /// The ROM CRC and family code.
+    pub rom: Result<()>,
+    /// The scratchpad read, skipped if the ROM check failed.
+    pub scratchpad: Result<()>,
+}
+
+impl DeviceReport {
+    pub fn passed(&self) -> bool {
+        self.rom.is_ok() && self.scratchpad.is_ok()
+    }
+}
+
+/// Pull-up strength
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PullUp {
+    Strong,
+    /// Occasional ones read as zeros, e.g. a long cable or many sensors for
+    /// the resistor.
+    Marginal,
+    /// The line doesn't rise in time, typically the missing or much too
+    /// large resistor.
+    Weak,
+}
+
+impl Display for PullUp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PullUp::Strong => "strong",
+            PullUp::Marginal => "marginal, consider a smaller pull-up resistor",
+            PullUp::Weak => "weak, check the 4.7 kΩ resistor between DQ and VDD",
+        })
+    }
+}
+
+/// Pull-up evidence from repeated reads of the same scratchpad
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PullUpEvidence {
+    pub frames: usize,
+    /// Frames failing the CRC check
+    pub corrupted: usize,
+    /// Frames read as all zeros, the line never rose
+    pub stuck_low: usize,
+    /// Bits read as zero against the majority of the frames
+    pub dropped_ones: usize,
+    /// Bits read as one against the majority of the frames
+    pub spurious_ones: usize,
+}
+
+impl PullUpEvidence {
+    /// Adds the frames read from one scratchpad.
+    pub fn observe(&mut self, frames: &[[u8; 9]]) {
+        let mut majority = [0u8; 9];
+        for (index, byte) in majority.iter_mut().enumerate() {
+            for bit in 0..u8::BITS {
+                let ones = frames
+                    .iter()
+                    .filter(|frame| frame[index] >> bit & 1 == 1)
+                    .count();
+                if ones * 2 > frames.len() {
+                    *byte |= 1 << bit;
+                }
+            }
+        }
+        for frame in frames {
+            self.frames += 1;
+            if frame.iter().all(|byte| *byte == 0) {
+                self.stuck_low += 1;
+            }
+            if crc8::check(frame).is_err() {
+                self.corrupted += 1;
+            }
+            for (byte, expected) in frame.iter().zip(majority) {
+                self.dropped_ones += (expected & !byte).count_ones() as usize;
+                self.spurious_ones += (byte & !expected).count_ones() as usize;
+            }
+        }
+    }
+
+    /// Classifies the pull-up, `None` without frames.
+    pub fn classify(&self) -> Option<PullUp> {
+        if self.frames == 0 {
+            return None;
+        }
+        let lopsided = self.dropped_ones > 2 * self.spurious_ones;
+        Some(
+            if self.stuck_low > 0 || (lopsided && self.corrupted * 4 >= self.frames) {
+                PullUp::Weak
+            } else if lopsided {
+                PullUp::Marginal
+            } else {
+                PullUp::Strong
+            },
+        )
+    }
+}
+
+impl<'a> Ds18b20Driver<'a> {
+    /// Runs the self-test. Nothing is retried, so flaky wiring shows up in
+    /// the report.
+    pub fn self_test(&mut self) -> SelfTestReport {
+        let mut report = SelfTestReport {
+            presence: self.driver.reset().map_err(Error::from),
+            search: Ok(()),
+            devices: Vec::new(),
+            pull_up: None,
+        };
+        let mut evidence = PullUpEvidence::default();
+        if report.presence.is_ok() {
+            let mut addresses = Vec::new();
+            report.search = self
+                .driver
+                .search()
+                .map_err(Error::from)
+                .and_then(|search| {
+                    for address in search {
+                        addresses.push(Address::from(address?));
+                    }
+                    Ok(())
+                });
+            for address in addresses {
+                let rom = check_rom(&address);
+                let scratchpad = rom.and_then(|_| {
+                    self.initialization()?
+                        .match_rom(&address)?
+                        .read_scratchpad()
+                        .map(|_| ())
+                });
+                if rom.is_ok() {
+                    evidence.observe(&self.sample_scratchpad(&address));
+                }
+                report.devices.push(DeviceReport {
+                    address,
+                    rom,
+                    scratchpad,
+                });
+            }
+        }
+        report.pull_up = evidence.classify();
+        if let Some(pull_up @ (PullUp::Marginal | PullUp::Weak)) = report.pull_up {
+            log!(
+                Subsystem::Bus,
+                Level::Warn,
+                "Pull-up {pull_up}: {evidence:?}"
+            );
+        }
+        let (level, result) = match report.passed() {
+            true => (Level::Info, "passed"),
+            false => (Level::Error, "failed"),
+        };
+        log!(Subsystem::Bus, level, "Self-test {result}: {report:?}");
+        report
+    }
+
+    /// Reads the scratchpad [`SAMPLES`] times without the CRC check, skipping
+    /// the failed transactions.
+    fn sample_scratchpad(&mut self, address: &Address) -> Vec<[u8; 9]> {
+        (0..SAMPLES)
+            .filter_map(|_| {
+                let mut frame = [0; 9];
+                self.initialization()
+                    .and_then(|rom| rom.match_rom(address)?.read_scratchpad_bytes(&mut frame))
+                    .ok()
+                    .map(|_| frame)
+            })
+            .collect()
+    }
+}
+
+fn check_rom(address: &Address) -> Result<()> {
+    address.validate(Validation::Strict)?;
+    match address.family_code() {
+        FAMILY_CODE => Ok(()),
+        family_code => Err(Error::FamilyCode(family_code)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_rom() {
+        assert_eq!(super::check_rom(&Address(0x1E00_0000_0000_0028)), Ok(()));
+        assert_eq!(
+            super::check_rom(&Address(0x0000_0000_0000_0028)),
+            Err(Error::Crc(crate::error::CrcError { crc: 0x82 })),
+        );
+        assert_eq!(
+            super::check_rom(&Address(0xFB00_0000_0000_0010)),
+            Err(Error::FamilyCode(0x10)),
+        );
+    }
+
+    #[test]
+    fn passed() {
+        let device = DeviceReport {
+            address: Address(0x1E00_0000_0000_0028),
+            rom: Ok(()),
+            scratchpad: Ok(()),
+        };
+        let mut report = SelfTestReport {
+            presence: Ok(()),
+            search: Ok(()),
+            devices: Vec::new(),
+            pull_up: None,
+        };
+        assert!(!report.passed());
+        report.devices.push(device);
+        assert!(report.passed());
+        report.pull_up = Some(PullUp::Marginal);
+        assert!(report.passed());
+        report.pull_up = Some(PullUp::Weak);
+        assert!(!report.passed());
+        report.pull_up = Some(PullUp::Strong);
+        report.devices.push(DeviceReport {
+            scratchpad: Err(Error::DeviceNotFound),
+            ..device
+        });
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn pull_up() {
+        const FRAME: [u8; 9] = [99, 1, 75, 70, 127, 255, 13, 16, 21];
+        assert_eq!(PullUpEvidence::default().classify(), None);
+        let mut evidence = PullUpEvidence::default();
+        evidence.observe(&[FRAME; SAMPLES]);
+        assert_eq!(evidence.classify(), Some(PullUp::Strong));
+        // Noise flips bits both ways.
+        let mut frames = [FRAME; SAMPLES];
+        frames[0][0] ^= 0b0000_0011;
+        frames[1][2] ^= 0b1000_0000;
+        let mut evidence = PullUpEvidence::default();
+        evidence.observe(&frames);
+        assert_eq!(evidence.corrupted, 2);
+        assert_eq!((evidence.dropped_ones, evidence.spurious_ones), (2, 1));
+        assert_eq!(evidence.classify(), Some(PullUp::Strong));
+        // Ones read as zeros.
+        frames = [FRAME; SAMPLES];
+        frames[0][4] &= 0b0011_1111;
+        let mut evidence = PullUpEvidence::default();
+        evidence.observe(&frames);
+        assert_eq!(evidence.classify(), Some(PullUp::Marginal));
+        frames[1][5] &= 0b0111_0111;
+        evidence = PullUpEvidence::default();
+        evidence.observe(&frames);
+        assert_eq!(evidence.classify(), Some(PullUp::Weak));
+        frames = [FRAME; SAMPLES];
+        frames[7] = [0; 9];
+        evidence = PullUpEvidence::default();
+        evidence.observe(&frames);
+        assert_eq!(evidence.stuck_low, 1);
+        assert_eq!(evidence.classify(), Some(PullUp::Weak));
+    }
+}
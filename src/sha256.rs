@@ -0,0 +1,195 @@
+//! SHA-256 and HMAC-SHA-256
+//!
+//! Authenticates the remote commands without pulling in a crypto crate. Not
+//! hardened against side channels beyond the constant-time comparison of
+//! [`verify`].
+
+/// Digest length (bytes)
+pub const LENGTH: usize = 32;
+
+const BLOCK: usize = 64;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Running SHA-256
+///
+/// ```
+/// # use thermometer::sha256::Sha256;
+/// let digest = Sha256::new().update(b"a").update(b"bc").finish();
+/// assert_eq!(digest[..4], [0xba, 0x78, 0x16, 0xbf]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; BLOCK],
+    /// The bytes hashed so far.
+    length: u64,
+}
+
+impl Sha256 {
+    pub const fn new() -> Self {
+        Self {
+            state: H,
+            buffer: [0; BLOCK],
+            length: 0,
+        }
+    }
+
+    pub fn update(mut self, data: &[u8]) -> Self {
+        for &byte in data {
+            self.buffer[self.length as usize % BLOCK] = byte;
+            self.length += 1;
+            if (self.length as usize).is_multiple_of(BLOCK) {
+                self.compress();
+            }
+        }
+        self
+    }
+
+    pub fn finish(mut self) -> [u8; LENGTH] {
+        let bits = self.length.wrapping_mul(8);
+        self = self.update(&[0x80]);
+        while self.length as usize % BLOCK != BLOCK - 8 {
+            self = self.update(&[0]);
+        }
+        self = self.update(&bits.to_be_bytes());
+        let mut digest = [0; LENGTH];
+        for (bytes, word) in digest.as_chunks_mut::<4>().0.iter_mut().zip(self.state) {
+            *bytes = word.to_be_bytes();
+        }
+        digest
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0; 64];
+        for (word, bytes) in w.iter_mut().zip(self.buffer.as_chunks::<4>().0) {
+            *word = u32::from_be_bytes(*bytes);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for (k, w) in K.into_iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(k)
+                .wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            (h, g, f, e) = (g, f, e, d.wrapping_add(t1));
+            (d, c, b, a) = (c, b, a, t1.wrapping_add(t2));
+        }
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// HMAC-SHA-256 of the concatenated parts.
+pub fn hmac(key: &[u8], parts: &[&[u8]]) -> [u8; LENGTH] {
+    let mut padded = [0; BLOCK];
+    if key.len() > BLOCK {
+        padded[..LENGTH].copy_from_slice(&Sha256::new().update(key).finish());
+    } else {
+        padded[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new().update(&padded.map(|byte| byte ^ 0x36));
+    for part in parts {
+        inner = inner.update(part);
+    }
+    Sha256::new()
+        .update(&padded.map(|byte| byte ^ 0x5c))
+        .update(&inner.finish())
+        .finish()
+}
+
+/// Compares the digests in constant time.
+pub fn verify(digest: &[u8], expected: &[u8]) -> bool {
+    digest.len() == expected.len()
+        && digest
+            .iter()
+            .zip(expected)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hex(digest: [u8; LENGTH]) -> alloc::string::String {
+        digest
+            .iter()
+            .map(|byte| alloc::format!("{byte:02x}"))
+            .collect()
+    }
+
+    #[test]
+    fn sha256() {
+        assert_eq!(
+            hex(Sha256::new().finish()),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(Sha256::new().update(b"abc").finish()),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // Two blocks.
+        assert_eq!(
+            hex(Sha256::new()
+                .update(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")
+                .finish()),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn hmac() {
+        // RFC 4231, test cases 2 and 6.
+        assert_eq!(
+            hex(super::hmac(
+                b"Jefe",
+                &[b"what do ya want ", b"for nothing?"]
+            )),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(super::hmac(
+                &[0xaa; 131],
+                &[b"Test Using Larger Than Block-Size Key - Hash Key First"]
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+        assert!(verify(&[1, 2], &[1, 2]));
+        assert!(!verify(&[1, 2], &[1, 3]));
+        assert!(!verify(&[1], &[1, 2]));
+    }
+}
@@ -0,0 +1,186 @@
+//! Shared thermometer
+//!
+//! Serializes the bus between tasks. Reads requested within the coalescing
+//! window share one conversion: the first caller waits out the window, then
+//! converts all sensors with Skip ROM and every caller reads its own
+//! scratchpad:
+//!
+//! ```ignore
+//! let thermometer = Arc::new(SharedThermometer::new(driver).window(Duration::from_millis(50)));
+//! let boiler = thread::spawn({
+//!     let thermometer = thermometer.clone();
+//!     move || thermometer.read(&BOILER)
+//! });
+//! let room = thermometer.read(&ROOM)?;
+//! ```
+
+use crate::{
+    Ds18b20Driver, Result,
+    address::Address,
+    cancellation::Cancellation,
+    driver::preflight,
+    logging::{Subsystem, log},
+    pipeline::Reading,
+    timing,
+};
+use log::Level;
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Default coalescing window.
+pub const WINDOW: Duration = Duration::from_millis(20);
+
+/// The time the conversion of a batch is done, once it has been started.
+type Converted = Arc<OnceLock<Result<Instant>>>;
+
+/// Thermometer shared between tasks
+pub struct SharedThermometer<'a> {
+    state: Mutex<State<'a>>,
+    converted: Condvar,
+    window: Duration,
+    cancellation: Cancellation,
+}
+
+struct State<'a> {
+    driver: Ds18b20Driver<'a>,
+    batches: Batches,
+}
+
+impl<'a> SharedThermometer<'a> {
+    pub fn new(driver: Ds18b20Driver<'a>) -> Self {
+        Self {
+            cancellation: driver.cancellation(),
+            state: Mutex::new(State {
+                driver,
+                batches: Batches::default(),
+            }),
+            converted: Condvar::new(),
+            window: WINDOW,
+        }
+    }
+
+    /// Sets the coalescing window. A zero window still coalesces the reads
+    /// requested while the bus is busy.
+    pub fn window(self, window: Duration) -> Self {
+        Self { window, ..self }
+    }
+
+    /// Runs the operation with exclusive access to the driver.
+    pub fn with<T>(&self, operation: impl FnOnce(&mut Ds18b20Driver<'a>) -> T) -> T {
+        operation(&mut self.lock().driver)
+    }
+
+    /// Receive reading, sharing the conversion with the reads requested
+    /// within the window.
+    pub fn read(&self, address: &Address) -> Result<Reading> {
+        preflight(address)?;
+        let mut state = self.lock();
+        let (converted, leader) = state.batches.join(*address);
+        if leader {
+            drop(state);
+            let slept = self.cancellation.sleep(self.window);
+            state = self.lock();
+            let addresses = state.batches.close();
+            let result = slept.and_then(|_| Self::convert(&mut state.driver, &addresses));
+            let _ = converted.set(result.map(|_| Instant::now() + timing::CONVERSION));
+            self.converted.notify_all();
+        } else {
+            while converted.get().is_none() {
+                state = self
+                    .converted
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+        drop(state);
+        let ready_at = converted.get().copied().expect("set before notified")?;
+        self.cancellation
+            .sleep(ready_at.saturating_duration_since(Instant::now()))?;
+        let scratchpad = self
+            .lock()
+            .driver
+            .retry(|this| this.initialization()?.match_rom(address)?.read_scratchpad())?;
+        let converted = ready_at - timing::CONVERSION;
+        Ok(Reading::new(*address, scratchpad.temperature).converted_at(converted))
+    }
+
+    /// Starts the conversion of the batch, broadcast if it has more than one
+    /// sensor.
+    fn convert(driver: &mut Ds18b20Driver, addresses: &[Address]) -> Result<()> {
+        log!(
+            Subsystem::Bus,
+            Level::Debug,
+            "Coalesced conversion of {} sensors",
+            addresses.len()
+        );
+        match addresses {
+            [address] => driver.retry(|this| {
+                this.initialization()?
+                    .match_rom(address)?
+                    .start_conversion()
+            }),
+            _ => driver.retry(|this| this.initialization()?.skip_rom()?.start_conversion()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<'a>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The batch collecting reads, if any.
+#[derive(Debug, Default)]
+struct Batches {
+    collecting: Option<(Converted, Vec<Address>)>,
+}
+
+impl Batches {
+    /// Joins the collecting batch or opens a new one, in which case the
+    /// caller leads it.
+    fn join(&mut self, address: Address) -> (Converted, bool) {
+        match &mut self.collecting {
+            Some((converted, addresses)) => {
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+                (converted.clone(), false)
+            }
+            None => {
+                let converted = Converted::default();
+                self.collecting = Some((converted.clone(), vec![address]));
+                (converted, true)
+            }
+        }
+    }
+
+    /// Closes the collecting batch, the later reads open a new one.
+    fn close(&mut self) -> Vec<Address> {
+        self.collecting
+            .take()
+            .map_or_else(Vec::new, |(_, addresses)| addresses)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn batches() {
+        let mut batches = Batches::default();
+        let (first, leader) = batches.join(Address(1));
+        assert!(leader);
+        let (second, leader) = batches.join(Address(2));
+        assert!(!leader);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!batches.join(Address(1)).1);
+        assert_eq!(batches.close(), [Address(1), Address(2)]);
+        let (third, leader) = batches.join(Address(2));
+        assert!(leader);
+        assert!(!Arc::ptr_eq(&first, &third));
+        assert_eq!(batches.close(), [Address(2)]);
+        assert_eq!(batches.close(), []);
+    }
+}
@@ -0,0 +1,180 @@
+//! Simulated sensors
+//!
+//! Sensors without hardware for demos, UI development and CI. A simulated
+//! sensor has a valid DS18B20 address, so it can be registered and sampled
+//! alongside real devices:
+//!
+//! ```ignore
+//! let boiler = SimulatedSensor::new(1, Waveform::Sine {
+//!     mean: 60.0,
+//!     amplitude: 5.0,
+//!     period: Duration::from_secs(600),
+//! })
+//! .noise(0.1);
+//! registry.set_zone(boiler.address(), "boiler")?;
+//! let mut sampler = Sampler::new(driver, addresses, interval).simulate(boiler);
+//! ```
+
+use crate::{
+    FAMILY_CODE,
+    address::Address,
+    backoff::{Entropy, Xorshift},
+    crc8::Crc8,
+    pipeline::Reading,
+};
+use std::{f32::consts::TAU, time::Duration};
+
+/// Simulated temperature course (°C)
+#[derive(Clone, Debug, PartialEq)]
+pub enum Waveform {
+    Constant(f32),
+    Sine {
+        mean: f32,
+        amplitude: f32,
+        period: Duration,
+    },
+    /// Changes by `rate` °C per minute from `start` until it reaches `end`.
+    Ramp {
+        start: f32,
+        rate: f32,
+        end: f32,
+    },
+    /// Plays back the recorded points, interpolating linearly between them
+    /// and holding the last one.
+    Profile(Vec<(Duration, f32)>),
+}
+
+impl Waveform {
+    /// The temperature at the time since the start.
+    pub fn temperature(&self, at: Duration) -> f32 {
+        match self {
+            Waveform::Constant(temperature) => *temperature,
+            Waveform::Sine {
+                mean,
+                amplitude,
+                period,
+            } => {
+                let phase = at.as_secs_f32() / period.as_secs_f32().max(f32::MIN_POSITIVE);
+                mean + amplitude * (TAU * phase.fract()).sin()
+            }
+            Waveform::Ramp { start, rate, end } => {
+                let temperature = start + rate * at.as_secs_f32() / 60.0;
+                if start <= end {
+                    temperature.min(*end)
+                } else {
+                    temperature.max(*end)
+                }
+            }
+            Waveform::Profile(points) => {
+                let index = points.partition_point(|(time, _)| *time <= at);
+                match (
+                    index.checked_sub(1).map(|index| points[index]),
+                    points.get(index),
+                ) {
+                    (None, None) => f32::NAN,
+                    (Some((_, temperature)), None) | (None, Some(&(_, temperature))) => temperature,
+                    (Some((from, start)), Some(&(to, end))) => {
+                        let fraction = (at - from).as_secs_f32() / (to - from).as_secs_f32();
+                        start + (end - start) * fraction
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Simulated sensor
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulatedSensor {
+    address: Address,
+    waveform: Waveform,
+    noise: f32,
+    entropy: Xorshift,
+}
+
+impl SimulatedSensor {
+    /// The address is made of the family code, the serial number and a
+    /// valid CRC.
+    pub fn new(serial: u64, waveform: Waveform) -> Self {
+        let serial = serial & 0xFFFF_FFFF_FFFF;
+        let address = (serial << 8) | FAMILY_CODE as u64;
+        let crc = Crc8::new()
+            .update(address.to_le_bytes().into_iter().take(7))
+            .finish();
+        Self {
+            address: Address(address | (crc as u64) << 56),
+            waveform,
+            noise: 0.0,
+            entropy: Xorshift::new(serial as _),
+        }
+    }
+
+    /// Adds uniform noise of up to the amplitude (°C).
+    pub fn noise(self, amplitude: f32) -> Self {
+        Self {
+            noise: amplitude.abs(),
+            ..self
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Reads the temperature at the time since the start, quantized to the
+    /// 12-bit resolution.
+    pub fn read(&mut self, at: Duration) -> Reading {
+        let mut temperature = self.waveform.temperature(at);
+        if self.noise > 0.0 {
+            let fraction = self.entropy.next_u32() as f32 / u32::MAX as f32;
+            temperature += self.noise * (fraction * 2.0 - 1.0);
+        }
+        Reading::new(self.address, (temperature * 16.0).round() / 16.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::address::Validation;
+
+    #[test]
+    fn address() {
+        let sensor = SimulatedSensor::new(1, Waveform::Constant(21.0));
+        assert_eq!(sensor.address().validate(Validation::Strict), Ok(()));
+        assert_eq!(sensor.address().family_code(), FAMILY_CODE);
+    }
+
+    #[test]
+    fn waveform() {
+        let sine = Waveform::Sine {
+            mean: 20.0,
+            amplitude: 5.0,
+            period: Duration::from_secs(60),
+        };
+        assert!((sine.temperature(Duration::from_secs(15)) - 25.0).abs() < 1e-3);
+        let ramp = Waveform::Ramp {
+            start: 20.0,
+            rate: -1.0,
+            end: 18.0,
+        };
+        assert_eq!(ramp.temperature(Duration::from_secs(60)), 19.0);
+        assert_eq!(ramp.temperature(Duration::from_secs(600)), 18.0);
+        let profile = Waveform::Profile(vec![
+            (Duration::from_secs(10), 20.0),
+            (Duration::from_secs(20), 30.0),
+        ]);
+        assert_eq!(profile.temperature(Duration::ZERO), 20.0);
+        assert_eq!(profile.temperature(Duration::from_secs(15)), 25.0);
+        assert_eq!(profile.temperature(Duration::from_secs(60)), 30.0);
+    }
+
+    #[test]
+    fn noise() {
+        let mut sensor = SimulatedSensor::new(1, Waveform::Constant(21.0)).noise(0.5);
+        for _ in 0..100 {
+            let reading = sensor.read(Duration::ZERO);
+            assert!((reading.temperature - 21.0).abs() <= 0.5 + 1.0 / 32.0);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Reading sinks
+//!
+//! Sinks receive the readings of the [`Sampler`](crate::sampler::Sampler),
+//! e.g. an MQTT publisher or an on-flash datalog. Each sink declares its
+//! [`QoS`], so a flaky link of one sink stalls neither the others nor the
+//! control loop unless it must:
+//!
+//! A sink receives the readings of a sample as one borrowed slice. Nothing is
+//! copied on the way unless a delivery fails and the readings are buffered,
+//! and the buffers are allocated once, so sampling at a high rate doesn't
+//! allocate per sample:
+//!
+//! ```ignore
+//! impl Sink for Mqtt {
+//!     fn send(&mut self, readings: &[Reading]) -> Result<()> {
+//!         self.payload.clear();
+//!         encode(readings, &mut self.payload);
+//!         self.client.publish(TOPIC, &self.payload)
+//!     }
+//!
+//!     fn qos(&self) -> QoS {
+//!         QoS::Buffered(100)
+//!     }
+//! }
+//! let mut sampler = Sampler::new(driver, addresses, interval)
+//!     .sink(Mqtt::new(client))
+//!     .sink(Datalog::new(partition));
+//! ```
+
+use crate::{Result, cancellation::Cancellation, pipeline::Reading};
+use std::{collections::VecDeque, time::Duration};
+
+/// The delay between the delivery attempts of a [`QoS::MustDeliver`] sink.
+pub const RETRY: Duration = Duration::from_millis(100);
+
+/// Delivery guarantee
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QoS {
+    /// Readings that fail to send are dropped.
+    #[default]
+    BestEffort,
+    /// Readings that fail to send are buffered and resent in order, up to
+    /// the number of readings; the oldest ones are dropped first.
+    Buffered(usize),
+    /// Blocks until all readings are sent, retrying every [`RETRY`]. The
+    /// wait is interrupted by cancellation, the readings are kept.
+    MustDeliver,
+}
+
+/// Reading sink
+pub trait Sink {
+    /// Sends the readings, all or none of them. Never called with an empty
+    /// slice.
+    fn send(&mut self, readings: &[Reading]) -> Result<()>;
+
+    fn qos(&self) -> QoS {
+        QoS::BestEffort
+    }
+}
+
+impl<F: FnMut(&[Reading]) -> Result<()>> Sink for F {
+    fn send(&mut self, readings: &[Reading]) -> Result<()> {
+        self(readings)
+    }
+}
+
+/// Delivery accounting of a sink
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SinkStats {
+    pub delivered: usize,
+    pub dropped: usize,
+    /// Failed sends, including the retried ones. A send carries all the
+    /// readings of a sample or of the buffer.
+    pub failures: usize,
+    /// Readings waiting to be sent.
+    pub buffered: usize,
+}
+
+/// A sink with its buffer
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) struct Outlet {
+    sink: Box<dyn Sink>,
+    qos: QoS,
+    buffer: VecDeque<Reading>,
+    stats: SinkStats,
+}
+
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+impl Outlet {
+    pub(crate) fn new(sink: impl Sink + 'static) -> Self {
+        let qos = sink.qos();
+        Self {
+            qos,
+            sink: Box::new(sink),
+            buffer: match qos {
+                QoS::Buffered(capacity) => VecDeque::with_capacity(capacity),
+                QoS::BestEffort | QoS::MustDeliver => VecDeque::new(),
+            },
+            stats: SinkStats::default(),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> SinkStats {
+        SinkStats {
+            buffered: self.buffer.len(),
+            ..self.stats
+        }
+    }
+
+    /// Delivers the readings straight from the slice, buffering them only
+    /// behind earlier undelivered ones or on failure.
+    pub(crate) fn deliver(&mut self, readings: &[Reading], cancellation: &Cancellation) {
+        if readings.is_empty() {
+            return;
+        }
+        if self.buffer.is_empty() && self.send(readings) {
+            return;
+        }
+        match self.qos {
+            QoS::BestEffort => self.stats.dropped += readings.len(),
+            QoS::Buffered(capacity) => {
+                for reading in readings {
+                    if self.buffer.len() >= capacity {
+                        self.buffer.pop_front();
+                        self.stats.dropped += 1;
+                    }
+                    if capacity != 0 {
+                        self.buffer.push_back(*reading);
+                    } else {
+                        self.stats.dropped += 1;
+                    }
+                }
+                self.flush();
+            }
+            QoS::MustDeliver => {
+                self.buffer.extend(readings);
+                while !self.flush() && cancellation.sleep(RETRY).is_ok() {}
+            }
+        }
+    }
+
+    /// Sends the buffered readings at once. Returns whether the buffer is
+    /// empty.
+    pub(crate) fn flush(&mut self) -> bool {
+        if self.buffer.is_empty() {
+            return true;
+        }
+        let readings = self.buffer.make_contiguous();
+        let count = readings.len();
+        let sent = self.sink.send(readings).is_ok();
+        if sent {
+            self.buffer.clear();
+            self.stats.delivered += count;
+        } else {
+            self.stats.failures += 1;
+        }
+        sent
+    }
+
+    fn send(&mut self, readings: &[Reading]) -> bool {
+        let sent = self.sink.send(readings).is_ok();
+        if sent {
+            self.stats.delivered += readings.len();
+        } else {
+            self.stats.failures += 1;
+        }
+        sent
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Error, address::Address};
+    use std::{cell::Cell, rc::Rc};
+
+    /// Fails while the link is down.
+    struct Link {
+        up: Rc<Cell<bool>>,
+        sent: Rc<Cell<usize>>,
+        qos: QoS,
+    }
+
+    impl Sink for Link {
+        fn send(&mut self, readings: &[Reading]) -> Result<()> {
+            if !self.up.get() {
+                return Err(Error::DeviceNotFound);
+            }
+            self.sent.set(self.sent.get() + readings.len());
+            Ok(())
+        }
+
+        fn qos(&self) -> QoS {
+            self.qos
+        }
+    }
+
+    fn link(qos: QoS) -> (Outlet, Rc<Cell<bool>>) {
+        let up = Rc::new(Cell::new(false));
+        let link = Link {
+            up: up.clone(),
+            sent: Rc::default(),
+            qos,
+        };
+        (Outlet::new(link), up)
+    }
+
+    #[test]
+    fn deliver() {
+        let readings = [1, 2, 3].map(|address| Reading::new(Address(address), 20.0));
+        let cancellation = Cancellation::new();
+
+        let (mut outlet, up) = link(QoS::BestEffort);
+        outlet.deliver(&readings, &cancellation);
+        up.set(true);
+        outlet.deliver(&readings[..1], &cancellation);
+        assert_eq!(
+            outlet.stats(),
+            SinkStats {
+                delivered: 1,
+                dropped: 3,
+                failures: 1,
+                buffered: 0,
+            }
+        );
+
+        let (mut outlet, up) = link(QoS::Buffered(2));
+        outlet.deliver(&readings, &cancellation);
+        assert_eq!(outlet.stats().buffered, 2);
+        assert_eq!(outlet.stats().dropped, 1);
+        up.set(true);
+        assert!(outlet.flush());
+        outlet.deliver(&readings[..1], &cancellation);
+        assert_eq!(outlet.stats().delivered, 3);
+        assert_eq!(outlet.stats().buffered, 0);
+
+        let (mut outlet, _) = link(QoS::MustDeliver);
+        cancellation.cancel();
+        outlet.deliver(&readings, &cancellation);
+        assert_eq!(outlet.stats().buffered, 3);
+        assert_eq!(outlet.stats().dropped, 0);
+    }
+}
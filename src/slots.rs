@@ -0,0 +1,203 @@
+//! Slot assignment
+//!
+//! Fixed, numbered slots for the sensors: the register blocks of a Modbus
+//! map, the rows of a fixed-layout display or the indices of a compact radio
+//! payload. A sensor keeps its slot until it is explicitly moved or
+//! released, and the assignment survives reboots:
+//!
+//! ```ignore
+//! let mut slots = SlotMap::<8>::new();
+//! slots.restore(&mut nvs)?;
+//! for address in &addresses {
+//!     slots.assign(*address)?;
+//! }
+//! // Replaced probe: the new sensor takes over the register block.
+//! slots.assign_to(slots.release(&old).unwrap(), new)?;
+//! slots.persist(&mut nvs)?;
+//! ```
+
+use crate::{
+    address::Address,
+    collections::CAPACITY,
+    error::{Error, Result},
+    persistence::Store,
+};
+use alloc::vec::Vec;
+
+/// The store key of the slots.
+const SLOTS: &str = "slots";
+
+/// Sensors assigned to `N` numbered slots
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotMap<const N: usize = CAPACITY> {
+    slots: [Option<Address>; N],
+}
+
+impl<const N: usize> SlotMap<N> {
+    pub const fn new() -> Self {
+        Self { slots: [None; N] }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The sensor in the slot.
+    pub fn get(&self, slot: usize) -> Option<Address> {
+        self.slots.get(slot).copied().flatten()
+    }
+
+    /// The slot of the sensor.
+    pub fn slot(&self, address: &Address) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|assigned| assigned.as_ref() == Some(address))
+    }
+
+    /// The assigned slots and their sensors, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Address)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, address)| Some((slot, address.as_ref()?)))
+    }
+
+    /// Assigns the sensor to the first free slot, unless it has one. Fails
+    /// with [`Error::Capacity`] if all slots are taken.
+    pub fn assign(&mut self, address: Address) -> Result<usize> {
+        if let Some(slot) = self.slot(&address) {
+            return Ok(slot);
+        }
+        let slot = self
+            .slots
+            .iter()
+            .position(Option::is_none)
+            .ok_or(Error::Capacity(N))?;
+        self.slots[slot] = Some(address);
+        Ok(slot)
+    }
+
+    /// Moves the sensor to the slot, returning the sensor displaced from it,
+    /// which is left unassigned. Fails with [`Error::Slot`] if there is no
+    /// such slot.
+    pub fn assign_to(&mut self, slot: usize, address: Address) -> Result<Option<Address>> {
+        if slot >= N {
+            return Err(Error::Slot(slot));
+        }
+        self.release(&address);
+        Ok(self.slots[slot].replace(address))
+    }
+
+    /// Swaps the sensors of the slots.
+    pub fn swap(&mut self, a: usize, b: usize) -> Result<()> {
+        if let Some(slot) = [a, b].into_iter().find(|slot| *slot >= N) {
+            return Err(Error::Slot(slot));
+        }
+        self.slots.swap(a, b);
+        Ok(())
+    }
+
+    /// Unassigns the sensor, returning its slot.
+    pub fn release(&mut self, address: &Address) -> Option<usize> {
+        let slot = self.slot(address)?;
+        self.slots[slot] = None;
+        Some(slot)
+    }
+
+    pub fn clear(&mut self) {
+        self.slots = [None; N];
+    }
+
+    /// Saves the assignment under `slots`: per assigned slot the
+    /// little-endian slot number (`u16`) and address.
+    pub fn persist(&self, store: &mut dyn Store) -> Result<()> {
+        let mut value = Vec::with_capacity(self.len() * 10);
+        for (slot, address) in self.iter() {
+            value.extend_from_slice(&(slot as u16).to_le_bytes());
+            value.extend_from_slice(&address.0.to_le_bytes());
+        }
+        store.save(SLOTS, &value)
+    }
+
+    /// Replaces the assignment with the persisted one, if any. Nothing is
+    /// changed on failure, e.g. with [`Error::Slot`] if the assignment was
+    /// persisted with more slots.
+    pub fn restore(&mut self, store: &mut dyn Store) -> Result<()> {
+        let Some(value) = store.load(SLOTS)? else {
+            return Ok(());
+        };
+        let (chunks, remainder) = value.as_chunks::<10>();
+        if !remainder.is_empty() {
+            return Err(Error::Decode {
+                offset: value.len() - remainder.len(),
+            });
+        }
+        let mut slots = Self::new();
+        for &[low, high, ref address @ ..] in chunks {
+            slots.assign_to(
+                u16::from_le_bytes([low, high]) as _,
+                Address(u64::from_le_bytes(*address)),
+            )?;
+        }
+        *self = slots;
+        Ok(())
+    }
+}
+
+impl<const N: usize> Default for SlotMap<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{collections::BTreeMap, string::String};
+
+    #[test]
+    fn assign() {
+        let mut slots = SlotMap::<3>::new();
+        assert_eq!(slots.assign(Address(1)), Ok(0));
+        assert_eq!(slots.assign(Address(2)), Ok(1));
+        assert_eq!(slots.assign(Address(1)), Ok(0));
+        assert_eq!(slots.assign_to(2, Address(3)), Ok(None));
+        assert_eq!(slots.assign(Address(4)), Err(Error::Capacity(3)));
+        assert_eq!(slots.assign_to(3, Address(4)), Err(Error::Slot(3)));
+        // Moving a sensor frees its slot and displaces the occupant.
+        assert_eq!(slots.assign_to(2, Address(1)), Ok(Some(Address(3))));
+        assert_eq!(slots.get(0), None);
+        assert_eq!(slots.slot(&Address(1)), Some(2));
+        slots.swap(1, 2).unwrap();
+        assert_eq!(
+            slots.iter().collect::<Vec<_>>(),
+            [(1, &Address(1)), (2, &Address(2))]
+        );
+        assert_eq!(slots.release(&Address(2)), Some(2));
+        assert_eq!(slots.len(), 1);
+    }
+
+    #[test]
+    fn persist() {
+        let mut store = BTreeMap::<String, Vec<u8>>::new();
+        let mut slots = SlotMap::<4>::new();
+        slots.assign_to(3, Address(7)).unwrap();
+        slots.assign(Address(5)).unwrap();
+        slots.persist(&mut store).unwrap();
+        let mut restored = SlotMap::<4>::new();
+        restored.restore(&mut store).unwrap();
+        assert_eq!(restored, slots);
+        let mut smaller = SlotMap::<2>::new();
+        smaller.assign(Address(1)).unwrap();
+        assert_eq!(smaller.restore(&mut store), Err(Error::Slot(3)));
+        assert_eq!(smaller.get(0), Some(Address(1)));
+    }
+}
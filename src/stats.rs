@@ -0,0 +1,164 @@
+//! Bus statistics
+//!
+//! The driver records every bus operation: the number of RMT symbols, the
+//! transaction durations and the error codes. Comparing them between RMT
+//! channels, clock dividers or with and without Wi-Fi traffic shows where
+//! flaky readings come from:
+//!
+//! ```ignore
+//! let stats = thermometer.bus_stats();
+//! let read = stats.get(Operation::Read);
+//! info!("read: {} failures, max {:?}", read.failures, read.max);
+//! for (code, count) in read.errors() {
+//!     info!("  {code:#x}: {count}");
+//! }
+//! thermometer.reset_bus_stats();
+//! ```
+//!
+//! Retries are counted against the operation whose failure caused them;
+//! failures detected after the transfer (CRC, invalid data) against the last
+//! read.
+
+use crate::collections::Map;
+use core::time::Duration;
+
+/// The number of distinct error codes kept per operation.
+pub const ERROR_CODES: usize = 8;
+
+/// Bus operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Reset,
+    Write,
+    Read,
+    /// One search pass, yielding one address.
+    Search,
+}
+
+impl Operation {
+    pub const ALL: [Self; 4] = [Self::Reset, Self::Write, Self::Read, Self::Search];
+
+    /// The approximate number of RMT symbols of an operation transferring
+    /// the bytes: one per time slot, two per reset pulse (pulse and
+    /// presence).
+    pub fn symbols(&self, bytes: usize) -> usize {
+        match self {
+            Operation::Reset => 2,
+            Operation::Write | Operation::Read => bytes * 8,
+            // Reset, Search ROM command and three slots per ROM bit.
+            Operation::Search => 2 + 8 + 3 * 64,
+        }
+    }
+}
+
+/// Statistics of an operation
+#[derive(Clone, Debug, Default)]
+pub struct OperationStats {
+    pub count: usize,
+    pub failures: usize,
+    /// RMT symbols
+    pub symbols: usize,
+    /// Total duration
+    pub total: Duration,
+    /// Longest duration
+    pub max: Duration,
+    /// Retries of failed driver operations
+    pub retries: usize,
+    /// Driver operations that failed after all retries
+    pub exhausted: usize,
+    errors: Map<i32, usize, ERROR_CODES>,
+}
+
+impl OperationStats {
+    /// Mean duration
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count != 0).then(|| self.total / self.count as u32)
+    }
+
+    /// Error codes and their counts. Codes beyond [`ERROR_CODES`] are only
+    /// counted in `failures`.
+    pub fn errors(&self) -> impl Iterator<Item = (i32, usize)> + '_ {
+        self.errors.iter().map(|(code, count)| (*code, *count))
+    }
+
+    fn record(&mut self, symbols: usize, duration: Duration, error: Option<i32>) {
+        self.count += 1;
+        self.symbols += symbols;
+        self.total += duration;
+        self.max = self.max.max(duration);
+        if let Some(code) = error {
+            self.failures += 1;
+            if let Ok(count) = self.errors.get_or_insert(code, 0) {
+                *count += 1;
+            }
+        }
+    }
+}
+
+/// Bus statistics
+#[derive(Clone, Debug, Default)]
+pub struct BusStats {
+    operations: [OperationStats; 4],
+}
+
+impl BusStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, operation: Operation) -> &OperationStats {
+        &self.operations[operation as usize]
+    }
+
+    /// Records an operation transferring the bytes. `error` is the error
+    /// code of a failed one.
+    pub fn record(
+        &mut self,
+        operation: Operation,
+        bytes: usize,
+        duration: Duration,
+        error: Option<i32>,
+    ) {
+        self.operations[operation as usize].record(operation.symbols(bytes), duration, error);
+    }
+
+    /// Records a retry caused by a failure of the operation.
+    pub fn record_retry(&mut self, operation: Operation) {
+        self.operations[operation as usize].retries += 1;
+    }
+
+    /// Records running out of retries after a failure of the operation.
+    pub fn record_exhausted(&mut self, operation: Operation) {
+        self.operations[operation as usize].exhausted += 1;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record() {
+        let mut stats = BusStats::new();
+        let millisecond = Duration::from_millis(1);
+        stats.record(Operation::Write, 9, millisecond, None);
+        stats.record(Operation::Write, 1, 3 * millisecond, Some(0x107));
+        stats.record(Operation::Write, 1, 2 * millisecond, Some(0x107));
+        let write = stats.get(Operation::Write);
+        assert_eq!(write.count, 3);
+        assert_eq!(write.failures, 2);
+        assert_eq!(write.symbols, 88);
+        assert_eq!(write.max, 3 * millisecond);
+        assert_eq!(write.mean(), Some(2 * millisecond));
+        assert_eq!(write.errors().collect::<Vec<_>>(), [(0x107, 2)]);
+        assert_eq!(stats.get(Operation::Read).mean(), None);
+        stats.record_retry(Operation::Reset);
+        assert_eq!(stats.get(Operation::Reset).retries, 1);
+        stats.clear();
+        assert_eq!(stats.get(Operation::Write).count, 0);
+    }
+}
@@ -0,0 +1,569 @@
+//! Multi-sensor sweep
+//!
+//! A sweep converts and reads a set of sensors. On parasite-powered buses
+//! every converting sensor draws current from the strong pull-up, so the
+//! number of simultaneous conversions can be limited with a [`PowerBudget`].
+//! Addressing the next sensor of a batch resets the bus, which drops the
+//! strong pull-up of the sensors converting already, so parasite-powered
+//! buses convert one sensor at a time under any budget but
+//! [`Unlimited`](PowerBudget::Unlimited).
+//!
+//! The conversion time depends on the resolution. Given the resolutions of the
+//! sensors, [`read_all_with`](Ds18b20Driver::read_all_with) waits only as long
+//! as the slowest sensor of a batch and, with [`Schedule::Auto`], groups the
+//! sensors by resolution whenever that shortens the sweep:
+//!
+//! ```ignore
+//! let sensors: Vec<_> = addresses
+//!     .iter()
+//!     .map(|address| Ok((*address, thermometer.info(address)?.resolution)))
+//!     .collect::<Result<_>>()?;
+//! let sweep = thermometer.read_all_with(&sensors, PowerBudget::MaxSimultaneousConversions(4), Schedule::Auto)?;
+//! ```
+//!
+//! Memory-constrained builds can stream the readings one at a time with
+//! [`read_iter`](Ds18b20Driver::read_iter) instead of collecting a [`Sweep`]:
+//!
+//! ```ignore
+//! for reading in thermometer.read_iter(&addresses, PowerBudget::Unlimited) {
+//!     // ...
+//! }
+//! ```
+
+use crate::{
+    Ds18b20Driver, Result,
+    address::Address,
+    driver::preflight,
+    logging::{Subsystem, log},
+    pipeline::Reading,
+    power::PowerMode,
+    scratchpad::Resolution,
+    timing,
+};
+use log::Level;
+use std::{
+    slice::{Chunks, Iter},
+    time::{Duration, Instant},
+};
+
+/// The maximum batch size of a budgeted streaming sweep.
+const STREAM_BATCH: usize = 8;
+
+/// The bus time of a conversion start by Match ROM: the reset pulse and ten
+/// bytes at standard speed.
+const START: Duration = timing::transaction(10, 0);
+
+/// Power budget
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PowerBudget {
+    /// All sensors convert simultaneously (Skip ROM + Convert T).
+    #[default]
+    Unlimited,
+    /// At most N sensors convert simultaneously. The sensors are converted in
+    /// batches of N, one batch after another, one sensor at a time on
+    /// parasite-powered buses.
+    MaxSimultaneousConversions(usize),
+}
+
+impl PowerBudget {
+    /// The number of sensors per batch.
+    fn batch_size(&self, count: usize) -> usize {
+        match *self {
+            PowerBudget::Unlimited => count.max(1),
+            PowerBudget::MaxSimultaneousConversions(count) => count.max(1),
+        }
+    }
+}
+
+/// Conversion schedule of sensors with different resolutions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Schedule {
+    /// Batches in the order of the addresses.
+    InOrder,
+    /// Batches of sensors with the same resolution, fastest first.
+    ByResolution,
+    /// The schedule with the shorter estimated sweep.
+    #[default]
+    Auto,
+}
+
+/// Conversion batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Batch {
+    /// The indices of the sensors.
+    indices: Vec<usize>,
+    /// Converted all at once by Skip ROM.
+    broadcast: bool,
+    /// The conversion time of the slowest sensor.
+    conversion: Duration,
+}
+
+/// Plans the conversion batches, the chosen schedule is never `Auto`.
+fn plan(
+    resolutions: &[Resolution],
+    budget: PowerBudget,
+    schedule: Schedule,
+) -> (Schedule, Vec<Batch>) {
+    if resolutions.is_empty() {
+        let schedule = match schedule {
+            Schedule::Auto => Schedule::InOrder,
+            schedule => schedule,
+        };
+        return (schedule, Vec::new());
+    }
+    let mut indices: Vec<_> = (0..resolutions.len()).collect();
+    let batches = match schedule {
+        Schedule::InOrder => match budget {
+            PowerBudget::Unlimited => vec![batch(resolutions, indices, true)],
+            PowerBudget::MaxSimultaneousConversions(_) => indices
+                .chunks(budget.batch_size(resolutions.len()))
+                .map(|chunk| batch(resolutions, chunk.to_vec(), false))
+                .collect(),
+        },
+        Schedule::ByResolution => {
+            indices.sort_by_key(|index| resolutions[*index].bits());
+            let size = budget.batch_size(resolutions.len());
+            indices
+                .chunk_by(|left, right| resolutions[*left] == resolutions[*right])
+                .flat_map(|group| group.chunks(size))
+                .map(|chunk| batch(resolutions, chunk.to_vec(), false))
+                .collect()
+        }
+        Schedule::Auto => {
+            let in_order = plan(resolutions, budget, Schedule::InOrder);
+            let by_resolution = plan(resolutions, budget, Schedule::ByResolution);
+            return if estimate(&by_resolution.1) < estimate(&in_order.1) {
+                by_resolution
+            } else {
+                in_order
+            };
+        }
+    };
+    (schedule, batches)
+}
+
+fn batch(resolutions: &[Resolution], indices: Vec<usize>, broadcast: bool) -> Batch {
+    let conversion = indices
+        .iter()
+        .map(|index| timing::conversion(resolutions[*index]))
+        .max()
+        .unwrap_or_default();
+    Batch {
+        indices,
+        broadcast,
+        conversion,
+    }
+}
+
+/// The estimated time spent starting and waiting for the conversions.
+fn estimate(batches: &[Batch]) -> Duration {
+    batches
+        .iter()
+        .map(|batch| match batch.broadcast {
+            true => START + batch.conversion,
+            false => START * batch.indices.len() as u32 + batch.conversion,
+        })
+        .sum()
+}
+
+/// Sweep
+#[derive(Clone, Debug)]
+pub struct Sweep {
+    /// The readings in the order of the requested addresses.
+    pub readings: Vec<Result<Reading>>,
+    /// The read timings in the order of the readings.
+    pub timings: Vec<Timing>,
+    /// The number of conversion batches.
+    pub batches: usize,
+    /// The schedule of the batches.
+    pub schedule: Schedule,
+    /// The time spent waiting for conversions.
+    pub conversion: Duration,
+    /// The total sweep duration.
+    pub duration: Duration,
+}
+
+impl Sweep {
+    /// The slowest sensor read.
+    pub fn max_latency(&self) -> Option<Duration> {
+        self.timings.iter().map(|timing| timing.latency).max()
+    }
+
+    /// The mean sensor read time.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        let count = self.timings.len() as u32;
+        (count != 0).then(|| {
+            self.timings
+                .iter()
+                .map(|timing| timing.latency)
+                .sum::<Duration>()
+                / count
+        })
+    }
+}
+
+/// Read timing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// The start of the read since the start of the sweep.
+    pub offset: Duration,
+    /// The read time including retries.
+    pub latency: Duration,
+}
+
+impl<'a> Ds18b20Driver<'a> {
+    /// Converts and reads all the sensors within the power budget.
+    ///
+    /// Bus failures while converting the whole bus at once fail the sweep,
+    /// failures of a single sensor are reported in its reading.
+    pub fn read_all(&mut self, addresses: &[Address], budget: PowerBudget) -> Result<Sweep> {
+        let resolutions = vec![Resolution::Twelve; addresses.len()];
+        let budget = self.budget(budget);
+        let (schedule, batches) = plan(&resolutions, budget, Schedule::InOrder);
+        self.run_batches(addresses, schedule, batches)
+    }
+
+    /// Converts and reads all the sensors of the known resolutions within the
+    /// power budget, waiting for each batch only as long as its slowest
+    /// sensor needs.
+    pub fn read_all_with(
+        &mut self,
+        sensors: &[(Address, Resolution)],
+        budget: PowerBudget,
+        schedule: Schedule,
+    ) -> Result<Sweep> {
+        let (addresses, resolutions): (Vec<_>, Vec<_>) = sensors.iter().copied().unzip();
+        let budget = self.budget(budget);
+        let (schedule, batches) = plan(&resolutions, budget, schedule);
+        self.run_batches(&addresses, schedule, batches)
+    }
+
+    /// The budget the bus can take: one conversion at a time on
+    /// parasite-powered buses, unless all convert at once.
+    fn budget(&mut self, budget: PowerBudget) -> PowerBudget {
+        let PowerBudget::MaxSimultaneousConversions(count) = budget else {
+            return budget;
+        };
+        if count > 1 && self.power_mode.is_none() {
+            // Detected by the first transaction, a failure shows again in the
+            // readings.
+            let _ = self.initialization();
+        }
+        if count > 1 && self.power_mode == Some(PowerMode::Parasite) {
+            log!(
+                Subsystem::Sampler,
+                Level::Debug,
+                "Parasite-powered bus, converting one sensor at a time instead of {count}",
+            );
+            return PowerBudget::MaxSimultaneousConversions(1);
+        }
+        budget
+    }
+
+    fn run_batches(
+        &mut self,
+        addresses: &[Address],
+        schedule: Schedule,
+        batches: Vec<Batch>,
+    ) -> Result<Sweep> {
+        let start = Instant::now();
+        let mut sweep = Sweep {
+            readings: Vec::with_capacity(addresses.len()),
+            timings: Vec::with_capacity(addresses.len()),
+            batches: 0,
+            schedule,
+            conversion: Duration::ZERO,
+            duration: Duration::ZERO,
+        };
+        // The readings and timings by the index of the address.
+        let mut read = Vec::with_capacity(addresses.len());
+        for batch in batches {
+            let converted = Instant::now();
+            let mut started = Vec::with_capacity(batch.indices.len());
+            if batch.broadcast {
+                self.initialization()?
+                    .skip_rom()?
+                    .start_conversion_for(batch.conversion)?;
+                started.extend(
+                    batch
+                        .indices
+                        .iter()
+                        .map(|index| preflight(&addresses[*index])),
+                );
+            } else {
+                for index in &batch.indices {
+                    let address = &addresses[*index];
+                    started.push(preflight(address).and_then(|_| {
+                        self.initialization()?
+                            .match_rom(address)?
+                            .start_conversion_for(batch.conversion)
+                    }));
+                }
+            }
+            self.cancellation.sleep(batch.conversion)?;
+            sweep.conversion += batch.conversion;
+            sweep.batches += 1;
+            for (index, started) in batch.indices.into_iter().zip(started) {
+                let address = &addresses[index];
+                let instant = Instant::now();
+                let reading = started.and_then(|_| {
+                    let scratchpad = self.retry(|this| {
+                        this.initialization()?.match_rom(address)?.read_scratchpad()
+                    })?;
+                    Ok(Reading::new(*address, scratchpad.temperature).converted_at(converted))
+                });
+                let timing = Timing {
+                    offset: instant - start,
+                    latency: instant.elapsed(),
+                };
+                read.push((index, reading, timing));
+            }
+        }
+        read.sort_by_key(|(index, ..)| *index);
+        for (_, reading, timing) in read {
+            sweep.readings.push(reading);
+            sweep.timings.push(timing);
+        }
+        sweep.duration = start.elapsed();
+        log!(
+            Subsystem::Sampler,
+            Level::Debug,
+            "Sweep of {} sensors in {} batches ({schedule:?}) took {:?} (max read {:?})",
+            addresses.len(),
+            sweep.batches,
+            sweep.duration,
+            sweep.max_latency().unwrap_or_default(),
+        );
+        Ok(sweep)
+    }
+
+    /// Converts and reads the sensors within the power budget one at a time,
+    /// without allocating. Budgeted batches hold at most 8 sensors.
+    pub fn read_iter<'d, 's>(
+        &'d mut self,
+        addresses: &'s [Address],
+        budget: PowerBudget,
+    ) -> Readings<'d, 'a, 's> {
+        let budget = self.budget(budget);
+        let size = match budget {
+            PowerBudget::Unlimited => budget.batch_size(addresses.len()),
+            PowerBudget::MaxSimultaneousConversions(_) => {
+                budget.batch_size(addresses.len()).min(STREAM_BATCH)
+            }
+        };
+        Readings {
+            driver: self,
+            budget,
+            batches: addresses.chunks(size),
+            batch: [].iter(),
+            started: [const { Ok(()) }; STREAM_BATCH],
+            converted: Instant::now(),
+            index: 0,
+        }
+    }
+}
+
+/// Streaming sweep
+///
+/// Yields the readings in the order of the addresses. A failure to convert
+/// the whole bus at once is yielded for each sensor of the batch.
+pub struct Readings<'d, 'a, 's> {
+    driver: &'d mut Ds18b20Driver<'a>,
+    budget: PowerBudget,
+    batches: Chunks<'s, Address>,
+    batch: Iter<'s, Address>,
+    /// The conversion starts of a budgeted batch.
+    started: [Result<()>; STREAM_BATCH],
+    /// The conversion start of the batch.
+    converted: Instant,
+    index: usize,
+}
+
+impl Readings<'_, '_, '_> {
+    fn convert(&mut self, batch: &[Address]) {
+        self.converted = Instant::now();
+        match self.budget {
+            PowerBudget::Unlimited => {
+                let started = self
+                    .driver
+                    .initialization()
+                    .and_then(|rom| rom.skip_rom()?.start_conversion());
+                self.started[0] = started;
+            }
+            PowerBudget::MaxSimultaneousConversions(_) => {
+                for (started, address) in self.started.iter_mut().zip(batch) {
+                    *started = preflight(address).and_then(|_| {
+                        self.driver
+                            .initialization()?
+                            .match_rom(address)?
+                            .start_conversion()
+                    });
+                }
+            }
+        }
+        let slept = self.driver.cancellation.sleep(timing::CONVERSION);
+        if let Err(error) = slept {
+            self.started.fill(Err(error));
+        }
+    }
+}
+
+impl Iterator for Readings<'_, '_, '_> {
+    type Item = Result<Reading>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let address = match self.batch.next() {
+            Some(address) => address,
+            None => {
+                let batch = self.batches.next()?;
+                self.convert(batch);
+                self.batch = batch.iter();
+                self.index = 0;
+                self.batch.next()?
+            }
+        };
+        let started = match self.budget {
+            PowerBudget::Unlimited => self.started[0].and_then(|_| preflight(address)),
+            PowerBudget::MaxSimultaneousConversions(_) => self.started[self.index],
+        };
+        self.index += 1;
+        Some(started.and_then(|_| {
+            let scratchpad = self
+                .driver
+                .retry(|this| this.initialization()?.match_rom(address)?.read_scratchpad())?;
+            Ok(Reading::new(*address, scratchpad.temperature).converted_at(self.converted))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{command::CommandCode, power::StrongPullUp};
+    use esp_idf_svc::hal::{
+        gpio::{AnyIOPin, AnyOutputPin, Level},
+        rmt::CHANNEL0,
+    };
+
+    #[test]
+    fn batch_size() {
+        assert_eq!(PowerBudget::Unlimited.batch_size(0), 1);
+        assert_eq!(PowerBudget::Unlimited.batch_size(5), 5);
+        assert_eq!(PowerBudget::MaxSimultaneousConversions(0).batch_size(5), 1);
+        assert_eq!(PowerBudget::MaxSimultaneousConversions(2).batch_size(5), 2);
+        assert_eq!(PowerBudget::MaxSimultaneousConversions(8).batch_size(5), 8);
+    }
+
+    #[test]
+    fn latency() {
+        let mut sweep = Sweep {
+            readings: Vec::new(),
+            timings: Vec::new(),
+            batches: 0,
+            schedule: Schedule::InOrder,
+            conversion: Duration::ZERO,
+            duration: Duration::ZERO,
+        };
+        assert_eq!(sweep.max_latency(), None);
+        assert_eq!(sweep.mean_latency(), None);
+        for latency in [10, 30] {
+            sweep.timings.push(Timing {
+                offset: Duration::ZERO,
+                latency: Duration::from_millis(latency),
+            });
+        }
+        assert_eq!(sweep.max_latency(), Some(Duration::from_millis(30)));
+        assert_eq!(sweep.mean_latency(), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn schedule() {
+        use Resolution::{Nine, Twelve};
+
+        let conversion = timing::CONVERSION;
+        let mixed = [Twelve, Nine, Twelve, Nine];
+        // One broadcast conversion waits for the slowest sensor.
+        let (schedule, batches) = plan(&mixed, PowerBudget::Unlimited, Schedule::Auto);
+        assert_eq!(schedule, Schedule::InOrder);
+        assert_eq!(
+            batches,
+            [Batch {
+                indices: vec![0, 1, 2, 3],
+                broadcast: true,
+                conversion,
+            }]
+        );
+        // Two batches of two wait for the slowest sensor twice in order.
+        let budget = PowerBudget::MaxSimultaneousConversions(2);
+        let (_, batches) = plan(&mixed, budget, Schedule::InOrder);
+        assert_eq!(estimate(&batches), START * 4 + conversion * 2);
+        let (schedule, batches) = plan(&mixed, budget, Schedule::Auto);
+        assert_eq!(schedule, Schedule::ByResolution);
+        assert_eq!(
+            batches,
+            [
+                Batch {
+                    indices: vec![1, 3],
+                    broadcast: false,
+                    conversion: conversion / 8,
+                },
+                Batch {
+                    indices: vec![0, 2],
+                    broadcast: false,
+                    conversion,
+                },
+            ]
+        );
+        // Nothing to gain with a uniform fleet.
+        let (schedule, _) = plan(&[Nine; 4], budget, Schedule::Auto);
+        assert_eq!(schedule, Schedule::InOrder);
+        assert_eq!(plan(&[], budget, Schedule::Auto).1, []);
+        assert_eq!(plan(&[], PowerBudget::Unlimited, Schedule::Auto).1, []);
+    }
+
+    /// Each conversion is read before the next sensor is addressed, which
+    /// would release the strong pull-up.
+    #[test]
+    fn parasite() {
+        let pin = unsafe { AnyIOPin::new(4) };
+        let mut driver = Ds18b20Driver::new(pin, unsafe { CHANNEL0::new() }).unwrap();
+        driver.power_mode = Some(PowerMode::Parasite);
+        driver.set_strong_pull_up(StrongPullUp::new(AnyOutputPin, Level::Low).unwrap());
+        driver.retries = 0;
+        let addresses = [
+            Address(0x2900_0000_0000_0128),
+            Address(0x7000_0000_0000_0228),
+        ];
+        let sensors = addresses.map(|address| (address, Resolution::Nine));
+        let budget = PowerBudget::MaxSimultaneousConversions(2);
+        let sweep = driver
+            .read_all_with(&sensors, budget, Schedule::InOrder)
+            .unwrap();
+        assert_eq!(sweep.batches, 2);
+        // Match ROM and the function command are written separately.
+        let mut address = None;
+        let commands: Vec<_> = driver
+            .last_transactions()
+            .filter_map(|transaction| {
+                address = transaction.address.or(address);
+                Some((transaction.function?, address?))
+            })
+            .collect();
+        use CommandCode::{ConvertTemperature, ReadScratchpad};
+        assert_eq!(
+            commands,
+            [
+                (ConvertTemperature, addresses[0]),
+                (ReadScratchpad, addresses[0]),
+                (ConvertTemperature, addresses[1]),
+                (ReadScratchpad, addresses[1]),
+            ]
+        );
+
+        // Nothing to convert.
+        driver.power_mode = None;
+        let sweep = driver.read_all(&[], PowerBudget::Unlimited).unwrap();
+        assert_eq!(sweep.batches, 0);
+        assert_eq!(driver.power_mode, None);
+    }
+}
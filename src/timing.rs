@@ -0,0 +1,102 @@
+//! Datasheet timing
+//!
+//! The timing parameters of the DS18B20 datasheet at standard speed, shared
+//! by the driver, the schedulers and external transports, and the
+//! calculators built on them:
+//!
+//! ```ignore
+//! let ready_at = started_at + timing::conversion(Resolution::Eleven);
+//! // Match ROM and Read Scratchpad, then the nine scratchpad bytes.
+//! let read = timing::transaction(10, 9);
+//! ```
+//!
+//! The limits are the datasheet's worst cases: a sensor is done converting
+//! after [`CONVERSION`] at the latest, a master holds the reset pulse for at
+//! least [`RESET_LOW`].
+
+use crate::{command::CommandCode, scratchpad::Resolution};
+use core::time::Duration;
+
+/// Temperature conversion time at 12 bits, the maximum (tCONV)
+pub const CONVERSION: Duration = Duration::from_millis(750);
+/// EEPROM write time of a Copy Scratchpad (tWR)
+pub const EEPROM_WRITE: Duration = Duration::from_millis(10);
+/// Recall E² time. The datasheet specifies none, the recall takes
+/// microseconds.
+pub const RECALL: Duration = Duration::from_millis(1);
+/// The latest the strong pull-up has to be enabled after a Convert T or Copy
+/// Scratchpad (tSPON)
+pub const STRONG_PULL_UP_ON: Duration = Duration::from_micros(10);
+/// Minimum reset pulse (tRSTL)
+pub const RESET_LOW: Duration = Duration::from_micros(480);
+/// Minimum time after the reset pulse, presence pulse included (tRSTH)
+pub const RESET_HIGH: Duration = Duration::from_micros(480);
+/// Maximum wait for the presence pulse after the reset pulse (tPDHIGH)
+pub const PRESENCE_HIGH: Duration = Duration::from_micros(60);
+/// Maximum presence pulse (tPDLOW)
+pub const PRESENCE_LOW: Duration = Duration::from_micros(240);
+/// Minimum time slot (tSLOT)
+pub const SLOT: Duration = Duration::from_micros(60);
+/// Minimum recovery between the time slots (tREC)
+pub const RECOVERY: Duration = Duration::from_micros(1);
+
+/// The conversion time at the resolution (tCONV).
+pub const fn conversion(resolution: Resolution) -> Duration {
+    match resolution {
+        Resolution::Nine => Duration::from_micros(93_750),
+        Resolution::Ten => Duration::from_micros(187_500),
+        Resolution::Eleven => Duration::from_millis(375),
+        Resolution::Twelve => CONVERSION,
+    }
+}
+
+/// The time the strong pull-up has to power a parasite-powered sensor after
+/// the command, `None` for the commands that don't need it.
+pub const fn strong_pull_up(command: CommandCode, resolution: Resolution) -> Option<Duration> {
+    match command {
+        CommandCode::ConvertTemperature => Some(conversion(resolution)),
+        CommandCode::CopyScratchpad => Some(EEPROM_WRITE),
+        _ => None,
+    }
+}
+
+/// The duration of the reset and presence sequence.
+pub const fn reset() -> Duration {
+    RESET_LOW.saturating_add(RESET_HIGH)
+}
+
+/// The minimum duration of transferring the bytes.
+pub const fn bytes(count: u32) -> Duration {
+    SLOT.saturating_add(RECOVERY).saturating_mul(count * 8)
+}
+
+/// The minimum duration of a transaction writing and reading the bytes,
+/// the reset included.
+pub const fn transaction(written: u32, read: u32) -> Duration {
+    reset().saturating_add(bytes(written + read))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timing() {
+        assert_eq!(conversion(Resolution::Nine) * 8, CONVERSION);
+        assert_eq!(conversion(Resolution::Eleven) * 2, CONVERSION);
+        assert_eq!(
+            strong_pull_up(CommandCode::ConvertTemperature, Resolution::Ten),
+            Some(Duration::from_micros(187_500))
+        );
+        assert_eq!(
+            strong_pull_up(CommandCode::CopyScratchpad, Resolution::Ten),
+            Some(EEPROM_WRITE)
+        );
+        assert_eq!(
+            strong_pull_up(CommandCode::ReadScratchpad, Resolution::Ten),
+            None
+        );
+        // Match ROM and Convert T
+        assert_eq!(transaction(10, 0), Duration::from_micros(5_840));
+    }
+}
@@ -0,0 +1,503 @@
+//! Compressed trace capture
+//!
+//! Records samples and bus faults for long-running captures of intermittent
+//! faults. The trace is split into self-contained chunks of at most `C`
+//! bytes, at most `K` of them are kept, the oldest chunks being dropped
+//! first. Sealed chunks can be exported one by one (e.g. as MQTT messages or
+//! HTTP bodies) and decoded independently with [`decode`].
+//!
+//! Chunk format (varints are LEB128):
+//!
+//! ```text
+//! chunk  = time record*
+//! record = 0x00 address             defines the next slot
+//!        | 0x01 delta slot value    sample
+//!        | 0x02 delta slot code     fault, see `fault_code`
+//!        | 0x03 count               repeats the previous record
+//! ```
+//!
+//! - `time`: varint, ms since boot
+//! - `address`: 8 bytes, bus order
+//! - `delta`: varint, ms since the previous record
+//! - `slot`: varint, the index of the sensor's definition in the chunk
+//! - `value`: zigzag varint, 1/16 °C since the previous sample of the slot
+//! - `code`: byte
+//! - `count`: varint, the number of repeats
+
+use crate::{
+    address::{Address, Validation},
+    collections::Deque,
+    error::{Error, Result},
+    format::round,
+    pipeline::Reading,
+};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Default chunk size (bytes).
+pub const CHUNK: usize = 512;
+/// Default number of kept chunks.
+pub const CHUNKS: usize = 64;
+
+const DEFINE: u8 = 0x00;
+const SAMPLE: u8 = 0x01;
+const FAULT: u8 = 0x02;
+const REPEAT: u8 = 0x03;
+/// The maximum size of a repeat record.
+const REPEAT_SIZE: usize = 1 + 5;
+
+/// Trace record
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record {
+    /// Time since boot, millisecond resolution.
+    pub time: Duration,
+    pub address: Address,
+    pub event: Event,
+}
+
+/// Trace event
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// Temperature (°C), 1/16 °C resolution.
+    Sample(f32),
+    /// Fault, see [`fault_code`].
+    Fault(u8),
+}
+
+/// The fault code of the error.
+///
+/// | code | error |
+/// |------|-------|
+/// | 1 | device not found |
+/// | 2 | CRC |
+/// | 3 | configuration register |
+/// | 4 | family code |
+/// | 5 | conversion timeout |
+/// | 6 | bus (esp-idf) |
+/// | 0 | other |
+pub fn fault_code(error: &Error) -> u8 {
+    match error {
+        Error::DeviceNotFound => 1,
+        Error::Crc(_) | Error::ScratchpadCrc { .. } => 2,
+        Error::ConfigurationRegister { .. } => 3,
+        Error::FamilyCode(_) => 4,
+        Error::ConversionTimeout => 5,
+        #[cfg(feature = "esp-idf")]
+        Error::Esp(_) => 6,
+        _ => 0,
+    }
+}
+
+/// Trace
+#[derive(Clone, Debug)]
+pub struct Trace<const C: usize = CHUNK, const K: usize = CHUNKS> {
+    sealed: Deque<Vec<u8>, K>,
+    chunk: Chunk,
+    dropped: usize,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self {
+            sealed: Deque::new(),
+            chunk: Chunk::default(),
+            dropped: 0,
+        }
+    }
+}
+
+impl<const C: usize, const K: usize> Trace<C, K> {
+    /// Sets the chunk size (bytes) and the number of kept chunks. The trace
+    /// is cleared.
+    pub fn capacity<const D: usize, const L: usize>(self) -> Trace<D, L> {
+        Trace {
+            sealed: Deque::new(),
+            chunk: Chunk::default(),
+            dropped: 0,
+        }
+    }
+
+    /// The number of dropped chunks.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// The number of recorded bytes.
+    pub fn len(&self) -> usize {
+        self.sealed.iter().map(Vec::len).sum::<usize>() + self.chunk.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records the reading.
+    pub fn sample(&mut self, time: Duration, reading: &Reading) {
+        let value = round(reading.temperature * 16.0) as i32;
+        self.record(time, reading.address, Value::Sample(value));
+    }
+
+    /// Records the fault.
+    pub fn fault(&mut self, time: Duration, address: Address, error: &Error) {
+        self.record(time, address, Value::Fault(fault_code(error)));
+    }
+
+    /// Seals the current chunk, so it can be exported.
+    pub fn seal(&mut self) {
+        if self.chunk.bytes.is_empty() {
+            return;
+        }
+        self.chunk.flush();
+        let bytes = core::mem::take(&mut self.chunk).bytes;
+        if self.sealed.is_full() {
+            self.sealed.pop_front();
+            self.dropped += 1;
+        }
+        let _ = self.sealed.push_back(bytes);
+    }
+
+    /// The sealed chunks, oldest first.
+    pub fn chunks(&self) -> impl Iterator<Item = &[u8]> {
+        self.sealed.iter().map(Vec::as_slice)
+    }
+
+    /// Removes the oldest sealed chunk, e.g. once it has been transmitted.
+    pub fn pop_chunk(&mut self) -> Option<Vec<u8>> {
+        self.sealed.pop_front()
+    }
+
+    /// Seals the current chunk and removes all the chunks, oldest first.
+    pub fn export(&mut self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.seal();
+        core::iter::from_fn(|| self.pop_chunk())
+    }
+
+    fn record(&mut self, time: Duration, address: Address, value: Value) {
+        let time = time.as_millis() as u64;
+        if !self.chunk.encode(time, address, value, C) {
+            self.seal();
+            // Only a record bigger than the chunk size is lost.
+            self.chunk.encode(time, address, value, C);
+        }
+    }
+}
+
+/// Encoded value
+#[derive(Clone, Copy, Debug)]
+enum Value {
+    /// 1/16 °C
+    Sample(i32),
+    Fault(u8),
+}
+
+/// Chunk encoder
+#[derive(Clone, Debug, Default)]
+struct Chunk {
+    bytes: Vec<u8>,
+    /// The addresses and the last sample values of the slots.
+    slots: Vec<(Address, i32)>,
+    time: u64,
+    /// The last record and the number of its pending repeats.
+    last: Vec<u8>,
+    repeats: u32,
+}
+
+impl Chunk {
+    /// Encodes the record. Returns `false` if it doesn't fit.
+    fn encode(&mut self, time: u64, address: Address, value: Value, capacity: usize) -> bool {
+        let mut bytes = Vec::new();
+        if self.bytes.is_empty() {
+            varint(&mut bytes, time);
+            self.time = time;
+        }
+        let slot = match self.slots.iter().position(|(slot, _)| *slot == address) {
+            Some(slot) => slot,
+            None => {
+                bytes.push(DEFINE);
+                bytes.extend_from_slice(&address.to_bytes());
+                self.slots.len()
+            }
+        };
+        let mut record = Vec::new();
+        let delta = time.saturating_sub(self.time);
+        match value {
+            Value::Sample(value) => {
+                let last = self.slots.get(slot).map_or(0, |(_, last)| *last);
+                record.push(SAMPLE);
+                varint(&mut record, delta);
+                varint(&mut record, slot as _);
+                varint(&mut record, zigzag(value - last));
+            }
+            Value::Fault(code) => {
+                record.push(FAULT);
+                varint(&mut record, delta);
+                varint(&mut record, slot as _);
+                record.push(code);
+            }
+        }
+        let repeat = bytes.is_empty() && record == self.last;
+        if !repeat {
+            bytes.extend_from_slice(&record);
+        }
+        if self.bytes.len() + bytes.len() + REPEAT_SIZE > capacity {
+            return false;
+        }
+        if slot == self.slots.len() {
+            self.slots.push((address, 0));
+        }
+        if let Value::Sample(value) = value {
+            self.slots[slot].1 = value;
+        }
+        self.time = time;
+        if repeat {
+            self.repeats += 1;
+        } else {
+            self.flush();
+            self.bytes.extend_from_slice(&bytes);
+            self.last = record;
+        }
+        true
+    }
+
+    /// Writes the pending repeats.
+    fn flush(&mut self) {
+        if self.repeats != 0 {
+            self.bytes.push(REPEAT);
+            varint(&mut self.bytes, self.repeats as _);
+            self.repeats = 0;
+        }
+    }
+}
+
+/// Decodes a chunk.
+pub fn decode(chunk: &[u8]) -> impl Iterator<Item = Result<Record>> + '_ {
+    Decoder {
+        bytes: chunk,
+        offset: 0,
+        time: None,
+        slots: Vec::new(),
+        last: None,
+        repeats: 0,
+    }
+}
+
+/// Chunk decoder
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    time: Option<u64>,
+    slots: Vec<(Address, i32)>,
+    /// The last record: tag, delta, slot and value delta or code.
+    last: Option<(u8, u64, usize, i32)>,
+    repeats: u64,
+}
+
+impl Decoder<'_> {
+    fn byte(&mut self) -> Result<u8> {
+        let byte = *self.bytes.get(self.offset).ok_or(self.error())?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(self.error())
+    }
+
+    fn error(&self) -> Error {
+        Error::Decode {
+            offset: self.offset,
+        }
+    }
+
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        if self.time.is_none() {
+            self.time = Some(self.varint()?);
+        }
+        let (tag, delta, slot, value) = if self.repeats != 0 {
+            self.repeats -= 1;
+            self.last.ok_or(self.error())?
+        } else {
+            loop {
+                if self.offset == self.bytes.len() {
+                    return Ok(None);
+                }
+                match self.byte()? {
+                    DEFINE => {
+                        let mut bytes = [0; 8];
+                        for byte in &mut bytes {
+                            *byte = self.byte()?;
+                        }
+                        let address = Address::from_bytes(bytes, Validation::Lenient)?;
+                        self.slots.push((address, 0));
+                    }
+                    REPEAT => {
+                        self.repeats = self.varint()?;
+                        if self.repeats == 0 || self.last.is_none() {
+                            return Err(self.error());
+                        }
+                        self.repeats -= 1;
+                        break self.last.ok_or(self.error())?;
+                    }
+                    tag @ (SAMPLE | FAULT) => {
+                        let delta = self.varint()?;
+                        let slot = self.varint()? as usize;
+                        let value = match tag {
+                            SAMPLE => unzigzag(self.varint()?),
+                            _ => self.byte()? as i32,
+                        };
+                        self.last = Some((tag, delta, slot, value));
+                        break (tag, delta, slot, value);
+                    }
+                    _ => {
+                        return Err(Error::Decode {
+                            offset: self.offset - 1,
+                        });
+                    }
+                }
+            }
+        };
+        let time = self.time.unwrap_or_default() + delta;
+        self.time = Some(time);
+        let error = self.error();
+        let (address, last) = self.slots.get_mut(slot).ok_or(error)?;
+        let event = match tag {
+            SAMPLE => {
+                *last += value;
+                Event::Sample(*last as f32 / 16.0)
+            }
+            _ => Event::Fault(value as _),
+        };
+        Ok(Some(Record {
+            time: Duration::from_millis(time),
+            address: *address,
+            event,
+        }))
+    }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(record) => record.map(Ok),
+            Err(error) => {
+                // Stop after the first error.
+                self.offset = self.bytes.len();
+                self.repeats = 0;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+fn varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn zigzag(value: i32) -> u64 {
+    ((value << 1) ^ (value >> 31)) as u32 as _
+}
+
+fn unzigzag(value: u64) -> i32 {
+    let value = value as u32;
+    (value >> 1) as i32 ^ -((value & 1) as i32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn zigzag() {
+        for value in [0, 1, -1, 16, -16, i16::MAX as _, i16::MIN as _] {
+            assert_eq!(unzigzag(super::zigzag(value)), value);
+        }
+        assert_eq!(super::zigzag(-1), 1);
+        assert_eq!(super::zigzag(1), 2);
+    }
+
+    #[test]
+    fn round_trip() {
+        let (first, second) = (Address(1), Address(2));
+        let mut trace = Trace::new();
+        let mut expected = Vec::new();
+        for index in 0..100 {
+            let time = ms(1000 + index * 750);
+            let reading = Reading::new(first, 21.0 + (index / 10) as f32 * 0.0625);
+            trace.sample(time, &reading);
+            expected.push(Record {
+                time,
+                address: first,
+                event: Event::Sample(reading.temperature),
+            });
+        }
+        trace.fault(ms(80_000), second, &Error::DeviceNotFound);
+        expected.push(Record {
+            time: ms(80_000),
+            address: second,
+            event: Event::Fault(1),
+        });
+        let chunks: Vec<_> = trace.export().collect();
+        assert_eq!(chunks.len(), 1);
+        // The repeated records are run-length encoded, less than 2 bytes per
+        // record.
+        assert!(chunks[0].len() < 2 * expected.len());
+        let records: Result<Vec<_>> = super::decode(&chunks[0]).collect();
+        assert_eq!(records, Ok(expected));
+        assert!(trace.is_empty());
+    }
+
+    #[test]
+    fn bounded() {
+        let mut trace = Trace::new().capacity::<64, 2>();
+        for index in 0..100 {
+            let reading = Reading::new(Address(index % 7), index as f32);
+            trace.sample(ms(index * 1000), &reading);
+        }
+        trace.seal();
+        assert_eq!(trace.chunks().count(), 2);
+        assert!(trace.dropped() > 0);
+        assert!(trace.len() <= 2 * 64);
+        // Every chunk decodes on its own.
+        for chunk in trace.chunks() {
+            let records: Vec<_> = super::decode(chunk).collect::<Result<_>>().unwrap();
+            assert!(!records.is_empty());
+        }
+        let last = trace.export().last().unwrap();
+        let last = super::decode(&last).last().unwrap().unwrap();
+        assert_eq!(last.time, ms(99_000));
+        assert_eq!(last.event, Event::Sample(99.0));
+    }
+
+    #[test]
+    fn malformed() {
+        let mut records = super::decode(&[0, SAMPLE, 0, 5, 0]);
+        assert_eq!(records.next(), Some(Err(Error::Decode { offset: 5 })));
+        assert_eq!(records.next(), None);
+        let mut records = super::decode(&[0, 0xFF]);
+        assert_eq!(records.next(), Some(Err(Error::Decode { offset: 1 })));
+    }
+}
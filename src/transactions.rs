@@ -0,0 +1,229 @@
+//! Transaction log
+//!
+//! The driver keeps the last bus transactions in a small ring, so the
+//! activity leading up to an error is at hand without tracing enabled
+//! beforehand. Only the commands and addresses are kept, not the payloads:
+//!
+//! ```ignore
+//! if let Err(error) = thermometer.temperature(&address) {
+//!     for transaction in thermometer.last_transactions() {
+//!         warn!("{transaction}");
+//!     }
+//! }
+//! ```
+
+use crate::{address::Address, collections::Deque, command::CommandCode, stats::Operation};
+use core::{
+    fmt::{self, Display, Formatter},
+    time::Duration,
+};
+
+/// Default number of kept transactions.
+pub const TRANSACTIONS: usize = 32;
+
+/// Bus transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    pub operation: Operation,
+    /// The ROM command written after the reset.
+    pub rom: Option<CommandCode>,
+    /// The function command written after the ROM command.
+    pub function: Option<CommandCode>,
+    /// The address of a Match ROM.
+    pub address: Option<Address>,
+    /// The number of bytes transferred.
+    pub bytes: usize,
+    pub duration: Duration,
+    /// The error code of a failed transaction.
+    pub error: Option<i32>,
+}
+
+impl Display for Transaction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {} bytes in {:?}",
+            self.operation, self.bytes, self.duration
+        )?;
+        for command in [self.rom, self.function].into_iter().flatten() {
+            write!(f, ", {command}")?;
+        }
+        if let Some(address) = self.address {
+            write!(f, ", {address}")?;
+        }
+        if let Some(error) = self.error {
+            write!(f, ", error {error:#x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The expected content of the next write.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Phase {
+    #[default]
+    Rom,
+    Function,
+    Data,
+}
+
+/// Ring of the last transactions
+#[derive(Clone, Debug)]
+pub struct TransactionLog<const N: usize = TRANSACTIONS> {
+    transactions: Deque<Transaction, N>,
+    phase: Phase,
+    enabled: bool,
+}
+
+impl TransactionLog {
+    pub fn new() -> Self {
+        Self {
+            transactions: Deque::new(),
+            phase: Phase::Rom,
+            enabled: true,
+        }
+    }
+}
+
+impl<const N: usize> TransactionLog<N> {
+    /// Sets the number of kept transactions.
+    pub fn capacity<const M: usize>(self) -> TransactionLog<M> {
+        TransactionLog {
+            transactions: Deque::new(),
+            phase: self.phase,
+            enabled: self.enabled,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enables or disables the recording, keeping the recorded
+    /// transactions.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Records an operation transferring the bytes. Only the commands and
+    /// the address are kept from a write.
+    pub fn record(
+        &mut self,
+        operation: Operation,
+        bytes: &[u8],
+        duration: Duration,
+        error: Option<i32>,
+    ) {
+        let mut transaction = Transaction {
+            operation,
+            rom: None,
+            function: None,
+            address: None,
+            bytes: bytes.len(),
+            duration,
+            error,
+        };
+        match operation {
+            Operation::Reset | Operation::Search => self.phase = Phase::Rom,
+            Operation::Read => {}
+            Operation::Write => self.parse(bytes, &mut transaction),
+        }
+        if !self.enabled {
+            return;
+        }
+        if self.transactions.is_full() {
+            self.transactions.pop_front();
+        }
+        let _ = self.transactions.push_back(transaction);
+    }
+
+    fn parse(&mut self, mut bytes: &[u8], transaction: &mut Transaction) {
+        if self.phase == Phase::Rom
+            && let Some((&code, rest)) = bytes.split_first()
+        {
+            let rom = CommandCode::try_from(code).ok();
+            transaction.rom = rom;
+            bytes = rest;
+            self.phase = match rom {
+                Some(CommandCode::MatchRom) => {
+                    if let Some(address) = bytes.first_chunk() {
+                        transaction.address = Some(Address(u64::from_le_bytes(*address)));
+                        bytes = &bytes[address.len()..];
+                    }
+                    Phase::Function
+                }
+                Some(CommandCode::SkipRom) => Phase::Function,
+                _ => Phase::Data,
+            };
+        }
+        if self.phase == Phase::Function
+            && let Some(&code) = bytes.first()
+        {
+            transaction.function = CommandCode::try_from(code).ok();
+            self.phase = Phase::Data;
+        }
+    }
+
+    /// The transactions, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        self.transactions.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.transactions.clear();
+    }
+}
+
+impl Default for TransactionLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::command::match_rom;
+    use alloc::{string::ToString, vec::Vec};
+
+    #[test]
+    fn record() {
+        let mut log = TransactionLog::new().capacity::<4>();
+        let address = Address(0x1E00_0000_0000_0028);
+        let us = Duration::from_micros(1);
+        log.record(Operation::Reset, &[], us, None);
+        log.record(Operation::Write, &match_rom(&address), us, None);
+        // Write Scratchpad data that reads like a command code.
+        log.record(Operation::Write, &[0x4E], us, None);
+        log.record(Operation::Write, &[0x44, 0x44, 0x7F], us, None);
+        log.record(Operation::Reset, &[], us, Some(0x107));
+        let transactions: Vec<_> = log.iter().collect();
+        assert_eq!(transactions.len(), 4);
+        assert_eq!(transactions[0].rom, Some(CommandCode::MatchRom));
+        assert_eq!(transactions[0].address, Some(address));
+        assert_eq!(transactions[0].function, None);
+        assert_eq!(transactions[1].function, Some(CommandCode::WriteScratchpad));
+        assert_eq!(
+            (transactions[2].rom, transactions[2].function),
+            (None, None)
+        );
+        assert_eq!(
+            transactions[3].to_string(),
+            "Reset 0 bytes in 1µs, error 0x107"
+        );
+        // Both commands in one write.
+        log.record(
+            Operation::Write,
+            &[CommandCode::SkipRom as _, CommandCode::ReadPowerSupply as _],
+            us,
+            None,
+        );
+        let last = log.iter().last().unwrap();
+        assert_eq!(last.rom, Some(CommandCode::SkipRom));
+        assert_eq!(last.function, Some(CommandCode::ReadPowerSupply));
+        // Disabled, the phases are still tracked.
+        log.set_enabled(false);
+        log.record(Operation::Reset, &[], us, None);
+        assert_eq!(log.iter().count(), 4);
+    }
+}
@@ -0,0 +1,99 @@
+//! Temperature trend
+
+/// Trend
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Trend {
+    Rising,
+    Falling,
+    Stable,
+}
+
+impl Trend {
+    /// Classifies the temperatures (oldest first) by the slope of their least
+    /// squares line. Returns `None` for less than two temperatures.
+    pub fn classify(
+        temperatures: impl IntoIterator<Item = f32>,
+        config: &TrendConfig,
+    ) -> Option<Self> {
+        let slope = slope(temperatures)?;
+        Some(if slope >= config.threshold {
+            Trend::Rising
+        } else if slope <= -config.threshold {
+            Trend::Falling
+        } else {
+            Trend::Stable
+        })
+    }
+}
+
+/// Trend classification parameters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrendConfig {
+    /// The number of latest samples the slope is fitted to.
+    pub window: usize,
+    /// The minimum absolute slope (°C per sample) of a rising or falling
+    /// trend.
+    pub threshold: f32,
+}
+
+impl Default for TrendConfig {
+    fn default() -> Self {
+        Self {
+            window: 8,
+            threshold: 0.05,
+        }
+    }
+}
+
+/// The slope (per sample) of the least squares line fitted to the values.
+/// Returns `None` for less than two values.
+pub fn slope(values: impl IntoIterator<Item = f32>) -> Option<f32> {
+    let (mut n, mut x, mut y, mut xy, mut xx) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for value in values {
+        x += n;
+        y += value;
+        xy += n * value;
+        xx += n * n;
+        n += 1.0;
+    }
+    if n < 2.0 {
+        return None;
+    }
+    Some((n * xy - x * y) / (n * xx - x * x))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slope() {
+        assert_eq!(super::slope([]), None);
+        assert_eq!(super::slope([1.0]), None);
+        assert_eq!(super::slope([1.0, 2.0]), Some(1.0));
+        assert_eq!(super::slope([3.0, 2.0, 1.0]), Some(-1.0));
+        assert_eq!(super::slope([5.0, 5.0, 5.0, 5.0]), Some(0.0));
+        assert_eq!(super::slope([0.0, 1.0, 0.0, 1.0]), Some(0.2));
+    }
+
+    #[test]
+    fn classify() {
+        let config = TrendConfig {
+            window: 4,
+            threshold: 0.1,
+        };
+        assert_eq!(Trend::classify([20.0], &config), None);
+        assert_eq!(
+            Trend::classify([20.0, 20.5, 21.0], &config),
+            Some(Trend::Rising)
+        );
+        assert_eq!(
+            Trend::classify([21.0, 20.5, 20.0], &config),
+            Some(Trend::Falling)
+        );
+        assert_eq!(
+            Trend::classify([20.0, 20.0625, 20.0], &config),
+            Some(Trend::Stable)
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Data types
+//!
+//! The values the crate hands out: plain, lifetime-free `Copy` data without
+//! ties to the driver or the esp-idf types, so they can be kept in static
+//! state, sent over channels to other threads and serialized:
+//!
+//! ```ignore
+//! static LAST: Mutex<Option<Reading>> = Mutex::new(None);
+//!
+//! let (sender, receiver) = mpsc::channel::<Reading>();
+//! thread::spawn(move || {
+//!     for reading in receiver {
+//!         // ...
+//!     }
+//! });
+//! ```
+//!
+//! The modules that use them re-export them, e.g.
+//! [`pipeline::Reading`](crate::pipeline::Reading).
+
+pub use crate::{address::Address, bus::BusId, compensation::Compensation, trend::Trend};
+
+use crate::{max31850::Status, timing, unit::Celsius};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Sensor identifier, the ROM code of the sensor
+///
+/// A `u64` newtype with `Hash` and `Ord`, converted from `OWAddress` with
+/// [`From`], which keys the registry, the history and the telemetry.
+pub type SensorId = Address;
+
+/// Temperature (°C)
+pub type Temperature = Celsius;
+
+/// Reading
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reading {
+    pub address: Address,
+    /// Temperature (°C)
+    pub temperature: f32,
+    /// Trend, set by the [`History`](crate::history::History) stage.
+    pub trend: Option<Trend>,
+    /// The age of a last-known-good temperature served in place of a failed
+    /// read, `None` for a fresh reading.
+    pub stale: Option<Duration>,
+    /// The bus the reading was taken from, set by the
+    /// [`BusManager`](crate::bus::BusManager).
+    pub bus: Option<BusId>,
+    /// The adjustment and its input, set by the
+    /// [`AmbientCompensation`](crate::compensation::AmbientCompensation)
+    /// stage.
+    pub compensation: Option<Compensation>,
+    /// The cold junction and the faults of a MAX31850 thermocouple, set by
+    /// [`Thermocouple::reading`](crate::max31850::Thermocouple::reading).
+    pub thermocouple: Option<Status>,
+    /// The start of the conversion, the time of the measurement. Set by the
+    /// readers on the bus, deferred reads can follow it by seconds.
+    #[cfg(feature = "std")]
+    pub converted: Option<Instant>,
+}
+
+impl Reading {
+    pub fn new(address: Address, temperature: f32) -> Self {
+        Self {
+            address,
+            temperature,
+            trend: None,
+            stale: None,
+            bus: None,
+            compensation: None,
+            thermocouple: None,
+            #[cfg(feature = "std")]
+            converted: None,
+        }
+    }
+
+    /// Sets the start of the conversion.
+    #[cfg(feature = "std")]
+    pub fn converted_at(self, converted: Instant) -> Self {
+        Self {
+            converted: Some(converted),
+            ..self
+        }
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.stale.is_some()
+    }
+
+    /// The time since the start of the conversion.
+    #[cfg(feature = "std")]
+    pub fn age(&self) -> Option<Duration> {
+        self.age_at(Instant::now())
+    }
+
+    /// The time from the start of the conversion to the instant.
+    #[cfg(feature = "std")]
+    pub fn age_at(&self, now: Instant) -> Option<Duration> {
+        self.converted
+            .map(|converted| now.saturating_duration_since(converted))
+    }
+
+    /// Returns `true` if the temperature was measured within the window.
+    /// Readings without a conversion instant are never valid.
+    #[cfg(feature = "std")]
+    pub fn is_valid_for(&self, window: Duration) -> bool {
+        self.age().is_some_and(|age| age <= window)
+    }
+}
+
+/// Temperature resolution: 9, 10, 11 or 12 bits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Resolution {
+    /// 9-bit, equates to a temperature resolution of 0.5°C
+    Nine,
+    /// 10-bit, equates to a temperature resolution of 0.25°C
+    Ten,
+    /// 11-bit, equates to a temperature resolution of 0.125°C
+    Eleven,
+    /// 12-bit, equates to a temperature resolution of 0.0625°C
+    #[default]
+    Twelve,
+}
+
+impl Resolution {
+    pub const fn bits(&self) -> u8 {
+        match self {
+            Resolution::Nine => 9,
+            Resolution::Ten => 10,
+            Resolution::Eleven => 11,
+            Resolution::Twelve => 12,
+        }
+    }
+
+    pub const fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            9 => Some(Resolution::Nine),
+            10 => Some(Resolution::Ten),
+            11 => Some(Resolution::Eleven),
+            12 => Some(Resolution::Twelve),
+            _ => None,
+        }
+    }
+
+    /// Temperature step (°C)
+    pub const fn step(&self) -> f32 {
+        match self {
+            Resolution::Nine => 0.5,
+            Resolution::Ten => 0.25,
+            Resolution::Eleven => 0.125,
+            Resolution::Twelve => 0.0625,
+        }
+    }
+
+    /// The fastest resolution with a step of at most the precision (°C), the
+    /// finest one if none is fine enough.
+    pub fn for_precision(precision: f32) -> Self {
+        [Self::Nine, Self::Ten, Self::Eleven]
+            .into_iter()
+            .find(|resolution| resolution.step() <= precision)
+            .unwrap_or(Self::Twelve)
+    }
+
+    /// Conversion time (ns)
+    pub fn conversion_time(&self) -> u32 {
+        timing::conversion(*self).as_nanos() as _
+    }
+}
+
+/// Power supply mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PowerMode {
+    /// Powered through VDD.
+    #[default]
+    External,
+    /// Powered from the data line, VDD grounded.
+    Parasite,
+}
+
+impl PowerMode {
+    /// The mode signalled in the read time slot after a Read Power Supply:
+    /// parasite-powered sensors pull the bus low.
+    pub const fn from_bit(bit: bool) -> Self {
+        if bit { Self::External } else { Self::Parasite }
+    }
+
+    /// Whether conversions and EEPROM writes need the strong pull-up.
+    pub const fn needs_strong_pull_up(&self) -> bool {
+        matches!(self, Self::Parasite)
+    }
+}
+
+/// Alarm kind
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AlarmKind {
+    High,
+    Low,
+}
+
+/// Alarm event
+///
+/// `bus` is the bus and `temperature` the temperature of the reading that
+/// raised or cleared the alarm, `kind` the tripped boundary and `threshold`
+/// its limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlarmEvent {
+    Raised {
+        address: Address,
+        bus: Option<BusId>,
+        kind: AlarmKind,
+        temperature: Celsius,
+        threshold: Celsius,
+    },
+    Cleared {
+        address: Address,
+        bus: Option<BusId>,
+        kind: AlarmKind,
+        temperature: Celsius,
+        threshold: Celsius,
+    },
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn age() {
+        let converted = Instant::now();
+        let reading = Reading::new(Address(1), 21.0);
+        assert_eq!(reading.age(), None);
+        assert!(!reading.is_valid_for(Duration::MAX));
+        let reading = reading.converted_at(converted);
+        let now = converted + Duration::from_secs(2);
+        assert_eq!(reading.age_at(now), Some(Duration::from_secs(2)));
+        assert_eq!(reading.age_at(converted), Some(Duration::ZERO));
+        assert!(reading.is_valid_for(Duration::from_secs(60)));
+    }
+}
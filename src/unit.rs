@@ -0,0 +1,118 @@
+use crate::format::{Scale, round};
+use core::fmt::{self, Display, Formatter};
+
+/// Temperature unit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Unit {
+    /// Degree Celsius (°C)
+    #[default]
+    Celsius,
+    /// Degree Fahrenheit (°F)
+    Fahrenheit,
+    /// Kelvin (K)
+    Kelvin,
+}
+
+impl Unit {
+    /// Converts the temperature from degrees Celsius to the unit.
+    pub fn from_celsius(&self, celsius: f32) -> f32 {
+        match self {
+            Unit::Celsius => celsius,
+            Unit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            Unit::Kelvin => celsius + 273.15,
+        }
+    }
+
+    /// Converts the temperature from the unit to degrees Celsius.
+    pub fn to_celsius(&self, value: f32) -> f32 {
+        match self {
+            Unit::Celsius => value,
+            Unit::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+            Unit::Kelvin => value - 273.15,
+        }
+    }
+
+    /// Unit symbol
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Celsius => "°C",
+            Unit::Fahrenheit => "°F",
+            Unit::Kelvin => "K",
+        }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// Temperature in degrees Celsius
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Celsius(pub f32);
+
+impl Celsius {
+    /// Converts the temperature to the unit.
+    pub fn to(&self, unit: Unit) -> f32 {
+        unit.from_celsius(self.0)
+    }
+
+    /// The temperature in tenths of a degree, rounded to the nearest. Out of
+    /// range temperatures saturate, NaN is 0.
+    pub fn deci_celsius(&self) -> i16 {
+        round(self.0 * Scale::Ten.factor() as f32) as _
+    }
+
+    pub fn from_deci_celsius(tenths: i16) -> Self {
+        Self(Scale::Ten.decode(tenths as _))
+    }
+}
+
+impl From<i8> for Celsius {
+    fn from(value: i8) -> Self {
+        Self(value as _)
+    }
+}
+
+impl Display for Celsius {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.0, Unit::Celsius)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_celsius() {
+        assert_eq!(Unit::Celsius.from_celsius(-40.0), -40.0);
+        assert_eq!(Unit::Fahrenheit.from_celsius(-40.0), -40.0);
+        assert_eq!(Unit::Fahrenheit.from_celsius(100.0), 212.0);
+        assert_eq!(Unit::Kelvin.from_celsius(0.0), 273.15);
+    }
+
+    #[test]
+    fn to_celsius() {
+        assert_eq!(Unit::Celsius.to_celsius(25.0), 25.0);
+        assert_eq!(Unit::Fahrenheit.to_celsius(212.0), 100.0);
+        assert_eq!(Unit::Kelvin.to_celsius(273.15), 0.0);
+    }
+
+    #[test]
+    fn celsius() {
+        assert_eq!(Celsius::from(-40i8).to(Unit::Fahrenheit), -40.0);
+        assert_eq!(Celsius::from(100i8).to(Unit::Fahrenheit), 212.0);
+        assert_eq!(Celsius::from(75i8).to_string(), "75 °C");
+    }
+
+    #[test]
+    fn deci_celsius() {
+        assert_eq!(Celsius(21.4375).deci_celsius(), 214);
+        assert_eq!(Celsius(-10.25).deci_celsius(), -103);
+        assert_eq!(Celsius(1e9).deci_celsius(), i16::MAX);
+        assert_eq!(Celsius(f32::NAN).deci_celsius(), 0);
+        assert_eq!(Celsius::from_deci_celsius(-103), Celsius(-10.3));
+    }
+}
@@ -0,0 +1,388 @@
+//! Host integration tests of the reading pipeline
+//!
+//! A scripted bus of simulated sensors stands in for the hardware: sensors
+//! can be plugged and unplugged between the samples and their scratchpads
+//! corrupted. The sampler reads the bus as its transport and hands the
+//! readings through the pipeline and the alarm engine to the sinks, a CSV one
+//! read back by the host parser. The hot-plug test rescans the bus between
+//! the samples, keeping the registry up to date.
+//!
+//! Run with `cargo test --no-default-features --features host`.
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+use thermometer::{
+    Error, Result,
+    address::Address,
+    alarm::{AlarmKind, Alarms, Event, Limits},
+    cancellation::Cancellation,
+    command::CommandCode,
+    csv,
+    event::Overflow,
+    host,
+    pipeline::{Calibration, Pipeline, Reading, Stage},
+    quarantine::Quarantine,
+    registry::{Presence, Registry},
+    sampler::{Sampler, Transport},
+    scratchpad::Scratchpad,
+    search::{BitBus, Search},
+    simulation::{SimulatedSensor, Waveform},
+    sink::Sink,
+};
+
+/// The simulated time between the samples
+const INTERVAL: Duration = Duration::from_secs(10);
+
+/// The real time between the samples of the sampler
+const PERIOD: Duration = Duration::from_millis(1);
+
+/// Simulated sensor on the scripted bus
+struct Device {
+    sensor: SimulatedSensor,
+    plugged: bool,
+    /// The number of scratchpad reads left to corrupt.
+    corrupt: usize,
+    /// The scratchpad reads so far.
+    reads: usize,
+}
+
+/// Scripted bus, wired-AND during the search
+#[derive(Default)]
+struct Bus {
+    devices: Vec<Device>,
+    active: Vec<u64>,
+    bit: u32,
+    complement: bool,
+    at: Duration,
+    conversions: u32,
+    cancellation: Cancellation,
+}
+
+impl Bus {
+    fn plug(&mut self, sensor: SimulatedSensor) -> Address {
+        let address = sensor.address();
+        self.devices.push(Device {
+            sensor,
+            plugged: true,
+            corrupt: 0,
+            reads: 0,
+        });
+        address
+    }
+
+    fn device(&mut self, address: &Address) -> &mut Device {
+        self.devices
+            .iter_mut()
+            .find(|device| device.sensor.address() == *address)
+            .unwrap()
+    }
+
+    fn scratchpad(&mut self, address: &Address) -> Result<[u8; 9]> {
+        let at = self.at;
+        let device = self.device(address);
+        device.reads += 1;
+        if !device.plugged {
+            return Err(Error::DeviceNotFound);
+        }
+        let mut buffer = Scratchpad {
+            temperature: device.sensor.read(at).temperature,
+            ..Default::default()
+        }
+        .to_bytes();
+        if device.corrupt > 0 {
+            device.corrupt -= 1;
+            buffer[0] ^= 0x10;
+        }
+        Ok(buffer)
+    }
+}
+
+impl BitBus for Bus {
+    fn reset(&mut self) -> Result<()> {
+        self.active = self
+            .devices
+            .iter()
+            .filter(|device| device.plugged)
+            .map(|device| device.sensor.address().0)
+            .collect();
+        self.bit = 0;
+        Ok(())
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<()> {
+        assert_eq!(byte, CommandCode::SearchRom as u8);
+        Ok(())
+    }
+
+    fn read_bit(&mut self) -> Result<bool> {
+        let complement = self.complement;
+        self.complement = !complement;
+        Ok(self
+            .active
+            .iter()
+            .all(|rom| (rom >> self.bit & 1 == 1) != complement))
+    }
+
+    fn write_bit(&mut self, bit: bool) -> Result<()> {
+        self.active.retain(|rom| (rom >> self.bit & 1 == 1) == bit);
+        self.bit += 1;
+        Ok(())
+    }
+}
+
+/// Each conversion samples the sensors one interval later.
+impl Transport for Bus {
+    fn start_conversion(&mut self) -> Result<()> {
+        self.at = INTERVAL * self.conversions;
+        self.conversions += 1;
+        Ok(())
+    }
+
+    fn read_scratchpad(&mut self, address: &Address) -> Result<Scratchpad> {
+        self.scratchpad(address).and_then(Scratchpad::from_bytes)
+    }
+
+    fn commit(&mut self, _address: &Address) -> Result<()> {
+        Ok(())
+    }
+
+    fn power_down(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn scan(&mut self) -> Result<Vec<Address>> {
+        let mut search = Search::new(CommandCode::SearchRom);
+        let mut found = Vec::new();
+        while let Some(address) = search.next(self) {
+            found.push(address?);
+        }
+        Ok(found)
+    }
+
+    fn cancellation(&self) -> Cancellation {
+        self.cancellation.clone()
+    }
+
+    fn conversion_time(&self) -> Duration {
+        PERIOD
+    }
+}
+
+/// A sink collecting CSV telemetry
+fn telemetry() -> (impl Sink, Rc<RefCell<String>>) {
+    let output = Rc::new(RefCell::new(format!("{}\n", csv::HEADER)));
+    let sink = {
+        let output = output.clone();
+        move |readings: &[Reading]| {
+            let mut output = output.borrow_mut();
+            for reading in readings {
+                output.push_str(&csv::encode(reading));
+                output.push('\n');
+            }
+            Ok(())
+        }
+    };
+    (sink, output)
+}
+
+/// A sink recording the size of each batch
+fn batches() -> (impl Sink, Rc<RefCell<Vec<usize>>>) {
+    let batches = Rc::new(RefCell::new(Vec::new()));
+    let sink = {
+        let batches = batches.clone();
+        move |readings: &[Reading]| {
+            batches.borrow_mut().push(readings.len());
+            Ok(())
+        }
+    };
+    (sink, batches)
+}
+
+/// Polls the sampler until the sample is collected.
+fn sample(sampler: &mut Sampler<Bus>) -> (Vec<Reading>, Vec<Error>) {
+    let readings = loop {
+        if let Some(readings) = sampler.poll().unwrap() {
+            break readings;
+        }
+    };
+    let (readings, failures): (Vec<_>, Vec<_>) = readings.into_iter().partition(Result::is_ok);
+    (
+        readings.into_iter().map(Result::unwrap).collect(),
+        failures.into_iter().map(Result::unwrap_err).collect(),
+    )
+}
+
+fn addresses(readings: &[Reading]) -> Vec<Address> {
+    let mut addresses: Vec<_> = readings.iter().map(|reading| reading.address).collect();
+    addresses.sort();
+    addresses
+}
+
+#[test]
+fn hot_plug() {
+    let mut bus = Bus::default();
+    let boiler = bus.plug(SimulatedSensor::new(1, Waveform::Constant(60.0)));
+    let room = bus.plug(SimulatedSensor::new(2, Waveform::Constant(21.0)));
+    let (sink, output) = telemetry();
+    let mut registry = Registry::new();
+    let mut sampler = Sampler::new(bus, Vec::new(), PERIOD).sink(sink);
+
+    let mut presence = sampler.rescan(&mut registry).unwrap();
+    presence.added.sort();
+    assert_eq!(presence.added, [boiler, room]);
+    let (readings, failures) = sample(&mut sampler);
+    assert!(failures.is_empty());
+    assert_eq!(addresses(&readings), [boiler, room]);
+    registry.set_label(boiler, "boiler").unwrap();
+
+    // Unplugged, the sensor stays registered with its label.
+    sampler.driver().device(&boiler).plugged = false;
+    let presence = sampler.rescan(&mut registry).unwrap();
+    assert!(presence.added.is_empty());
+    assert_eq!(presence.missing, [boiler]);
+    let (readings, failures) = sample(&mut sampler);
+    assert!(failures.is_empty());
+    assert_eq!(addresses(&readings), [room]);
+    assert_eq!(registry.find("boiler"), Some(&boiler));
+
+    // A new sensor is registered on the rescan.
+    let outside = sampler
+        .driver()
+        .plug(SimulatedSensor::new(3, Waveform::Constant(-5.0)));
+    sampler.driver().device(&boiler).plugged = true;
+    let presence = sampler.rescan(&mut registry).unwrap();
+    assert_eq!(
+        presence,
+        Presence {
+            added: vec![outside],
+            missing: Vec::new(),
+        }
+    );
+    let (readings, failures) = sample(&mut sampler);
+    assert!(failures.is_empty());
+    assert_eq!(readings.len(), 3);
+    assert_eq!(registry.len(), 3);
+
+    let readings: Vec<_> = host::parse_csv(&output.borrow())
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(readings.len(), 6);
+    assert!(readings.contains(&Reading::new(outside, -5.0)));
+}
+
+#[test]
+fn crc_storm() {
+    let mut bus = Bus::default();
+    let noisy = bus.plug(SimulatedSensor::new(1, Waveform::Constant(40.0)));
+    let quiet = bus.plug(SimulatedSensor::new(2, Waveform::Constant(20.0)));
+    bus.device(&noisy).corrupt = 3;
+    let (sink, output) = telemetry();
+    let mut sampler = Sampler::new(bus, vec![noisy, quiet], PERIOD).sink(sink);
+
+    // The corrupted sensor fails alone, the others keep reporting.
+    for _ in 0..3 {
+        let (readings, failures) = sample(&mut sampler);
+        assert_eq!(addresses(&readings), [quiet]);
+        assert!(matches!(failures[..], [Error::ScratchpadCrc { .. }]));
+    }
+    let (readings, failures) = sample(&mut sampler);
+    assert!(failures.is_empty());
+    assert_eq!(addresses(&readings), [noisy, quiet]);
+    // No corrupted temperature made it into the telemetry.
+    let readings: Vec<_> = host::parse_csv(&output.borrow())
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(readings.len(), 5);
+    for reading in readings {
+        assert!(reading.temperature == 40.0 || reading.temperature == 20.0);
+    }
+}
+
+#[test]
+fn alarm_trip() {
+    let mut bus = Bus::default();
+    // Heats up by 6 °C per minute from 50 °C, crossing 60 °C after 100 s.
+    let tank = bus.plug(SimulatedSensor::new(
+        1,
+        Waveform::Ramp {
+            start: 50.0,
+            rate: 6.0,
+            end: 70.0,
+        },
+    ));
+    let alarms = Rc::new(RefCell::new(Alarms::new(Overflow::DropOldest).limits(
+        tank,
+        Limits {
+            low: None,
+            high: Some(60.0),
+        },
+    )));
+    let pipeline = Pipeline::new()
+        // A probe reading 0.5 °C high.
+        .stage(Calibration::new().offset(tank, -0.5))
+        .stage({
+            let alarms = alarms.clone();
+            move |reading| alarms.borrow_mut().process(reading)
+        });
+    let (sink, batches) = batches();
+    let mut sampler = Sampler::new(bus, vec![tank], PERIOD)
+        .pipeline(pipeline)
+        .sink(sink);
+
+    let mut raised = None;
+    for index in 0..20 {
+        sample(&mut sampler);
+        if let Some(event) = alarms.borrow_mut().events().pop() {
+            raised = Some((index, event));
+            break;
+        }
+    }
+    // 60 °C at 100 s reads 59.5 °C after the calibration, still in range.
+    let (index, event) = raised.unwrap();
+    assert_eq!(index, 11);
+    assert!(matches!(
+        event,
+        Event::Raised {
+            address,
+            kind: AlarmKind::High,
+            ..
+        } if address == tank
+    ));
+    assert_eq!(alarms.borrow().active(&tank), Some(AlarmKind::High));
+    assert_eq!(batches.borrow()[..], [1; 12]);
+}
+
+#[test]
+fn quarantine() {
+    let mut bus = Bus::default();
+    let boiler = bus.plug(SimulatedSensor::new(1, Waveform::Constant(60.0)));
+    let room = bus.plug(SimulatedSensor::new(2, Waveform::Constant(21.0)));
+    // A broken cable.
+    bus.device(&boiler).plugged = false;
+    let (sink, batches) = batches();
+    let mut sampler = Sampler::new(bus, vec![boiler, room], PERIOD)
+        .quarantine(Quarantine::new(2, 3))
+        .sink(sink);
+
+    for _ in 0..2 {
+        let (readings, failures) = sample(&mut sampler);
+        assert_eq!(addresses(&readings), [room]);
+        assert_eq!(failures, [Error::DeviceNotFound]);
+    }
+    assert_eq!(sampler.quarantined().collect::<Vec<_>>(), [&boiler]);
+
+    // Skipped while quarantined, the other sensors keep reporting.
+    for _ in 0..3 {
+        let (readings, failures) = sample(&mut sampler);
+        assert_eq!(addresses(&readings), [room]);
+        assert!(failures.is_empty());
+    }
+    assert_eq!(sampler.driver().device(&boiler).reads, 2);
+
+    // Read again after the quarantine and released once repaired.
+    sampler.driver().device(&boiler).plugged = true;
+    let (readings, failures) = sample(&mut sampler);
+    assert_eq!(addresses(&readings), [boiler, room]);
+    assert!(failures.is_empty());
+    assert_eq!(sampler.quarantined().count(), 0);
+    assert_eq!(batches.borrow()[..], [1, 1, 1, 1, 1, 2]);
+}